@@ -1,21 +1,35 @@
-/// DuckDice Bot API Client
-/// 
-/// This module provides a client for interacting with the DuckDice Bot API
-/// as documented at https://duckdice.io/bot-api
+//! DuckDice Bot API Client
+//!
+//! This module provides a client for interacting with the DuckDice Bot API
+//! as documented at https://duckdice.io/bot-api
 
 use log::{debug, error, info};
-use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// Error types for DuckDice API operations
+// The names are part of the JNI-facing API, so they keep their suffix.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum DuckDiceError {
     NetworkError(String),
     ApiError(String),
     JsonError(String),
     AuthenticationError,
-    RateLimitError(u64), // seconds to wait
+    RateLimitError(Option<u64>), // seconds to wait, if the server said
+}
+
+/// Attempts at a rate-limited bet before giving up
+pub const MAX_BET_ATTEMPTS: u32 = 3;
+
+/// Longest wait before retrying a rate-limited bet, however long the server asks for
+pub const MAX_BET_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Wait before retrying a bet refused on `attempt` (1-based): the server's
+/// `retry-after` when present, otherwise 1s, 2s, 4s, ..., at most `MAX_BET_BACKOFF`
+pub fn rate_limit_backoff(retry_after: Option<u64>, attempt: u32) -> Duration {
+    Duration::from_secs(retry_after.unwrap_or(1 << attempt.saturating_sub(1).min(6))).min(MAX_BET_BACKOFF)
 }
 
 impl std::fmt::Display for DuckDiceError {
@@ -25,7 +39,8 @@ impl std::fmt::Display for DuckDiceError {
             DuckDiceError::ApiError(msg) => write!(f, "API error: {}", msg),
             DuckDiceError::JsonError(msg) => write!(f, "JSON error: {}", msg),
             DuckDiceError::AuthenticationError => write!(f, "Authentication failed"),
-            DuckDiceError::RateLimitError(secs) => write!(f, "Rate limited, retry after {} seconds", secs),
+            DuckDiceError::RateLimitError(Some(secs)) => write!(f, "Rate limited, retry after {} seconds", secs),
+            DuckDiceError::RateLimitError(None) => write!(f, "Rate limited"),
         }
     }
 }
@@ -45,6 +60,8 @@ impl From<serde_json::Error> for DuckDiceError {
 }
 
 /// User information response
+// Mirrors the API reply; not every field is used by the app.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UserInfo {
@@ -65,7 +82,7 @@ pub struct Balance {
 }
 
 /// Bet request payload
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BetRequest {
     pub symbol: String,
@@ -85,6 +102,8 @@ pub struct BetResponse {
 }
 
 /// Bet information
+// Mirrors the API reply; not every field is used by the app.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BetInfo {
@@ -102,6 +121,8 @@ pub struct BetInfo {
 }
 
 /// User info in bet response
+// Mirrors the API reply; not every field is used by the app.
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UserInBet {
@@ -111,6 +132,7 @@ pub struct UserInBet {
 }
 
 /// DuckDice Bot API Client
+#[derive(Clone)]
 pub struct DuckDiceClient {
     client: reqwest::Client,
     api_key: String,
@@ -195,6 +217,7 @@ impl DuckDiceClient {
     }
 
     /// Randomize client seed
+    #[allow(dead_code)] // Not exposed to the app yet
     pub async fn randomize_seed(&self, client_seed: String) -> Result<(), DuckDiceError> {
         let url = format!("{}/randomize?api_key={}", self.base_url, self.api_key);
         
@@ -229,11 +252,11 @@ impl DuckDiceClient {
             if let Some(retry_after) = response.headers().get("retry-after") {
                 if let Ok(seconds_str) = retry_after.to_str() {
                     if let Ok(seconds) = seconds_str.parse::<u64>() {
-                        return Err(DuckDiceError::RateLimitError(seconds));
+                        return Err(DuckDiceError::RateLimitError(Some(seconds)));
                     }
                 }
             }
-            return Err(DuckDiceError::RateLimitError(60)); // Default to 60 seconds
+            return Err(DuckDiceError::RateLimitError(None));
         }
         Ok(())
     }
@@ -257,4 +280,13 @@ mod tests {
         assert!(json.contains("\"symbol\":\"BTC\""));
         assert!(json.contains("\"chance\":50"));
     }
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        assert_eq!(rate_limit_backoff(Some(30), 1), Duration::from_secs(30));
+        assert_eq!(rate_limit_backoff(None, 1), Duration::from_secs(1));
+        assert_eq!(rate_limit_backoff(None, 2), Duration::from_secs(2));
+        assert_eq!(rate_limit_backoff(None, 3), Duration::from_secs(4));
+        assert_eq!(rate_limit_backoff(Some(3600), 1), MAX_BET_BACKOFF);
+    }
 }
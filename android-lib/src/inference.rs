@@ -0,0 +1,123 @@
+//! On-device prediction
+//!
+//! Predictions go through the same `inference` module as the desktop bot, so identical
+//! history gives identical predictions on both.
+
+use burn::prelude::*;
+use burn::record::{CompactRecorder, Recorder};
+use freebitco_in::inference::{self, Prediction};
+use freebitco_in::model::Model;
+use freebitco_in::normalizer::Normalizer;
+use freebitco_in::sites::{BetError, BetResult};
+use freebitco_in::training::TrainingConfig;
+use freebitco_in::util::BucketAnchor;
+
+/// CPU backend used on Android, where Vulkan support varies between devices
+pub type AndroidBackend = burn::backend::NdArray;
+
+/// Loads the trained model from `model_dir`, laid out as training saves it
+///
+/// The directory holds `config.json`, the `model` record and, for a model trained with
+/// `normalize_features`, the normalizer, as on the desktop.
+pub fn load_model(
+    model_dir: &str,
+    device: &<AndroidBackend as Backend>::Device,
+) -> Result<(Model<AndroidBackend>, Option<Normalizer>), BetError> {
+    let config = TrainingConfig::load(format!("{model_dir}/config.json"))
+        .map_err(|e| BetError::ModelError(format!("Failed to load model config: {e}")))?;
+
+    let record = CompactRecorder::new()
+        .load(format!("{model_dir}/model").into(), device)
+        .map_err(|e| BetError::ModelError(format!("Failed to load trained model: {e}")))?;
+    let normalizer = inference::load_normalizer(model_dir, &config)?;
+
+    Ok((config.model.init(device).load_record(record), normalizer))
+}
+
+/// Predicts the next roll from the latest `model.history_size()` rolls of `history`.
+///
+/// Returns `None` until the history fills one model window.
+pub fn predict<B: Backend>(
+    model: &Model<B>,
+    normalizer: Option<&Normalizer>,
+    history: &[BetResult],
+    device: &B::Device,
+) -> Option<Result<Prediction, BetError>> {
+    let window = history.get(history.len().checked_sub(model.history_size())?..)?;
+
+    Some(inference::predict_next(
+        model,
+        window,
+        normalizer,
+        BucketAnchor::default(),
+        device,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use freebitco_in::inference::FeatureCache;
+    use freebitco_in::model::ModelConfig;
+    use freebitco_in::util::{Roll, HISTORY_SIZE};
+
+    fn roll(nonce: u32) -> BetResult {
+        BetResult {
+            hash_previous_roll: format!("{:064x}", nonce),
+            hash_next_roll: format!("{:064x}", nonce + 1),
+            client_seed: "BeO2jZRd4nidPz4U40e2G7hT22s9GA".to_string(),
+            nonce,
+            symbol: "BTC".to_string(),
+            result: nonce.is_multiple_of(2),
+            is_high: nonce.is_multiple_of(3),
+            rolled_high: Roll::clamped(nonce * 611).is_high(),
+            number: Roll::clamped(nonce * 611),
+            threshold: 0,
+            chance: 50.,
+            payout: 2.,
+            bet_amount: 0.00000001,
+            win_amount: 0.00000001,
+        }
+    }
+
+    #[test]
+    fn test_missing_model_is_an_error() {
+        let device = Default::default();
+
+        assert!(matches!(
+            load_model("/nonexistent/model/dir", &device),
+            Err(BetError::ModelError(_))
+        ));
+    }
+
+    #[test]
+    fn test_prediction_matches_desktop_path() {
+        let device = Default::default();
+        // The decoder blends sampled noise into every forward pass, so only the model
+        // without it is deterministic enough to compare outputs exactly.
+        let model = ModelConfig::new()
+            .with_use_decoder(false)
+            .init::<AndroidBackend>(&device);
+        let history = (0..HISTORY_SIZE as u32 + 5)
+            .map(roll)
+            .collect::<Vec<BetResult>>();
+
+        assert!(predict(&model, None, &history[..HISTORY_SIZE - 1], &device).is_none());
+
+        let mut features = FeatureCache::<AndroidBackend>::default();
+        for end in HISTORY_SIZE..=history.len() {
+            let android = predict(&model, None, &history[..end], &device).unwrap().unwrap();
+
+            let window = &history[end - HISTORY_SIZE..end];
+            let desktop = inference::predict_encoded(
+                &model,
+                features.encode_window(window, None, &device),
+                BucketAnchor::default(),
+                &device,
+            )
+            .unwrap();
+
+            assert_eq!(android, desktop, "history of {end} rolls");
+        }
+    }
+}
@@ -1,11 +1,17 @@
 mod duckdice_api;
+pub mod inference;
 
-use duckdice_api::{BetRequest, DuckDiceClient, DuckDiceError};
+use duckdice_api::{rate_limit_backoff, BetInfo, BetRequest, DuckDiceClient, DuckDiceError, MAX_BET_ATTEMPTS};
+use freebitco_in::model::Model;
+use freebitco_in::normalizer::Normalizer;
+use freebitco_in::sites::{push_history, BetResult};
+use freebitco_in::util::{Roll, HISTORY_SIZE};
+use inference::AndroidBackend;
 use jni::objects::{JClass, JString};
 use jni::sys::{jboolean, jfloat};
 use jni::JNIEnv;
 use log::{debug, error, info, warn};
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 
 // Global state for the Android app
 lazy_static::lazy_static! {
@@ -17,6 +23,46 @@ lazy_static::lazy_static! {
         .expect("Failed to create tokio runtime");
 }
 
+/// Which DuckDice balance bets are funded from
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum BalanceSource {
+    Main,
+    #[default]
+    Faucet,
+    Affiliate,
+}
+
+impl BalanceSource {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "main" => Some(Self::Main),
+            "faucet" => Some(Self::Faucet),
+            "affiliate" => Some(Self::Affiliate),
+            _ => None,
+        }
+    }
+
+    fn select<'a>(&self, balance: &'a duckdice_api::Balance) -> Option<&'a String> {
+        match self {
+            Self::Main => balance.main.as_ref(),
+            Self::Faucet => balance.faucet.as_ref(),
+            Self::Affiliate => balance.affiliate.as_ref(),
+        }
+    }
+}
+
+/// Lock the global state, recovering it if a previous holder panicked.
+///
+/// A poisoned lock would otherwise turn every later JNI call into a panic and
+/// disable the bot until the app is restarted.
+fn lock_state() -> MutexGuard<'static, AppState> {
+    STATE.lock().unwrap_or_else(|poisoned| {
+        warn!("Recovering from poisoned state lock");
+        STATE.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
 #[derive(Default)]
 struct AppState {
     initialized: bool,
@@ -29,8 +75,16 @@ struct AppState {
     balance: f64,
     total_bets: u32,
     wins: u32,
-    use_faucet: bool,
+    balance_source: BalanceSource,
     api_client: Option<DuckDiceClient>,
+    /// Trained model, or `None` to fall back to pseudo-predictions
+    model: Option<Model<AndroidBackend>>,
+    /// Normalizer the model was trained with, if any
+    normalizer: Option<Normalizer>,
+    /// Latest settled bets, the model's input window
+    history: Vec<BetResult>,
+    /// Set once a bet exceeded the balance; betting stays halted until reconfigured
+    out_of_funds: bool,
 }
 
 impl AppState {
@@ -49,10 +103,49 @@ impl AppState {
         self.api_client = Some(DuckDiceClient::new(self.api_key.clone())?);
         Ok(())
     }
+
+    /// Records a settled bet in the model's input window
+    ///
+    /// DuckDice only reports the hash of the seed a bet was rolled with, so the previous
+    /// bet's hash stands in for the previous roll's, as on the desktop.
+    fn record_bet(&mut self, bet: &BetInfo) {
+        let previous_hash = self
+            .history
+            .last()
+            .map(|roll| roll.hash_next_roll.clone())
+            .unwrap_or_default();
+        let number = Roll::clamped(bet.number);
+
+        let bet_result = BetResult {
+            hash_previous_roll: previous_hash,
+            hash_next_roll: bet.hash.clone(),
+            client_seed: String::new(),
+            nonce: bet.nonce as u32,
+            symbol: bet.symbol.clone(),
+            result: bet.result,
+            is_high: bet.choice.starts_with('>'),
+            rolled_high: number.is_high(),
+            number,
+            threshold: 0,
+            chance: bet.chance as f32,
+            payout: bet.payout as f32,
+            bet_amount: bet.bet_amount.parse().unwrap_or(0.),
+            // Positive for both outcomes, like the desktop sites record it.
+            win_amount: bet.profit.parse::<f32>().unwrap_or(0.).abs(),
+        };
+        let window = self.model.as_ref().map_or(HISTORY_SIZE, |model| model.history_size());
+        push_history(&mut self.history, bet_result, window);
+    }
+
+    /// Pseudo-prediction used while no model is loaded or the history is too short
+    fn pseudo_prediction(&mut self) {
+        self.prediction = 50.0 + (rand::random::<f32>() * 10.0 - 5.0);
+        self.confidence = 0.5 + rand::random::<f32>() * 0.3;
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn JNI_OnLoad(vm: jni::JavaVM, _reserved: *mut std::ffi::c_void) -> jni::sys::jint {
+pub extern "C" fn JNI_OnLoad(_vm: jni::JavaVM, _reserved: *mut std::ffi::c_void) -> jni::sys::jint {
     // Initialize Android logger
     android_logger::init_once(
         android_logger::Config::default()
@@ -66,12 +159,12 @@ pub extern "C" fn JNI_OnLoad(vm: jni::JavaVM, _reserved: *mut std::ffi::c_void)
 
 #[no_mangle]
 pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_initialize(
-    env: JNIEnv,
+    _env: JNIEnv,
     _class: JClass,
 ) {
     info!("Initializing PredictiveRolls native library");
     
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
     state.initialized = true;
     
     info!("Native library initialized successfully");
@@ -79,38 +172,57 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_initialize(
 
 #[no_mangle]
 pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_configure(
-    env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
     site: JString,
     api_key: JString,
     currency: JString,
     strategy: JString,
+    model_dir: JString,
 ) {
     let site_str: String = env
-        .get_string(site)
+        .get_string(&site)
         .expect("Couldn't get site string")
         .into();
     let api_key_str: String = env
-        .get_string(api_key)
+        .get_string(&api_key)
         .expect("Couldn't get API key string")
         .into();
     let currency_str: String = env
-        .get_string(currency)
+        .get_string(&currency)
         .expect("Couldn't get currency string")
         .into();
     let strategy_str: String = env
-        .get_string(strategy)
+        .get_string(&strategy)
         .expect("Couldn't get strategy string")
         .into();
+    let model_dir_str: String = env
+        .get_string(&model_dir)
+        .expect("Couldn't get model directory string")
+        .into();
 
     info!("Configuring: site={}, currency={}, strategy={}", site_str, currency_str, strategy_str);
     
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
     state.site = site_str.clone();
     state.api_key = api_key_str;
     state.currency = currency_str;
     state.strategy = strategy_str;
-    state.use_faucet = true; // Default to faucet balance for safety
+    state.balance_source = BalanceSource::Faucet; // Default to faucet balance for safety
+    state.history.clear();
+    state.out_of_funds = false;
+    
+    // Inference runs on the CPU; phones lack a dependable Vulkan/wgpu setup
+    (state.model, state.normalizer) = match inference::load_model(&model_dir_str, &Default::default()) {
+        Ok((model, normalizer)) => {
+            info!("Model loaded from {}", model_dir_str);
+            (Some(model), normalizer)
+        }
+        Err(e) => {
+            error!("{} in {}, falling back to pseudo-predictions", e, model_dir_str);
+            (None, None)
+        }
+    };
     
     // Initialize API client based on site
     if site_str == "duck_dice" || site_str == "duckdice" {
@@ -127,11 +239,7 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_configure(
                             // Find balance for the configured currency
                             for balance in user_info.balances {
                                 if balance.currency == state.currency {
-                                    let balance_str = if state.use_faucet {
-                                        balance.faucet.as_ref()
-                                    } else {
-                                        balance.main.as_ref()
-                                    };
+                                    let balance_str = state.balance_source.select(&balance);
                                     
                                     if let Some(bal_str) = balance_str {
                                         state.balance = bal_str.parse().unwrap_or(0.0);
@@ -159,23 +267,62 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_configure(
     debug!("Configuration complete");
 }
 
+#[no_mangle]
+pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_setBalanceSource(
+    mut env: JNIEnv,
+    _class: JClass,
+    source: JString,
+) -> jboolean {
+    let source_str: String = env
+        .get_string(&source)
+        .expect("Couldn't get balance source string")
+        .into();
+
+    match BalanceSource::parse(&source_str) {
+        Some(balance_source) => {
+            info!("Using {:?} balance", balance_source);
+            lock_state().balance_source = balance_source;
+            1
+        }
+        None => {
+            warn!("Unknown balance source '{}', keeping the current one", source_str);
+            0
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_getPrediction(
     _env: JNIEnv,
     _class: JClass,
 ) -> jfloat {
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
+    let state = &mut *state;
+    
+    let prediction = state.model.as_ref().and_then(|model| {
+        match inference::predict(model, state.normalizer.as_ref(), &state.history, &Default::default()) {
+            Some(Ok(prediction)) => Some(prediction),
+            Some(Err(e)) => {
+                error!("Inference failed: {}", e);
+                None
+            }
+            None => {
+                debug!("{} of {} rolls seen, model not used yet", state.history.len(), model.history_size());
+                None
+            }
+        }
+    });
     
-    // TODO: Integrate with the full Burn-based ML model from the main crate
-    // For now, generate a pseudo-prediction for demonstration purposes
-    // Real implementation should:
-    // 1. Load the trained model from MODEL_DIR
-    // 2. Prepare input data from historical rolls
-    // 3. Run inference through the neural network
-    // 4. Return the model's prediction
-    state.prediction = 50.0 + (rand::random::<f32>() * 10.0 - 5.0);
+    match prediction {
+        // The app works in percent, the model on the 0-9999 roll scale
+        Some(prediction) => {
+            state.prediction = prediction.roll / 100.0;
+            state.confidence = prediction.confidence / 100.0;
+        }
+        None => state.pseudo_prediction(),
+    }
     
-    debug!("Generated prediction: {}", state.prediction);
+    debug!("Prediction: {}, confidence: {}", state.prediction, state.confidence);
     state.prediction
 }
 
@@ -184,14 +331,8 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_getConfidence(
     _env: JNIEnv,
     _class: JClass,
 ) -> jfloat {
-    let mut state = STATE.lock().unwrap();
-    
-    // TODO: Calculate actual confidence from ML model output
-    // Generate confidence value for demonstration
-    state.confidence = 0.5 + rand::random::<f32>() * 0.3;
-    
-    debug!("Generated confidence: {}", state.confidence);
-    state.confidence
+    // Computed alongside the prediction by getPrediction
+    lock_state().confidence
 }
 
 #[no_mangle]
@@ -201,12 +342,15 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_placeBet(
     prediction: jfloat,
     confidence: jfloat,
 ) -> jboolean {
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
     
-    state.total_bets += 1;
+    if state.out_of_funds {
+        warn!("Betting halted: insufficient funds, balance {}", state.balance);
+        return 0;
+    }
     
     // Use real DuckDice API if client is initialized
-    if let Some(client) = &state.api_client {
+    if let Some(client) = state.api_client.clone() {
         // Determine bet parameters based on prediction and confidence
         let is_high = prediction > 50.0;
         let chance = if confidence > 0.7 {
@@ -224,17 +368,47 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_placeBet(
             0.00000050 // Lower bet
         };
         
+        if bet_amount > state.balance {
+            error!("Insufficient funds: the bet needs {:.8} but the balance is {:.8}, stopping",
+                bet_amount, state.balance);
+            info!("Final balance: {:.8} {}", state.balance, state.currency);
+            state.out_of_funds = true;
+            return 0;
+        }
+        
         let bet_request = BetRequest {
             symbol: state.currency.clone(),
             chance,
             is_high,
             amount: bet_amount,
-            faucet: if state.use_faucet { Some(true) } else { None },
+            // Only faucet bets are flagged; main and affiliate bets omit the field
+            faucet: if state.balance_source == BalanceSource::Faucet { Some(true) } else { None },
         };
         
-        match RUNTIME.block_on(client.place_bet(bet_request)) {
+        // Unlock the state while the bet is sent and rate limits are waited out, so the
+        // app's other calls are not blocked meanwhile
+        drop(state);
+        
+        // Retry rate-limited bets, which never executed, with a bounded backoff
+        let mut attempt = 1;
+        let result = loop {
+            match RUNTIME.block_on(client.place_bet(bet_request.clone())) {
+                Err(DuckDiceError::RateLimitError(retry_after)) if attempt < MAX_BET_ATTEMPTS => {
+                    let delay = rate_limit_backoff(retry_after, attempt);
+                    warn!("Rate limited, retrying in {:?}", delay);
+                    RUNTIME.block_on(tokio::time::sleep(delay));
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+        
+        let mut state = lock_state();
+        match result {
             Ok(response) => {
                 let won = response.bet.result;
+                state.total_bets += 1;
+                state.record_bet(&response.bet);
                 
                 if won {
                     state.wins += 1;
@@ -255,12 +429,6 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_placeBet(
             Err(e) => {
                 error!("Bet failed: {}", e);
                 
-                // Handle rate limiting
-                if let DuckDiceError::RateLimitError(seconds) = e {
-                    warn!("Rate limited, waiting {} seconds", seconds);
-                    // In a real app, we should pause betting and notify the user
-                }
-                
                 // Return false on error
                 return 0;
             }
@@ -270,6 +438,7 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_placeBet(
     // Fallback to simulation if no API client
     warn!("No API client initialized, using simulation mode");
     let won = rand::random::<f32>() < confidence;
+    state.total_bets += 1;
     
     if won {
         state.wins += 1;
@@ -288,18 +457,14 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_getBalance(
     env: JNIEnv,
     _class: JClass,
 ) -> jni::sys::jstring {
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
     
     // Optionally fetch fresh balance from API
     if let Some(client) = &state.api_client {
         if let Ok(user_info) = RUNTIME.block_on(client.get_user_info()) {
             for balance in user_info.balances {
                 if balance.currency == state.currency {
-                    let balance_str = if state.use_faucet {
-                        balance.faucet.as_ref()
-                    } else {
-                        balance.main.as_ref()
-                    };
+                    let balance_str = state.balance_source.select(&balance);
                     
                     if let Some(bal_str) = balance_str {
                         state.balance = bal_str.parse().unwrap_or(state.balance);
@@ -322,7 +487,7 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_getWinRate(
     _env: JNIEnv,
     _class: JClass,
 ) -> jfloat {
-    let state = STATE.lock().unwrap();
+    let state = lock_state();
     state.win_rate()
 }
 
@@ -333,7 +498,7 @@ pub extern "C" fn Java_com_predictiverolls_PredictiveRollsNative_cleanup(
 ) {
     info!("Cleaning up native library");
     
-    let mut state = STATE.lock().unwrap();
+    let mut state = lock_state();
     *state = AppState::default();
     
     info!("Cleanup complete");
@@ -18,6 +18,7 @@ import androidx.security.crypto.MasterKeys;
 import com.google.android.material.appbar.MaterialToolbar;
 import com.google.android.material.button.MaterialButton;
 
+import java.io.File;
 import java.io.IOException;
 import java.security.GeneralSecurityException;
 import java.util.concurrent.ExecutorService;
@@ -182,7 +183,8 @@ public class MainActivity extends AppCompatActivity {
         executorService.execute(() -> {
             try {
                 // Configure the native library
-                PredictiveRollsNative.configure(site, apiKey, currency, strategy);
+                String modelDir = new File(getFilesDir(), "model").getAbsolutePath();
+                PredictiveRollsNative.configure(site, apiKey, currency, strategy, modelDir);
                 
                 mainHandler.post(() -> {
                     startStopButton.setEnabled(true);
@@ -18,8 +18,18 @@ public class PredictiveRollsNative {
      * @param apiKey The API key for authentication
      * @param currency The currency to use (e.g., "BTC", "ETH")
      * @param strategy The betting strategy (e.g., "None", "AiFight", "BlaksRunner", "MyStrategy")
+     * @param modelDir Directory holding the trained model's config.json and model files;
+     *                 pseudo-predictions are used when it cannot be loaded
      */
-    public static native void configure(String site, String apiKey, String currency, String strategy);
+    public static native void configure(String site, String apiKey, String currency, String strategy, String modelDir);
+    
+    /**
+     * Select which balance bets are funded from. Call after configure(), which resets it to "faucet".
+     * 
+     * @param source One of "main", "faucet" or "affiliate"
+     * @return true if the source was recognized and applied
+     */
+    public static native boolean setBalanceSource(String source);
     
     /**
      * Get the current prediction from the ML model.
@@ -0,0 +1,214 @@
+//! Backtesting of strategies against recorded rolls.
+//!
+//! Each recorded roll settles the bet the strategy asks for, with the previous roll as
+//! the prediction as in `simulation`, so a strategy's accounting can be checked against
+//! a known sequence without a site or a model. `SessionStats` summarizes any sequence
+//! of settled bets, including full sessions against the fake server.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::dataset::BetResultCsvRecord;
+use crate::sites::BetResult;
+use crate::strategies::Strategy;
+use crate::util::Roll;
+
+/// Accounting of one backtest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktestReport {
+    pub bets: u64,
+    pub wins: u64,
+    pub profit: f32,
+    pub final_balance: f32,
+    /// Largest fall of the balance from its highest point so far.
+    pub max_drawdown: f32,
+    /// Stopped early because the strategy asked for more than the balance.
+    pub busted: bool,
+}
+
+/// Running statistics of a session, fed one settled bet at a time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionStats {
+    pub bets: u64,
+    pub wins: u64,
+    pub profit: f32,
+    peak_profit: f32,
+    /// Largest fall of the profit from its highest point so far.
+    pub max_drawdown: f32,
+    losing_streak: u64,
+    pub longest_losing_streak: u64,
+}
+
+impl SessionStats {
+    /// Records a settled bet, with `profit` the session profit after it.
+    pub fn record(&mut self, won: bool, profit: f32) {
+        self.bets += 1;
+        if won {
+            self.wins += 1;
+            self.losing_streak = 0;
+        } else {
+            self.losing_streak += 1;
+            self.longest_losing_streak = self.longest_losing_streak.max(self.losing_streak);
+        }
+
+        self.profit = profit;
+        self.peak_profit = self.peak_profit.max(profit);
+        self.max_drawdown = self.max_drawdown.max(self.peak_profit - profit);
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        if self.bets == 0 {
+            return 0.;
+        }
+
+        self.wins as f32 / self.bets as f32
+    }
+
+    /// Summary of the session, which took `elapsed`.
+    pub fn summary(&self, strategy: String, elapsed: Duration, busted: bool) -> SessionSummary {
+        SessionSummary {
+            strategy,
+            bets: self.bets,
+            wins: self.wins,
+            win_rate: self.win_rate(),
+            final_profit: self.profit,
+            max_drawdown: self.max_drawdown,
+            longest_losing_streak: self.longest_losing_streak,
+            bets_per_second: self.bets as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            busted,
+        }
+    }
+}
+
+/// Machine-readable result of one strategy's session.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub strategy: String,
+    pub bets: u64,
+    pub wins: u64,
+    pub win_rate: f32,
+    pub final_profit: f32,
+    pub max_drawdown: f32,
+    pub longest_losing_streak: u64,
+    pub bets_per_second: f64,
+    /// Stopped early because the balance could not cover the next bet.
+    pub busted: bool,
+}
+
+/// Whether a bet at `chance` percent on the `high` or low side wins on `roll`.
+pub fn bet_wins(roll: Roll, chance: f32, high: bool) -> bool {
+    let threshold = (chance * 100.).round() as u32;
+    if high {
+        roll.value() > Roll::MAX - threshold
+    } else {
+        roll.value() < threshold
+    }
+}
+
+/// Replays `records` through `strategy`, one bet per recorded roll.
+pub fn replay(
+    strategy: &mut dyn Strategy,
+    records: &[BetResultCsvRecord],
+    start_balance: f32,
+) -> BacktestReport {
+    strategy.set_balance(start_balance);
+
+    let mut stats = SessionStats::default();
+    let mut balance = start_balance;
+    let mut busted = false;
+    let mut prediction = 5000.;
+
+    for record in records {
+        let (bet, multiplier, chance, high) = strategy.get_next_bet(prediction, 0.);
+        if bet > balance || bet <= 0. {
+            busted = true;
+            break;
+        }
+
+        let number = Roll::clamped(record.rolled_number);
+        let result = bet_wins(number, chance, high);
+        // Positive for both outcomes, as sites pass it to `on_lose`.
+        let win_amount = if result { bet * (multiplier - 1.) } else { bet };
+        let bet_result = BetResult {
+            hash_previous_roll: record.server_seed_hash_previous_roll.clone(),
+            hash_next_roll: record.server_seed_hash_next_roll.clone(),
+            client_seed: record.client_seed.clone(),
+            nonce: record.nonce as u32,
+            symbol: String::new(),
+            result,
+            is_high: high,
+            rolled_high: number.is_high(),
+            number,
+            threshold: 0,
+            chance,
+            payout: multiplier,
+            bet_amount: bet,
+            win_amount,
+        };
+
+        if result {
+            balance += win_amount;
+            strategy.on_win(&bet_result);
+        } else {
+            balance -= win_amount;
+            strategy.on_lose(&bet_result);
+        }
+        stats.record(result, balance - start_balance);
+
+        prediction = number.value() as f32;
+    }
+
+    BacktestReport {
+        bets: stats.bets,
+        wins: stats.wins,
+        profit: balance - start_balance,
+        final_balance: balance,
+        max_drawdown: stats.max_drawdown,
+        busted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bet_wins_at_the_chance_boundaries() {
+        let roll = |value| Roll::new(value).unwrap();
+
+        assert!(bet_wins(roll(4999), 50., false));
+        assert!(!bet_wins(roll(5000), 50., false));
+        assert!(bet_wins(roll(5000), 50., true));
+        assert!(!bet_wins(roll(4999), 50., true));
+        assert!(bet_wins(roll(9999), 0.01, true));
+        assert!(!bet_wins(roll(9998), 0.01, true));
+    }
+
+    #[test]
+    fn test_session_stats_track_streaks_and_drawdown() {
+        let mut stats = SessionStats::default();
+        for (won, profit) in [
+            (true, 1.),
+            (false, 0.),
+            (false, -1.),
+            (true, 0.5),
+            (false, -0.5),
+            (false, -1.5),
+            (false, -2.),
+            (true, 3.),
+        ] {
+            stats.record(won, profit);
+        }
+
+        assert_eq!(stats.bets, 8);
+        assert_eq!(stats.wins, 3);
+        assert_eq!(stats.longest_losing_streak, 3);
+        assert_eq!(stats.max_drawdown, 3.);
+
+        let summary = stats.summary("Flat".to_string(), Duration::from_secs(2), false);
+        assert_eq!(summary.win_rate, 0.375);
+        assert_eq!(summary.final_profit, 3.);
+        assert_eq!(summary.bets_per_second, 4.);
+    }
+}
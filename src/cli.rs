@@ -0,0 +1,137 @@
+//! Command-line argument handling.
+//!
+//! Configuration lives in `config.toml`; the command line only selects what to run.
+
+/// The mode the application runs in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Command {
+    /// Log into the configured site and bet with the trained model.
+    #[default]
+    Run,
+    /// Estimate risk of ruin over many simulated sessions.
+    MonteCarlo,
+    /// Place minimum bets only to record rolls for training.
+    Collect,
+    /// Record rolls for training from DuckDice's feed of everyone's bets, placing none.
+    DataCollector,
+    /// Check the model, fake site and inference offline, without a config file.
+    SelfTest,
+    /// Compare strategies with the model against the fake server, printing JSON stats.
+    Backtest,
+}
+
+#[derive(Debug, Default)]
+pub struct Cli {
+    pub command: Command,
+    /// Start with a freshly initialized model when no trained model can be loaded.
+    pub allow_untrained: bool,
+    /// Disable ANSI colors in the console output.
+    pub no_color: bool,
+}
+
+impl Cli {
+    /// Parses the process arguments.
+    pub fn parse() -> Result<Self, String> {
+        Self::from_args(std::env::args().skip(1))
+    }
+
+    /// Parses arguments, excluding the program name.
+    pub fn from_args<I, S>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut cli = Self::default();
+
+        for arg in args {
+            match arg.as_ref() {
+                "run" => cli.command = Command::Run,
+                "montecarlo" => cli.command = Command::MonteCarlo,
+                "collect" => cli.command = Command::Collect,
+                "data-collector" => cli.command = Command::DataCollector,
+                "selftest" => cli.command = Command::SelfTest,
+                "backtest" | "--backtest" => cli.command = Command::Backtest,
+                "--allow-untrained" => cli.allow_untrained = true,
+                "--no-color" => cli.no_color = true,
+                other => return Err(format!("Unknown argument: {other}")),
+            }
+        }
+
+        Ok(cli)
+    }
+
+    /// Whether console output should be colored.
+    ///
+    /// Colors are off with `--no-color`, when `NO_COLOR` is set, or when stdout is not a
+    /// terminal (piped or redirected to a file), unless `CLICOLOR_FORCE` asks for them.
+    pub fn use_color(
+        &self,
+        no_color_env: bool,
+        force_color_env: bool,
+        stdout_is_tty: bool,
+    ) -> bool {
+        if self.no_color || no_color_env {
+            return false;
+        }
+
+        force_color_env || stdout_is_tty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subcommands() {
+        assert_eq!(
+            Cli::from_args(Vec::<String>::new()).unwrap().command,
+            Command::Run
+        );
+        assert_eq!(
+            Cli::from_args(["montecarlo"]).unwrap().command,
+            Command::MonteCarlo
+        );
+        assert_eq!(
+            Cli::from_args(["collect"]).unwrap().command,
+            Command::Collect
+        );
+        assert_eq!(
+            Cli::from_args(["data-collector"]).unwrap().command,
+            Command::DataCollector
+        );
+        assert_eq!(
+            Cli::from_args(["selftest"]).unwrap().command,
+            Command::SelfTest
+        );
+        assert_eq!(
+            Cli::from_args(["backtest"]).unwrap().command,
+            Command::Backtest
+        );
+        assert_eq!(
+            Cli::from_args(["--backtest"]).unwrap().command,
+            Command::Backtest
+        );
+        assert!(Cli::from_args(["bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let cli = Cli::from_args(["run", "--allow-untrained"]).unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert!(cli.allow_untrained);
+        assert!(!Cli::from_args(["run"]).unwrap().allow_untrained);
+    }
+
+    #[test]
+    fn test_use_color() {
+        let cli = Cli::default();
+        assert!(cli.use_color(false, false, true));
+        assert!(!cli.use_color(false, false, false));
+        assert!(!cli.use_color(true, false, true));
+        assert!(cli.use_color(false, true, false));
+
+        let cli = Cli::from_args(["--no-color"]).unwrap();
+        assert!(!cli.use_color(false, true, true));
+    }
+}
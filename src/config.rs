@@ -3,24 +3,118 @@
 //! This module handles loading and validating configuration from TOML files,
 //! including site credentials and betting strategies.
 
+use std::collections::HashMap;
+
 use crate::currency::Currency;
+use crate::dataset::CsvFormat;
+use crate::sites::latency::DelayJitter;
+use crate::sites::limiter::RequestLimiter;
+use crate::sites::retry::RetryPolicy;
+use crate::sites::seeds::RotationPolicy;
+use crate::sites::signing::RequestSigner;
+use crate::sites::tor::{CircuitRenewal, TorController};
+use crate::sites::totp::Totp;
+use crate::sites::Sites;
+use crate::util::BucketAnchor;
 use serde::Deserialize;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
 pub enum TomlStrategies {
     AiFight,
     BlaksRunner,
     MyStrategy,
+    Flat,
+    Kelly,
+    FollowLast,
+    OppositeLast,
     #[default]
     None,
 }
 
+/// Which site balance bets are funded from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BalanceSource {
+    Main,
+    #[default]
+    Faucet,
+    Affiliate,
+}
+
+/// One API key, or a list of keys for accounts that are switched between when the
+/// active one is rate limited.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ApiKeys {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl ApiKeys {
+    pub fn keys(&self) -> &[String] {
+        match self {
+            ApiKeys::Single(key) => std::slice::from_ref(key),
+            ApiKeys::Multiple(keys) => keys,
+        }
+    }
+
+    /// True when no key, or an empty key, is configured.
+    pub fn is_empty(&self) -> bool {
+        self.keys().is_empty() || self.keys().iter().any(|key| key.is_empty())
+    }
+}
+
+/// How the model history is filled before real bets are placed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupMode {
+    /// Places minimum bets at 50% until the history is full.
+    #[default]
+    MinBet,
+    /// Fills the history at login from the feed of everyone's bets, falling back to
+    /// `MinBet` for whatever the feed did not provide.
+    Feed,
+    /// Fills the history from the account's last bets at login, falling back to
+    /// `MinBet` when they cannot be fetched.
+    History,
+}
+
+/// What the bot bets on when the model fails to produce a prediction.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InferenceErrorMode {
+    /// Places no bet until the model predicts again.
+    #[default]
+    Skip,
+    /// Bets on the middle of the roll range, i.e. roughly 50/50.
+    Neutral,
+    /// Bets on the last prediction the model made.
+    ReuseLast,
+}
+
+/// What the bot does when a settled bet skipped nonces.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NonceGapMode {
+    /// Logs a warning and bets on.
+    #[default]
+    Warn,
+    /// Stops betting with `BetError::NonceMismatch`.
+    Halt,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CryptoGamesConfig {
     pub enabled: bool,
     pub api_key: String,
     pub currency: Currency,
     pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    #[serde(default)]
+    pub min_bet_override: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,14 +123,672 @@ pub struct FreeBitcoInConfig {
     pub btc_address: String,
     pub password: String,
     pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    #[serde(default)]
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    #[serde(default)]
+    pub min_chance: Option<f32>,
+    /// File the login session is saved to, so restarts reuse it instead of logging in.
+    #[serde(default)]
+    pub session_file: Option<String>,
+    /// Two-factor code for the login, for accounts with 2FA enabled.
+    #[serde(default)]
+    pub tfa_code: Option<String>,
+    /// Base32 secret of the account's authenticator, to generate the two-factor code
+    /// at each login instead of `tfa_code`.
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    /// Reward-point bonus ids to activate whenever the account has the points.
+    #[serde(default)]
+    pub reward_bonuses: Vec<String>,
+    /// Bets between attempts to activate `reward_bonuses`; 100 when unset.
+    #[serde(default)]
+    pub reward_check_every: Option<u64>,
+    /// Claim the free roll every hour, paying with reward points instead of a captcha.
+    #[serde(default)]
+    pub claim_faucet: bool,
+    /// Client seed rotation and history.
+    #[serde(flatten)]
+    pub seeds: SeedConfig,
+    /// Stop betting when a roll does not match the server seed revealed after it,
+    /// instead of only logging a warning.
+    #[serde(default)]
+    pub halt_on_unverified_roll: bool,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DuckDiceConfig {
     pub enabled: bool,
+    pub api_key: ApiKeys,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    #[serde(default)]
+    pub balance_source: BalanceSource,
+    /// Smallest bet placed, when above the currency's site minimum.
+    #[serde(default)]
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    #[serde(default)]
+    pub min_chance: Option<f32>,
+    /// Settled bets kept in memory; the model still only sees the last `HISTORY_SIZE`.
+    #[serde(default)]
+    pub max_history: Option<usize>,
+    /// HMAC-signs request bodies with an API secret, for sites that require it.
+    #[serde(default)]
+    pub signing: Option<RequestSigningConfig>,
+    /// Seconds a fetched balance may be relied on; older balances are refreshed before
+    /// betting, and only the minimum is bet while the refresh keeps failing.
+    #[serde(default)]
+    pub max_balance_staleness_secs: Option<u64>,
+    /// DuckDice symbol to use for a currency whose name differs on the site.
+    #[serde(default)]
+    pub symbols: HashMap<Currency, String>,
+    #[serde(default)]
+    pub warmup: WarmupMode,
+    /// Tips part of every banked win target to another account.
+    #[serde(default)]
+    pub tip: Option<TipConfig>,
+    /// Client seed rotation and history.
+    #[serde(flatten)]
+    pub seeds: SeedConfig,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// Account that banked profit is tipped to.
+#[derive(Debug, Deserialize)]
+pub struct TipConfig {
+    /// Username of the receiving account.
+    pub to: String,
+    /// Share of each banked win target tipped, from just above 0 to 1; defaults to all
+    /// of it.
+    #[serde(default)]
+    pub share: Option<f32>,
+}
+
+/// Windice settings; the section may be left out when Windice is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WindiceConfig {
+    pub enabled: bool,
+    pub api_key: String,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// PrimeDice settings; the section may be left out when PrimeDice is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PrimeDiceConfig {
+    pub enabled: bool,
+    /// Access token from the account's security settings.
+    pub api_key: String,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// Bitsler settings; the section may be left out when Bitsler is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BitslerConfig {
+    pub enabled: bool,
+    /// API access token from the account settings.
+    pub api_key: String,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// BC.Game settings; the section may be left out when BC.Game is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BcGameConfig {
+    pub enabled: bool,
+    /// API key from the account's API settings.
+    pub api_key: String,
+    /// Secret every request body is signed with.
+    pub api_secret: String,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// Wolf.bet settings; the section may be left out when Wolf.bet is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WolfBetConfig {
+    pub enabled: bool,
+    /// Personal access token from the account's API settings.
+    pub api_key: String,
+    pub currency: Currency,
+    pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// 999dice settings; the section may be left out when 999dice is not used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Dice999Config {
+    pub enabled: bool,
+    /// API key; requests are sessionless, so no username or password is needed.
     pub api_key: String,
     pub currency: Currency,
     pub strategy: TomlStrategies,
+    /// Smallest bet placed, when above the site minimum.
+    pub min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at; lower chances are raised to it.
+    pub min_chance: Option<f32>,
+    /// Strategy bet sizing and model window; unset fields keep the site defaults.
+    #[serde(flatten)]
+    pub sizing: BetSizingConfig,
+}
+
+/// Bankroll and bet sizes a site seeds its strategy with, and the window of settled
+/// bets the model predicts from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BetSizingConfig {
+    /// First bet of the strategy; defaults to `min_bet`.
+    pub initial_bet: Option<f32>,
+    /// Smallest bet the strategy places; defaults to the site minimum.
+    pub min_bet: Option<f32>,
+    /// Bankroll the strategy starts from until the site reports a balance.
+    pub balance: Option<f32>,
+    /// Settled bets per prediction, which must match the model's `history_size`; bets
+    /// stay at the minimum until this many settled.
+    pub history_size: Option<usize>,
+}
+
+impl BetSizingConfig {
+    /// Builds `strategy`, with the configured sizes taking precedence over the site's
+    /// `balance` and `min_bet`.
+    pub fn strategy(
+        &self,
+        strategy: TomlStrategies,
+        balance: f32,
+        min_bet: f32,
+    ) -> Box<dyn crate::strategies::Strategy> {
+        let min_bet = self.min_bet.unwrap_or(min_bet);
+        crate::strategies::from_config(
+            strategy,
+            self.balance.unwrap_or(balance),
+            min_bet,
+            self.initial_bet.unwrap_or(min_bet),
+        )
+    }
+
+    fn validate(&self, site: &str) -> Result<(), String> {
+        if [self.initial_bet, self.min_bet, self.balance]
+            .iter()
+            .flatten()
+            .any(|amount| *amount <= 0. || amount.is_nan())
+        {
+            return Err(format!(
+                "{site} initial_bet, min_bet and balance must be positive"
+            ));
+        }
+        if self.history_size == Some(0) {
+            return Err(format!("{site} history_size must be positive"));
+        }
+        if let (Some(min_bet), Some(initial_bet)) = (self.min_bet, self.initial_bet) {
+            if min_bet > initial_bet {
+                return Err(format!(
+                    "{site} min_bet ({min_bet}) cannot exceed initial_bet ({initial_bet})"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Client seed rotation and history of a site that manages its client seed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SeedConfig {
+    /// Randomize the client seed after this many bets; unset keeps the same seed.
+    pub seed_rotation_interval: Option<u64>,
+    /// Start every session with a fresh client seed.
+    pub rotate_seed_per_session: bool,
+    /// File the client seeds and the nonces each rolled are kept in.
+    pub seed_file: Option<String>,
+}
+
+impl SeedConfig {
+    pub fn policy(&self) -> RotationPolicy {
+        RotationPolicy {
+            every_bets: self.seed_rotation_interval,
+            per_session: self.rotate_seed_per_session,
+        }
+    }
+
+    fn validate(&self, site: &str) -> Result<(), String> {
+        if self.seed_rotation_interval == Some(0) {
+            return Err(format!("{site} seed_rotation_interval must be at least 1"));
+        }
+        if self.seed_file.as_ref().is_some_and(|path| path.is_empty()) {
+            return Err(format!("{site} seed_file cannot be empty"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Secret and header used to sign request bodies.
+#[derive(Debug, Deserialize)]
+pub struct RequestSigningConfig {
+    pub secret: String,
+    /// Header carrying the signature; defaults to `X-Signature`.
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+impl RequestSigningConfig {
+    pub fn signer(&self) -> RequestSigner {
+        let signer = RequestSigner::new(self.secret.clone());
+
+        match &self.header {
+            Some(header) => signer.with_header(header.clone()),
+            None => signer,
+        }
+    }
+}
+
+/// Settings for offline simulation against the fake provably-fair server.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// Bet against the fake server instead of the live site.
+    pub enabled: bool,
+    /// Starting bankroll used when no per-currency balance is configured.
+    pub start_balance: f32,
+    /// Per-currency starting bankrolls, e.g. `BTC = 0.001`.
+    pub start_balances: HashMap<Currency, f32>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_balance: 10.,
+            start_balances: HashMap::new(),
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Returns the starting bankroll for `currency`, falling back to `start_balance`.
+    pub fn start_balance_for(&self, currency: &Currency) -> f32 {
+        self.start_balances
+            .get(currency)
+            .copied()
+            .unwrap_or(self.start_balance)
+    }
+}
+
+/// Settings for the `montecarlo` subcommand.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MonteCarloConfig {
+    /// Number of independent fake sessions to run.
+    pub runs: u32,
+    /// Maximum number of rolls per session.
+    pub rolls_per_run: u64,
+    /// Starting bankroll; falls back to the simulation start balance when absent.
+    pub start_balance: Option<f32>,
+    /// Base seed; run `n` uses `seed + n`.
+    pub seed: u64,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            runs: 1000,
+            rolls_per_run: 1000,
+            start_balance: None,
+            seed: 42,
+        }
+    }
+}
+
+/// Settings for the `backtest` subcommand.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct BacktestConfig {
+    /// Bets placed per strategy.
+    pub bets: u64,
+    /// Strategies compared on the same rolls; empty backtests the configured one.
+    pub strategies: Vec<TomlStrategies>,
+    /// Starting bankroll; falls back to the simulation start balance when absent.
+    pub start_balance: Option<f32>,
+    /// Seed of the fake server, reset to before each strategy's run.
+    pub seed: u64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            bets: 1000,
+            strategies: Vec::new(),
+            start_balance: None,
+            seed: 42,
+        }
+    }
+}
+
+/// Settings for the `collect` subcommand.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CollectConfig {
+    /// CSV file the collected rolls are appended to.
+    pub output: String,
+    /// Stop after this many bets; runs until Ctrl-C when absent. The `data-collector`
+    /// mode stops after this many rows instead.
+    pub max_bets: Option<u64>,
+    /// WebSocket feed of everyone's DuckDice bets read by the `data-collector` mode.
+    pub feed_url: String,
+}
+
+impl Default for CollectConfig {
+    fn default() -> Self {
+        Self {
+            output: "data/collected.csv".to_string(),
+            max_bets: None,
+            feed_url: crate::sites::duck_dice::feed::FEED_URL.to_string(),
+        }
+    }
+}
+
+/// Recording of live bets in the training CSV format.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RecordConfig {
+    /// CSV file every settled bet is appended to; nothing is recorded when absent.
+    /// `RECORD_PATH` in the environment takes precedence.
+    pub path: Option<String>,
+}
+
+impl FreeBitcoInConfig {
+    /// Two-factor code for the login, from `FREEBITCOIN_TFA_CODE` or the config, since a
+    /// code is only valid for a moment.
+    pub fn tfa_code(&self) -> Option<String> {
+        std::env::var("FREEBITCOIN_TFA_CODE")
+            .ok()
+            .filter(|code| !code.is_empty())
+            .or_else(|| self.tfa_code.clone())
+    }
+}
+
+impl RecordConfig {
+    /// Path bets are recorded to, from `RECORD_PATH` or the config.
+    pub fn path(&self) -> Option<String> {
+        std::env::var("RECORD_PATH")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .or_else(|| self.path.clone())
+    }
+
+    /// Path the bets of `site` are recorded to when several sites are bet on at once,
+    /// so their rolls are not mixed: `path()` with the site's config key before the
+    /// extension.
+    pub fn site_path(&self, site: Sites) -> Option<String> {
+        let path = std::path::PathBuf::from(self.path()?);
+        let stem = path.file_stem()?.to_string_lossy();
+        let file_name = match path.extension() {
+            Some(extension) => format!(
+                "{stem}-{}.{}",
+                site.config_key(),
+                extension.to_string_lossy()
+            ),
+            None => format!("{stem}-{}", site.config_key()),
+        };
+
+        Some(
+            path.with_file_name(file_name)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+/// Settings for outbound requests to the site.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Upper bound on requests in flight at once, shared by bets and balance refreshes.
+    pub max_concurrent_requests: usize,
+    /// Random spread of the delay between bets, in ms either side; 0 keeps it fixed.
+    pub delay_jitter_ms: u64,
+    /// Seed for the jitter, so the delays repeat between runs; random when absent.
+    pub jitter_seed: Option<u64>,
+    /// Attempts at a request that failed with a timeout, a refused connection or a
+    /// server error, the first one included, before the session gives up.
+    pub retry_attempts: u32,
+    /// Pause before the first retry, in ms; it doubles with every further retry.
+    pub retry_backoff_ms: u64,
+    /// Longest pause between two retries, in ms.
+    pub max_retry_backoff_ms: u64,
+    /// Proxy every site request goes through, as an `http://`, `https://`, `socks5://`
+    /// or `socks5h://` URL; credentials go in the URL as `user:password@`.
+    pub proxy: Option<String>,
+    /// Fetch the balance from the site after every this many settled bets, correcting
+    /// the locally tracked one; `None` only fetches it at login.
+    pub refresh_balance_every: Option<u64>,
+    /// Sends site requests through a local Tor daemon instead.
+    pub tor: TorConfig,
+}
+
+impl NetworkConfig {
+    pub fn delay_jitter(&self) -> DelayJitter {
+        DelayJitter::new(
+            std::time::Duration::from_millis(self.delay_jitter_ms),
+            self.jitter_seed,
+        )
+    }
+
+    /// The configured proxy; `None` when there is none or its URL is invalid, which
+    /// `TomlConfig::validate` rejects.
+    pub fn proxy(&self) -> Option<reqwest::Proxy> {
+        if self.tor.enabled {
+            // socks5h, so Tor resolves the site's hostname and DNS does not leak.
+            return reqwest::Proxy::all(format!("socks5h://{}", self.tor.socks_addr)).ok();
+        }
+        self.proxy
+            .as_deref()
+            .and_then(|url| reqwest::Proxy::all(url).ok())
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_attempts,
+            initial_backoff: std::time::Duration::from_millis(self.retry_backoff_ms),
+            max_backoff: std::time::Duration::from_millis(self.max_retry_backoff_ms),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: 1,
+            delay_jitter_ms: 0,
+            jitter_seed: None,
+            retry_attempts: 3,
+            retry_backoff_ms: 500,
+            max_retry_backoff_ms: 30_000,
+            proxy: None,
+            refresh_balance_every: None,
+            tor: TorConfig::default(),
+        }
+    }
+}
+
+/// Tor transport, through the SOCKS port of a local daemon.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TorConfig {
+    pub enabled: bool,
+    /// Address of Tor's SOCKS port.
+    pub socks_addr: String,
+    /// Address of Tor's control port, used to request new circuits.
+    pub control_addr: String,
+    /// Password for the control port, when Tor has a `HashedControlPassword`.
+    pub control_password: Option<String>,
+    /// Switch to a new circuit, and so a new exit IP, after this many bets; `None`
+    /// keeps the circuit Tor picks.
+    pub renew_circuit_every: Option<u64>,
+}
+
+impl TorConfig {
+    /// Circuit renewal asked for by `renew_circuit_every`, when Tor is enabled.
+    pub fn circuit_renewal(&self) -> Option<CircuitRenewal> {
+        let every = self.renew_circuit_every.filter(|_| self.enabled)?;
+        let controller =
+            TorController::new(self.control_addr.clone(), self.control_password.clone());
+        Some(CircuitRenewal::new(controller, every))
+    }
+}
+
+impl Default for TorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            socks_addr: "127.0.0.1:9050".to_string(),
+            control_addr: "127.0.0.1:9051".to_string(),
+            control_password: None,
+            renew_circuit_every: None,
+        }
+    }
+}
+
+/// How model outputs are turned into predictions.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct InferenceConfig {
+    /// Roll of the predicted bucket used for the chance and high/low decision.
+    pub bucket_decode_anchor: BucketAnchor,
+    /// Keep the encoded features of each roll, so only the newest roll of the sliding
+    /// window is encoded per bet.
+    pub cache_features: bool,
+    /// Fallback used when inference fails, e.g. on a shape mismatch or NaN output.
+    pub on_inference_error: InferenceErrorMode,
+    /// What to do when a bet skipped nonces, so the history window has a hole.
+    pub on_nonce_gap: NonceGapMode,
+    /// Run one forward pass at startup, so the first bet is not delayed by shader
+    /// compilation and buffer allocation.
+    pub warmup: bool,
+}
+
+impl Default for InferenceConfig {
+    fn default() -> Self {
+        Self {
+            bucket_decode_anchor: BucketAnchor::default(),
+            cache_features: true,
+            on_inference_error: InferenceErrorMode::default(),
+            on_nonce_gap: NonceGapMode::default(),
+            warmup: true,
+        }
+    }
+}
+
+/// Remote control of a running bot.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Betting pauses while this file exists and resumes once it is removed.
+    pub pause_file: Option<String>,
+}
+
+/// Limits on bet size and volume shared by every site.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BetLimitsConfig {
+    /// Share of the site's maximum bet that bets are capped at, from just above 0 to 1.
+    pub max_bet_fraction_of_site_max: Option<f32>,
+    /// Betting stops once the session has wagered this much in total.
+    pub max_wagered: Option<f32>,
+}
+
+/// UTC hours during which bets are placed; outside them the bot idles.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// `[start, end]` hour ranges, end exclusive, wrapping past midnight when `end` is
+    /// before `start`. Empty bets around the clock.
+    pub hours: Vec<[u8; 2]>,
+}
+
+impl ScheduleConfig {
+    /// Whether bets may be placed during the UTC `hour`.
+    pub fn allows(&self, hour: u8) -> bool {
+        self.hours.is_empty()
+            || self.hours.iter().any(|&[start, end]| {
+                if start < end {
+                    (start..end).contains(&hour)
+                } else {
+                    hour >= start || hour < end
+                }
+            })
+    }
+}
+
+/// Settings for resetting the strategy once it reaches its win target.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProfitTargetConfig {
+    /// Reset the strategy when its profit reaches `Strategy::get_win_target`.
+    pub enabled: bool,
+    /// Also take the profit out of the working balance, so each cycle restarts from the
+    /// starting bankroll.
+    pub bank_profit: bool,
+    /// Stop betting and withdraw the profit once it reaches this amount.
+    pub withdraw_at_profit: Option<f32>,
+    /// Address the profit is withdrawn to.
+    pub withdraw_address: String,
+    /// Stop betting once the session profit reaches this amount.
+    pub take_profit: Option<f32>,
+    /// Stop betting once the session has lost this amount.
+    pub stop_loss: Option<f32>,
+    /// Stop betting once the session profit reaches the strategy's win target, instead
+    /// of resetting the strategy there.
+    pub stop_at_win_target: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,43 +796,353 @@ pub struct TomlConfig {
     pub crypto_games: CryptoGamesConfig,
     pub freebitcoin: FreeBitcoInConfig,
     pub duck_dice: DuckDiceConfig,
+    #[serde(default)]
+    pub windice: WindiceConfig,
+    #[serde(default)]
+    pub primedice: PrimeDiceConfig,
+    #[serde(default)]
+    pub bitsler: BitslerConfig,
+    #[serde(default)]
+    pub bc_game: BcGameConfig,
+    #[serde(default)]
+    pub wolf_bet: WolfBetConfig,
+    #[serde(default)]
+    pub dice999: Dice999Config,
+    #[serde(default)]
+    pub simulation: SimulationConfig,
+    #[serde(default)]
+    pub montecarlo: MonteCarloConfig,
+    #[serde(default)]
+    pub backtest: BacktestConfig,
+    #[serde(default)]
+    pub collect: CollectConfig,
+    #[serde(default)]
+    pub record: RecordConfig,
+    #[serde(default)]
+    pub profit_target: ProfitTargetConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Layout of recorded CSVs loaded for training.
+    #[serde(default)]
+    pub csv: CsvFormat,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub bet_limits: BetLimitsConfig,
+    #[serde(default)]
+    pub inference: InferenceConfig,
 }
 
 impl TomlConfig {
+    /// Sites enabled in the configuration, each of which is bet on.
+    pub fn enabled_sites(&self) -> Vec<Sites> {
+        [
+            (self.duck_dice.enabled, Sites::DuckDiceIo),
+            (self.crypto_games.enabled, Sites::CryptoGames),
+            (self.freebitcoin.enabled, Sites::FreeBitcoIn),
+            (self.windice.enabled, Sites::Windice),
+            (self.primedice.enabled, Sites::PrimeDice),
+            (self.bitsler.enabled, Sites::Bitsler),
+            (self.bc_game.enabled, Sites::BcGame),
+            (self.wolf_bet.enabled, Sites::WolfBet),
+            (self.dice999.enabled, Sites::Dice999),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, site)| enabled.then_some(site))
+        .collect()
+    }
+
     /// Validates the configuration
     pub fn validate(&self) -> Result<(), String> {
-        let mut enabled_count = 0;
-
-        if self.duck_dice.enabled {
-            enabled_count += 1;
-            if self.duck_dice.api_key.is_empty() {
-                return Err("DuckDice API key cannot be empty".to_string());
-            }
+        if self.duck_dice.enabled && self.duck_dice.api_key.is_empty() {
+            return Err("DuckDice API keys cannot be empty".to_string());
         }
 
         if self.crypto_games.enabled {
-            enabled_count += 1;
             if self.crypto_games.api_key.is_empty() {
                 return Err("CryptoGames API key cannot be empty".to_string());
             }
+            crate::sites::crypto_games::Currency::try_from(&self.crypto_games.currency)?;
         }
 
         if self.freebitcoin.enabled {
-            enabled_count += 1;
             if self.freebitcoin.btc_address.is_empty() {
                 return Err("FreeBitco.in BTC address cannot be empty".to_string());
             }
             if self.freebitcoin.password.is_empty() {
                 return Err("FreeBitco.in password cannot be empty".to_string());
             }
+            if let Some(secret) = &self.freebitcoin.totp_secret {
+                if self.freebitcoin.tfa_code.is_some() {
+                    return Err("FreeBitco.in takes either a tfa_code or a totp_secret".to_string());
+                }
+                Totp::from_base32(secret).map_err(|e| format!("FreeBitco.in {e}"))?;
+            }
+            if self.freebitcoin.reward_bonuses.iter().any(String::is_empty) {
+                return Err("FreeBitco.in reward bonus ids cannot be empty".to_string());
+            }
+            if self.freebitcoin.reward_check_every == Some(0) {
+                return Err("FreeBitco.in reward_check_every must be at least 1".to_string());
+            }
+            if self.freebitcoin.session_file.as_deref() == Some("") {
+                return Err("FreeBitco.in session_file cannot be empty".to_string());
+            }
+        }
+
+        if self.windice.enabled && self.windice.api_key.is_empty() {
+            return Err("Windice API key cannot be empty".to_string());
+        }
+
+        if self.primedice.enabled && self.primedice.api_key.is_empty() {
+            return Err("PrimeDice API key cannot be empty".to_string());
+        }
+
+        if self.bitsler.enabled && self.bitsler.api_key.is_empty() {
+            return Err("Bitsler API key cannot be empty".to_string());
+        }
+
+        if self.bc_game.enabled
+            && (self.bc_game.api_key.is_empty() || self.bc_game.api_secret.is_empty())
+        {
+            return Err("BC.Game API key and secret cannot be empty".to_string());
         }
 
-        if enabled_count == 0 {
+        if self.wolf_bet.enabled && self.wolf_bet.api_key.is_empty() {
+            return Err("Wolf.bet API key cannot be empty".to_string());
+        }
+
+        if self.dice999.enabled && self.dice999.api_key.is_empty() {
+            return Err("999dice API key cannot be empty".to_string());
+        }
+
+        if self.enabled_sites().is_empty() {
             return Err("At least one site must be enabled".to_string());
         }
 
-        if enabled_count > 1 {
-            return Err("Only one site can be enabled at a time".to_string());
+        if self.simulation.start_balance <= 0.
+            || self
+                .simulation
+                .start_balances
+                .values()
+                .any(|val| *val <= 0.)
+        {
+            return Err("Simulation start balance must be positive".to_string());
+        }
+
+        if self.montecarlo.runs == 0 || self.montecarlo.rolls_per_run == 0 {
+            return Err("Monte Carlo runs and rolls_per_run must be positive".to_string());
+        }
+
+        if [
+            self.duck_dice.min_bet_override,
+            self.crypto_games.min_bet_override,
+            self.freebitcoin.min_bet_override,
+            self.windice.min_bet_override,
+            self.primedice.min_bet_override,
+            self.bitsler.min_bet_override,
+            self.bc_game.min_bet_override,
+            self.wolf_bet.min_bet_override,
+            self.dice999.min_bet_override,
+        ]
+        .iter()
+        .flatten()
+        .any(|min_bet| *min_bet <= 0. || min_bet.is_nan())
+        {
+            return Err("min_bet_override must be positive".to_string());
+        }
+
+        for (site, sizing) in [
+            ("DuckDice", &self.duck_dice.sizing),
+            ("CryptoGames", &self.crypto_games.sizing),
+            ("FreeBitco.in", &self.freebitcoin.sizing),
+            ("Windice", &self.windice.sizing),
+            ("PrimeDice", &self.primedice.sizing),
+            ("Bitsler", &self.bitsler.sizing),
+            ("BC.Game", &self.bc_game.sizing),
+            ("Wolf.bet", &self.wolf_bet.sizing),
+            ("999dice", &self.dice999.sizing),
+        ] {
+            sizing.validate(site)?;
+        }
+
+        if [self.profit_target.take_profit, self.profit_target.stop_loss]
+            .iter()
+            .flatten()
+            .any(|target| *target <= 0. || target.is_nan())
+        {
+            return Err("take_profit and stop_loss must be positive".to_string());
+        }
+
+        for (site, min_chance) in [
+            ("DuckDice", self.duck_dice.min_chance),
+            ("FreeBitco.in", self.freebitcoin.min_chance),
+            ("Windice", self.windice.min_chance),
+            ("PrimeDice", self.primedice.min_chance),
+            ("Bitsler", self.bitsler.min_chance),
+            ("BC.Game", self.bc_game.min_chance),
+            ("Wolf.bet", self.wolf_bet.min_chance),
+            ("999dice", self.dice999.min_chance),
+        ] {
+            if min_chance.is_some_and(|min_chance| !(min_chance > 0. && min_chance < 100.)) {
+                return Err(format!("{site} min_chance must be between 0 and 100"));
+            }
+        }
+
+        let history_size = self
+            .duck_dice
+            .sizing
+            .history_size
+            .unwrap_or(crate::util::HISTORY_SIZE);
+        if self
+            .duck_dice
+            .max_history
+            .is_some_and(|max_history| max_history < history_size)
+        {
+            return Err(format!(
+                "DuckDice max_history must be at least the model window ({history_size})"
+            ));
+        }
+
+        if self.duck_dice.max_balance_staleness_secs == Some(0) {
+            return Err("DuckDice max_balance_staleness_secs must be at least 1".to_string());
+        }
+
+        for (site, seeds) in [
+            ("DuckDice", &self.duck_dice.seeds),
+            ("FreeBitco.in", &self.freebitcoin.seeds),
+        ] {
+            seeds.validate(site)?;
+        }
+
+        if let Some(signing) = &self.duck_dice.signing {
+            if signing.secret.is_empty() {
+                return Err("DuckDice signing secret cannot be empty".to_string());
+            }
+            if signing.header.as_ref().is_some_and(|header| {
+                reqwest::header::HeaderName::from_bytes(header.as_bytes()).is_err()
+            }) {
+                return Err("DuckDice signing header is not a valid header name".to_string());
+            }
+        }
+
+        if let Some(tip) = &self.duck_dice.tip {
+            if tip.to.is_empty() {
+                return Err("DuckDice tip recipient cannot be empty".to_string());
+            }
+            if tip.share.is_some_and(|share| share <= 0. || share > 1.) {
+                return Err("DuckDice tip share must be above 0 and at most 1".to_string());
+            }
+            if !(self.profit_target.enabled && self.profit_target.bank_profit) {
+                return Err(
+                    "DuckDice tips need [profit_target] enabled with bank_profit".to_string(),
+                );
+            }
+        }
+
+        if let Some(at_profit) = self.profit_target.withdraw_at_profit {
+            if at_profit <= 0. {
+                return Err("withdraw_at_profit must be positive".to_string());
+            }
+            if self.profit_target.withdraw_address.is_empty() {
+                return Err("withdraw_address is required with withdraw_at_profit".to_string());
+            }
+        }
+
+        if self
+            .control
+            .pause_file
+            .as_ref()
+            .is_some_and(|pause_file| pause_file.is_empty())
+        {
+            return Err("Control pause_file cannot be empty".to_string());
+        }
+
+        if self
+            .bet_limits
+            .max_bet_fraction_of_site_max
+            .is_some_and(|fraction| !(fraction > 0. && fraction <= 1.))
+        {
+            return Err("max_bet_fraction_of_site_max must be above 0 and at most 1".to_string());
+        }
+
+        if self
+            .bet_limits
+            .max_wagered
+            .is_some_and(|max_wagered| max_wagered <= 0. || max_wagered.is_nan())
+        {
+            return Err("max_wagered must be positive".to_string());
+        }
+
+        for &[start, end] in &self.schedule.hours {
+            if start >= 24 || end > 24 || start == end {
+                return Err(format!(
+                    "Schedule hours [{start}, {end}] must be distinct hours from 0 to 24"
+                ));
+            }
+        }
+
+        if !self.csv.delimiter.is_ascii() {
+            return Err("CSV delimiter must be a single ASCII character".to_string());
+        }
+
+        if self.network.max_concurrent_requests == 0 {
+            return Err("max_concurrent_requests must be at least 1".to_string());
+        }
+
+        if let Some(proxy) = &self.network.proxy {
+            let scheme = reqwest::Url::parse(proxy).map(|url| url.scheme().to_string());
+            if !matches!(
+                scheme.as_deref(),
+                Ok("http" | "https" | "socks5" | "socks5h")
+            ) {
+                return Err(format!(
+                    "Proxy {proxy} must be an http, https, socks5 or socks5h URL"
+                ));
+            }
+        }
+
+        if self.network.tor.enabled && self.network.proxy.is_some() {
+            return Err("Tor and a proxy cannot both be set in [network]".to_string());
+        }
+        if self.network.tor.renew_circuit_every == Some(0) {
+            return Err("renew_circuit_every must be at least 1".to_string());
+        }
+
+        if !["ws://", "wss://"]
+            .iter()
+            .any(|scheme| self.collect.feed_url.starts_with(scheme))
+        {
+            return Err("[collect] feed_url must be a ws:// or wss:// URL".to_string());
+        }
+
+        if self.network.refresh_balance_every == Some(0) {
+            return Err("refresh_balance_every must be at least 1".to_string());
+        }
+
+        if self.network.retry_attempts == 0 {
+            return Err("retry_attempts must be at least 1".to_string());
+        }
+
+        if self.backtest.bets == 0 {
+            return Err("Backtest bets must be positive".to_string());
+        }
+
+        if self
+            .backtest
+            .start_balance
+            .is_some_and(|balance| balance <= 0. || balance.is_nan())
+        {
+            return Err("Backtest start balance must be positive".to_string());
+        }
+
+        if self.collect.output.is_empty() {
+            return Err("Collect output path cannot be empty".to_string());
+        }
+
+        if self.record.path.as_ref().is_some_and(String::is_empty) {
+            return Err("Record path cannot be empty".to_string());
         }
 
         Ok(())
@@ -95,14 +1157,27 @@ pub trait SiteConfig {
         self
     }
 
-    fn with_username(self) -> Self
+    /// Uses several accounts, switching to the next when the active one is rate limited.
+    /// Sites without rotation use the first key.
+    fn with_api_keys(self, api_keys: Vec<String>) -> Self
+    where
+        Self: Sized,
+    {
+        match api_keys.into_iter().next() {
+            Some(api_key) => self.with_api_key(api_key),
+            None => self,
+        }
+    }
+
+    /// Logs in as `username`, on sites with password logins.
+    fn with_username(self, _username: String) -> Self
     where
         Self: Sized,
     {
         self
     }
 
-    fn with_password(self) -> Self
+    fn with_password(self, _password: String) -> Self
     where
         Self: Sized,
     {
@@ -122,6 +1197,146 @@ pub trait SiteConfig {
     {
         self
     }
+
+    /// Switches the site to fake betting with the given starting bankroll.
+    fn with_simulation(self, _start_balance: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Selects which site balance bets are funded from.
+    fn with_balance_source(self, _balance_source: BalanceSource) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Sets the lowest chance (in percent) a bet is placed at.
+    fn with_min_chance(self, _min_chance: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Routes every request to the site through a shared concurrency budget.
+    fn with_request_limiter(self, _limiter: RequestLimiter) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Sends every request to the site through `proxy`.
+    fn with_proxy(self, _proxy: reqwest::Proxy) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Seeds the strategy with the configured bankroll and bet sizes. Sites building
+    /// their strategy in `with_strategy` need this first.
+    fn with_bet_sizing(self, _sizing: BetSizingConfig) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Feeds the model the latest `history_size` settled bets, betting the minimum until
+    /// that many settled.
+    fn with_history_size(self, _history_size: usize) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Keeps up to `max_history` settled bets, of which the model sees the latest window.
+    fn with_max_history(self, _max_history: usize) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Sends `symbol` to the site instead of the currency's own name.
+    fn with_symbol(self, _symbol: String) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Caps bets at `fraction` of the largest stake the site reports accepting. Sites
+    /// that report no maximum ignore it.
+    fn with_max_bet_fraction(self, _fraction: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Raises the smallest bet placed to `min_bet`; the site minimum still applies when
+    /// it is higher.
+    fn with_min_bet(self, _min_bet: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Refreshes the site balance before betting once it is older than `max_staleness`.
+    fn with_max_balance_staleness(self, _max_staleness: std::time::Duration) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Fills the model history before betting as `warmup` asks.
+    fn with_warmup(self, _warmup: WarmupMode) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Replaces the client seed as `policy` asks.
+    fn with_seed_rotation(self, _policy: RotationPolicy) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Keeps the client seeds and the nonces each rolled in `path`.
+    fn with_seed_file(self, _path: String) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Signs request bodies with `signer` before they are sent.
+    fn with_request_signer(self, _signer: RequestSigner) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Resets the strategy whenever it reaches its win target, optionally banking the profit.
+    fn with_profit_target(self, _bank_profit: bool) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
 }
 
 #[cfg(test)]
@@ -133,22 +1348,64 @@ mod tests {
         let config = TomlConfig {
             duck_dice: DuckDiceConfig {
                 enabled: false,
-                api_key: "test".to_string(),
+                api_key: ApiKeys::Single("test".to_string()),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                balance_source: BalanceSource::default(),
+                min_bet_override: None,
+                min_chance: None,
+                max_history: None,
+                signing: None,
+                max_balance_staleness_secs: None,
+                symbols: HashMap::new(),
+                warmup: WarmupMode::default(),
+                tip: None,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
             crypto_games: CryptoGamesConfig {
                 enabled: false,
                 api_key: "test".to_string(),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                sizing: BetSizingConfig::default(),
             },
             freebitcoin: FreeBitcoInConfig {
                 enabled: false,
                 btc_address: "test".to_string(),
                 password: "test".to_string(),
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                min_chance: None,
+                session_file: None,
+                tfa_code: None,
+                totp_secret: None,
+                reward_bonuses: Vec::new(),
+                reward_check_every: None,
+                claim_faucet: false,
+                halt_on_unverified_roll: false,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
+            windice: WindiceConfig::default(),
+            primedice: PrimeDiceConfig::default(),
+            dice999: Dice999Config::default(),
+            wolf_bet: WolfBetConfig::default(),
+            bc_game: BcGameConfig::default(),
+            bitsler: BitslerConfig::default(),
+            simulation: SimulationConfig::default(),
+            montecarlo: MonteCarloConfig::default(),
+            backtest: BacktestConfig::default(),
+            collect: CollectConfig::default(),
+            record: RecordConfig::default(),
+            profit_target: ProfitTargetConfig::default(),
+            network: NetworkConfig::default(),
+            csv: CsvFormat::default(),
+            control: ControlConfig::default(),
+            schedule: ScheduleConfig::default(),
+            bet_limits: BetLimitsConfig::default(),
+            inference: InferenceConfig::default(),
         };
 
         assert!(config.validate().is_err());
@@ -159,22 +1416,64 @@ mod tests {
         let config = TomlConfig {
             duck_dice: DuckDiceConfig {
                 enabled: true,
-                api_key: "".to_string(),
+                api_key: ApiKeys::Single("".to_string()),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                balance_source: BalanceSource::default(),
+                min_bet_override: None,
+                min_chance: None,
+                max_history: None,
+                signing: None,
+                max_balance_staleness_secs: None,
+                symbols: HashMap::new(),
+                warmup: WarmupMode::default(),
+                tip: None,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
             crypto_games: CryptoGamesConfig {
                 enabled: false,
                 api_key: "test".to_string(),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                sizing: BetSizingConfig::default(),
             },
             freebitcoin: FreeBitcoInConfig {
                 enabled: false,
                 btc_address: "test".to_string(),
                 password: "test".to_string(),
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                min_chance: None,
+                session_file: None,
+                tfa_code: None,
+                totp_secret: None,
+                reward_bonuses: Vec::new(),
+                reward_check_every: None,
+                claim_faucet: false,
+                halt_on_unverified_roll: false,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
+            windice: WindiceConfig::default(),
+            primedice: PrimeDiceConfig::default(),
+            dice999: Dice999Config::default(),
+            wolf_bet: WolfBetConfig::default(),
+            bc_game: BcGameConfig::default(),
+            bitsler: BitslerConfig::default(),
+            simulation: SimulationConfig::default(),
+            montecarlo: MonteCarloConfig::default(),
+            backtest: BacktestConfig::default(),
+            collect: CollectConfig::default(),
+            record: RecordConfig::default(),
+            profit_target: ProfitTargetConfig::default(),
+            network: NetworkConfig::default(),
+            csv: CsvFormat::default(),
+            control: ControlConfig::default(),
+            schedule: ScheduleConfig::default(),
+            bet_limits: BetLimitsConfig::default(),
+            inference: InferenceConfig::default(),
         };
 
         assert!(config.validate().is_err());
@@ -185,24 +1484,470 @@ mod tests {
         let config = TomlConfig {
             duck_dice: DuckDiceConfig {
                 enabled: true,
-                api_key: "valid_key".to_string(),
+                api_key: ApiKeys::Single("valid_key".to_string()),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                balance_source: BalanceSource::default(),
+                min_bet_override: None,
+                min_chance: None,
+                max_history: None,
+                signing: None,
+                max_balance_staleness_secs: None,
+                symbols: HashMap::new(),
+                warmup: WarmupMode::default(),
+                tip: None,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
             crypto_games: CryptoGamesConfig {
                 enabled: false,
                 api_key: "test".to_string(),
                 currency: Currency::BTC,
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                sizing: BetSizingConfig::default(),
             },
             freebitcoin: FreeBitcoInConfig {
                 enabled: false,
                 btc_address: "test".to_string(),
                 password: "test".to_string(),
                 strategy: TomlStrategies::None,
+                min_bet_override: None,
+                min_chance: None,
+                session_file: None,
+                tfa_code: None,
+                totp_secret: None,
+                reward_bonuses: Vec::new(),
+                reward_check_every: None,
+                claim_faucet: false,
+                halt_on_unverified_roll: false,
+                seeds: SeedConfig::default(),
+                sizing: BetSizingConfig::default(),
             },
+            windice: WindiceConfig::default(),
+            primedice: PrimeDiceConfig::default(),
+            dice999: Dice999Config::default(),
+            wolf_bet: WolfBetConfig::default(),
+            bc_game: BcGameConfig::default(),
+            bitsler: BitslerConfig::default(),
+            simulation: SimulationConfig::default(),
+            montecarlo: MonteCarloConfig::default(),
+            backtest: BacktestConfig::default(),
+            collect: CollectConfig::default(),
+            record: RecordConfig::default(),
+            profit_target: ProfitTargetConfig::default(),
+            network: NetworkConfig::default(),
+            csv: CsvFormat::default(),
+            control: ControlConfig::default(),
+            schedule: ScheduleConfig::default(),
+            bet_limits: BetLimitsConfig::default(),
+            inference: InferenceConfig::default(),
         };
 
         assert!(config.validate().is_ok());
     }
+
+    #[test]
+    fn test_simulation_start_balance_per_currency() {
+        let config: SimulationConfig = toml::from_str(
+            r#"
+            enabled = true
+            start_balance = 0.1
+
+            [start_balances]
+            BTC = 0.001
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.start_balance_for(&Currency::BTC), 0.001);
+        assert_eq!(config.start_balance_for(&Currency::DOGE), 0.1);
+    }
+
+    /// Builds a config TOML with one site section per `(section, enabled, strategy, currency)`.
+    fn sites_toml(
+        duck: (bool, &str, &str),
+        crypto: (bool, &str, &str),
+        free: (bool, &str),
+    ) -> String {
+        format!(
+            r#"
+[crypto_games]
+enabled = {}
+api_key = "key"
+currency = "{}"
+strategy = "{}"
+
+[freebitcoin]
+enabled = {}
+btc_address = "address"
+password = "password"
+strategy = "{}"
+
+[duck_dice]
+enabled = {}
+api_key = "key"
+currency = "{}"
+strategy = "{}"
+"#,
+            crypto.0, crypto.2, crypto.1, free.0, free.1, duck.0, duck.2, duck.1
+        )
+    }
+
+    #[test]
+    fn test_example_config_parses_and_validates() {
+        let config: TomlConfig = toml::from_str(include_str!("../config.toml.example")).unwrap();
+
+        assert!(config.duck_dice.enabled);
+        assert_eq!(config.duck_dice.currency, Currency::BTC);
+        assert_eq!(config.duck_dice.strategy, TomlStrategies::None);
+        assert_eq!(config.csv, CsvFormat::default());
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_parses_every_strategy() {
+        let strategies = [
+            ("AiFight", TomlStrategies::AiFight),
+            ("BlaksRunner", TomlStrategies::BlaksRunner),
+            ("MyStrategy", TomlStrategies::MyStrategy),
+            ("Flat", TomlStrategies::Flat),
+            ("Kelly", TomlStrategies::Kelly),
+            ("FollowLast", TomlStrategies::FollowLast),
+            ("OppositeLast", TomlStrategies::OppositeLast),
+            ("None", TomlStrategies::None),
+        ];
+
+        for (name, strategy) in strategies {
+            let config: TomlConfig = toml::from_str(&sites_toml(
+                (true, name, "BTC"),
+                (false, name, "BTC"),
+                (false, name),
+            ))
+            .unwrap();
+
+            assert_eq!(config.duck_dice.strategy, strategy, "{name}");
+            assert_eq!(config.crypto_games.strategy, strategy, "{name}");
+            assert_eq!(config.freebitcoin.strategy, strategy, "{name}");
+            assert_eq!(config.validate(), Ok(()), "{name}");
+        }
+
+        assert!(toml::from_str::<TomlConfig>(&sites_toml(
+            (true, "Martingale", "BTC"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_parses_currencies() {
+        let currencies = [
+            ("BTC", Currency::BTC),
+            ("ETH", Currency::ETH),
+            ("USDT", Currency::USDT),
+            ("DOGE", Currency::DOGE),
+            ("TRX", Currency::TRX),
+        ];
+
+        for (name, currency) in currencies {
+            let config: TomlConfig = toml::from_str(&sites_toml(
+                (true, "None", name),
+                (false, "None", name),
+                (false, "None"),
+            ))
+            .unwrap();
+
+            assert_eq!(config.duck_dice.currency, currency, "{name}");
+            assert_eq!(config.crypto_games.currency, currency, "{name}");
+        }
+
+        assert!(toml::from_str::<TomlConfig>(&sites_toml(
+            (true, "None", "btc"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_requires_a_site() {
+        let parse = |duck, crypto, free| -> TomlConfig {
+            toml::from_str(&sites_toml(
+                (duck, "None", "BTC"),
+                (crypto, "None", "BTC"),
+                (free, "None"),
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(parse(true, false, false).validate(), Ok(()));
+        assert_eq!(parse(false, true, false).validate(), Ok(()));
+        assert_eq!(parse(false, false, true).validate(), Ok(()));
+        assert!(parse(false, false, false).validate().is_err());
+        assert_eq!(parse(true, true, false).validate(), Ok(()));
+        assert_eq!(parse(true, false, true).validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_parses_optional_sections() {
+        let toml = sites_toml(
+            (true, "Flat", "ETH"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        ) + r#"
+balance_source = "main"
+min_bet_override = 0.0001
+min_chance = 5.0
+max_history = 500
+max_balance_staleness_secs = 60
+warmup = "feed"
+initial_bet = 0.0002
+min_bet = 0.0001
+balance = 1.5
+history_size = 20
+
+[duck_dice.symbols]
+USDT = "USDT_TRC20"
+
+[duck_dice.signing]
+secret = "secret"
+
+[simulation]
+enabled = true
+start_balance = 2.0
+
+[simulation.start_balances]
+ETH = 0.5
+
+[network]
+max_concurrent_requests = 4
+retry_attempts = 5
+proxy = "socks5h://127.0.0.1:9050"
+
+[inference]
+bucket_decode_anchor = "center"
+cache_features = false
+on_inference_error = "reuse_last"
+on_nonce_gap = "halt"
+
+[record]
+path = "data/live.csv"
+
+[csv]
+delimiter = ";"
+
+[csv.columns]
+rolled_number = "roll"
+"#;
+        let config: TomlConfig = toml::from_str(&toml).unwrap();
+
+        assert_eq!(config.duck_dice.balance_source, BalanceSource::Main);
+        assert_eq!(config.duck_dice.min_bet_override, Some(0.0001));
+        assert_eq!(config.duck_dice.min_chance, Some(5.));
+        assert_eq!(config.duck_dice.max_history, Some(500));
+        assert_eq!(config.duck_dice.max_balance_staleness_secs, Some(60));
+        assert_eq!(config.duck_dice.warmup, WarmupMode::Feed);
+        assert_eq!(
+            config.duck_dice.sizing,
+            BetSizingConfig {
+                initial_bet: Some(0.0002),
+                min_bet: Some(0.0001),
+                balance: Some(1.5),
+                history_size: Some(20),
+            }
+        );
+        assert_eq!(config.crypto_games.sizing, BetSizingConfig::default());
+        assert_eq!(
+            config
+                .duck_dice
+                .symbols
+                .get(&Currency::USDT)
+                .map(String::as_str),
+            Some("USDT_TRC20")
+        );
+        assert_eq!(
+            config
+                .duck_dice
+                .signing
+                .as_ref()
+                .map(|signing| signing.secret.as_str()),
+            Some("secret")
+        );
+        assert!(config.simulation.enabled);
+        assert_eq!(config.simulation.start_balance_for(&Currency::ETH), 0.5);
+        assert_eq!(config.simulation.start_balance_for(&Currency::BTC), 2.);
+        assert_eq!(config.network.max_concurrent_requests, 4);
+        assert_eq!(config.network.retry_policy().max_attempts, 5);
+        assert!(config.network.proxy().is_some());
+        assert_eq!(
+            config.network.retry_policy().initial_backoff,
+            std::time::Duration::from_millis(500)
+        );
+        assert_eq!(config.inference.bucket_decode_anchor, BucketAnchor::Center);
+        assert!(!config.inference.cache_features);
+        assert_eq!(
+            config.inference.on_inference_error,
+            InferenceErrorMode::ReuseLast
+        );
+        assert_eq!(config.inference.on_nonce_gap, NonceGapMode::Halt);
+        assert_eq!(config.csv.delimiter, ';');
+        assert_eq!(config.record.path.as_deref(), Some("data/live.csv"));
+        assert_eq!(config.validate(), Ok(()));
+
+        let invalid = [
+            "min_chance = 0.0",
+            "min_bet_override = 0.0",
+            "max_history = 5",
+            "history_size = 0",
+            "history_size = 20\nmax_history = 15",
+            "balance = 0.0",
+            "min_bet = 0.2\ninitial_bet = 0.1",
+            "max_balance_staleness_secs = 0",
+            "seed_rotation_interval = 0",
+            "seed_file = \"\"",
+            "[schedule]\nhours = [[8, 8]]",
+            "[bet_limits]\nmax_bet_fraction_of_site_max = 0.0",
+            "[bet_limits]\nmax_bet_fraction_of_site_max = 1.5",
+            "[bet_limits]\nmax_wagered = 0.0",
+            "[schedule]\nhours = [[24, 2]]",
+            "[profit_target]\nwithdraw_at_profit = 0.1",
+            "[profit_target]\nwithdraw_at_profit = 0.0\nwithdraw_address = \"addr\"",
+            "[profit_target]\ntake_profit = 0.0",
+            "[backtest]\nbets = 0",
+            "[record]\npath = \"\"",
+            "[backtest]\nstart_balance = -1.0",
+            "[profit_target]\nstop_loss = -1.0",
+            "[network]\nretry_attempts = 0",
+            "[network]\nproxy = \"localhost:9050\"",
+            "[network]\nproxy = \"ftp://localhost:21\"",
+            "[network]\nproxy = \"http://localhost:8080\"\n[network.tor]\nenabled = true",
+            "[network.tor]\nrenew_circuit_every = 0",
+            "[network]\nrefresh_balance_every = 0",
+            "[collect]\nfeed_url = \"https://duckdice.io\"",
+            "[duck_dice.tip]\nto = \"cold\"",
+            "[profit_target]\nenabled = true\nbank_profit = true\n[duck_dice.tip]\nto = \"\"",
+            "[profit_target]\nenabled = true\nbank_profit = true\n[duck_dice.tip]\nto = \"cold\"\nshare = 1.5",
+        ];
+        for line in invalid {
+            let toml = sites_toml(
+                (true, "None", "BTC"),
+                (false, "None", "BTC"),
+                (false, "None"),
+            ) + line;
+            let config: TomlConfig = toml::from_str(&toml).unwrap();
+            assert!(config.validate().is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_several_sites_may_be_enabled() {
+        let toml = sites_toml(
+            (true, "None", "BTC"),
+            (true, "None", "BTC"),
+            (false, "None"),
+        ) + "[record]\npath = \"data/live.csv\"";
+        let config: TomlConfig = toml::from_str(&toml).unwrap();
+
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(
+            config.enabled_sites(),
+            [Sites::DuckDiceIo, Sites::CryptoGames]
+        );
+        assert_eq!(
+            config.record.site_path(Sites::CryptoGames).as_deref(),
+            Some("data/live-crypto_games.csv")
+        );
+    }
+
+    #[test]
+    fn test_duck_dice_tips_banked_profit() {
+        let toml = sites_toml(
+            (true, "None", "BTC"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        ) + "[profit_target]\nenabled = true\nbank_profit = true\n\
+             [duck_dice.tip]\nto = \"cold\"\nshare = 0.5";
+        let config: TomlConfig = toml::from_str(&toml).unwrap();
+
+        assert_eq!(config.validate(), Ok(()));
+        let tip = config.duck_dice.tip.unwrap();
+        assert_eq!((tip.to.as_str(), tip.share), ("cold", Some(0.5)));
+    }
+
+    #[test]
+    fn test_validate_checks_totp_secret() {
+        let toml = sites_toml(
+            (false, "None", "BTC"),
+            (false, "None", "BTC"),
+            (true, "None"),
+        );
+        let mut config: TomlConfig = toml::from_str(&toml).unwrap();
+        config.freebitcoin.totp_secret = Some("JBSWY3DPEHPK3PXP".to_string());
+        assert_eq!(config.validate(), Ok(()));
+
+        config.freebitcoin.tfa_code = Some("123456".to_string());
+        assert!(config.validate().is_err());
+
+        config.freebitcoin.tfa_code = None;
+        config.freebitcoin.totp_secret = Some("not base32!".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_tor_renews_circuits_only_when_enabled() {
+        let mut network: NetworkConfig =
+            toml::from_str("[tor]\nrenew_circuit_every = 100").unwrap();
+        assert!(network.proxy().is_none());
+        assert!(network.tor.circuit_renewal().is_none());
+
+        network.tor.enabled = true;
+        assert!(network.proxy().is_some());
+        assert!(network.tor.circuit_renewal().is_some());
+    }
+
+    #[test]
+    fn test_schedule_allows_hours() {
+        let schedule = ScheduleConfig {
+            hours: vec![[8, 12], [22, 2]],
+        };
+
+        assert!(schedule.allows(8));
+        assert!(!schedule.allows(12));
+        assert!(schedule.allows(23));
+        assert!(schedule.allows(1));
+        assert!(!schedule.allows(2));
+        assert!(ScheduleConfig::default().allows(3));
+    }
+
+    #[test]
+    fn test_parses_api_key_list() {
+        let toml = sites_toml(
+            (true, "None", "BTC"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        )
+        .replace(
+            "[duck_dice]\nenabled = true\napi_key = \"key\"",
+            "[duck_dice]\nenabled = true\napi_key = [\"first\", \"second\"]",
+        );
+        let config: TomlConfig = toml::from_str(&toml).unwrap();
+
+        assert_eq!(config.duck_dice.api_key.keys(), ["first", "second"]);
+        assert_eq!(config.validate(), Ok(()));
+
+        let single: TomlConfig = toml::from_str(&sites_toml(
+            (true, "None", "BTC"),
+            (false, "None", "BTC"),
+            (false, "None"),
+        ))
+        .unwrap();
+        assert_eq!(single.duck_dice.api_key, ApiKeys::Single("key".to_string()));
+
+        let mut empty = config;
+        empty.duck_dice.api_key = ApiKeys::Multiple(vec![]);
+        assert!(empty.validate().is_err());
+        empty.duck_dice.api_key = ApiKeys::Multiple(vec!["first".to_string(), String::new()]);
+        assert!(empty.validate().is_err());
+    }
 }
@@ -2,7 +2,7 @@ use serde::Deserialize;
 
 use crate::sites::Sites;
 
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Hash)]
 pub enum Currency {
     XRP,
     DECOY,
@@ -1,91 +1,162 @@
 use burn::{data::dataloader::batcher::Batcher, prelude::*};
+use log::warn;
 
 use crate::dataset::BetResultCsvRecord;
+use crate::model::OutputHead;
+use crate::normalizer::Normalizer;
+use crate::util::{self, HexError, Roll};
 
 #[derive(Clone)]
 pub struct BetBatcher<B: Backend> {
     device: B::Device,
+    head: OutputHead,
+    history_size: usize,
+    /// Standardizes the continuous features; `None` leaves their slots zero.
+    normalizer: Option<Normalizer>,
 }
 
 impl<B: Backend> BetBatcher<B> {
     pub fn new(device: B::Device) -> Self {
-        Self { device }
+        Self {
+            device,
+            head: OutputHead::Classification {
+                num_classes: util::BUCKET_COUNT,
+            },
+            history_size: util::HISTORY_SIZE,
+            normalizer: None,
+        }
+    }
+
+    /// Batches rolls in windows of `history_size`, which must match the model's.
+    pub fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+
+        self
+    }
+
+    /// Builds targets for the model's output `head`.
+    pub fn with_head(mut self, head: OutputHead) -> Self {
+        self.head = head;
+
+        self
+    }
+
+    /// Writes the continuous features standardized by `normalizer`, which must be the
+    /// one saved with the model.
+    pub fn with_normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = Some(normalizer);
+
+        self
+    }
+
+    /// Target of one window whose next roll is `next_number`.
+    fn target(&self, next_number: u32) -> Vec<B::FloatElem> {
+        let roll = Roll::clamped(next_number).value();
+        match self.head {
+            OutputHead::Classification { num_classes } => {
+                let mut target = vec![(-1f32).elem::<B::FloatElem>(); num_classes];
+                target[roll as usize * num_classes / (Roll::MAX as usize + 1)] =
+                    1f32.elem::<B::FloatElem>();
+                target
+            }
+            OutputHead::Regression => vec![(roll as f32 / Roll::MAX as f32).elem()],
+        }
     }
 }
 
+/// Encodes one record into its `FINAL_FEATURE_SIZE` features, failing when either
+/// server seed hash is not hex.
+///
+/// Matches `inference::encode_roll` for the roll the record was made from, so the model
+/// sees the same inputs in training and at inference.
+pub fn encode_record<B: Backend>(
+    record: &BetResultCsvRecord,
+    normalizer: Option<&Normalizer>,
+) -> Result<Vec<B::FloatElem>, HexError> {
+    let mut vals = util::hex_string_to_binary_vec_checked::<B>(&record.server_seed_hash_next_roll)?;
+    vals.resize(util::HASH_NEXT_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
+
+    vals.append(&mut util::hex_string_to_binary_vec_checked::<B>(
+        &record.server_seed_hash_previous_roll,
+    )?);
+    vals.resize(util::HASH_PREVIOUS_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
+
+    // Client seeds are free text on most sites, so they are not required to be hex.
+    vals.append(&mut util::hex_string_to_binary_vec::<B>(
+        &record.client_seed,
+    ));
+    vals.resize(util::CLIENT_SEED_SIZE, 0f32.elem::<B::FloatElem>());
+
+    vals.append(&mut util::nonce_to_binary_vec::<B>(record.nonce));
+    vals.resize(util::FINAL_FEATURE_SIZE, 0f32.elem::<B::FloatElem>());
+
+    if let Some(normalizer) = normalizer {
+        normalizer.write::<B>(continuous_features(record), &mut vals);
+    }
+
+    Ok(vals)
+}
+
+/// Continuous (non-binary) values of a record, to be standardized by a `Normalizer`.
+///
+/// Only values a settled bet also carries are used, as `inference::continuous_features`
+/// must give the same ones for the live roll; the balance is left out for that reason.
+pub fn continuous_features(record: &BetResultCsvRecord) -> Vec<f32> {
+    vec![record.rolled_number as f32, record.amount_won as f32]
+}
+
 #[derive(Clone, Debug)]
 pub struct BetBatch<B: Backend> {
     pub inputs: Tensor<B, 4>,
-    pub targets: Tensor<B, 2, Int>,
+    /// One row per window: 1 for the roll's class and -1 elsewhere, or the roll
+    /// scaled to 0-1 for a regression head.
+    pub targets: Tensor<B, 2>,
 }
 
 impl<B: Backend> Batcher<B, BetResultCsvRecord, BetBatch<B>> for BetBatcher<B> {
     fn batch(&self, items: Vec<BetResultCsvRecord>, device: &B::Device) -> BetBatch<B> {
-        let history_size: usize = 10;
-
-        let inputs_data = items.clone();
-        let inputs_hash = inputs_data
+        let history_size = self.history_size;
+        // Records with a malformed hash are dropped before windowing, so the windows
+        // stay whole.
+        let normalizer = self.normalizer.as_ref();
+        let rows = items
             .iter()
-            .flat_map(|itm| {
-                let mut vals =
-                    crate::util::hex_string_to_binary_vec::<B>(&itm.server_seed_hash_next_roll);
-                vals.resize(
-                    crate::util::HASH_NEXT_ROLL_SIZE,
-                    0f32.elem::<B::FloatElem>(),
-                );
-
-                vals.append(&mut crate::util::hex_string_to_binary_vec::<B>(
-                    &itm.server_seed_hash_previous_roll,
-                ));
-                vals.resize(
-                    crate::util::HASH_PREVIOUS_ROLL_SIZE,
-                    0f32.elem::<B::FloatElem>(),
-                );
-
-                vals.append(&mut crate::util::hex_string_to_binary_vec::<B>(
-                    &itm.client_seed,
-                ));
-                vals.resize(crate::util::CLIENT_SEED_SIZE, 0f32.elem::<B::FloatElem>());
-
-                vals.append(
-                    &mut (0..32)
-                        .map(|i| ((itm.nonce >> i) & 1).elem::<B::FloatElem>())
-                        .collect::<Vec<B::FloatElem>>(),
-                );
-
-                vals.resize(crate::util::FINAL_FEATURE_SIZE, 0f32.elem::<B::FloatElem>());
-
-                vals
+            .filter_map(|itm| match encode_record::<B>(itm, normalizer) {
+                Ok(features) => Some((features, itm.next_number)),
+                Err(err) => {
+                    warn!("Skipping record with nonce {}: {}", itm.nonce, err);
+                    None
+                }
             })
+            .collect::<Vec<_>>();
+        let rows = crate::util::fit_to_windows(rows, history_size);
+
+        let targets = rows
+            .chunks(history_size)
+            .filter_map(|window| window.last())
+            .flat_map(|(_, next_number)| self.target(*next_number))
+            .collect::<Vec<B::FloatElem>>();
+        let windows = rows.len() / history_size;
+        let inputs_hash = rows
+            .into_iter()
+            .flat_map(|(features, _)| features)
             .collect::<Vec<B::FloatElem>>();
 
         let hash_data = TensorData::new(
             inputs_hash,
             [
-                items.len() / history_size,
+                windows,
                 history_size,
-                4,
-                crate::util::HASH_NEXT_ROLL_SIZE,
+                util::FEATURE_BLOCKS,
+                util::FEATURE_STRIDE,
             ],
         );
         let hash_data: Tensor<B, 4> =
             Tensor::from(hash_data.convert::<B::FloatElem>()).to_device(&self.device);
 
-        let targets = items
-            .chunks(history_size)
-            .flat_map(|itm| {
-                let mut arr = [(-1f32).elem::<B::FloatElem>(); 100];
-                if let Some(itm) = itm.last() {
-                    arr[itm.next_number as usize / 100] = 1f32.elem::<B::FloatElem>();
-                }
-                arr
-            })
-            .collect::<Vec<B::FloatElem>>();
-
-        let target_data = TensorData::new(targets, [items.len() / history_size, 100]);
+        let target_data = TensorData::new(targets, [windows, self.head.outputs()]);
         let target_data: Tensor<B, 2> =
             Tensor::from(target_data.convert::<B::FloatElem>()).to_device(device);
-        let target_data = target_data.int();
 
         BetBatch {
             inputs: hash_data,
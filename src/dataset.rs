@@ -2,12 +2,15 @@ use burn::data::dataset::Dataset;
 use serde::{Deserialize, Serialize};
 
 use lazy_static::lazy_static;
-use rand::Rng;
-use ring::hmac;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use ring::rand::{SecureRandom, SystemRandom};
-use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::sync::Mutex;
 
+use crate::sites::roll_scheme::{FreeBitcoScheme, RollScheme};
+
 lazy_static! {
     pub static ref SERVER_STORAGE: Mutex<FakeServerStorage> =
         Mutex::new(FakeServerStorage::default());
@@ -45,20 +48,76 @@ pub struct BetResultCsvRecord {
     pub duplicate_rolls: Vec<u32>,
 }
 
-/// Returns: (rolled_number, server_seed, nonce)
+/// Layout of a recorded CSV, for files that do not use the `BetResultCsvRecord` headers.
+///
+/// Columns are matched by header name, so extra and reordered columns are fine;
+/// `columns` maps a record field to the header it is stored under in the file.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CsvFormat {
+    pub delimiter: char,
+    /// Record field name -> header name in the file, e.g. `rolled_number = "roll"`.
+    pub columns: HashMap<String, String>,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            columns: HashMap::new(),
+        }
+    }
+}
+
+impl CsvFormat {
+    /// Reads every record of the CSV at `path`.
+    pub fn read_records<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<BetResultCsvRecord>, csv::Error> {
+        let delimiter = u8::try_from(self.delimiter).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("CSV delimiter {:?} is not a single byte", self.delimiter),
+            )
+        })?;
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_path(path)?;
+
+        if !self.columns.is_empty() {
+            let renamed = reader
+                .headers()?
+                .iter()
+                .map(|header| {
+                    self.columns
+                        .iter()
+                        .find(|(_, source)| source.as_str() == header)
+                        .map_or(header, |(field, _)| field.as_str())
+                })
+                .collect::<csv::StringRecord>();
+            reader.set_headers(renamed);
+        }
+
+        reader.deserialize().collect()
+    }
+}
+
+/// Rolls with the provably-fair `scheme` of the site the data stands in for.
+///
+/// Returns: (rolled_number, server_seed_hash, client_seed, nonce)
 pub fn gen_fake_bet(
     server_storage: &mut FakeServerStorage,
+    scheme: &dyn RollScheme,
     _client_seed: &str,
     nonce: u64,
 ) -> (u32, String, String, u64) {
     let sys_random = SystemRandom::new();
 
-    let mut server_seed = [0u8; 64];
+    let mut server_seed = [0u8; 32];
     sys_random.fill(&mut server_seed).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(server_seed);
-    let result = hasher.finalize();
-    let server_seed_hash = hex::encode(result);
+    let server_seed = hex::encode(server_seed);
+    let server_seed_hash = scheme.server_seed_hash(&server_seed);
 
     let mut rng = rand::rng();
     let client_seed_len = rng.random_range(0..64);
@@ -68,18 +127,7 @@ pub fn gen_fake_bet(
         .map(char::from)
         .collect();
 
-    let mut combined_seed = Vec::new();
-    combined_seed.extend_from_slice(&server_seed);
-    combined_seed.extend_from_slice(client_seed.as_bytes());
-    combined_seed.extend_from_slice(&nonce.to_be_bytes());
-
-    let key = hmac::Key::new(hmac::HMAC_SHA256, &server_seed);
-    let tag = hmac::sign(&key, &combined_seed);
-
-    let random_bytes = &tag.as_ref()[..4];
-    let random_u32 = u32::from_le_bytes(random_bytes.try_into().unwrap());
-
-    let number = random_u32 % 10_000;
+    let number = scheme.roll(&server_seed, &client_seed, nonce);
 
     (
         number,
@@ -99,7 +147,7 @@ pub fn free_bitcoin_fake_bet(
     let server_storage: &mut FakeServerStorage = &mut SERVER_STORAGE.lock().unwrap();
 
     let (rolled_number, server_seed, s_client_seed, nonce) =
-        gen_fake_bet(server_storage, client_seed, nonce);
+        gen_fake_bet(server_storage, &FreeBitcoScheme, client_seed, nonce);
     server_storage.server_seed_hash_previous_roll = server_storage.current_seed_hash.clone();
     server_storage.current_seed_hash = server_storage.server_seed_hash_next_roll.clone();
     server_storage.server_seed_hash_next_roll = server_seed.clone();
@@ -131,7 +179,7 @@ pub fn free_bitcoin_fake_bet(
     };
 
     let (rolled_number, server_seed, _client_seed, nonce) =
-        gen_fake_bet(server_storage, client_seed, nonce);
+        gen_fake_bet(server_storage, &FreeBitcoScheme, client_seed, nonce);
     server_storage.server_seed_hash_previous_roll = server_storage.current_seed_hash.clone();
     server_storage.current_seed_hash = server_storage.server_seed_hash_next_roll.clone();
     server_storage.server_seed_hash_next_roll = server_seed.clone();
@@ -149,20 +197,122 @@ pub fn free_bitcoin_fake_bet(
 
 pub struct BetResultsDataset {
     len: usize,
+    records: Option<Vec<BetResultCsvRecord>>,
 }
 
 impl BetResultsDataset {
     pub fn train() -> Result<Self, std::io::Error> {
-        Ok(Self { len: 1_000_000 })
+        Ok(Self::synthetic(1_000_000))
     }
 
     pub fn test() -> Result<Self, std::io::Error> {
-        Ok(Self { len: 1_000 })
+        Ok(Self::synthetic(1_000))
+    }
+
+    /// Serves `len` bets from the fake FreeBitco.in server, generated as they are read.
+    pub fn synthetic(len: usize) -> Self {
+        Self { len, records: None }
+    }
+
+    /// Loads every recorded roll from the CSV at `path`, in file order.
+    ///
+    /// Fails when the file holds fewer rows than one `HISTORY_SIZE` window, which the
+    /// batcher needs to build a single sample.
+    pub fn from_csv<P: AsRef<std::path::Path>>(path: P) -> Result<Self, std::io::Error> {
+        Self::from_csv_with(path, &CsvFormat::default())
+    }
+
+    /// Like `from_csv`, reading a file laid out as described by `format`.
+    pub fn from_csv_with<P: AsRef<std::path::Path>>(
+        path: P,
+        format: &CsvFormat,
+    ) -> Result<Self, std::io::Error> {
+        Ok(Self::from_records(read_history(path, format)?))
+    }
+
+    /// Loads recorded rolls from `path` and splits them into `(train, valid)` datasets.
+    ///
+    /// Rolls are sequential, so the split is done on whole `HISTORY_SIZE` windows of
+    /// consecutive rows rather than on single rows: a window never straddles the
+    /// boundary, which would otherwise leak the same rolls into both sides. Windows are
+    /// shuffled with `seed` before `valid_ratio` of them are moved to the validation
+    /// set, and trailing rows that do not fill a whole window are dropped.
+    pub fn split_from_csv<P: AsRef<std::path::Path>>(
+        path: P,
+        valid_ratio: f32,
+        seed: u64,
+    ) -> Result<(Self, Self), std::io::Error> {
+        Self::split_from_csv_with(path, &CsvFormat::default(), valid_ratio, seed)
+    }
+
+    /// Like `split_from_csv`, reading a file laid out as described by `format`.
+    pub fn split_from_csv_with<P: AsRef<std::path::Path>>(
+        path: P,
+        format: &CsvFormat,
+        valid_ratio: f32,
+        seed: u64,
+    ) -> Result<(Self, Self), std::io::Error> {
+        if !(0. ..1.).contains(&valid_ratio) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("valid_ratio must be in [0, 1), got {valid_ratio}"),
+            ));
+        }
+
+        let records = read_history(path, format)?;
+
+        let mut windows = records
+            .chunks_exact(crate::util::HISTORY_SIZE)
+            .map(|window| window.to_vec())
+            .collect::<Vec<Vec<BetResultCsvRecord>>>();
+        windows.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let valid_windows = (windows.len() as f32 * valid_ratio).round() as usize;
+        let train_windows = windows.split_off(valid_windows);
+
+        Ok((
+            Self::from_records(train_windows.into_iter().flatten().collect()),
+            Self::from_records(windows.into_iter().flatten().collect()),
+        ))
+    }
+
+    fn from_records(records: Vec<BetResultCsvRecord>) -> Self {
+        Self {
+            len: records.len(),
+            records: Some(records),
+        }
     }
 }
 
+/// Reads the records at `path`, failing when they do not fill one `HISTORY_SIZE` window.
+fn read_history<P: AsRef<std::path::Path>>(
+    path: P,
+    format: &CsvFormat,
+) -> Result<Vec<BetResultCsvRecord>, std::io::Error> {
+    let path = path.as_ref();
+    let records = format.read_records(path)?;
+
+    if records.len() < crate::util::HISTORY_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} has {} rows, fewer than the model window of {}",
+                path.display(),
+                records.len(),
+                crate::util::HISTORY_SIZE
+            ),
+        ));
+    }
+
+    Ok(records)
+}
+
 impl Dataset<BetResultCsvRecord> for BetResultsDataset {
     fn get(&self, index: usize) -> Option<BetResultCsvRecord> {
+        if let Some(records) = &self.records {
+            return records.get(index).cloned();
+        }
+
         Some(free_bitcoin_fake_bet(
             true,
             "lYypIPVEgzvCflWF",
@@ -176,3 +326,126 @@ impl Dataset<BetResultCsvRecord> for BetResultsDataset {
         self.len
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(rolled_number: u32) -> BetResultCsvRecord {
+        BetResultCsvRecord {
+            result: true,
+            rolled_number,
+            next_number: rolled_number + 1,
+            user_balance: 0.,
+            amount_won: 0.,
+            server_seed_hash_next_roll: String::new(),
+            client_seed: String::new(),
+            nonce_next_roll: rolled_number as u64 + 1,
+            nonce: rolled_number as u64,
+            server_seed_previous_roll: String::new(),
+            server_seed_hash_previous_roll: String::new(),
+            previous_nonce: 0,
+            duplicate_rolls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_split_from_csv_keeps_windows_whole() {
+        let history_size = crate::util::HISTORY_SIZE;
+        let path = std::env::temp_dir().join(format!("split_test_{}.csv", std::process::id()));
+        let mut writer = csv::Writer::from_path(&path).unwrap();
+        for i in 0..(history_size * 10 + 3) {
+            writer.serialize(record(i as u32)).unwrap();
+        }
+        drop(writer);
+
+        let (train, valid) = BetResultsDataset::split_from_csv(&path, 0.2, 7).unwrap();
+        let (train_again, _) = BetResultsDataset::split_from_csv(&path, 0.2, 7).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(train.len(), history_size * 8);
+        assert_eq!(valid.len(), history_size * 2);
+        for dataset in [&train, &valid] {
+            for start in (0..dataset.len()).step_by(history_size) {
+                let first = dataset.get(start).unwrap().rolled_number as usize;
+                assert_eq!(first % history_size, 0);
+                for offset in 1..history_size {
+                    let rolled = dataset.get(start + offset).unwrap().rolled_number as usize;
+                    assert_eq!(rolled, first + offset);
+                }
+            }
+        }
+        assert_eq!(
+            train.get(0).unwrap().rolled_number,
+            train_again.get(0).unwrap().rolled_number
+        );
+    }
+
+    #[test]
+    fn test_from_csv_serves_recorded_rows() {
+        let history_size = crate::util::HISTORY_SIZE;
+        let path = std::env::temp_dir().join(format!("from_csv_test_{}.csv", std::process::id()));
+        let write = |rows: usize| {
+            let mut writer = csv::Writer::from_path(&path).unwrap();
+            for i in 0..rows {
+                writer.serialize(record(i as u32 * 7)).unwrap();
+            }
+        };
+
+        write(history_size + 2);
+        let dataset = BetResultsDataset::from_csv(&path);
+        write(history_size - 1);
+        let short = BetResultsDataset::from_csv(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let dataset = dataset.unwrap();
+        assert_eq!(dataset.len(), history_size + 2);
+        assert_eq!(dataset.get(3).unwrap().rolled_number, 21);
+        assert!(dataset.get(history_size + 2).is_none());
+        assert_eq!(
+            short.err().map(|err| err.kind()),
+            Some(std::io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_csv_format_maps_renamed_and_reordered_columns() {
+        let path = std::env::temp_dir().join(format!("format_test_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "site;roll;next;won;balance;profit;hash_next;client;nonce_next;nonce;seed_prev;hash_prev;nonce_prev\n\
+             duck;42;43;true;1.5;0.5;aa;cs;2;1;bb;cc;0\n",
+        )
+        .unwrap();
+
+        let format = CsvFormat {
+            delimiter: ';',
+            columns: [
+                ("result", "won"),
+                ("rolled_number", "roll"),
+                ("next_number", "next"),
+                ("user_balance", "balance"),
+                ("amount_won", "profit"),
+                ("server_seed_hash_next_roll", "hash_next"),
+                ("client_seed", "client"),
+                ("nonce_next_roll", "nonce_next"),
+                ("server_seed_previous_roll", "seed_prev"),
+                ("server_seed_hash_previous_roll", "hash_prev"),
+                ("previous_nonce", "nonce_prev"),
+            ]
+            .into_iter()
+            .map(|(field, header)| (field.to_string(), header.to_string()))
+            .collect(),
+        };
+        let records = format.read_records(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let records = records.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].result);
+        assert_eq!(records[0].rolled_number, 42);
+        assert_eq!(records[0].next_number, 43);
+        assert_eq!(records[0].nonce, 1);
+        assert_eq!(records[0].client_seed, "cs");
+    }
+}
@@ -1 +1,454 @@
+//! Turning a window of settled bets into the next prediction.
 
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use burn::prelude::*;
+
+use crate::data::BetBatch;
+use crate::model::Model;
+use crate::normalizer::{Normalizer, NORMALIZER_FILE};
+use crate::sites::{BetError, BetResult};
+use crate::training::TrainingConfig;
+use crate::util::{self, BucketAnchor, Roll};
+
+/// The model's guess for the next roll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prediction {
+    /// Predicted roll on the 0-9999 scale.
+    pub roll: f32,
+    /// Model output of the chosen bucket, scaled by 100.
+    pub confidence: f32,
+}
+
+/// Checks that both server seed hashes of `roll` are hex; `encode_roll` would encode a
+/// malformed one as zeros.
+pub fn check_hashes(roll: &BetResult) -> Result<(), util::HexError> {
+    util::check_hex(&roll.hash_next_roll)?;
+    util::check_hex(&roll.hash_previous_roll)
+}
+
+/// Loads the normalizer saved next to a model trained with `normalize_features`, and
+/// `None` for a model trained without one.
+pub fn load_normalizer(
+    artifact_dir: &str,
+    config: &TrainingConfig,
+) -> Result<Option<Normalizer>, BetError> {
+    if !config.normalize_features {
+        return Ok(None);
+    }
+
+    Normalizer::load_from(artifact_dir).map(Some).ok_or_else(|| {
+        BetError::ModelError(format!(
+            "Model was trained with normalize_features but {artifact_dir}/{NORMALIZER_FILE} is missing"
+        ))
+    })
+}
+
+/// Continuous values of a roll, the same ones `data::continuous_features` takes from
+/// the training row recorded for it.
+pub fn continuous_features(roll: &BetResult) -> Vec<f32> {
+    vec![roll.number.value() as f32, roll.profit()]
+}
+
+/// Encodes one roll into its `FINAL_FEATURE_SIZE` feature block, with the continuous
+/// features standardized by `normalizer` when the model was trained with one.
+pub fn encode_roll<B: Backend>(
+    roll: &BetResult,
+    normalizer: Option<&Normalizer>,
+) -> Vec<B::FloatElem> {
+    let mut vals = util::hex_string_to_binary_vec::<B>(&roll.hash_next_roll);
+    vals.resize(util::HASH_NEXT_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
+
+    vals.append(&mut util::hex_string_to_binary_vec::<B>(
+        &roll.hash_previous_roll,
+    ));
+    vals.resize(util::HASH_PREVIOUS_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
+
+    vals.append(&mut util::hex_string_to_binary_vec::<B>(&roll.client_seed));
+    vals.resize(util::CLIENT_SEED_SIZE, 0f32.elem::<B::FloatElem>());
+
+    vals.append(&mut util::nonce_to_binary_vec::<B>(roll.nonce.into()));
+    vals.resize(util::FINAL_FEATURE_SIZE, 0f32.elem::<B::FloatElem>());
+
+    if let Some(normalizer) = normalizer {
+        normalizer.write::<B>(continuous_features(roll), &mut vals);
+    }
+
+    vals
+}
+
+/// Builds a model input of shape `[1, rolls, FEATURE_BLOCKS, FEATURE_STRIDE]` from
+/// per-roll features.
+fn window_tensor<B: Backend>(
+    inputs: Vec<B::FloatElem>,
+    rolls: usize,
+    device: &B::Device,
+) -> Tensor<B, 4> {
+    let hash_data = TensorData::new(
+        inputs,
+        [1, rolls, util::FEATURE_BLOCKS, util::FEATURE_STRIDE],
+    );
+
+    Tensor::from(hash_data.convert::<B::FloatElem>()).to_device(device)
+}
+
+/// Encodes `window` into a model input of shape
+/// `[1, window.len(), FEATURE_BLOCKS, FEATURE_STRIDE]`.
+pub fn encode_window<B: Backend>(
+    window: &[BetResult],
+    normalizer: Option<&Normalizer>,
+    device: &B::Device,
+) -> Tensor<B, 4> {
+    let inputs = window
+        .iter()
+        .flat_map(|roll| encode_roll::<B>(roll, normalizer))
+        .collect::<Vec<B::FloatElem>>();
+
+    window_tensor::<B>(inputs, window.len(), device)
+}
+
+/// The fields of a roll its feature block is built from.
+#[derive(Clone, Debug, PartialEq)]
+struct RollKey {
+    hash_next_roll: String,
+    hash_previous_roll: String,
+    client_seed: String,
+    nonce: u32,
+    number: u32,
+    profit: f32,
+}
+
+impl RollKey {
+    fn of(roll: &BetResult) -> Self {
+        Self {
+            hash_next_roll: roll.hash_next_roll.clone(),
+            hash_previous_roll: roll.hash_previous_roll.clone(),
+            client_seed: roll.client_seed.clone(),
+            nonce: roll.nonce,
+            number: roll.number.value(),
+            profit: roll.profit(),
+        }
+    }
+}
+
+/// Feature blocks of the last encoded window, so that sliding the window by one roll
+/// only hex-decodes the new roll.
+pub struct FeatureCache<B: Backend> {
+    blocks: VecDeque<(RollKey, Vec<B::FloatElem>)>,
+    /// Rolls encoded from scratch rather than taken from the cache.
+    encoded: u64,
+}
+
+impl<B: Backend> Default for FeatureCache<B> {
+    fn default() -> Self {
+        Self {
+            blocks: VecDeque::new(),
+            encoded: 0,
+        }
+    }
+}
+
+impl<B: Backend> FeatureCache<B> {
+    /// Encodes `window` like `encode_window`, reusing the blocks of rolls that were part
+    /// of the previous window. `normalizer` must be the same on every call.
+    pub fn encode_window(
+        &mut self,
+        window: &[BetResult],
+        normalizer: Option<&Normalizer>,
+        device: &B::Device,
+    ) -> Tensor<B, 4> {
+        let mut blocks = VecDeque::with_capacity(window.len());
+        for roll in window {
+            let key = RollKey::of(roll);
+            // Rolls older than a cached match have left the window and are dropped.
+            let block = match self.blocks.iter().position(|(cached, _)| *cached == key) {
+                Some(index) => {
+                    self.blocks.drain(..index);
+                    self.blocks.pop_front().map(|(_, block)| block)
+                }
+                None => None,
+            };
+            let block = block.unwrap_or_else(|| {
+                self.encoded += 1;
+                encode_roll::<B>(roll, normalizer)
+            });
+            blocks.push_back((key, block));
+        }
+        self.blocks = blocks;
+
+        let inputs = self
+            .blocks
+            .iter()
+            .flat_map(|(_, block)| block.iter().copied())
+            .collect::<Vec<B::FloatElem>>();
+
+        window_tensor::<B>(inputs, window.len(), device)
+    }
+}
+
+/// Runs the model over `window` and decodes the most likely bucket to the roll `anchor`
+/// picks within it.
+pub fn predict_next<B: Backend>(
+    model: &Model<B>,
+    window: &[BetResult],
+    normalizer: Option<&Normalizer>,
+    anchor: BucketAnchor,
+    device: &B::Device,
+) -> Result<Prediction, BetError> {
+    predict_encoded(
+        model,
+        encode_window::<B>(window, normalizer, device),
+        anchor,
+        device,
+    )
+}
+
+/// Like `predict_next`, for a window that was already encoded.
+///
+/// A panicking forward pass (e.g. a shape mismatch) is reported as a `ModelError` rather
+/// than unwinding through the betting loop.
+pub fn predict_encoded<B: Backend>(
+    model: &Model<B>,
+    inputs: Tensor<B, 4>,
+    anchor: BucketAnchor,
+    device: &B::Device,
+) -> Result<Prediction, BetError> {
+    decode_output(&forward(model, inputs, device)?, anchor)
+}
+
+/// Runs one forward pass on a zeroed window, so the backend compiles its shaders and
+/// allocates its buffers before the first bet instead of during it. Returns how long
+/// the pass took.
+pub fn warm_up<B: Backend>(model: &Model<B>, device: &B::Device) -> Result<Duration, BetError> {
+    let started = Instant::now();
+    let inputs = Tensor::zeros(
+        [
+            1,
+            model.history_size(),
+            util::FEATURE_BLOCKS,
+            util::FEATURE_STRIDE,
+        ],
+        device,
+    );
+    forward(model, inputs, device)?;
+
+    Ok(started.elapsed())
+}
+
+/// Raw model outputs for `inputs`, with panics in the backend turned into errors.
+fn forward<B: Backend>(
+    model: &Model<B>,
+    inputs: Tensor<B, 4>,
+    device: &B::Device,
+) -> Result<Vec<f32>, BetError> {
+    let output = catch_unwind(AssertUnwindSafe(|| {
+        model
+            .forward(BetBatch {
+                inputs,
+                targets: Tensor::zeros(Shape::new([1, 1]), device),
+            })
+            .into_data()
+    }))
+    .map_err(|_| BetError::ModelError("Forward pass panicked".to_string()))?;
+    output
+        .to_vec::<f32>()
+        .map_err(|e| BetError::ModelError(format!("Unreadable model output: {e:?}")))
+}
+
+/// Decodes the model's bucket scores into a prediction.
+pub fn decode_output(outputs: &[f32], anchor: BucketAnchor) -> Result<Prediction, BetError> {
+    if outputs.iter().any(|value| !value.is_finite()) {
+        return Err(BetError::ModelError(
+            "Model output is not finite".to_string(),
+        ));
+    }
+    // A regression head outputs the roll itself, scaled to 0-1, and no class score.
+    if let [roll] = outputs {
+        return Ok(Prediction {
+            roll: (roll.clamp(0., 1.) * Roll::MAX as f32).round(),
+            confidence: 0.,
+        });
+    }
+
+    let rolls = Roll::MAX as usize + 1;
+    if outputs.is_empty() || !rolls.is_multiple_of(outputs.len()) {
+        return Err(BetError::ModelError(format!(
+            "Model output has {} classes, which do not split the {} rolls evenly",
+            outputs.len(),
+            rolls
+        )));
+    }
+    let index = argmax(outputs);
+    let width = (rolls / outputs.len()) as u32;
+
+    Ok(Prediction {
+        roll: (index as u32 * width + anchor.offset(width)) as f32,
+        confidence: outputs.get(index).copied().unwrap_or(0.) * 100.,
+    })
+}
+
+/// Index of the largest value, choosing the lowest index on ties.
+///
+/// `Tensor::argmax` leaves ties to the backend, which makes backtests irreproducible
+/// across devices. NaN values are never selected; an empty or all-NaN slice gives 0.
+pub fn argmax(values: &[f32]) -> usize {
+    let mut best: Option<(usize, f32)> = None;
+    for (index, &value) in values.iter().enumerate() {
+        if value.is_nan() {
+            continue;
+        }
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((index, value));
+        }
+    }
+
+    best.map_or(0, |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argmax_breaks_ties_on_lowest_index() {
+        assert_eq!(argmax(&[0.1, 0.7, 0.3, 0.7]), 1);
+        assert_eq!(argmax(&[0.5, 0.5]), 0);
+        assert_eq!(argmax(&[f32::NAN, 0.2, 0.1]), 1);
+        assert_eq!(argmax(&[]), 0);
+    }
+
+    #[test]
+    fn test_decode_output_rejects_broken_outputs() {
+        let mut outputs = vec![0.; util::BUCKET_COUNT];
+        outputs[42] = 0.5;
+        assert_eq!(
+            decode_output(&outputs, BucketAnchor::Low).unwrap(),
+            Prediction {
+                roll: 4200.,
+                confidence: 50.
+            }
+        );
+
+        outputs[3] = f32::NAN;
+        assert!(decode_output(&outputs, BucketAnchor::Low).is_err());
+
+        let mut outputs = vec![0.; util::BUCKET_COUNT + 1];
+        outputs[util::BUCKET_COUNT] = 1.;
+        assert!(decode_output(&outputs, BucketAnchor::Low).is_err());
+        assert!(decode_output(&[], BucketAnchor::Low).is_err());
+    }
+
+    #[test]
+    fn test_decode_output_follows_the_head() {
+        let mut outputs = vec![0.; 50];
+        outputs[21] = 0.9;
+        assert_eq!(
+            decode_output(&outputs, BucketAnchor::Center).unwrap().roll,
+            4300.
+        );
+
+        assert_eq!(
+            decode_output(&[0.25], BucketAnchor::Low).unwrap(),
+            Prediction {
+                roll: 2500.,
+                confidence: 0.
+            }
+        );
+        assert_eq!(
+            decode_output(&[1.7], BucketAnchor::Low).unwrap().roll,
+            9999.
+        );
+    }
+
+    #[test]
+    fn test_feature_cache_matches_full_encoding() {
+        type TestBackend = burn::backend::NdArray;
+
+        let device = Default::default();
+        let rolls = (0..15)
+            .map(|nonce| {
+                let mut roll = crate::strategies::test_bet_result(true, nonce * 600, 0.);
+                roll.hash_next_roll = format!("{nonce:064x}");
+                roll.hash_previous_roll = format!("{:064x}", nonce + 1);
+                roll.nonce = nonce;
+                roll
+            })
+            .collect::<Vec<BetResult>>();
+
+        let mut cache = FeatureCache::<TestBackend>::default();
+        for start in 0..=rolls.len() - util::HISTORY_SIZE {
+            let window = &rolls[start..start + util::HISTORY_SIZE];
+            cache
+                .encode_window(window, None, &device)
+                .into_data()
+                .assert_eq(
+                    &encode_window::<TestBackend>(window, None, &device).into_data(),
+                    true,
+                );
+        }
+
+        // The first window is encoded in full; every slide after it adds one roll.
+        assert_eq!(cache.encoded, rolls.len() as u64);
+    }
+
+    #[test]
+    fn test_training_and_inference_inputs_match() {
+        use burn::data::dataloader::batcher::Batcher;
+
+        type TestBackend = burn::backend::NdArray;
+
+        let device = Default::default();
+        let rolls = (0..=util::HISTORY_SIZE as u32)
+            .map(|nonce| {
+                let mut roll =
+                    crate::strategies::test_bet_result(nonce.is_multiple_of(2), nonce * 700, 0.5);
+                roll.hash_next_roll = format!("{nonce:064x}");
+                roll.hash_previous_roll = format!("{:064x}", nonce + 1);
+                roll.nonce = nonce;
+                roll.win_amount = nonce as f32 * 0.25;
+                roll
+            })
+            .collect::<Vec<BetResult>>();
+        let records = rolls
+            .windows(2)
+            .map(|pair| crate::recorder::to_csv_record(&pair[0], &pair[1], 10.))
+            .collect::<Vec<_>>();
+        let normalizer = Normalizer::fit(
+            &records
+                .iter()
+                .map(crate::data::continuous_features)
+                .collect::<Vec<_>>(),
+        );
+
+        let training = crate::data::BetBatcher::<TestBackend>::new(device)
+            .with_normalizer(normalizer.clone())
+            .batch(records, &device)
+            .inputs;
+        let window = &rolls[..util::HISTORY_SIZE];
+        let inference = encode_window::<TestBackend>(window, Some(&normalizer), &device);
+        training
+            .into_data()
+            .assert_eq(&inference.clone().into_data(), true);
+
+        // The normalizer did write the continuous slots.
+        let block = inference.into_data().to_vec::<f32>().unwrap();
+        assert_ne!(
+            block[util::CONTINUOUS_OFFSET..util::CONTINUOUS_OFFSET + util::CONTINUOUS_FEATURES],
+            [0., 0.]
+        );
+    }
+
+    #[test]
+    fn test_warm_up_runs_a_forward_pass() {
+        type TestBackend = burn::backend::NdArray;
+
+        let device = Default::default();
+        let model = crate::model::ModelConfig::new()
+            .with_use_decoder(false)
+            .init::<TestBackend>(&device);
+
+        assert!(warm_up(&model, &device).is_ok());
+    }
+}
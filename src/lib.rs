@@ -0,0 +1,20 @@
+//! Dice roll prediction and betting, shared by the desktop bot and the Android app.
+
+#![recursion_limit = "256"]
+
+pub mod backtest;
+pub mod cli;
+pub mod config;
+pub mod currency;
+pub mod data;
+pub mod dataset;
+pub mod inference;
+pub mod model;
+pub mod normalizer;
+pub mod recorder;
+pub mod selftest;
+pub mod simulation;
+pub mod sites;
+pub mod strategies;
+pub mod training;
+pub mod util;
@@ -1,144 +1,394 @@
 #![recursion_limit = "256"]
 
-pub mod config;
-pub mod currency;
-pub mod data;
-pub mod dataset;
-pub mod inference;
-pub mod model;
-pub mod sites;
-pub mod strategies;
-pub mod training;
-pub mod util;
-
 use burn::{
     backend::{wgpu::WgpuDevice, Vulkan},
     prelude::*,
     record::{CompactRecorder, Recorder},
 };
+use chrono::Timelike;
 use colored::Colorize;
-use log::{error, info, warn};
-use model::Model;
-use training::TrainingConfig;
+use log::{debug, error, info, warn};
+use std::io::IsTerminal;
+
+use freebitco_in::cli::{Cli, Command};
+use freebitco_in::config::{InferenceErrorMode, NonceGapMode, SiteConfig, TomlStrategies};
+use freebitco_in::currency::Currency;
+use freebitco_in::model::{Model, ModelConfig};
+use freebitco_in::normalizer::Normalizer;
+use freebitco_in::sites::duck_dice::{self, feed, DuckDiceIo};
+use freebitco_in::sites::latency::{DelayJitter, LatencyStats};
+use freebitco_in::sites::limiter::RequestLimiter;
+use freebitco_in::sites::nonce::NonceTracker;
+use freebitco_in::sites::retry::{self, RetryPolicy};
+use freebitco_in::sites::tor::CircuitRenewal;
+use freebitco_in::sites::{BetError, BetResult, BetRule, Site, Sites};
+use freebitco_in::training::TrainingConfig;
+use freebitco_in::util::{Bucket, BucketAnchor, Roll};
+use freebitco_in::{
+    backtest, config::TomlConfig, inference, recorder, selftest, simulation, sites, strategies,
+};
+
+/// Shortest pause between bets; longer while the site's latency is higher than this.
+const BET_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+/// Prediction in the middle of the roll range, which strategies bet at about 50%.
+const NEUTRAL_PREDICTION: f32 = 5000.;
+/// How often a paused bot checks whether the pause file is gone.
+const PAUSE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often a paused bot reminds the log that it is paused.
+const PAUSE_LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Why the last bet looked the way it did, logged at debug after every bet.
+#[derive(Debug)]
+struct BetDecision {
+    bucket: Bucket,
+    prediction: f32,
+    confidence: f32,
+    high: bool,
+    chance: f32,
+    multiplier: f32,
+    bet: f32,
+    rule: BetRule,
+}
+
+impl BetDecision {
+    fn new(prediction: f32, confidence: f32, bet_result: &BetResult, site: &dyn Site) -> Self {
+        Self {
+            bucket: Roll::clamped(prediction.max(0.) as u32).bucket(),
+            prediction,
+            confidence,
+            high: bet_result.is_high,
+            chance: bet_result.chance,
+            multiplier: site.get_current_multiplier(),
+            bet: site.get_current_bet(),
+            rule: site.last_bet_rule(),
+        }
+    }
+}
 
-use crate::config::SiteConfig;
-#[allow(unused_imports)]
-use crate::sites::{crypto_games::CryptoGames, duck_dice::DuckDiceIo, free_bitco_in::FreeBitcoIn};
-use crate::sites::{BetError, BetResult, Site};
-use crate::{config::TomlConfig, model::ModelConfig};
+impl std::fmt::Display for BetDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bucket {} (predicted {:.0}, confidence {:.2}) -> {} at {:.2}% / {:.2}x, bet {:.8} [{}]",
+            self.bucket.index(),
+            self.prediction,
+            self.confidence,
+            if self.high { "high" } else { "low" },
+            self.chance,
+            self.multiplier,
+            self.bet,
+            self.rule
+        )
+    }
+}
 
 struct Game<B: Backend> {
     confidence: f32,
     site: Box<dyn Site>,
     model: Model<B>,
     device: B::Device,
+    /// Standardizes the continuous features, for models trained with a normalizer.
+    normalizer: Option<Normalizer>,
     prediction: f32,
     initialized: bool,
+    latency: LatencyStats,
+    jitter: DelayJitter,
+    anchor: BucketAnchor,
+    /// Encoded features of the last window; `None` re-encodes the whole window per bet.
+    features: Option<inference::FeatureCache<B>>,
+    on_inference_error: InferenceErrorMode,
+    /// False while inference keeps failing with `InferenceErrorMode::Skip`.
+    prediction_ready: bool,
+    /// Empty replies received in a row since the last settled bet.
+    empty_replies: u32,
+    /// Rate-limited refusals in a row since the last settled bet.
+    rate_limited: u32,
+    /// Retries of requests that failed for network reasons.
+    retry: RetryPolicy,
+    /// Bets failed for network reasons in a row since the last settled bet.
+    network_errors: u32,
+    /// Bets won this session.
+    wins: u64,
+    /// Session profit at which betting stops; `None` bets on.
+    take_profit: Option<f32>,
+    /// Session loss at which betting stops; `None` bets on.
+    stop_loss: Option<f32>,
+    /// Also stop when the profit reaches the strategy's win target.
+    stop_at_win_target: bool,
+    /// Print a line per settled bet.
+    print_bets: bool,
+    /// Appends settled bets to a training CSV; `None` records nothing.
+    recorder: Option<recorder::BetRecorder>,
+    /// Switches Tor circuits every few bets, when betting over Tor.
+    tor: Option<CircuitRenewal>,
+    /// Fetch the balance from the site after every this many bets; `None` never does.
+    refresh_balance_every: Option<u64>,
+    /// Nonce the next bet should report.
+    nonces: NonceTracker,
+    on_nonce_gap: NonceGapMode,
+    /// Put before the lines logged for this site, telling sites apart when several are
+    /// bet on at once; empty otherwise.
+    prefix: String,
 }
 
 impl<B: Backend> Game<B> {
+    fn new(
+        site: Box<dyn Site>,
+        model: Model<B>,
+        device: B::Device,
+        game_config: &TomlConfig,
+    ) -> Self {
+        Self {
+            confidence: 0.,
+            site,
+            model,
+            device,
+            normalizer: None,
+            prediction: 0.,
+            initialized: false,
+            latency: LatencyStats::default(),
+            jitter: game_config.network.delay_jitter(),
+            anchor: game_config.inference.bucket_decode_anchor,
+            features: game_config
+                .inference
+                .cache_features
+                .then(inference::FeatureCache::default),
+            on_inference_error: game_config.inference.on_inference_error,
+            prediction_ready: true,
+            empty_replies: 0,
+            rate_limited: 0,
+            retry: game_config.network.retry_policy(),
+            network_errors: 0,
+            wins: 0,
+            take_profit: game_config.profit_target.take_profit,
+            stop_loss: game_config.profit_target.stop_loss,
+            stop_at_win_target: game_config.profit_target.stop_at_win_target,
+            print_bets: true,
+            recorder: None,
+            tor: None,
+            refresh_balance_every: None,
+            nonces: NonceTracker::default(),
+            on_nonce_gap: game_config.inference.on_nonce_gap,
+            prefix: String::new(),
+        }
+    }
+
     async fn bet(&mut self) -> Result<(), BetError> {
         if !self.initialized {
             B::seed(42);
             self.initialized = true;
         }
-        let bet_result = match self.site.do_bet(self.prediction, self.confidence).await {
+        if !self.prediction_ready {
+            self.prediction_ready = self.update_prediction();
+            if !self.prediction_ready {
+                return Ok(());
+            }
+        }
+        let bet_result = match sites::settle_bet(
+            self.site.as_mut(),
+            self.prediction,
+            self.confidence,
+            &mut self.latency,
+        )
+        .await
+        {
             Ok(res) => res,
             Err(err) => match err {
-                BetError::EmptyReply => return Ok(()),
+                BetError::EmptyReply => {
+                    return sites::handle_empty_reply(self.site.as_mut(), &mut self.empty_replies)
+                        .await
+                }
+                BetError::RateLimited(retry_after) => {
+                    return sites::handle_rate_limit(retry_after, &mut self.rate_limited).await
+                }
+                err if retry::is_transient(&err) => {
+                    return retry::handle_transient_error(
+                        err,
+                        &self.retry,
+                        &mut self.network_errors,
+                    )
+                    .await
+                }
                 _ => return Err(err),
             },
         };
+        self.empty_replies = 0;
+        self.rate_limited = 0;
+        self.network_errors = 0;
+
+        log::debug!(
+            "Decision: {}",
+            BetDecision::new(
+                self.prediction,
+                self.confidence,
+                &bet_result,
+                self.site.as_ref()
+            )
+        );
 
         if bet_result.result {
+            self.wins += 1;
             self.site.on_win(&bet_result);
             self.print_res(&bet_result, true);
         } else {
             self.site.on_lose(&bet_result);
             self.print_res(&bet_result, false);
         }
-
-        let history = self.site.get_history();
-        let history_size = self.site.get_history_size();
-        // Get server seed hash next roll and convert it to a tensor of shape (-1, 256).
-        if history.len() >= history_size {
-            let inputs_hash = history
-                .iter()
-                .flat_map(|itm| {
-                    let mut vals = util::hex_string_to_binary_vec::<B>(&itm.hash_next_roll);
-                    vals.resize(util::HASH_NEXT_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
-
-                    vals.append(&mut util::hex_string_to_binary_vec::<B>(
-                        &itm.hash_previous_roll,
-                    ));
-                    vals.resize(util::HASH_PREVIOUS_ROLL_SIZE, 0f32.elem::<B::FloatElem>());
-
-                    vals.append(&mut util::hex_string_to_binary_vec::<B>(&itm.client_seed));
-                    vals.resize(util::CLIENT_SEED_SIZE, 0f32.elem::<B::FloatElem>());
-
-                    vals.append(
-                        &mut (0..32)
-                            .map(|i| ((itm.nonce >> i) & 1).elem::<B::FloatElem>())
-                            .collect::<Vec<B::FloatElem>>(),
-                    );
-                    vals.resize(util::FINAL_FEATURE_SIZE, 0f32.elem::<B::FloatElem>());
-
-                    vals
-                })
-                .collect::<Vec<B::FloatElem>>();
-
-            let hash_data = TensorData::new(
-                inputs_hash,
-                [
-                    history.len() / history_size,
-                    history_size,
-                    4,
-                    util::HASH_NEXT_ROLL_SIZE,
-                ],
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(&bet_result, self.site.get_balance()) {
+                error!("Failed to record bet: {}", e);
+            }
+        }
+        if let Err(e) = self.nonces.check(&bet_result) {
+            if self.on_nonce_gap == NonceGapMode::Halt {
+                return Err(e);
+            }
+            warn!(
+                "{}{}, bets were missed or placed elsewhere on the account",
+                self.prefix, e
             );
-            let hash_data: Tensor<B, 4> =
-                Tensor::from(hash_data.convert::<B::FloatElem>()).to_device(&self.device);
-
-            let output = self.model.forward(data::BetBatch {
-                inputs: hash_data,
-                targets: Tensor::zeros(Shape::new([1, 1]), &self.device),
-            });
-            let predicted_output = output
-                .clone()
-                .argmax(1)
-                .into_data()
-                .to_vec::<i32>()
-                .unwrap();
-            let predicted_output = predicted_output[0];
-            let confidence = output.clone().into_data().to_vec::<f32>().unwrap()
-                [predicted_output as usize]
-                * 100.;
-            // let predicted = (predicted_output[0] + 1.) * 10000. / 2.;
-            // let predicted = (((predicted - 4500.) / (5500. - 4500.)) * (10000. - 0.)) + 0.;
-
-            self.confidence = confidence;
-            self.prediction = predicted_output as f32 * 100.;
+        }
+        if let Some(tor) = &mut self.tor {
+            if let Err(e) = tor.on_bet(self.site.as_mut()).await {
+                warn!("Failed to switch Tor circuit: {}", e);
+            }
+        }
+        if self
+            .refresh_balance_every
+            .is_some_and(|every| self.site.get_rolls().is_multiple_of(every))
+        {
+            match self.site.refresh_balance().await {
+                Ok(balance) => debug!("Refreshed balance: {:.8}", balance),
+                Err(e) => warn!("Failed to refresh balance: {}", e),
+            }
         }
 
+        self.prediction_ready = self.update_prediction();
+
         Ok(())
     }
 
+    /// Whether the session profit reached a take-profit, stop-loss or win target,
+    /// logging a summary when it has.
+    fn target_reached(&self) -> bool {
+        let win_target = if self.stop_at_win_target {
+            self.site.get_win_target()
+        } else {
+            0.
+        };
+        let Some(reason) = strategies::stop_reason(
+            self.site.get_profit(),
+            self.take_profit,
+            self.stop_loss,
+            win_target,
+        ) else {
+            return false;
+        };
+
+        info!(
+            "{}Reached the {}, stopping || Rolls: {} || Wins: {} || Profit: {:.8} || Balance: {:.8}",
+            self.prefix,
+            reason,
+            self.site.get_rolls(),
+            self.wins,
+            self.site.get_profit(),
+            self.site.get_balance()
+        );
+        true
+    }
+
+    /// Predicts the next roll from the latest window, falling back as configured by
+    /// `on_inference_error` when the model fails.
+    ///
+    /// Returns `false` when the next bet must be skipped.
+    fn update_prediction(&mut self) -> bool {
+        // Rolls with a malformed hash would feed the model zeros, so they are left out.
+        let history = self
+            .site
+            .get_history()
+            .into_iter()
+            .filter(|roll| match inference::check_hashes(roll) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("Skipping roll with nonce {}: {}", roll.nonce, err);
+                    false
+                }
+            })
+            .collect::<Vec<_>>();
+        let history_size = self.site.get_history_size();
+        if history.len() < history_size {
+            return true;
+        }
+
+        // Sites may keep a longer history than the model window; only the latest
+        // `history_size` rolls are fed to the model.
+        let window = &history[history.len() - history_size..];
+        let inputs = match &mut self.features {
+            Some(features) => {
+                features.encode_window(window, self.normalizer.as_ref(), &self.device)
+            }
+            None => inference::encode_window(window, self.normalizer.as_ref(), &self.device),
+        };
+        let err = match inference::predict_encoded(&self.model, inputs, self.anchor, &self.device) {
+            Ok(prediction) => {
+                self.confidence = prediction.confidence;
+                self.prediction = prediction.roll;
+                return true;
+            }
+            Err(err) => err,
+        };
+
+        match self.on_inference_error {
+            InferenceErrorMode::Skip => {
+                warn!("Inference failed ({}), skipping the next bet", err);
+                false
+            }
+            InferenceErrorMode::Neutral => {
+                warn!("Inference failed ({}), betting neutral", err);
+                self.prediction = NEUTRAL_PREDICTION;
+                self.confidence = 0.;
+                true
+            }
+            InferenceErrorMode::ReuseLast => {
+                warn!(
+                    "Inference failed ({}), reusing the last prediction {:.0}",
+                    err, self.prediction
+                );
+                true
+            }
+        }
+    }
+
     fn print_res(&self, bet_result: &BetResult, win: bool) {
-        let profit_str = &format!("Profit: {:.8}", self.site.get_profit());
+        if !self.print_bets {
+            return;
+        }
+        let bonus_profit = self.site.get_bonus_profit();
+        let profit_str = &if bonus_profit != 0. {
+            format!(
+                "Profit: {:.8} (dice) + {:.8} (bonus)",
+                self.site.get_profit(),
+                bonus_profit
+            )
+        } else {
+            format!("Profit: {:.8}", self.site.get_profit())
+        };
         let profit_str = if self.site.get_profit() > 0. {
             profit_str.green()
         } else {
             profit_str.red()
         };
 
-        let golden_roll = if bet_result.number > 9900 || bet_result.number < 100 {
+        let roll = bet_result.number.value();
+        let golden_roll = if !(100..=9900).contains(&roll) {
             (&format!("{: <5}", bet_result.number)).yellow()
         } else {
             format!("{: <5}", bet_result.number).normal()
         };
 
         let output_str = &format!(
-            "#{: >6} || Balance: {:0>.8} || Roll: {: <5} || Multiplier: {: <6.2} || Wagered: {:.8} || Predicted: {: <5.0} || Confidence: {: <2.2} || {}",
+            "{}#{: >6} || Balance: {:0>.8} || Roll: {: <5} || Multiplier: {: <6.2} || Wagered: {:.8} || Predicted: {: <5.0} || Confidence: {: <2.2} || {}",
+            self.prefix,
             self.site.get_rolls(),
             self.site.get_balance(),
             golden_roll,
@@ -158,6 +408,442 @@ impl<B: Backend> Game<B> {
     }
 }
 
+/// Places minimum bets purely to record rolls in the training CSV format.
+async fn run_collect(mut site: Box<dyn Site>, game_config: &TomlConfig) -> Result<(), BetError> {
+    let output = &game_config.collect.output;
+    let mut recorder = recorder::BetRecorder::open(output).map_err(|e| {
+        error!("Failed to open collect output {}: {}", output, e);
+        BetError::ConfigError(format!("Failed to open {output}: {e}"))
+    })?;
+
+    let retry_policy = game_config.network.retry_policy();
+    info!("Logging into site");
+    retry::retry(&retry_policy, async || site.login().await).await?;
+    info!(
+        "Collecting rolls into {} (max bets: {:?}), press Ctrl-C to stop",
+        output, game_config.collect.max_bets
+    );
+
+    let mut bets = 0u64;
+    let mut latency = LatencyStats::default();
+    let mut jitter = game_config.network.delay_jitter();
+    let mut empty_replies = 0;
+    let mut rate_limited = 0;
+    let mut network_errors = 0;
+    let mut tor = game_config.network.tor.circuit_renewal();
+    while game_config
+        .collect
+        .max_bets
+        .is_none_or(|max_bets| bets < max_bets)
+    {
+        let bet_result = tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Ctrl-C received, stopping collection");
+                break;
+            }
+            res = sites::settle_bet(site.as_mut(), 0., 0., &mut latency) => match res {
+                Ok(res) => res,
+                Err(BetError::EmptyReply) => {
+                    sites::handle_empty_reply(site.as_mut(), &mut empty_replies).await?;
+                    continue;
+                }
+                Err(BetError::RateLimited(retry_after)) => {
+                    sites::handle_rate_limit(retry_after, &mut rate_limited).await?;
+                    continue;
+                }
+                Err(e) if retry::is_transient(&e) => {
+                    retry::handle_transient_error(e, &retry_policy, &mut network_errors).await?;
+                    continue;
+                }
+                Err(e @ BetError::InsufficientFunds { .. }) => {
+                    warn!("{}, stopping collection", e);
+                    break;
+                }
+                Err(e) => {
+                    error!("Bet failed: {:?}", e);
+                    return Err(e);
+                }
+            },
+        };
+
+        if bet_result.result {
+            site.on_win(&bet_result);
+        } else {
+            site.on_lose(&bet_result);
+        }
+        bets += 1;
+        empty_replies = 0;
+        rate_limited = 0;
+        network_errors = 0;
+
+        if let Err(e) = recorder.record(&bet_result, site.get_balance()) {
+            error!("Failed to record roll: {}", e);
+        }
+        if let Some(tor) = &mut tor {
+            if let Err(e) = tor.on_bet(site.as_mut()).await {
+                warn!("Failed to switch Tor circuit: {}", e);
+            }
+        }
+        if bets.is_multiple_of(100) {
+            info!(
+                "Collected {} rolls || Balance: {:.8}",
+                recorder.written(),
+                site.get_balance()
+            );
+        }
+
+        if wager_cap_reached(site.as_ref(), game_config.bet_limits.max_wagered) {
+            break;
+        }
+
+        tokio::time::sleep(jitter.apply(latency.bet_delay(BET_DELAY))).await;
+    }
+
+    info!(
+        "Collection finished: {} bets placed, {} rows written to {}",
+        bets,
+        recorder.written(),
+        output
+    );
+    info!("Session: {}", latency);
+
+    Ok(())
+}
+
+/// Records rolls from DuckDice's feed of everyone's bets, placing none.
+async fn run_data_collector(game_config: &TomlConfig) -> Result<(), BetError> {
+    let output = &game_config.collect.output;
+    let mut recorder = recorder::BetRecorder::open(output).map_err(|e| {
+        error!("Failed to open collect output {}: {}", output, e);
+        BetError::ConfigError(format!("Failed to open {output}: {e}"))
+    })?;
+    let client = sites::client_builder(game_config.network.proxy().as_ref()).build()?;
+    let limiter = RequestLimiter::new(game_config.network.max_concurrent_requests);
+    let retry_policy = game_config.network.retry_policy();
+    let url = &game_config.collect.feed_url;
+    if game_config.network.proxy().is_some() {
+        warn!("The bet feed is read directly, only seed lookups go through the proxy");
+    }
+
+    info!(
+        "Collecting rolls from {} into {} (max rows: {:?}), press Ctrl-C to stop",
+        url, output, game_config.collect.max_bets
+    );
+    let mut feed = retry::retry(&retry_policy, async || feed::BetFeed::connect(url).await).await?;
+    let mut chains = feed::SeedChains::default();
+    let mut feed_errors = 0;
+    let mut lookup_errors = 0;
+    let mut rate_limited = 0;
+    while game_config
+        .collect
+        .max_bets
+        .is_none_or(|max_rows| recorder.written() < max_rows)
+    {
+        let res = tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Ctrl-C received, stopping collection");
+                break;
+            }
+            res = feed.next_bets() => res,
+        };
+        let bets = match res {
+            Ok(Some(bets)) => bets,
+            Ok(None) => {
+                warn!("Bet feed closed, reconnecting");
+                feed =
+                    retry::retry(&retry_policy, async || feed::BetFeed::connect(url).await).await?;
+                continue;
+            }
+            Err(e) => {
+                retry::handle_transient_error(e, &retry_policy, &mut feed_errors).await?;
+                feed =
+                    retry::retry(&retry_policy, async || feed::BetFeed::connect(url).await).await?;
+                continue;
+            }
+        };
+        feed_errors = 0;
+
+        for bet in bets {
+            let seed = loop {
+                match duck_dice::fetch_bet_seed(&limiter, &client, duck_dice::API_URL, &bet.hash)
+                    .await
+                {
+                    Err(BetError::RateLimited(retry_after)) => {
+                        rate_limited += 1;
+                        let delay = sites::rate_limit_backoff(retry_after, rate_limited);
+                        warn!("Seed lookups rate limited, retrying in {:?}", delay);
+                        tokio::time::sleep(delay).await;
+                    }
+                    res => {
+                        rate_limited = 0;
+                        break res;
+                    }
+                }
+            };
+            let seed = match seed {
+                Ok(seed) => seed,
+                Err(e) => {
+                    // A missed seed only costs the rows around this bet.
+                    lookup_errors += 1;
+                    debug!("Failed to look up the seed of bet {}: {}", bet.hash, e);
+                    continue;
+                }
+            };
+            let Some((current, next)) = chains.push(bet, seed) else {
+                continue;
+            };
+            // The balance is another player's, so none is recorded.
+            if let Err(e) = recorder.record_pair(&current, &next, 0.) {
+                error!("Failed to record roll: {}", e);
+            }
+            if recorder.written().is_multiple_of(100) {
+                info!(
+                    "Collected {} rolls || Failed seed lookups: {}",
+                    recorder.written(),
+                    lookup_errors
+                );
+            }
+        }
+    }
+
+    info!(
+        "Collection finished: {} rows written to {}",
+        recorder.written(),
+        output
+    );
+
+    Ok(())
+}
+
+/// Whether the session has wagered `max_wagered`, logging a summary when it has.
+fn wager_cap_reached(site: &dyn Site, max_wagered: Option<f32>) -> bool {
+    let Some(max_wagered) = max_wagered else {
+        return false;
+    };
+    let wagered = site.get_wagered();
+    if wagered < max_wagered {
+        return false;
+    }
+
+    info!(
+        "Wagered {:.8} of the {:.8} cap over {} rolls, stopping || Profit: {:.8} || Balance: {:.8}",
+        wagered,
+        max_wagered,
+        site.get_rolls(),
+        site.get_profit(),
+        site.get_balance()
+    );
+    true
+}
+
+/// Withdraws `profit` to `address`, or tells the user to do it by hand when the site
+/// can't.
+async fn withdraw_profit(site: &mut dyn Site, profit: f32, address: &str) {
+    info!("Profit {:.8} reached the withdrawal threshold", profit);
+    let withdrawn = if site.capabilities().withdrawal {
+        site.withdraw(profit, address)
+            .await
+            .inspect_err(|e| error!("Automatic withdrawal failed: {}", e))
+            .is_ok()
+    } else {
+        false
+    };
+
+    if withdrawn {
+        info!("Withdrew {:.8} to {}", profit, address);
+    } else {
+        error!(
+            "{}",
+            format!("WITHDRAW NOW: {profit:.8} profit to {address}, betting stopped")
+                .red()
+                .bold()
+        );
+    }
+}
+
+/// Fails when the site's `history_size` differs from the window `model` was built for.
+fn check_model_window<B: Backend>(site: &dyn Site, model: &Model<B>) -> Result<(), BetError> {
+    if site.get_history_size() != model.history_size() {
+        return Err(BetError::ConfigError(format!(
+            "history_size is {} but the model was trained on windows of {}",
+            site.get_history_size(),
+            model.history_size()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Loads the trained model, its config and the normalizer it was trained with, if any,
+/// from `artifact_dir`.
+fn load_model<B: Backend>(
+    artifact_dir: &str,
+    device: &B::Device,
+) -> Result<(Model<B>, Option<Normalizer>), BetError> {
+    let config = TrainingConfig::load(format!("{artifact_dir}/config.json")).map_err(|e| {
+        error!("Failed to load model config: {}", e);
+        BetError::ModelError(format!("Failed to load model config: {e}"))
+    })?;
+
+    let record = CompactRecorder::new()
+        .load(format!("{artifact_dir}/model").into(), device)
+        .map_err(|e| {
+            error!("Failed to load trained model: {}", e);
+            BetError::ModelError(format!("Failed to load trained model: {e}"))
+        })?;
+    let normalizer = inference::load_normalizer(artifact_dir, &config)?;
+
+    Ok((config.model.init(device).load_record(record), normalizer))
+}
+
+/// Runs the offline self-test, failing when any check does.
+async fn run_selftest<B: Backend<Device = WgpuDevice>>() -> Result<(), BetError> {
+    info!("Initializing GPU device");
+    let device = WgpuDevice::default();
+
+    let checks = selftest::run::<B>(&device).await;
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if failed > 0 {
+        error!(
+            "Self-test FAILED: {} of {} checks failed",
+            failed,
+            checks.len()
+        );
+        return Err(BetError::ConfigError(format!(
+            "{failed} self-test checks failed"
+        )));
+    }
+
+    info!("Self-test PASSED: all {} checks passed", checks.len());
+    Ok(())
+}
+
+fn run_montecarlo(game_config: &TomlConfig) {
+    let currency = &game_config.duck_dice.currency;
+    let strategy = game_config.duck_dice.strategy;
+    let min_bet = sites::effective_min_bet(
+        currency.get_min_bet(Sites::DuckDiceIo),
+        game_config.duck_dice.min_bet_override,
+    );
+    let start_balance = game_config
+        .montecarlo
+        .start_balance
+        .unwrap_or_else(|| game_config.simulation.start_balance_for(currency));
+
+    info!(
+        "Running {} Monte Carlo sessions of {} rolls with {:?} from {:.8} {}",
+        game_config.montecarlo.runs,
+        game_config.montecarlo.rolls_per_run,
+        strategy,
+        start_balance,
+        currency
+    );
+
+    let report = simulation::monte_carlo(
+        || strategies::from_config(strategy, start_balance, min_bet, min_bet),
+        game_config.montecarlo.runs,
+        game_config.montecarlo.rolls_per_run,
+        start_balance,
+        min_bet,
+        game_config.montecarlo.seed,
+    );
+
+    print_montecarlo_report(&report, currency);
+}
+
+/// Runs every configured strategy for the same number of bets against the same fake
+/// server rolls, through the full model pipeline, and prints one JSON object of stats.
+async fn run_backtest<B: Backend>(
+    game_config: &TomlConfig,
+    model: Model<B>,
+    normalizer: Option<Normalizer>,
+    device: B::Device,
+) -> Result<(), BetError> {
+    let config = &game_config.backtest;
+    let currency = &game_config.duck_dice.currency;
+    let start_balance = config
+        .start_balance
+        .unwrap_or_else(|| game_config.simulation.start_balance_for(currency));
+    let strategies = if config.strategies.is_empty() {
+        vec![sites::site_strategy(
+            game_config,
+            game_config.enabled_sites()[0],
+        )]
+    } else {
+        config.strategies.clone()
+    };
+
+    let mut runs = Vec::new();
+    for strategy in strategies {
+        info!(
+            "Backtesting {:?} for {} bets from {:.8} {}",
+            strategy, config.bets, start_balance, currency
+        );
+        // Every strategy sees the same server seeds and so the same rolls.
+        sites::fake_test::reset_server_seed_with(config.seed);
+
+        let mut site = DuckDiceIo::default()
+            .with_currency(currency.clone())
+            .with_bet_sizing(game_config.duck_dice.sizing);
+        if let Some(min_bet) = game_config.duck_dice.min_bet_override {
+            site = site.with_min_bet(min_bet);
+        }
+        if let Some(history_size) = game_config.duck_dice.sizing.history_size {
+            site = site.with_history_size(history_size);
+        }
+        let site = site.with_strategy(strategy).with_simulation(start_balance);
+        check_model_window(&site, &model)?;
+
+        let mut game = Game::new(Box::new(site), model.clone(), device.clone(), game_config);
+        game.normalizer = normalizer.clone();
+        game.print_bets = false;
+        game.site.login().await?;
+
+        let mut stats = backtest::SessionStats::default();
+        let mut busted = false;
+        let started = std::time::Instant::now();
+        while stats.bets < config.bets {
+            // The fake site restarts a busted session instead of stopping, so stop first.
+            if game.site.get_balance() - game.site.get_current_bet() <= 0. {
+                busted = true;
+                break;
+            }
+
+            let (rolls, wins) = (game.site.get_rolls(), game.wins);
+            game.bet().await?;
+            if game.site.get_rolls() > rolls {
+                stats.record(game.wins > wins, game.site.get_profit());
+            }
+        }
+
+        runs.push(stats.summary(format!("{strategy:?}"), started.elapsed(), busted));
+    }
+
+    let report = serde_json::json!({
+        "seed": config.seed,
+        "start_balance": start_balance,
+        "currency": currency.to_string(),
+        "runs": runs,
+    });
+    println!("{report}");
+
+    Ok(())
+}
+
+fn print_montecarlo_report(report: &simulation::MonteCarloReport, currency: &Currency) {
+    println!("Runs:            {}", report.runs);
+    println!("Start balance:   {:.8} {}", report.start_balance, currency);
+    println!("Busted:          {:.2}%", report.busted_percent);
+    println!(
+        "Final balance:   p5 {:.8} || median {:.8} || p95 {:.8}",
+        report.p5_balance, report.median_balance, report.p95_balance
+    );
+    println!(
+        "Expected profit: {:.8} {}",
+        report.expected_profit, currency
+    );
+}
+
 #[tokio::main]
 async fn main() -> Result<(), BetError> {
     // Initialize logger
@@ -165,13 +851,31 @@ async fn main() -> Result<(), BetError> {
 
     info!("Starting PredictiveRolls application");
 
+    let cli = Cli::parse().map_err(|e| {
+        error!("{}", e);
+        BetError::ConfigError(e)
+    })?;
+
+    let use_color = cli.use_color(
+        std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+        std::env::var_os("CLICOLOR_FORCE").is_some_and(|value| value != "0"),
+        std::io::stdout().is_terminal(),
+    );
+    colored::control::set_override(use_color);
+
+    type MyBackend = Vulkan<f32, i32>;
+
+    if cli.command == Command::SelfTest {
+        return run_selftest::<MyBackend>().await;
+    }
+
     // Read configuration
     let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
     info!("Loading configuration from: {}", config_path);
 
     let config_contents = tokio::fs::read_to_string(&config_path).await.map_err(|e| {
         error!("Failed to read config file {}: {}", config_path, e);
-        BetError::Failed
+        BetError::IoError(e)
     })?;
 
     let game_config: TomlConfig = toml::from_str(&config_contents).map_err(|e| {
@@ -187,21 +891,21 @@ async fn main() -> Result<(), BetError> {
 
     info!("Configuration validated successfully");
 
-    // Initialize the configured site
-    let site: Box<dyn Site> = if game_config.duck_dice.enabled {
-        info!("Using DuckDice site");
-        Box::new(
-            DuckDiceIo::default()
-                .with_api_key(game_config.duck_dice.api_key.clone())
-                .with_currency(game_config.duck_dice.currency.clone())
-                .with_strategy(game_config.duck_dice.strategy),
-        )
-    } else {
-        warn!("No site enabled in configuration");
-        return Err(BetError::Failed);
-    };
+    if cli.command == Command::MonteCarlo {
+        run_montecarlo(&game_config);
+        return Ok(());
+    }
 
-    type MyBackend = Vulkan<f32, i32>;
+    if cli.command == Command::DataCollector {
+        return run_data_collector(&game_config).await;
+    }
+
+    if cli.command == Command::Collect {
+        // Collecting records a single site; with several enabled, the first is used.
+        let site_kind = game_config.enabled_sites()[0];
+        let site = sites::build_site(&game_config, site_kind, TomlStrategies::Flat)?;
+        return run_collect(site, &game_config).await;
+    }
 
     info!("Initializing GPU device");
     let device = WgpuDevice::default();
@@ -210,43 +914,357 @@ async fn main() -> Result<(), BetError> {
     let artifact_dir = std::env::var("MODEL_DIR").unwrap_or_else(|_| "./artifacts".to_string());
     info!("Loading model from: {}", artifact_dir);
 
-    let _config = TrainingConfig::load(format!("{artifact_dir}/config.json")).map_err(|e| {
-        error!("Failed to load model config: {}", e);
-        BetError::Failed
-    })?;
+    let (model, normalizer) = match load_model::<MyBackend>(&artifact_dir, &device) {
+        Ok(loaded) => {
+            info!("Model loaded successfully");
+            loaded
+        }
+        Err(e) if cli.allow_untrained => {
+            warn!(
+                "No trained model available ({}), continuing with an UNTRAINED model",
+                e
+            );
+            warn!("Predictions are RANDOM; use this only to exercise the pipeline or collect data");
+            (ModelConfig::new().init(&device), None)
+        }
+        Err(e) => return Err(e),
+    };
 
-    let record = CompactRecorder::new()
-        .load(format!("{artifact_dir}/model").into(), &device)
-        .map_err(|e| {
-            error!("Failed to load trained model: {}", e);
-            BetError::Failed
-        })?;
+    if game_config.inference.warmup {
+        match inference::warm_up(&model, &device) {
+            Ok(took) => info!("Model warmup took {:?}", took),
+            Err(e) => warn!("Model warmup failed: {}", e),
+        }
+    }
 
-    info!("Model loaded successfully");
-    let model = ModelConfig::new().init(&device).load_record(record);
+    if cli.command == Command::Backtest {
+        return run_backtest(&game_config, model, normalizer, device).await;
+    }
 
-    let mut game = Game::<MyBackend> {
-        confidence: 0.,
-        site,
-        model,
-        device,
-        prediction: 0.,
-        initialized: false,
-    };
+    // Bet on every enabled site at once, each in its own task with its own handle to the
+    // model. The tasks share this thread, as sites are not `Send`; they spend most of
+    // their time waiting on the network anyway.
+    let sites = game_config.enabled_sites();
+    let several = sites.len() > 1;
+    let game_config = std::rc::Rc::new(game_config);
+    let local = tokio::task::LocalSet::new();
+    let mut games = tokio::task::JoinSet::new();
+    for site_kind in sites {
+        let site = sites::build_site(
+            &game_config,
+            site_kind,
+            sites::site_strategy(&game_config, site_kind),
+        )?;
+        check_model_window(site.as_ref(), &model)?;
+        let mut game = Game::new(site, model.clone(), device.clone(), &game_config);
+        game.normalizer = normalizer.clone();
+        let record_path = if several {
+            game_config.record.site_path(site_kind)
+        } else {
+            game_config.record.path()
+        };
+        if let Some(path) = record_path {
+            let recorder = recorder::BetRecorder::open(&path).map_err(|e| {
+                error!("Failed to open bet record {}: {}", path, e);
+                BetError::IoError(e)
+            })?;
+            info!("Recording {} bets to {}", site_kind, path);
+            game.recorder = Some(recorder);
+        }
+        if game_config.network.tor.enabled {
+            info!(
+                "Sending site requests through Tor at {}",
+                game_config.network.tor.socks_addr
+            );
+            game.tor = game_config.network.tor.circuit_renewal();
+        }
+        game.refresh_balance_every = game_config.network.refresh_balance_every;
+        if several {
+            game.prefix = format!("[{site_kind}] ");
+        }
 
-    info!("Logging into site");
-    game.site.login().await?;
-    info!("Login successful, starting betting loop");
+        games.spawn_local_on(run_game(game, game_config.clone()), &local);
+    }
+
+    // A site that fails leaves the others betting; the first failure is returned once
+    // all have stopped.
+    local
+        .run_until(async {
+            let mut result = Ok(());
+            while let Some(res) = games.join_next().await {
+                let res = res.unwrap_or_else(|e| {
+                    Err(BetError::ConfigError(format!("Betting task failed: {e}")))
+                });
+                if let Err(e) = res {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+
+            result
+        })
+        .await
+}
+
+/// Logs in to the game's site and bets until the session stops.
+async fn run_game<B: Backend>(
+    mut game: Game<B>,
+    game_config: std::rc::Rc<TomlConfig>,
+) -> Result<(), BetError> {
+    info!("{}Logging into site", game.prefix);
+    retry::retry(&game.retry, async || game.site.login().await).await?;
+    info!("{}Login successful, starting betting loop", game.prefix);
+
+    let pause_file = game_config
+        .control
+        .pause_file
+        .as_deref()
+        .map(std::path::Path::new);
+    let mut paused_since: Option<std::time::Instant> = None;
+    let mut last_pause_log = std::time::Instant::now();
 
     loop {
-        match game.bet().await {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Bet failed: {:?}", e);
-                return Err(e);
+        let pause_reason = if pause_file.is_some_and(|pause_file| pause_file.exists()) {
+            Some("pause file found, paused until it is removed")
+        } else if !game_config.schedule.allows(chrono::Utc::now().hour() as u8) {
+            Some("outside the scheduled betting hours")
+        } else {
+            None
+        };
+        if let Some(reason) = pause_reason {
+            let since = *paused_since.get_or_insert_with(|| {
+                info!("{}Betting paused: {}", game.prefix, reason);
+                last_pause_log = std::time::Instant::now();
+                std::time::Instant::now()
+            });
+            if last_pause_log.elapsed() >= PAUSE_LOG_INTERVAL {
+                info!(
+                    "{}Still paused ({:?}), balance {:.8}",
+                    game.prefix,
+                    since.elapsed(),
+                    game.site.get_balance()
+                );
+                last_pause_log = std::time::Instant::now();
+            }
+
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Ctrl-C received, stopping");
+                    break;
+                }
+                _ = tokio::time::sleep(PAUSE_POLL_INTERVAL) => continue,
+            }
+        }
+        if let Some(since) = paused_since.take() {
+            info!(
+                "{}Resuming after a pause of {:?}",
+                game.prefix,
+                since.elapsed()
+            );
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Ctrl-C received, stopping");
+                break;
             }
+            res = game.bet() => {
+                match res {
+                    Ok(()) => {}
+                    Err(e @ BetError::InsufficientFunds { .. }) => {
+                        warn!("{}{}, stopping", game.prefix, e);
+                        info!("{}Final balance: {:.8}", game.prefix, game.site.get_balance());
+                        break;
+                    }
+                    Err(e) => {
+                        error!("{}Bet failed: {:?}", game.prefix, e);
+                        info!("{}Session: {}", game.prefix, game.latency);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        if let Some(at_profit) = game_config.profit_target.withdraw_at_profit {
+            let profit = game.site.get_profit();
+            if profit >= at_profit {
+                withdraw_profit(
+                    game.site.as_mut(),
+                    profit,
+                    &game_config.profit_target.withdraw_address,
+                )
+                .await;
+                break;
+            }
+        }
+
+        if game.target_reached() {
+            break;
+        }
+
+        if wager_cap_reached(game.site.as_ref(), game_config.bet_limits.max_wagered) {
+            break;
+        }
+
+        let delay = game.jitter.apply(game.latency.bet_delay(BET_DELAY));
+        tokio::time::sleep(delay).await;
+    }
+
+    info!("{}Session: {}", game.prefix, game.latency);
+    let balances = game.site.get_account_balances();
+    if balances.len() > 1 {
+        for (i, balance) in balances.iter().enumerate() {
+            info!("{}Account {}: {:.8}", game.prefix, i + 1, balance);
+        }
+        info!(
+            "{}All accounts: {:.8}",
+            game.prefix,
+            balances.iter().sum::<f32>()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use freebitco_in::sites::{BetId, BetOutcome, Capabilities};
+
+    type TestBackend = burn::backend::NdArray;
+
+    /// Settles queued bets in order, without a network.
+    struct ScriptedSite {
+        bets: std::collections::VecDeque<BetResult>,
+        rolls: u64,
+    }
+
+    #[async_trait::async_trait]
+    impl Site for ScriptedSite {
+        async fn login(&mut self) -> Result<(), BetError> {
+            Ok(())
+        }
+
+        async fn do_bet(&mut self, _: f32, _: f32) -> Result<BetOutcome, BetError> {
+            self.rolls += 1;
+            self.bets
+                .pop_front()
+                .map(BetOutcome::Resolved)
+                .ok_or(BetError::Failed)
         }
 
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+            Err(BetError::Failed)
+        }
+
+        async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+            Err(BetError::Unsupported("withdrawals".to_string()))
+        }
+
+        async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+            Ok(1.)
+        }
+
+        fn on_win(&mut self, _bet_result: &BetResult) {}
+
+        fn on_lose(&mut self, _bet_result: &BetResult) {}
+
+        fn get_history(&self) -> Vec<BetResult> {
+            Vec::new()
+        }
+
+        fn get_history_size(&self) -> usize {
+            freebitco_in::util::HISTORY_SIZE
+        }
+
+        fn get_rolls(&self) -> u64 {
+            self.rolls
+        }
+
+        fn get_current_bet(&self) -> f32 {
+            1e-8
+        }
+
+        fn get_current_multiplier(&self) -> f32 {
+            2.
+        }
+
+        fn get_profit(&self) -> f32 {
+            0.
+        }
+
+        fn get_wagered(&self) -> f32 {
+            0.
+        }
+
+        fn get_balance(&self) -> f32 {
+            1.
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::default()
+        }
+    }
+
+    fn bet(client_seed: &str, nonce: u32, number: u32) -> BetResult {
+        BetResult {
+            hash_previous_roll: format!("{nonce:064x}"),
+            hash_next_roll: format!("{nonce:064x}"),
+            client_seed: client_seed.to_string(),
+            nonce,
+            symbol: "BTC".to_string(),
+            result: true,
+            is_high: true,
+            rolled_high: number >= 5000,
+            number: Roll::clamped(number),
+            threshold: 0,
+            chance: 49.5,
+            payout: 2.,
+            bet_amount: 1e-8,
+            win_amount: 1e-8,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_game_records_only_bets_followed_on_their_seed_chain() {
+        let config: TomlConfig = toml::from_str(include_str!("../config.toml.example")).unwrap();
+        let path = std::env::temp_dir().join(format!("game_record_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Nonce 3 was missed, then the seed changed.
+        let bets = [
+            bet("first", 1, 1111),
+            bet("first", 2, 2222),
+            bet("first", 4, 4444),
+            bet("second", 0, 5555),
+            bet("second", 1, 6666),
+        ];
+        let site = ScriptedSite {
+            bets: bets.into_iter().collect(),
+            rolls: 0,
+        };
+        let device = Default::default();
+        let model = ModelConfig::new().init::<TestBackend>(&device);
+
+        let mut game = Game::new(Box::new(site), model, device, &config);
+        game.print_bets = false;
+        game.recorder = Some(recorder::BetRecorder::open(&path).unwrap());
+        for _ in 0..5 {
+            game.bet().await.unwrap();
+        }
+        drop(game);
+
+        let rows = csv::Reader::from_path(&path)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<Vec<freebitco_in::dataset::BetResultCsvRecord>, csv::Error>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let pairs = rows
+            .iter()
+            .map(|row| (row.client_seed.as_str(), row.nonce, row.next_number))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [("first", 1, 2222), ("second", 0, 6666)]);
     }
 }
@@ -6,6 +6,7 @@
 use burn::{prelude::*, tensor::Distribution};
 
 use crate::data::BetBatch;
+use crate::util::{self, Roll};
 
 /// The main neural network model for dice roll prediction.
 ///
@@ -14,46 +15,184 @@ use crate::data::BetBatch;
 /// - Positional encoding for sequence awareness
 /// - Transformer encoder/decoder for pattern recognition
 /// - LSTM layers for temporal modeling
+///
+/// The decoder is absent when the model was built with `use_decoder = false`.
 #[derive(Module, Debug)]
 pub struct Model<B: Backend> {
     input_layer: nn::conv::Conv2d<B>,
+    /// Maps the hash features to `d_model`; absent when they already match.
+    input_projection: Option<nn::Linear<B>>,
     positional_encoding: nn::PositionalEncoding<B>,
     transformer_encoder: nn::transformer::TransformerEncoder<B>,
     lstm1: nn::Lstm<B>,
     lstm2: nn::Lstm<B>,
-    transformer_decoder: nn::transformer::TransformerDecoder<B>,
+    transformer_decoder: Option<nn::transformer::TransformerDecoder<B>>,
     output_layer: nn::Linear<B>,
+    /// Built with `OutputHead::Regression`; not stored in checkpoints.
+    regression: bool,
+}
+
+/// What the model outputs for the next roll.
+#[derive(Config, Debug, PartialEq)]
+pub enum OutputHead {
+    /// One score per range of `10_000 / num_classes` rolls, trained with cross-entropy.
+    /// Every roll of the window scores `num_classes / history_size` of the classes.
+    Classification { num_classes: usize },
+    /// The roll itself, scaled to 0-1 and trained with mean squared error.
+    Regression,
+}
+
+impl OutputHead {
+    /// Checks that the classes split evenly over a window of `history_size` rolls and
+    /// the roll range.
+    pub fn validate(&self, history_size: usize) -> Result<(), String> {
+        match *self {
+            Self::Classification { num_classes }
+                if num_classes == 0
+                    || !num_classes.is_multiple_of(history_size)
+                    || !(Roll::MAX as usize + 1).is_multiple_of(num_classes) =>
+            {
+                Err(format!(
+                    "num_classes must be a multiple of the history size ({}) that divides {}, got {}",
+                    history_size,
+                    Roll::MAX + 1,
+                    num_classes
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Outputs of the model: the class count, or 1 for regression.
+    pub fn outputs(&self) -> usize {
+        match *self {
+            Self::Classification { num_classes } => num_classes,
+            Self::Regression => 1,
+        }
+    }
 }
 
 /// Configuration for the model.
+///
+/// Training saves it in `config.json`, so a model is always rebuilt with the
+/// dimensions it was trained with.
 #[derive(Config)]
-pub struct ModelConfig {}
+pub struct ModelConfig {
+    /// Blend a transformer decoder pass into the LSTM output; when `false` the LSTM
+    /// output goes straight to the output layer.
+    #[config(default = true)]
+    pub use_decoder: bool,
+    #[config(default = "OutputHead::Classification { num_classes: util::BUCKET_COUNT }")]
+    pub head: OutputHead,
+    /// Width of the transformer; the hash features are projected to it when it differs
+    /// from `FEATURE_STRIDE`.
+    #[config(default = 256)]
+    pub d_model: usize,
+    /// Width of the transformer feed-forward layers.
+    #[config(default = 1024)]
+    pub d_ff: usize,
+    /// Attention heads; must divide `d_model`.
+    #[config(default = 8)]
+    pub n_heads: usize,
+    /// Layers of the transformer encoder, and of the decoder when it is used.
+    #[config(default = 4)]
+    pub n_layers: usize,
+    #[config(default = 512)]
+    pub lstm_hidden_1: usize,
+    /// Must equal `d_model` when the decoder is used, as their outputs are averaged.
+    #[config(default = 256)]
+    pub lstm_hidden_2: usize,
+    /// Rolls per input window; the data is batched and the sites predict in windows of
+    /// this size.
+    #[config(default = "util::HISTORY_SIZE")]
+    pub history_size: usize,
+}
 
 impl ModelConfig {
+    /// Checks that the dimensions fit together, so `init` and `forward` cannot panic
+    /// on a shape mismatch.
+    pub fn validate(&self) -> Result<(), String> {
+        let dims = [
+            ("d_model", self.d_model),
+            ("d_ff", self.d_ff),
+            ("n_heads", self.n_heads),
+            ("n_layers", self.n_layers),
+            ("lstm_hidden_1", self.lstm_hidden_1),
+            ("lstm_hidden_2", self.lstm_hidden_2),
+            ("history_size", self.history_size),
+        ];
+        if let Some((name, _)) = dims.iter().find(|(_, dim)| *dim == 0) {
+            return Err(format!("{name} must be positive"));
+        }
+        if !self.d_model.is_multiple_of(self.n_heads) {
+            return Err(format!(
+                "n_heads ({}) must divide d_model ({})",
+                self.n_heads, self.d_model
+            ));
+        }
+        if self.use_decoder && self.lstm_hidden_2 != self.d_model {
+            return Err(format!(
+                "lstm_hidden_2 ({}) must equal d_model ({}) when the decoder is used",
+                self.lstm_hidden_2, self.d_model
+            ));
+        }
+
+        self.head.validate(self.history_size)
+    }
+
     pub fn init<B: Backend>(&self, device: &B::Device) -> Model<B> {
-        let input_layer = nn::conv::Conv2dConfig::new([10, 10], [4, 1]).init(device);
-        let positional_encoding = nn::PositionalEncodingConfig::new(256).init(device);
-        let transformer_encoder =
-            nn::transformer::TransformerEncoderConfig::new(256, 1024, 8, 4).init(device);
-        let lstm1 = nn::LstmConfig::new(transformer_encoder.d_model, 512, true).init(device);
-        let lstm2 = nn::LstmConfig::new(lstm1.d_hidden, 256, true).init(device);
-        let transformer_decoder =
-            nn::transformer::TransformerDecoderConfig::new(256, 1024, 8, 4).init(device);
-        let output_layer = nn::LinearConfig::new(256, 10).init(device);
+        let input_layer = nn::conv::Conv2dConfig::new(
+            [self.history_size, self.history_size],
+            [util::FEATURE_BLOCKS, 1],
+        )
+        .init(device);
+        let input_projection = (self.d_model != util::FEATURE_STRIDE)
+            .then(|| nn::LinearConfig::new(util::FEATURE_STRIDE, self.d_model).init(device));
+        let positional_encoding = nn::PositionalEncodingConfig::new(self.d_model).init(device);
+        let transformer_encoder = nn::transformer::TransformerEncoderConfig::new(
+            self.d_model,
+            self.d_ff,
+            self.n_heads,
+            self.n_layers,
+        )
+        .init(device);
+        let lstm1 = nn::LstmConfig::new(self.d_model, self.lstm_hidden_1, true).init(device);
+        let lstm2 = nn::LstmConfig::new(self.lstm_hidden_1, self.lstm_hidden_2, true).init(device);
+        let transformer_decoder = self.use_decoder.then(|| {
+            nn::transformer::TransformerDecoderConfig::new(
+                self.d_model,
+                self.d_ff,
+                self.n_heads,
+                self.n_layers,
+            )
+            .init(device)
+        });
+        let outputs_per_roll = match self.head {
+            OutputHead::Classification { num_classes } => num_classes / self.history_size,
+            OutputHead::Regression => 1,
+        };
+        let output_layer = nn::LinearConfig::new(self.lstm_hidden_2, outputs_per_roll).init(device);
 
         Model {
             input_layer,
+            input_projection,
             positional_encoding,
             transformer_encoder,
             lstm1,
             lstm2,
             transformer_decoder,
             output_layer,
+            regression: self.head == OutputHead::Regression,
         }
     }
 }
 
 impl<B: Backend> Model<B> {
+    /// Rolls per input window the model was built for.
+    pub fn history_size(&self) -> usize {
+        self.input_layer.weight.dims()[1]
+    }
+
     pub fn forward(&self, item: BetBatch<B>) -> Tensor<B, 2> {
         let device = &self.devices()[0];
 
@@ -61,6 +200,10 @@ impl<B: Backend> Model<B> {
 
         let inputs = self.input_layer.forward(inputs);
         let inputs = inputs.flatten(2, 3);
+        let inputs = match &self.input_projection {
+            Some(projection) => projection.forward(inputs),
+            None => inputs,
+        };
 
         let pos_encode = self.positional_encoding.forward(inputs.clone());
         let combined = (inputs.clone() + pos_encode) / 2;
@@ -70,6 +213,10 @@ impl<B: Backend> Model<B> {
         let lstm = self.lstm1.forward(encoded.clone(), None);
         let lstm = self.lstm2.forward(lstm.0, None);
 
+        let Some(transformer_decoder) = &self.transformer_decoder else {
+            return self.head(lstm.0);
+        };
+
         let te_decode = nn::transformer::TransformerDecoderInput::new(
             Tensor::random(
                 Shape::new(encoded.clone().dims()),
@@ -78,9 +225,152 @@ impl<B: Backend> Model<B> {
             ),
             lstm.0.clone(),
         );
-        let decoded = self.transformer_decoder.forward(te_decode);
+        let decoded = transformer_decoder.forward(te_decode);
         let combined = (lstm.0 + decoded) / 2;
 
-        self.output_layer.forward(combined).flatten(1, 2)
+        self.head(combined)
+    }
+
+    /// Maps the per-roll features to `[batch, outputs]`: the class scores of every roll
+    /// side by side, or for regression the mean of the per-roll estimates.
+    fn head(&self, features: Tensor<B, 3>) -> Tensor<B, 2> {
+        let output = self.output_layer.forward(features);
+        if self.regression {
+            return output.mean_dim(1).flatten(1, 2);
+        }
+
+        output.flatten(1, 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::BetBatcher;
+    use crate::dataset::BetResultsDataset;
+    use burn::data::{dataloader::batcher::Batcher, dataset::Dataset};
+
+    type TestBackend = burn::backend::NdArray;
+
+    #[test]
+    fn test_model_without_decoder() {
+        let device = Default::default();
+        let dataset = BetResultsDataset::test().unwrap();
+        let items = (0..crate::util::HISTORY_SIZE)
+            .filter_map(|index| dataset.get(index))
+            .collect();
+        let batch = BetBatcher::<TestBackend>::new(device).batch(items, &device);
+
+        let with_decoder = ModelConfig::new().init::<TestBackend>(&device);
+        let without_decoder = ModelConfig::new()
+            .with_use_decoder(false)
+            .init::<TestBackend>(&device);
+        assert!(with_decoder.transformer_decoder.is_some());
+        assert!(without_decoder.transformer_decoder.is_none());
+
+        assert_eq!(
+            without_decoder.forward(batch.clone()).dims(),
+            with_decoder.forward(batch).dims()
+        );
+    }
+
+    #[test]
+    fn test_batcher_skips_records_with_malformed_hashes() {
+        let device = Default::default();
+        let dataset = BetResultsDataset::test().unwrap();
+        let mut items = (0..2 * util::HISTORY_SIZE)
+            .filter_map(|index| dataset.get(index))
+            .collect::<Vec<_>>();
+        let batcher = BetBatcher::<TestBackend>::new(device);
+        assert_eq!(
+            batcher.batch(items.clone(), &device).inputs.dims(),
+            [
+                2,
+                util::HISTORY_SIZE,
+                util::FEATURE_BLOCKS,
+                util::FEATURE_STRIDE
+            ]
+        );
+
+        items[3].server_seed_hash_previous_roll = "not a hash".to_string();
+        let batch = batcher.batch(items, &device);
+        assert_eq!(batch.inputs.dims()[0], 1);
+        assert_eq!(batch.targets.dims()[0], 1);
+    }
+
+    #[test]
+    fn test_output_head_sets_output_width() {
+        let device = Default::default();
+        let dataset = BetResultsDataset::test().unwrap();
+        let items = (0..crate::util::HISTORY_SIZE)
+            .filter_map(|index| dataset.get(index))
+            .collect();
+        let batch = BetBatcher::<TestBackend>::new(device).batch(items, &device);
+
+        for (head, outputs) in [
+            (OutputHead::Classification { num_classes: 50 }, 50),
+            (OutputHead::Regression, 1),
+        ] {
+            let model = ModelConfig::new()
+                .with_use_decoder(false)
+                .with_head(head)
+                .init::<TestBackend>(&device);
+            assert_eq!(model.forward(batch.clone()).dims(), [1, outputs]);
+        }
+
+        assert!(OutputHead::Classification { num_classes: 100 }
+            .validate(util::HISTORY_SIZE)
+            .is_ok());
+        assert!(OutputHead::Regression.validate(util::HISTORY_SIZE).is_ok());
+        for num_classes in [0, 15, 30] {
+            assert!(OutputHead::Classification { num_classes }
+                .validate(util::HISTORY_SIZE)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_custom_dimensions_round_trip_through_the_saved_config() {
+        let device = Default::default();
+        let dataset = BetResultsDataset::test().unwrap();
+        let items = (0..crate::util::HISTORY_SIZE)
+            .filter_map(|index| dataset.get(index))
+            .collect();
+        let batch = BetBatcher::<TestBackend>::new(device).batch(items, &device);
+
+        let config = ModelConfig::new()
+            .with_d_model(64)
+            .with_d_ff(128)
+            .with_n_heads(4)
+            .with_n_layers(1)
+            .with_lstm_hidden_1(32)
+            .with_lstm_hidden_2(64);
+        assert_eq!(config.validate(), Ok(()));
+
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: ModelConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.d_model, 64);
+        assert_eq!(loaded.lstm_hidden_2, 64);
+        let model = loaded.init::<TestBackend>(&device);
+        assert_eq!(model.forward(batch).dims(), [1, util::BUCKET_COUNT]);
+
+        assert_eq!(model.history_size(), util::HISTORY_SIZE);
+
+        let items = (0..5).filter_map(|index| dataset.get(index)).collect();
+        let batch = BetBatcher::<TestBackend>::new(device)
+            .with_history_size(5)
+            .batch(items, &device);
+        let model = config
+            .clone()
+            .with_history_size(5)
+            .init::<TestBackend>(&device);
+        assert_eq!(model.history_size(), 5);
+        assert_eq!(model.forward(batch).dims(), [1, util::BUCKET_COUNT]);
+
+        assert!(config.clone().with_history_size(0).validate().is_err());
+        assert!(config.clone().with_history_size(3).validate().is_err());
+        assert!(config.clone().with_n_heads(5).validate().is_err());
+        assert!(config.clone().with_lstm_hidden_2(32).validate().is_err());
+        assert!(config.with_d_ff(0).validate().is_err());
     }
 }
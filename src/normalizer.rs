@@ -0,0 +1,100 @@
+//! Standardization of continuous feature blocks.
+//!
+//! The hash, seed and nonce features are already 0/1 bits, but continuous values such as
+//! the rolled number or the amount won span many orders of magnitude and would dominate
+//! the transformer input if fed in raw. A `Normalizer` is fitted on the training data,
+//! saved next to the model and reapplied at inference so both sides see the same
+//! scaling. The standardized values go into the free slots after the nonce bits; models
+//! trained without a normalizer leave those slots zero.
+
+use burn::prelude::*;
+
+use crate::util;
+
+/// File name of the saved normalizer inside the artifact directory.
+pub const NORMALIZER_FILE: &str = "normalizer.json";
+
+/// Smallest standard deviation used when scaling, so constant features map to zero.
+const MIN_STD: f32 = 1e-6;
+
+/// Per-feature mean and standard deviation of a continuous feature block.
+#[derive(Config, Debug)]
+pub struct Normalizer {
+    pub mean: Vec<f32>,
+    pub std: Vec<f32>,
+}
+
+impl Normalizer {
+    /// Computes per-feature statistics over `rows`, which must all have the same length.
+    pub fn fit(rows: &[Vec<f32>]) -> Self {
+        let width = rows.first().map_or(0, Vec::len);
+        let count = rows.len().max(1) as f32;
+
+        let mut mean = vec![0f32; width];
+        for row in rows {
+            for (sum, value) in mean.iter_mut().zip(row) {
+                *sum += value;
+            }
+        }
+        mean.iter_mut().for_each(|sum| *sum /= count);
+
+        let mut std = vec![0f32; width];
+        for row in rows {
+            for ((var, value), mean) in std.iter_mut().zip(row).zip(&mean) {
+                *var += (value - mean).powi(2);
+            }
+        }
+        std.iter_mut().for_each(|var| *var = (*var / count).sqrt());
+
+        Self::new(mean, std)
+    }
+
+    /// Standardizes `features` in place to zero mean and unit variance.
+    pub fn apply(&self, features: &mut [f32]) {
+        assert_eq!(
+            features.len(),
+            self.mean.len(),
+            "Normalizer was fitted on a different number of features"
+        );
+
+        for ((value, mean), std) in features.iter_mut().zip(&self.mean).zip(&self.std) {
+            *value = (*value - mean) / std.max(MIN_STD);
+        }
+    }
+
+    /// Standardizes `values` and writes them into the continuous slots of `block`, the
+    /// `FINAL_FEATURE_SIZE` features of one roll.
+    pub fn write<B: Backend>(&self, mut values: Vec<f32>, block: &mut [B::FloatElem]) {
+        self.apply(&mut values);
+        for (slot, value) in block[util::CONTINUOUS_OFFSET..].iter_mut().zip(values) {
+            *slot = value.elem();
+        }
+    }
+
+    /// Saves the statistics to `NORMALIZER_FILE` in `artifact_dir`.
+    pub fn save_to(&self, artifact_dir: &str) -> std::io::Result<()> {
+        self.save(format!("{artifact_dir}/{NORMALIZER_FILE}"))
+    }
+
+    /// Loads the statistics saved by `save_to`, if any.
+    pub fn load_from(artifact_dir: &str) -> Option<Self> {
+        Self::load(format!("{artifact_dir}/{NORMALIZER_FILE}")).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizer_standardizes_features() {
+        let normalizer = Normalizer::fit(&[vec![1., 100.], vec![3., 100.]]);
+
+        assert_eq!(normalizer.mean, vec![2., 100.]);
+        assert_eq!(normalizer.std, vec![1., 0.]);
+
+        let mut features = [3., 100.];
+        normalizer.apply(&mut features);
+        assert_eq!(features, [1., 0.]);
+    }
+}
@@ -0,0 +1,176 @@
+//! Recording of played rolls in the training CSV format.
+//!
+//! A `BetResultCsvRecord` needs the roll that *followed* it (`next_number`), so each
+//! bet is held back until the next one arrives and then written out.
+
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use crate::dataset::BetResultCsvRecord;
+use crate::sites::BetResult;
+
+/// Appends consecutive bets to a CSV file loadable by the training dataset.
+pub struct BetRecorder {
+    writer: csv::Writer<std::fs::File>,
+    pending: Option<(BetResult, f32)>,
+    written: u64,
+}
+
+impl BetRecorder {
+    /// Opens `path` for appending, writing the header row when the file is new or empty.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let is_new = std::fs::metadata(path)
+            .map(|meta| meta.len() == 0)
+            .unwrap_or(true);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let writer = csv::WriterBuilder::new()
+            .has_headers(is_new)
+            .from_writer(file);
+
+        Ok(Self {
+            writer,
+            pending: None,
+            written: 0,
+        })
+    }
+
+    /// Records a settled bet along with the balance after it.
+    ///
+    /// The previous bet is written as soon as this one supplies its `next_number`, when
+    /// this one directly follows it on the seed chain. Otherwise bets were missed or the
+    /// seed changed in between, and the previous bet is dropped.
+    pub fn record(&mut self, bet_result: &BetResult, balance: f32) -> Result<(), csv::Error> {
+        if let Some((previous, previous_balance)) = self.pending.take() {
+            if bet_result.follows(&previous) {
+                self.record_pair(&previous, bet_result, previous_balance)?;
+            } else {
+                log::debug!(
+                    "Not recording the bet with nonce {}, the next bet does not follow it",
+                    previous.nonce
+                );
+            }
+        }
+        self.pending = Some((bet_result.clone(), balance));
+
+        Ok(())
+    }
+
+    /// Writes `current` with `next` as the roll that followed it, for callers that pair
+    /// the bets themselves, such as bets of many players read from a feed.
+    pub fn record_pair(
+        &mut self,
+        current: &BetResult,
+        next: &BetResult,
+        balance: f32,
+    ) -> Result<(), csv::Error> {
+        self.writer
+            .serialize(to_csv_record(current, next, balance))?;
+        self.writer.flush()?;
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Number of rows written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+/// Builds a training row for `current`, using `next` to supply the following roll.
+pub fn to_csv_record(current: &BetResult, next: &BetResult, balance: f32) -> BetResultCsvRecord {
+    BetResultCsvRecord {
+        result: current.result,
+        rolled_number: current.number.value(),
+        next_number: next.number.value(),
+        user_balance: balance as f64,
+        amount_won: current.profit() as f64,
+        server_seed_hash_next_roll: current.hash_next_roll.clone(),
+        client_seed: current.client_seed.clone(),
+        nonce_next_roll: next.nonce as u64,
+        nonce: current.nonce as u64,
+        server_seed_previous_roll: String::new(),
+        server_seed_hash_previous_roll: current.hash_previous_roll.clone(),
+        previous_nonce: (current.nonce as u64).saturating_sub(1),
+        duplicate_rolls: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::test_bet_result;
+
+    fn bet(number: u32, nonce: u32) -> BetResult {
+        BetResult {
+            hash_previous_roll: format!("prev{nonce}"),
+            hash_next_roll: format!("next{nonce}"),
+            client_seed: "seed".to_string(),
+            nonce,
+            ..test_bet_result(true, number, 1e-8)
+        }
+    }
+
+    #[test]
+    fn test_recorder_writes_previous_bet_with_next_number() {
+        let path = std::env::temp_dir().join(format!("recorder_test_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut recorder = BetRecorder::open(&path).unwrap();
+        recorder.record(&bet(1234, 7), 1.).unwrap();
+        assert_eq!(recorder.written(), 0);
+        recorder.record(&bet(4321, 8), 1.).unwrap();
+        assert_eq!(recorder.written(), 1);
+        drop(recorder);
+
+        let rows = csv::Reader::from_path(&path)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<Vec<BetResultCsvRecord>, csv::Error>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].rolled_number, 1234);
+        assert_eq!(rows[0].next_number, 4321);
+        assert_eq!(rows[0].nonce, 7);
+        assert_eq!(rows[0].nonce_next_roll, 8);
+        assert_eq!(rows[0].server_seed_hash_next_roll, "next7");
+    }
+
+    #[test]
+    fn test_recorder_drops_bets_not_followed_on_their_seed_chain() {
+        let path = std::env::temp_dir().join(format!("recorder_gap_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut recorder = BetRecorder::open(&path).unwrap();
+        recorder.record(&bet(1111, 7), 1.).unwrap();
+        // Nonce 8 was missed.
+        recorder.record(&bet(2222, 9), 1.).unwrap();
+        // The seed changed, and the new chain starts at nonce 10.
+        for (number, nonce) in [(3333, 10), (4444, 11)] {
+            let mut reseeded = bet(number, nonce);
+            reseeded.client_seed = "other".to_string();
+            recorder.record(&reseeded, 1.).unwrap();
+        }
+        drop(recorder);
+
+        let rows = csv::Reader::from_path(&path)
+            .unwrap()
+            .deserialize()
+            .collect::<Result<Vec<BetResultCsvRecord>, csv::Error>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].nonce, 10);
+        assert_eq!(rows[0].next_number, 4444);
+    }
+}
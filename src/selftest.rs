@@ -0,0 +1,231 @@
+//! Offline smoke test of the whole betting pipeline.
+//!
+//! An untrained model and the fake provably-fair server stand in for the trained model
+//! and the live site, so a user can confirm the backend works before risking money.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use burn::prelude::*;
+
+use crate::config::{SiteConfig, TomlStrategies};
+use crate::currency::Currency;
+use crate::inference;
+use crate::model::{Model, ModelConfig};
+use crate::sites::duck_dice::DuckDiceIo;
+use crate::sites::latency::LatencyStats;
+use crate::sites::{self, BetError, BetResult, Site};
+use crate::util::{self, BucketAnchor, Roll};
+
+/// Simulated bets placed by the self-test.
+pub const SELFTEST_BETS: u64 = 100;
+const START_BALANCE: f32 = 0.001;
+/// Largest accounting drift tolerated from summing `f32` amounts.
+const BALANCE_TOLERANCE: f32 = 1e-7;
+
+/// Outcome of one checked invariant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, passed: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.passed { "PASS" } else { "FAIL" };
+        write!(f, "[{status}] {}: {}", self.name, self.detail)
+    }
+}
+
+/// State of the fake site after the simulated bets.
+struct BetSession {
+    bets: u64,
+    won: f32,
+    profit: f32,
+    balance: f32,
+    history: Vec<BetResult>,
+    history_size: usize,
+}
+
+/// Runs every check, printing each as it completes so a crash shows how far it got.
+pub async fn run<B: Backend>(device: &B::Device) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut report = |check: Check| {
+        println!("{check}");
+        checks.push(check);
+    };
+
+    let model = match catch_unwind(AssertUnwindSafe(|| ModelConfig::new().init::<B>(device))) {
+        Ok(model) => {
+            report(Check::new("model", true, "untrained model initialized"));
+            model
+        }
+        Err(_) => {
+            report(Check::new("model", false, "model initialization panicked"));
+            return checks;
+        }
+    };
+
+    // Bets run in their own task so a panic in the site or strategy is reported
+    // instead of aborting the self-test; sites are not `Send`, hence a local task.
+    let bets = tokio::task::LocalSet::new()
+        .run_until(async { tokio::task::spawn_local(simulate_bets()).await })
+        .await;
+    let session = match bets {
+        Ok(Ok(session)) => session,
+        Ok(Err(e)) => {
+            report(Check::new("bets", false, format!("betting failed: {e}")));
+            return checks;
+        }
+        Err(e) => {
+            report(Check::new("bets", false, format!("betting panicked: {e}")));
+            return checks;
+        }
+    };
+    report(Check::new(
+        "bets",
+        session.bets == SELFTEST_BETS,
+        format!(
+            "{} of {} simulated bets settled",
+            session.bets, SELFTEST_BETS
+        ),
+    ));
+    report(accounting_check(
+        START_BALANCE,
+        session.won,
+        session.profit,
+        session.balance,
+    ));
+
+    if session.history.len() < session.history_size {
+        report(Check::new(
+            "history",
+            false,
+            format!(
+                "{} of {} rolls kept for the model window",
+                session.history.len(),
+                session.history_size
+            ),
+        ));
+        return checks;
+    }
+    let window = &session.history[session.history.len() - session.history_size..];
+
+    report(window_shape_check::<B>(window, device));
+    report(inference_check(&model, window, device));
+
+    checks
+}
+
+/// Places `SELFTEST_BETS` flat bets against the fake server.
+async fn simulate_bets() -> Result<BetSession, BetError> {
+    let mut site = DuckDiceIo::default()
+        .with_currency(Currency::BTC)
+        .with_strategy(TomlStrategies::Flat)
+        .with_simulation(START_BALANCE);
+    site.login().await?;
+
+    let mut latency = LatencyStats::default();
+    let mut prediction = 0.;
+    let mut won = 0.;
+    let mut bets = 0;
+    while bets < SELFTEST_BETS {
+        let bet_result = match sites::settle_bet(&mut site, prediction, 0., &mut latency).await {
+            Ok(res) => res,
+            Err(BetError::EmptyReply) => continue,
+            Err(e) => return Err(e),
+        };
+        if bet_result.result {
+            site.on_win(&bet_result);
+        } else {
+            site.on_lose(&bet_result);
+        }
+        won += bet_result.profit();
+        prediction = bet_result.number.value() as f32;
+        bets += 1;
+    }
+
+    Ok(BetSession {
+        bets,
+        won,
+        profit: site.get_profit(),
+        balance: site.get_balance(),
+        history: site.get_history(),
+        history_size: site.get_history_size(),
+    })
+}
+
+/// Profit and balance must both agree with the amounts the settled bets reported.
+fn accounting_check(start_balance: f32, won: f32, profit: f32, balance: f32) -> Check {
+    let passed = (profit - won).abs() <= BALANCE_TOLERANCE
+        && (balance - (start_balance + won)).abs() <= BALANCE_TOLERANCE;
+
+    Check::new(
+        "accounting",
+        passed,
+        format!(
+            "bets sum to {won:.8}, profit {profit:.8}, balance {start_balance:.8} -> {balance:.8}"
+        ),
+    )
+}
+
+fn window_shape_check<B: Backend>(window: &[BetResult], device: &B::Device) -> Check {
+    let expected = [1, window.len(), util::FEATURE_BLOCKS, util::FEATURE_STRIDE];
+    match catch_unwind(AssertUnwindSafe(|| {
+        inference::encode_window::<B>(window, None, device).dims()
+    })) {
+        Ok(dims) => Check::new(
+            "features",
+            dims == expected,
+            format!("input shape {dims:?}, expected {expected:?}"),
+        ),
+        Err(_) => Check::new("features", false, "feature encoding panicked"),
+    }
+}
+
+fn inference_check<B: Backend>(
+    model: &Model<B>,
+    window: &[BetResult],
+    device: &B::Device,
+) -> Check {
+    match catch_unwind(AssertUnwindSafe(|| {
+        inference::predict_next(model, window, None, BucketAnchor::default(), device)
+    })) {
+        Ok(Ok(prediction)) => Check::new(
+            "inference",
+            prediction.roll <= Roll::MAX as f32 && prediction.confidence.is_finite(),
+            format!(
+                "predicted {:.0} with confidence {:.2}",
+                prediction.roll, prediction.confidence
+            ),
+        ),
+        Ok(Err(e)) => Check::new("inference", false, format!("inference failed: {e}")),
+        Err(_) => Check::new("inference", false, "inference panicked"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounting_check() {
+        assert!(accounting_check(1., -0.25, -0.25, 0.75).passed);
+        assert!(!accounting_check(1., -0.25, 0., 1.).passed);
+        assert!(!accounting_check(1., -0.25, -0.25, 1.).passed);
+        assert_eq!(
+            Check::new("bets", false, "0 of 100").to_string(),
+            "[FAIL] bets: 0 of 100"
+        );
+    }
+}
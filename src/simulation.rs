@@ -0,0 +1,192 @@
+//! Offline simulation of betting sessions against the fake provably-fair server.
+//!
+//! Sessions here skip model inference entirely: the previous roll is used as the
+//! prediction, which keeps thousands of runs cheap enough for risk-of-ruin analysis.
+
+use crate::sites::fake_test::{duckdice_fake_bet_on, FakeServerStorage};
+use crate::sites::BetResult;
+use crate::strategies::Strategy;
+
+const CLIENT_SEED: &str = "BeO2jZRd4nidPz4U40e2G7hT22s9GA";
+
+/// Result of a single simulated session.
+#[derive(Clone, Debug)]
+pub struct SessionOutcome {
+    pub final_balance: f32,
+    pub rolls: u64,
+    pub busted: bool,
+}
+
+/// Aggregate statistics over many simulated sessions.
+#[derive(Clone, Debug)]
+pub struct MonteCarloReport {
+    pub runs: usize,
+    pub start_balance: f32,
+    pub busted_percent: f32,
+    pub median_balance: f32,
+    pub p5_balance: f32,
+    pub p95_balance: f32,
+    pub expected_profit: f32,
+}
+
+/// Runs one session of up to `rolls` bets on a fake server seeded with `seed`.
+///
+/// The session rolls on its own fake server rather than the shared one, so concurrent
+/// sessions never advance each other's nonces. It ends early when the strategy asks for
+/// more than the remaining balance.
+pub fn run_session(
+    strategy: &mut dyn Strategy,
+    start_balance: f32,
+    min_bet: f32,
+    rolls: u64,
+    seed: u64,
+) -> SessionOutcome {
+    let mut server = FakeServerStorage::seeded(seed);
+    strategy.set_balance(start_balance);
+
+    let mut balance = start_balance;
+    let mut prediction = 5000.;
+    let mut played = 0;
+
+    while played < rolls {
+        let (bet, multiplier, _chance, high) = strategy.get_next_bet(prediction, 0.);
+        let bet = bet.max(min_bet);
+        if bet > balance || bet <= 0. {
+            return SessionOutcome {
+                final_balance: balance,
+                rolls: played,
+                busted: true,
+            };
+        }
+
+        let bet_result: BetResult =
+            duckdice_fake_bet_on(&mut server, high, CLIENT_SEED, bet, multiplier).into();
+        played += 1;
+        balance += bet_result.profit();
+
+        if bet_result.result {
+            strategy.on_win(&bet_result);
+        } else {
+            strategy.on_lose(&bet_result);
+        }
+
+        prediction = bet_result.number.value() as f32;
+    }
+
+    SessionOutcome {
+        final_balance: balance,
+        rolls: played,
+        busted: balance < min_bet,
+    }
+}
+
+/// Runs `runs` independent sessions, building a fresh strategy for each, and summarizes them.
+pub fn monte_carlo<F>(
+    make_strategy: F,
+    runs: u32,
+    rolls_per_run: u64,
+    start_balance: f32,
+    min_bet: f32,
+    seed: u64,
+) -> MonteCarloReport
+where
+    F: Fn() -> Box<dyn Strategy>,
+{
+    let outcomes = (0..runs)
+        .map(|run| {
+            let mut strategy = make_strategy();
+            run_session(
+                strategy.as_mut(),
+                start_balance,
+                min_bet,
+                rolls_per_run,
+                seed.wrapping_add(run as u64),
+            )
+        })
+        .collect::<Vec<SessionOutcome>>();
+
+    summarize(&outcomes, start_balance)
+}
+
+/// Builds a report from a set of session outcomes.
+pub fn summarize(outcomes: &[SessionOutcome], start_balance: f32) -> MonteCarloReport {
+    let mut balances = outcomes
+        .iter()
+        .map(|outcome| outcome.final_balance)
+        .collect::<Vec<f32>>();
+    balances.sort_by(f32::total_cmp);
+
+    let runs = outcomes.len();
+    let busted = outcomes.iter().filter(|outcome| outcome.busted).count();
+    let mean_balance = balances.iter().sum::<f32>() / runs.max(1) as f32;
+
+    MonteCarloReport {
+        runs,
+        start_balance,
+        busted_percent: busted as f32 / runs.max(1) as f32 * 100.,
+        median_balance: percentile(&balances, 50.),
+        p5_balance: percentile(&balances, 5.),
+        p95_balance: percentile(&balances, 95.),
+        expected_profit: mean_balance - start_balance,
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[f32], percent: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.;
+    }
+    let rank = ((percent / 100.) * (sorted.len() - 1) as f32).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(final_balance: f32, busted: bool) -> SessionOutcome {
+        SessionOutcome {
+            final_balance,
+            rolls: 10,
+            busted,
+        }
+    }
+
+    #[test]
+    fn test_summarize_reports_bust_rate_and_percentiles() {
+        let outcomes = vec![
+            outcome(0., true),
+            outcome(0.5, false),
+            outcome(1., false),
+            outcome(1.5, false),
+            outcome(2., false),
+        ];
+
+        let report = summarize(&outcomes, 1.);
+
+        assert_eq!(report.runs, 5);
+        assert_eq!(report.busted_percent, 20.);
+        assert_eq!(report.median_balance, 1.);
+        assert_eq!(report.p5_balance, 0.);
+        assert_eq!(report.p95_balance, 2.);
+        assert_eq!(report.expected_profit, 0.);
+    }
+
+    #[test]
+    fn test_sessions_are_reproducible_per_seed() {
+        let run = |seed| {
+            let mut strategy = crate::strategies::from_config(
+                crate::config::TomlStrategies::None,
+                1.,
+                0.001,
+                0.001,
+            );
+            run_session(strategy.as_mut(), 1., 0.001, 50, seed).final_balance
+        };
+
+        let first = run(7);
+        // Rolls on the shared fake server must not shift the session's nonces.
+        crate::sites::fake_test::duckdice_fake_bet(true, CLIENT_SEED, 1e-8, 2.);
+        assert_eq!(first, run(7));
+    }
+}
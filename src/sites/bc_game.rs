@@ -0,0 +1,504 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        amount, bound_chance, check_funds, client_builder, effective_min_bet, payout_at,
+        proxied_client, push_history, send_request, signing::RequestSigner, BetError, BetId,
+        BetOutcome, BetResult, BetRule, Capabilities, Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+const API_URL: &str = "https://bc.game/api/open";
+/// Chances BC.Game accepts, in percent; targets have two decimals.
+const MIN_CHANCE: f32 = 0.01;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet BC.Game keeps, in percent.
+pub const HOUSE_EDGE: f32 = 1.;
+const API_KEY_HEADER: &str = "X-BC-Key";
+/// BC.Game expects the HMAC of the body keyed with the API secret in this header.
+pub const SIGNATURE_HEADER: &str = "X-BC-Signature";
+
+/// Every BC.Game reply wraps its payload in `{"code": ..., "msg": ..., "data": ...}`;
+/// any code but 0 is an error.
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub code: i64,
+    #[serde(default)]
+    pub msg: String,
+    pub data: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        match self.data {
+            Some(data) if self.code == 0 => Ok(data),
+            _ => {
+                log::error!("BC.Game request failed ({}): {}", self.code, self.msg);
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub nick_name: String,
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Balance {
+    pub currency: String,
+    #[serde(deserialize_with = "amount")]
+    pub amount: f64,
+}
+
+/// A classic dice bet; it wins when the roll lands over (or under) `target`.
+#[derive(Debug, Serialize)]
+pub struct Bet {
+    pub currency: String,
+    pub amount: String,
+    pub over: bool,
+    pub target: String,
+}
+
+/// A settled roll; rolls and targets are on a 0.00-99.99 scale.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Roll {
+    pub bet_id: String,
+    pub currency: String,
+    #[serde(deserialize_with = "amount")]
+    pub amount: f64,
+    /// Amount paid back, stake included; 0 on a loss.
+    #[serde(deserialize_with = "amount")]
+    pub payout: f64,
+    #[serde(deserialize_with = "amount")]
+    pub result: f64,
+    #[serde(deserialize_with = "amount")]
+    pub target: f64,
+    pub over: bool,
+    /// Hash of the server seed the roll was made with.
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+}
+
+impl Roll {
+    /// Roll on the model's 0-9999 scale.
+    pub fn scaled_result(&self) -> u32 {
+        (self.result * 100.).round() as u32
+    }
+
+    /// Win chance in percent of a roll against `target`.
+    pub fn chance(&self) -> f32 {
+        if self.over {
+            (99.99 - self.target) as f32
+        } else {
+            self.target as f32
+        }
+    }
+}
+
+pub struct BcGame {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    key: String,
+    /// Signs every request with the API secret; requests fail without it.
+    signer: Option<RequestSigner>,
+    currency: Currency,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl BcGame {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::BcGame),
+            self.min_bet_override,
+        )
+    }
+
+    /// Takes over the balance `user` holds in the betting currency.
+    fn set_balance(&mut self, user: &User) -> Result<(), BetError> {
+        let code = self.currency.to_string();
+        let balance = user
+            .balances
+            .iter()
+            .find(|balance| balance.currency.eq_ignore_ascii_case(&code))
+            .ok_or_else(|| {
+                BetError::ConfigError(format!("BC.Game has no {} balance", self.currency))
+            })?;
+        self.balance = balance.amount as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(())
+    }
+
+    /// Sends `body` signed to `path`. Takes the client, key and signer rather than
+    /// `&self`, whose strategy is not `Sync`.
+    async fn post<B: Serialize, T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        key: &str,
+        signer: Option<&RequestSigner>,
+        path: &str,
+        body: &B,
+    ) -> Result<T, BetError> {
+        let signer = signer.ok_or_else(|| {
+            BetError::ConfigError("BC.Game requests must be signed with the API secret".into())
+        })?;
+        let request = client
+            .post(format!("{API_URL}/{path}"))
+            .header(API_KEY_HEADER, key);
+        let response: Response<T> = send_request(signer.sign_json(request, body)?)
+            .await?
+            .json()
+            .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for BcGame {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.key = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+
+    fn with_request_signer(mut self, signer: RequestSigner) -> Self {
+        self.signer = Some(signer);
+
+        self
+    }
+}
+
+impl Default for BcGame {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::BcGame);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            key: String::new(),
+            signer: None,
+            currency,
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for BcGame {
+    async fn login(&mut self) -> Result<(), BetError> {
+        let user: User = Self::post(
+            &self.client,
+            &self.key,
+            self.signer.as_ref(),
+            "user/info",
+            &serde_json::json!({}),
+        )
+        .await
+        .map_err(|e| {
+            log::error!("BC.Game login failed: {e}");
+            BetError::LoginFailed
+        })?;
+
+        self.set_balance(&user)?;
+
+        log::info!(
+            "Logged in to BC.Game as {} with {:.8} {}",
+            user.nick_name,
+            self.balance,
+            self.currency
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // BC.Game pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        // BC.Game takes the target to roll over or under, with two decimals.
+        let chance = chance as f64;
+        let target = if high { 99.99 - chance } else { chance };
+
+        let roll: Roll = Self::post(
+            &self.client,
+            &self.key,
+            self.signer.as_ref(),
+            "game/dice/bet",
+            &Bet {
+                currency: self.currency.to_string(),
+                amount: format!("{:.8}", self.current_bet),
+                over: high,
+                target: format!("{target:.2}"),
+            },
+        )
+        .await?;
+
+        let bet_result: BetResult = roll.into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // BC.Game dice bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let user: User = Self::post(
+            &self.client,
+            &self.key,
+            self.signer.as_ref(),
+            "user/info",
+            &serde_json::json!({}),
+        )
+        .await?;
+        self.set_balance(&user)?;
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(reply: &str) -> BetResult {
+        serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_bet_reply_maps_seeds_and_amounts() {
+        let bet = roll(
+            r#"{"code": 0, "msg": "success", "data": {
+                "betId": "77120034", "currency": "BTC", "amount": "0.00000100",
+                "payout": "0.00000198", "result": "73.12", "target": "50.49", "over": true,
+                "serverSeedHash": "5e8ff9bf55ba3508199d22e984129be6", "clientSeed": "client",
+                "nonce": 41
+            }}"#,
+        );
+
+        assert_eq!(bet.hash_previous_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.client_seed, "client");
+        assert_eq!(bet.nonce, 41);
+        assert_eq!(bet.number.value(), 7312);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        assert!((bet.payout - 2.).abs() < 1e-4);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost = roll(
+            r#"{"code": 0, "data": {
+                "betId": "77120035", "currency": "BTC", "amount": 0.000001, "payout": 0,
+                "result": 62.5, "target": 49.5, "over": false,
+                "serverSeedHash": "5e8ff9bf55ba3508199d22e984129be6", "clientSeed": "client",
+                "nonce": 42
+            }}"#,
+        );
+
+        assert!(!lost.result && !lost.is_high && lost.rolled_high);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((lost.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_requests_are_refused() {
+        let mut site = BcGame::default().with_api_key("key".to_string());
+
+        assert!(matches!(site.login().await, Err(BetError::LoginFailed)));
+
+        let reply = r#"{"code": 4003, "msg": "invalid signature", "data": null}"#;
+        assert!(serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        amount, bound_chance, check_funds, client_builder, effective_min_bet, payout_at,
+        proxied_client, push_history, send_request, BetError, BetId, BetOutcome, BetResult,
+        BetRule, Capabilities, Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+const API_URL: &str = "https://www.bitsler.com/api";
+/// Chances Bitsler accepts, in percent; targets have two decimals.
+const MIN_CHANCE: f32 = 0.01;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet Bitsler keeps, in percent.
+pub const HOUSE_EDGE: f32 = 1.;
+
+/// Bitsler replies with the payload next to `success`, and an `error` when it is false.
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub success: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub data: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        match self.data {
+            Some(data) if self.success => Ok(data),
+            _ => {
+                log::error!(
+                    "Bitsler request failed: {}",
+                    self.error.as_deref().unwrap_or("no reason given")
+                );
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+/// Seed pair the next roll is made with.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Seeds {
+    /// SHA-256 of the active server seed, revealed when the seeds are changed.
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    /// Nonce of the next roll.
+    pub nonce: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub username: String,
+    /// Balance per lowercase currency code.
+    pub balances: std::collections::HashMap<String, serde_json::Value>,
+    pub seeds: Seeds,
+}
+
+/// Side of `game` a roll must land on to win.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Condition {
+    #[serde(rename = ">")]
+    Over,
+    #[serde(rename = "<")]
+    Under,
+}
+
+impl Condition {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Over => ">",
+            Self::Under => "<",
+        }
+    }
+}
+
+/// A settled dice roll; rolls and targets are on a 0.00-99.99 scale.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Roll {
+    pub id: String,
+    #[serde(deserialize_with = "amount")]
+    pub amount: f64,
+    /// Net result of the bet, negative on a loss.
+    #[serde(deserialize_with = "amount")]
+    pub profit: f64,
+    #[serde(deserialize_with = "amount")]
+    pub new_balance: f64,
+    #[serde(deserialize_with = "amount")]
+    pub result: f64,
+    /// Target the roll was compared to.
+    #[serde(deserialize_with = "amount")]
+    pub game: f64,
+    pub condition: Condition,
+    pub devise: String,
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+}
+
+impl Roll {
+    /// Roll on the model's 0-9999 scale.
+    pub fn scaled_result(&self) -> u32 {
+        (self.result * 100.).round() as u32
+    }
+
+    /// Win chance in percent of a roll against `game`.
+    pub fn chance(&self) -> f32 {
+        match self.condition {
+            Condition::Over => (99.99 - self.game) as f32,
+            Condition::Under => self.game as f32,
+        }
+    }
+}
+
+pub struct Bitsler {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    token: String,
+    currency: Currency,
+    seeds: Seeds,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl Bitsler {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::Bitsler),
+            self.min_bet_override,
+        )
+    }
+
+    /// Bitsler names currencies in lowercase.
+    fn currency_code(&self) -> String {
+        self.currency.to_string().to_lowercase()
+    }
+
+    /// Takes over the balance `user` holds in the betting currency.
+    fn set_balance(&mut self, user: &User) -> Result<(), BetError> {
+        let balance = user
+            .balances
+            .get(&self.currency_code())
+            .map(|balance| amount(balance).ok())
+            .ok_or_else(|| {
+                BetError::ConfigError(format!("Bitsler has no {} balance", self.currency))
+            })?
+            .ok_or(BetError::Failed)?;
+        self.balance = balance as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(())
+    }
+
+    /// Takes the client and token rather than `&self`, whose strategy is not `Sync`.
+    async fn post<T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        token: &str,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, BetError> {
+        let mut form = vec![("access_token", token.to_string())];
+        form.extend(params.iter().cloned());
+
+        let response: Response<T> =
+            send_request(client.post(format!("{API_URL}/{path}")).form(&form))
+                .await?
+                .json()
+                .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for Bitsler {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.token = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+}
+
+impl Default for Bitsler {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::Bitsler);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            token: String::new(),
+            currency,
+            seeds: Seeds::default(),
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for Bitsler {
+    async fn login(&mut self) -> Result<(), BetError> {
+        let user: User = Self::post(&self.client, &self.token, "user", &[])
+            .await
+            .map_err(|e| {
+                log::error!("Bitsler login failed: {e}");
+                BetError::LoginFailed
+            })?;
+
+        self.set_balance(&user)?;
+
+        self.seeds = user.seeds;
+        log::info!(
+            "Logged in to Bitsler as {} with {:.8} {}, nonce {}",
+            user.username,
+            self.balance,
+            self.currency,
+            self.seeds.nonce
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // Bitsler pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        // Bitsler takes the target to roll over or under, with two decimals.
+        let chance = chance as f64;
+        let (condition, game) = if high {
+            (Condition::Over, 99.99 - chance)
+        } else {
+            (Condition::Under, chance)
+        };
+
+        let roll: Roll = Self::post(
+            &self.client,
+            &self.token,
+            "bet-dice",
+            &[
+                ("devise", self.currency_code()),
+                ("amount", format!("{:.8}", self.current_bet)),
+                ("condition", condition.as_str().to_string()),
+                ("game", format!("{game:.2}")),
+            ],
+        )
+        .await?;
+
+        // The server seed stays until the seeds are changed, so the next roll is
+        // committed to the same hash with the nonce moved on.
+        self.seeds = Seeds {
+            server_seed_hash: roll.server_seed_hash.clone(),
+            client_seed: roll.client_seed.clone(),
+            nonce: roll.nonce + 1,
+        };
+        let bet_result: BetResult = roll.into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // Bitsler rolls settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let user: User = Self::post(&self.client, &self.token, "user", &[]).await?;
+        self.set_balance(&user)?;
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::check_hex;
+
+    fn roll(reply: &str) -> BetResult {
+        serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn test_bet_reply_maps_provably_fair_fields() {
+        let hash = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+        let bet = roll(&format!(
+            r#"{{"success": true, "id": "981422", "amount": "0.00000100",
+                "profit": "0.00000098", "new_balance": "0.00100098", "result": "73.12",
+                "game": "50.49", "condition": ">", "devise": "btc",
+                "server_seed_hash": "{hash}", "client_seed": "client", "nonce": 41}}"#
+        ));
+
+        assert_eq!(bet.hash_previous_roll, hash);
+        assert_eq!(bet.hash_next_roll, hash);
+        assert!(check_hex(&bet.hash_previous_roll).is_ok());
+        assert_eq!(bet.client_seed, "client");
+        assert_eq!(bet.nonce, 41);
+        assert_eq!(bet.symbol, "BTC");
+        assert_eq!(bet.number.value(), 7312);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        assert!((bet.payout - 2.).abs() < 1e-4);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost = roll(&format!(
+            r#"{{"success": true, "id": "981423", "amount": 0.000001, "profit": -0.000001,
+                "new_balance": 0.00099998, "result": 62.5, "game": 49.5, "condition": "<",
+                "devise": "btc", "server_seed_hash": "{hash}", "client_seed": "client",
+                "nonce": 42}}"#
+        ));
+
+        assert!(!lost.result && !lost.is_high && lost.rolled_high);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((lost.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_failed_reply_is_an_error() {
+        let reply = r#"{"success": false, "error": "Insufficient balance"}"#;
+
+        assert!(serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+    }
+}
@@ -2,7 +2,12 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    sites::{BetError, BetResult, Site},
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    sites::{
+        cap_bet, cap_multiplier, check_funds, client_builder, effective_min_bet, proxied_client,
+        push_history, send_request, BetError, BetId, BetOutcome, BetResult, BetRule, Capabilities,
+        Site,
+    },
     strategies::Strategy,
 };
 
@@ -49,6 +54,31 @@ impl Currency {
     }
 }
 
+impl TryFrom<&crate::currency::Currency> for Currency {
+    type Error = String;
+
+    fn try_from(currency: &crate::currency::Currency) -> Result<Self, Self::Error> {
+        use crate::currency::Currency as Configured;
+
+        Ok(match currency {
+            Configured::BTC => Self::BTC,
+            Configured::ETH => Self::ETH,
+            Configured::USDT => Self::USDT,
+            Configured::SOL => Self::SOL,
+            Configured::XRP => Self::XRP,
+            Configured::BNB => Self::BNB,
+            Configured::USDC => Self::USDC,
+            Configured::DOGE => Self::DOGE,
+            Configured::SHIB => Self::SHIB,
+            Configured::LTC => Self::LTC,
+            Configured::BCH => Self::BCH,
+            Configured::ETC => Self::ETC,
+            Configured::POL => Self::POL,
+            other => return Err(format!("CryptoGames does not support {other}")),
+        })
+    }
+}
+
 impl std::fmt::Display for Currency {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let currency_str = match self {
@@ -121,6 +151,14 @@ pub struct BetSiteResult {
     pub balance: f64,
 }
 
+#[derive(Debug, Serialize)]
+pub struct Withdrawal {
+    #[serde(rename(serialize = "Address"))]
+    pub address: String,
+    #[serde(rename(serialize = "Amount"))]
+    pub amount: f64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Balance {
     #[serde(rename(deserialize = "Balance"))]
@@ -140,6 +178,8 @@ impl Default for UserStats {
 
 pub struct CryptoGames {
     pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
     pub client_seed: String,
     pub current_bet: f32,
     pub multiplier: f32,
@@ -148,10 +188,131 @@ pub struct CryptoGames {
     pub prediction: u32,
     pub strategy: Box<dyn Strategy>,
     client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
     key: String,
     history: Vec<BetResult>,
     history_size: usize,
+    max_history: usize,
     currency: Currency,
+    /// Payout range of the coin, refreshed from the site at login.
+    min_payout: f32,
+    max_payout: f32,
+    /// Largest profit a single bet may win, refreshed from the site at login.
+    max_win: Option<f32>,
+    /// Smallest stake of the coin, refreshed from the site at login; the built-in
+    /// table of minimums applies until then.
+    coin_min_bet: Option<f32>,
+    /// House edge in percent, refreshed from the site at login.
+    edge: f32,
+    /// Share of the largest stake the site accepts that bets are capped at.
+    max_bet_fraction: f32,
+    /// Configured floor for bets, used when above the coin's minimum.
+    min_bet_override: Option<f32>,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+/// Bankroll the strategy starts from until the site reports the balance.
+const DEFAULT_BALANCE: f32 = 0.00037203;
+
+/// House edge in percent until the site reports the coin's.
+const DEFAULT_EDGE: f32 = 1.;
+
+impl CryptoGames {
+    /// Smallest bet placed: the configured override, but never below the coin minimum.
+    fn min_bet(&self) -> f32 {
+        let site_min_bet = self
+            .coin_min_bet
+            .unwrap_or_else(|| self.currency.get_min_bet());
+        effective_min_bet(site_min_bet, self.min_bet_override)
+    }
+
+    /// Takes the coin's live limits over the built-in ones.
+    fn apply_coin(&mut self, coin: &Coin) {
+        self.coin_min_bet = Some(coin.min_bet as f32).filter(|min_bet| *min_bet > 0.);
+        self.min_payout = coin.min_payout as f32;
+        self.max_payout = coin.max_payout as f32;
+        self.max_win = Some(coin.max_win as f32).filter(|max_win| *max_win > 0.);
+        self.edge = coin.edge as f32;
+    }
+
+    /// Withdrawal request on the v1 API's `/{call}/{coin}/{key}` paths shared with
+    /// `placebet` and `balance`, posting the `Address` and `Amount` to send.
+    fn withdraw_request(&self, amount: f32, address: &str) -> reqwest::RequestBuilder {
+        self.client
+            .post(format!(
+                "https://api.crypto.games/v1/withdraw/{}/{}",
+                self.currency, self.key
+            ))
+            .json(&Withdrawal {
+                address: address.to_string(),
+                amount: amount as f64,
+            })
+    }
+}
+
+impl SiteConfig for CryptoGames {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.key = api_key;
+
+        self
+    }
+
+    /// Coins CryptoGames does not list are rejected by `TomlConfig::validate`; they
+    /// keep the current coin here.
+    fn with_currency(mut self, currency: crate::currency::Currency) -> Self {
+        match Currency::try_from(&currency) {
+            Ok(currency) => {
+                self.current_bet = currency.get_min_bet();
+                self.currency = currency;
+            }
+            Err(e) => log::error!("{e}, betting {}", self.currency),
+        }
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self
+            .sizing
+            .strategy(strategy, DEFAULT_BALANCE, self.min_bet());
+
+        self
+    }
+
+    /// The largest stake is the coin's maximum win divided by the bet's profit multiple.
+    fn with_max_bet_fraction(mut self, fraction: f32) -> Self {
+        self.max_bet_fraction = fraction;
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
 }
 
 impl Default for CryptoGames {
@@ -160,25 +321,34 @@ impl Default for CryptoGames {
 
         Self {
             rolls: 0,
+            wagered: 0.,
             client_seed: "BeO2jZRd4nidPz4U40e2G7hT22s9GA".to_string(),
             current_bet: currency.get_min_bet(),
             multiplier: 2.,
             user_stats: UserStats::default(),
             profit: 0.,
             prediction: 0,
-            strategy: Box::new(
-                // crate::strategies::blaks_runner::BlaksRunner5_0::default()
-                crate::strategies::my_strategy::MyStrat::default()
-                    // crate::strategies::none::NoStrat::default()
-                    .with_balance(0.00037203)
-                    .with_min_bet(currency.get_min_bet())
-                    .with_initial_bet(currency.get_min_bet()),
+            strategy: BetSizingConfig::default().strategy(
+                TomlStrategies::MyStrategy,
+                DEFAULT_BALANCE,
+                currency.get_min_bet(),
             ),
             client: reqwest::Client::new(),
+            proxy: None,
             key: "".to_string(),
             history: Vec::new(),
-            history_size: 10,
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
             currency,
+            min_payout: 1.02,
+            max_payout: 9900.,
+            max_win: None,
+            coin_min_bet: None,
+            edge: DEFAULT_EDGE,
+            max_bet_fraction: 1.,
+            min_bet_override: None,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
         }
     }
 }
@@ -186,88 +356,143 @@ impl Default for CryptoGames {
 #[async_trait]
 impl Site for CryptoGames {
     async fn login(&mut self) -> Result<(), BetError> {
-        let balance: Balance = self
-            .client
-            .get(format!(
-                "https://api.crypto.games/v1/balance/{}/{}",
-                self.currency, self.key
-            ))
-            .send()
-            .await?
-            .json()
-            .await?;
-
-        self.user_stats.balance = balance.balance as f32;
-        self.strategy.set_balance(self.user_stats.balance);
+        self.refresh_balance().await?;
+
+        let coin: Coin = send_request(self.client.get(format!(
+            "https://api.crypto.games/v1/coininfo/{}",
+            self.currency
+        )))
+        .await?
+        .json()
+        .await?;
+        self.apply_coin(&coin);
+        log::info!(
+            "{} bets from {:.8} at payouts from {:.2}x to {:.2}x, {}% edge",
+            coin.coin,
+            self.min_bet(),
+            self.min_payout,
+            self.max_payout,
+            self.edge
+        );
 
         Ok(())
     }
 
-    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetResult, BetError> {
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
         self.rolls += 1;
         let next_bet_data = self.strategy.get_next_bet(prediction, confidence);
         self.current_bet = next_bet_data.0;
         self.multiplier = next_bet_data.1;
         let high = next_bet_data.3;
+        self.last_rule = BetRule::Normal;
 
         if self.history.len() < self.history_size {
-            self.current_bet = self.currency.get_min_bet();
+            self.current_bet = self.min_bet();
             self.multiplier = 2.;
+            self.last_rule = BetRule::Warmup;
         }
 
-        self.multiplier = self.multiplier.clamp(1.02, 9900.);
-        self.current_bet = self.current_bet.max(self.currency.get_min_bet());
+        let multiplier = cap_multiplier(self.multiplier, self.min_payout, self.max_payout);
+        if multiplier != self.multiplier {
+            self.last_rule = BetRule::Capped;
+        }
+        self.multiplier = multiplier;
+        if let Some(max_win) = self.max_win {
+            let bet = cap_bet(
+                self.current_bet,
+                max_win / (self.multiplier - 1.),
+                self.max_bet_fraction,
+            );
+            if bet != self.current_bet {
+                self.current_bet = bet;
+                self.last_rule = BetRule::Capped;
+            }
+        }
+        self.current_bet = self.current_bet.max(self.min_bet());
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        let res: serde_json::Value = send_request(
+            self.client
+                .post(format!(
+                    "https://api.crypto.games/v1/placebet/{}/{}",
+                    self.currency, self.key
+                ))
+                .json(&Bet {
+                    bet: self.current_bet as f64,
+                    payout: self.multiplier as f64,
+                    under_over: high,
+                    client_seed: self.client_seed.clone(),
+                }),
+        )
+        .await?
+        .json()
+        .await?;
+
+        let mut res: BetSiteResult = serde_json::from_value(res)?;
+        res.roll *= 100.;
+        let mut bet_result: BetResult = res.into();
+        bet_result.is_high = high;
+        bet_result.bet_amount = self.current_bet;
+        bet_result.symbol = self.currency.to_string();
+        bet_result.chance = (100. - self.edge) / self.multiplier;
 
-        let res: serde_json::Value = self
-            .client
-            .post(format!(
-                "https://api.crypto.games/v1/placebet/{}/{}",
-                self.currency, self.key
-            ))
-            .json(&Bet {
-                bet: self.current_bet as f64,
-                payout: self.multiplier as f64,
-                under_over: high,
-                client_seed: self.client_seed.clone(),
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
 
-        let mut res: BetSiteResult = serde_json::from_value(res).unwrap();
-        res.roll *= 100.;
+        Ok(BetOutcome::Resolved(bet_result))
+    }
 
-        self.history.push(res.clone().into());
-        if self.history.len() > self.history_size {
-            self.history = self.history[1..].to_vec();
-        }
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // CryptoGames bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
 
-        if self.current_bet > self.strategy.get_balance() {
-            panic!("Not enough money!");
+    async fn withdraw(&mut self, amount: f32, address: &str) -> Result<(), BetError> {
+        send_request(self.withdraw_request(amount, address))
+            .await?
+            .error_for_status()?;
+
+        // The withdrawn funds are gone from the account, so stop betting with them.
+        if let Err(e) = self.refresh_balance().await {
+            log::warn!("Failed to refresh the balance after withdrawing: {e}");
         }
 
-        Ok(res.into())
+        Ok(())
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let balance: Balance = send_request(self.client.get(format!(
+            "https://api.crypto.games/v1/balance/{}/{}",
+            self.currency, self.key
+        )))
+        .await?
+        .json()
+        .await?;
+
+        self.user_stats.balance = balance.balance as f32;
+        self.strategy.set_balance(self.user_stats.balance);
+
+        Ok(self.user_stats.balance)
     }
 
     fn on_win(&mut self, bet_result: &BetResult) {
         self.user_stats.balance += bet_result.win_amount;
         self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
 
         if self.history.len() >= self.history_size {
             self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
         }
     }
 
     fn on_lose(&mut self, bet_result: &BetResult) {
-        let mut bet_result = bet_result.clone();
-        bet_result.win_amount = -bet_result.win_amount;
-
         self.user_stats.balance -= bet_result.win_amount;
         self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
 
         if self.history.len() >= self.history_size {
-            self.strategy.on_lose(&bet_result);
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
         }
     }
 
@@ -279,10 +504,31 @@ impl Site for CryptoGames {
         self.rolls
     }
 
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            withdrawal: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn get_current_bet(&self) -> f32 {
         self.current_bet
     }
 
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
     fn get_current_multiplier(&self) -> f32 {
         self.multiplier
     }
@@ -295,7 +541,97 @@ impl Site for CryptoGames {
         self.profit
     }
 
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
     fn get_balance(&self) -> f32 {
         self.user_stats.balance
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_live_coin_limits_replace_the_table() {
+        let mut site = CryptoGames::default();
+        assert_eq!(site.min_bet(), Currency::PLAY.get_min_bet());
+
+        site.apply_coin(&Coin {
+            coin: "PLAY".to_string(),
+            min_bet: 50.,
+            max_win: 1000.,
+            min_payout: 1.1,
+            max_payout: 5000.,
+            edge: 0.8,
+        });
+
+        assert_eq!(site.min_bet(), 50.);
+        assert_eq!(site.max_win, Some(1000.));
+        assert_eq!((site.min_payout, site.max_payout), (1.1, 5000.));
+        assert_eq!(site.edge, 0.8);
+    }
+
+    #[test]
+    fn test_withdraw_request_posts_the_address_and_amount() {
+        let site = CryptoGames::default()
+            .with_api_key("key".to_string())
+            .with_currency(crate::currency::Currency::LTC);
+
+        let request = site.withdraw_request(0.5, "ltc1address").build().unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::POST);
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.crypto.games/v1/withdraw/LTC/key"
+        );
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap();
+        assert_eq!(body, br#"{"Address":"ltc1address","Amount":0.5}"#);
+    }
+
+    #[test]
+    fn test_configures_key_and_coin() {
+        let site = CryptoGames::default()
+            .with_api_key("key".to_string())
+            .with_currency(crate::currency::Currency::LTC);
+        assert_eq!(site.key, "key");
+        assert_eq!(site.currency.to_string(), "LTC");
+        assert_eq!(site.current_bet, Currency::LTC.get_min_bet());
+
+        // Coins the site does not list keep the current one.
+        let site = site.with_currency(crate::currency::Currency::TRX);
+        assert_eq!(site.currency.to_string(), "LTC");
+    }
+
+    #[test]
+    fn test_losing_bet_lowers_balance_and_profit_by_the_stake() {
+        let reply: BetSiteResult = serde_json::from_value(serde_json::json!({
+            "BetId": 1,
+            "Roll": 72.5,
+            "Target": ">50.5",
+            "Profit": -0.5,
+            "Payout": 2.0,
+            "ServerSeed": "seed",
+            "NextServerSeedHash": "hash",
+            "Balance": 1.5,
+        }))
+        .unwrap();
+        let mut bet_result: BetResult = reply.into();
+        bet_result.bet_amount = 0.5;
+        assert!(!bet_result.result);
+        assert_eq!(bet_result.win_amount, 0.5);
+
+        let mut site = CryptoGames::default();
+        site.user_stats.balance = 2.;
+        site.strategy.set_balance(2.);
+        site.history = vec![bet_result.clone(); site.history_size];
+
+        site.on_lose(&bet_result);
+        assert_eq!(site.get_balance(), 1.5);
+        assert_eq!(site.get_profit(), -0.5);
+        assert_eq!(site.strategy.get_balance(), 1.5);
+        assert_eq!(site.get_wagered(), 0.5);
+    }
+}
@@ -0,0 +1,528 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        bound_chance, check_funds, client_builder, effective_min_bet, payout_at, proxied_client,
+        push_history, send_request, BetError, BetId, BetOutcome, BetResult, BetRule, Capabilities,
+        Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+const API_URL: &str = "https://www.999dice.com/api/web.aspx";
+/// Chances 999dice accepts, in percent; the smallest wins on a single roll.
+const MIN_CHANCE: f32 = 0.0001;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet 999dice keeps, in percent.
+pub const HOUSE_EDGE: f32 = 0.1;
+/// Amounts are sent and received in integer units of 1e-8.
+const UNITS_PER_COIN: f64 = 1e8;
+/// Rolls run from 0 to `ROLL_MAX` inclusive, 100 times finer than the model's range.
+pub const ROLL_MAX: u32 = 999_999;
+const ROLL_SCALE: u32 = (ROLL_MAX + 1) / 10_000;
+
+/// 999dice reports failures as flags (`{"InsufficientFunds": 1}`) or an `error`
+/// message next to the payload, with the same status code as a success.
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(rename = "InsufficientFunds", default)]
+    pub insufficient_funds: Option<u8>,
+    #[serde(rename = "TooFast", default)]
+    pub too_fast: Option<u8>,
+    #[serde(flatten)]
+    pub data: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        if self.insufficient_funds.is_some() {
+            log::error!("999dice refused the bet: insufficient funds");
+            return Err(BetError::Failed);
+        }
+        if self.too_fast.is_some() {
+            return Err(BetError::RateLimited(None));
+        }
+
+        match (self.error, self.data) {
+            (None, Some(data)) => Ok(data),
+            (error, _) => {
+                log::error!(
+                    "999dice request failed: {}",
+                    error.as_deref().unwrap_or("unexpected reply")
+                );
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BalanceReply {
+    #[serde(rename = "Balance")]
+    pub balance: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HashReply {
+    /// SHA-256 of the server seed the next bet is rolled with.
+    #[serde(rename = "Hash")]
+    pub hash: String,
+}
+
+/// A settled bet. Every bet is rolled with a fresh server seed, which the reply
+/// reveals together with the hash of the next one.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaceBetReply {
+    pub bet_id: i64,
+    /// Amount paid back in 1e-8 units, stake included; 0 on a loss.
+    pub pay_out: i64,
+    /// The roll, 0-999999.
+    pub secret: u32,
+    pub server_seed: String,
+    /// Hash of the server seed the next bet is rolled with.
+    pub next: String,
+}
+
+/// A bet reply together with the request it answers, which the reply leaves out.
+#[derive(Clone, Debug)]
+pub struct BetSiteResult {
+    pub reply: PlaceBetReply,
+    /// Stake in 1e-8 units.
+    pub pay_in: i64,
+    /// Winning range of the roll, inclusive.
+    pub low: u32,
+    pub high: u32,
+    pub client_seed: u32,
+    /// Hash the server committed to for this bet.
+    pub server_seed_hash: String,
+    pub symbol: String,
+}
+
+/// Maps a 999dice roll onto the model's 0-9999 range.
+pub fn scale_roll(secret: u32) -> u32 {
+    secret.min(ROLL_MAX) / ROLL_SCALE
+}
+
+/// Winning range `[low, high]` of a bet at `chance` percent on the high or low side,
+/// for a chance already bounded to what 999dice accepts.
+pub fn bet_range(chance: f32, high: bool) -> (u32, u32) {
+    let width = ((chance as f64 * 10_000.).round() as u32).max(1);
+    if high {
+        (ROLL_MAX + 1 - width, ROLL_MAX)
+    } else {
+        (0, width - 1)
+    }
+}
+
+pub struct Dice999 {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    key: String,
+    currency: Currency,
+    /// Hash of the server seed the next bet is rolled with.
+    next_hash: String,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl Dice999 {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::Dice999),
+            self.min_bet_override,
+        )
+    }
+
+    /// 999dice names currencies in lowercase.
+    fn currency_code(&self) -> String {
+        self.currency.to_string().to_lowercase()
+    }
+
+    /// Runs API `action`. Requests are sessionless: each carries the API key instead of a
+    /// session cookie. Takes the client and key rather than `&self`, whose strategy is
+    /// not `Sync`.
+    async fn call<T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        key: &str,
+        action: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, BetError> {
+        let mut form = vec![("a", action.to_string()), ("Key", key.to_string())];
+        form.extend(params.iter().cloned());
+
+        let response: Response<T> = send_request(client.post(API_URL).form(&form))
+            .await?
+            .json()
+            .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for Dice999 {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.key = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+}
+
+impl Default for Dice999 {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::Dice999);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            key: String::new(),
+            currency,
+            next_hash: String::new(),
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for Dice999 {
+    async fn login(&mut self) -> Result<(), BetError> {
+        self.refresh_balance().await.map_err(|e| {
+            log::error!("999dice login failed: {e}");
+            BetError::LoginFailed
+        })?;
+
+        let HashReply { hash } =
+            Self::call(&self.client, &self.key, "GetServerSeedHash", &[]).await?;
+        self.next_hash = hash;
+        log::info!(
+            "Logged in to 999dice with {:.8} {}",
+            self.balance,
+            self.currency
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // 999dice pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        let (low, high_bound) = bet_range(chance, high);
+        let pay_in = (self.current_bet as f64 * UNITS_PER_COIN).round() as i64;
+        let client_seed = rand::random::<u32>() >> 1;
+
+        let reply: PlaceBetReply = Self::call(
+            &self.client,
+            &self.key,
+            "PlaceBet",
+            &[
+                ("PayIn", pay_in.to_string()),
+                ("Low", low.to_string()),
+                ("High", high_bound.to_string()),
+                ("ClientSeed", client_seed.to_string()),
+                ("Currency", self.currency_code()),
+            ],
+        )
+        .await?;
+
+        let server_seed_hash = std::mem::replace(&mut self.next_hash, reply.next.clone());
+        let bet_result: BetResult = BetSiteResult {
+            reply,
+            pay_in,
+            low,
+            high: high_bound,
+            client_seed,
+            server_seed_hash,
+            symbol: self.currency.to_string(),
+        }
+        .into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // 999dice bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let reply: BalanceReply = Self::call(
+            &self.client,
+            &self.key,
+            "GetBalance",
+            &[("Currency", self.currency_code())],
+        )
+        .await?;
+        self.balance = (reply.balance as f64 / UNITS_PER_COIN) as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolls_scale_to_model_range() {
+        assert_eq!(scale_roll(0), 0);
+        assert_eq!(scale_roll(731_299), 7312);
+        assert_eq!(scale_roll(ROLL_MAX), 9999);
+        assert_eq!(scale_roll(u32::MAX), 9999);
+
+        assert_eq!(bet_range(49.5, true), (505_000, ROLL_MAX));
+        assert_eq!(bet_range(49.5, false), (0, 494_999));
+    }
+
+    #[test]
+    fn test_bet_reply_maps_seeds_and_amounts() {
+        let reply = serde_json::from_str::<Response<PlaceBetReply>>(
+            r#"{"BetId": 901234, "PayOut": 198, "Secret": 731299,
+                "StartingBalance": 100000, "ServerSeed": "revealed",
+                "Next": "5e8ff9bf55ba3508199d22e984129be6"}"#,
+        )
+        .unwrap()
+        .into_data()
+        .unwrap();
+        let (low, high) = bet_range(49.5, true);
+
+        let bet: BetResult = BetSiteResult {
+            reply: reply.clone(),
+            pay_in: 100,
+            low,
+            high,
+            client_seed: 42,
+            server_seed_hash: "2c26b46b68ffc68ff99b453c1d30413413422d70".to_string(),
+            symbol: "BTC".to_string(),
+        }
+        .into();
+
+        assert_eq!(
+            bet.hash_previous_roll,
+            "2c26b46b68ffc68ff99b453c1d30413413422d70"
+        );
+        assert_eq!(bet.hash_next_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.client_seed, "42");
+        assert_eq!(bet.number.value(), 7312);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        assert!((bet.payout - 99.9 / 49.5).abs() < 1e-4);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost: BetResult = BetSiteResult {
+            reply: PlaceBetReply {
+                pay_out: 0,
+                ..reply
+            },
+            pay_in: 100,
+            low: 0,
+            high: 494_999,
+            client_seed: 42,
+            server_seed_hash: "5e8ff9bf55ba3508199d22e984129be6".to_string(),
+            symbol: "BTC".to_string(),
+        }
+        .into();
+
+        assert!(!lost.result && !lost.is_high && lost.rolled_high);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((lost.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_failure_flags_are_errors() {
+        let reply = r#"{"InsufficientFunds": 1}"#;
+        assert!(serde_json::from_str::<Response<PlaceBetReply>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+
+        let reply = r#"{"TooFast": 1}"#;
+        assert!(matches!(
+            serde_json::from_str::<Response<PlaceBetReply>>(reply)
+                .unwrap()
+                .into_data(),
+            Err(BetError::RateLimited(None))
+        ));
+    }
+}
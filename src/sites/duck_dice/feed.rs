@@ -0,0 +1,152 @@
+//! Passive collection of rolls from DuckDice's public feed of everyone's bets.
+//!
+//! The feed streams bets of all players as they settle. Each one is looked up for the
+//! seeds it was rolled with, and bets are paired with the next bet of the same seed
+//! chain, so the rows written match those recorded from our own bets without placing
+//! any.
+
+use std::collections::HashMap;
+
+use futures_util::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::sites::duck_dice::{Bet, BetJson, BetSeed};
+use crate::sites::{BetError, BetResult};
+
+/// Feed of all bets placed on DuckDice.
+pub const FEED_URL: &str = "wss://duckdice.io/ws/bets";
+
+/// Seed chains followed at once; all are dropped when more appear, which only costs
+/// the row of each chain's last bet.
+const MAX_CHAINS: usize = 10_000;
+
+type Stream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Connection to the bet feed.
+pub struct BetFeed {
+    stream: Stream,
+}
+
+impl BetFeed {
+    pub async fn connect(url: &str) -> Result<Self, BetError> {
+        let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+
+        Ok(Self { stream })
+    }
+
+    /// Waits for the next message carrying bets; `None` once the feed closed.
+    pub async fn next_bets(&mut self) -> Result<Option<Vec<BetJson>>, BetError> {
+        while let Some(message) = self.stream.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Ok(None),
+                // Pings are answered by the stream itself.
+                _ => continue,
+            };
+            let bets = parse_bets(&text);
+            if !bets.is_empty() {
+                return Ok(Some(bets));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Bets carried by a feed message: a bet, a list of bets, or either under a `data` or
+/// `bet` field. Anything else, such as subscription acknowledgements, carries none.
+pub fn parse_bets(message: &str) -> Vec<BetJson> {
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(message) else {
+        return Vec::new();
+    };
+    for field in ["data", "bet"] {
+        if let Some(inner) = value.get_mut(field) {
+            value = inner.take();
+            break;
+        }
+    }
+
+    match value {
+        serde_json::Value::Array(values) => values
+            .into_iter()
+            .filter_map(|value| serde_json::from_value(value).ok())
+            .collect(),
+        value => serde_json::from_value(value).into_iter().collect(),
+    }
+}
+
+/// Last bet seen on each seed chain, waiting for the bet that follows it.
+#[derive(Debug, Default)]
+pub struct SeedChains {
+    last_bets: HashMap<String, BetResult>,
+}
+
+impl SeedChains {
+    /// Adds `bet`, rolled with `seed`. Returns the previous bet of its chain together
+    /// with this one when it directly follows it; a gap in the nonces means bets were
+    /// missed, and the previous bet is dropped.
+    pub fn push(&mut self, bet: BetJson, seed: BetSeed) -> Option<(BetResult, BetResult)> {
+        let mut bet: Bet = bet.into();
+        bet.previous_hash = seed.server_seed_hash.clone();
+        bet.hash = seed.server_seed_hash.clone();
+        let mut bet: BetResult = bet.into();
+        bet.client_seed = seed.client_seed;
+
+        if self.last_bets.len() >= MAX_CHAINS && !self.last_bets.contains_key(&bet.hash_next_roll) {
+            self.last_bets.clear();
+        }
+
+        let previous = self
+            .last_bets
+            .insert(bet.hash_next_roll.clone(), bet.clone())?;
+        bet.follows(&previous).then_some((previous, bet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bet_json(hash: &str, number: u32, nonce: u64) -> String {
+        format!(
+            r#"{{"hash":"{hash}","symbol":"BTC","choice":">","result":true,"number":{number},"chance":49.5,"payout":2.0,"betAmount":"0.0001","winAmount":"0.0002","profit":"0.0001","mined":null,"nonce":{nonce},"created":1,"gameMode":"main"}}"#
+        )
+    }
+
+    fn seed(server_seed_hash: &str) -> BetSeed {
+        BetSeed {
+            server_seed_hash: server_seed_hash.to_string(),
+            client_seed: "client".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parses_bets_in_any_envelope() {
+        let bet = bet_json("a", 1234, 7);
+
+        assert_eq!(parse_bets(&bet).len(), 1);
+        assert_eq!(parse_bets(&format!(r#"{{"data":[{bet},{bet}]}}"#)).len(), 2);
+        assert_eq!(
+            parse_bets(&format!(r#"{{"event":"bet","bet":{bet}}}"#)).len(),
+            1
+        );
+        assert!(parse_bets(r#"{"event":"subscribed"}"#).is_empty());
+        assert!(parse_bets("not json").is_empty());
+    }
+
+    #[test]
+    fn test_pairs_consecutive_bets_of_a_seed_chain() {
+        let mut chains = SeedChains::default();
+        let bet = |number, nonce| parse_bets(&bet_json("h", number, nonce)).remove(0);
+
+        assert!(chains.push(bet(1000, 1), seed("chain")).is_none());
+        assert!(chains.push(bet(9000, 1), seed("other")).is_none());
+        let (current, next) = chains.push(bet(2000, 2), seed("chain")).unwrap();
+        assert_eq!((current.number.value(), next.number.value()), (1000, 2000));
+        assert_eq!(next.client_seed, "client");
+
+        // Nonce 4 does not follow 2, so bet 3 was missed.
+        assert!(chains.push(bet(3000, 4), seed("chain")).is_none());
+    }
+}
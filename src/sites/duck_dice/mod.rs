@@ -1,7 +1,8 @@
-use std::time::Duration;
+pub mod feed;
+
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use rand::Rng;
 use reqwest::header::{
     AUTHORIZATION, SERVER, STRICT_TRANSPORT_SECURITY, USER_AGENT, X_CONTENT_TYPE_OPTIONS,
     X_FRAME_OPTIONS,
@@ -14,13 +15,18 @@ use reqwest::{
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::config::{SiteConfig, TomlStrategies};
+use crate::config::{BalanceSource, BetSizingConfig, SiteConfig, TomlStrategies, WarmupMode};
 use crate::currency::Currency;
 use crate::sites::fake_test::{duckdice_fake_bet, reset_server_seed};
-use crate::sites::{BetError, BetResult, Site, Sites};
-use crate::strategies::Strategy;
-
-const API_KEY: &str = "";
+use crate::sites::limiter::RequestLimiter;
+use crate::sites::seeds::{RotationPolicy, SeedManager};
+use crate::sites::signing::{json_body, RequestSigner};
+use crate::sites::{
+    check_funds, client_builder, effective_min_bet, floor_chance, parse_balance,
+    parse_balance_or_default, parse_or_default, proxied_client, push_history, retry_after,
+    BetError, BetId, BetOutcome, BetResult, BetRule, Capabilities, Site, Sites,
+};
+use crate::strategies::{take_win_target, Strategy};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Jackpot {
@@ -88,14 +94,10 @@ impl From<BetJson> for Bet {
             number: value.number,
             chance: value.chance,
             payout: value.payout,
-            bet_amount: value.bet_amount.parse::<f32>().unwrap_or(0.),
-            win_amount: value.win_amount.parse::<f32>().unwrap_or(0.),
-            profit: value.profit.parse::<f32>().unwrap_or(0.),
-            mined: value
-                .mined
-                .unwrap_or("0.0".to_string())
-                .parse::<f32>()
-                .unwrap_or(0.),
+            bet_amount: parse_or_default(&value.bet_amount, "bet_amount"),
+            win_amount: parse_or_default(&value.win_amount, "win_amount"),
+            profit: parse_or_default(&value.profit, "profit"),
+            mined: parse_or_default(value.mined.as_deref().unwrap_or("0.0"), "mined"),
             nonce: value.nonce,
             created: value.created,
             game_mode: value.game_mode.clone(),
@@ -103,6 +105,25 @@ impl From<BetJson> for Bet {
     }
 }
 
+/// A page of the account's most recent bets, newest first.
+#[derive(Debug, Deserialize)]
+pub struct BetsPageJson {
+    pub bets: Vec<BetJson>,
+}
+
+/// Seeds a bet was rolled with, as `/api/bet/{hash}` reports them.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BetSeed {
+    pub server_seed_hash: String,
+    pub client_seed: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BetDetailsJson {
+    pub seed: BetSeed,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AbsoluteLevel {
@@ -153,9 +174,9 @@ impl From<UserJson> for User {
             nonce: value.nonce,
             wins: value.wins,
             luck: value.luck,
-            balance: value.balance.parse::<f32>().unwrap_or(0.),
-            profit: value.profit.parse::<f32>().unwrap_or(0.),
-            volume: value.volume.parse::<f32>().unwrap_or(0.),
+            balance: parse_balance_or_default(&value.balance, "balance"),
+            profit: parse_or_default(&value.profit, "profit"),
+            volume: parse_or_default(&value.volume, "volume"),
             absolute_level: value.absolute_level.clone(),
         }
     }
@@ -211,7 +232,7 @@ impl From<LastDepositJson> for LastDeposit {
         Self {
             created_at: value.created_at,
             currency: value.currency.clone(),
-            amount: value.amount.parse::<f32>().unwrap_or(0.),
+            amount: parse_or_default(&value.amount, "amount"),
         }
     }
 }
@@ -233,7 +254,7 @@ impl From<&WageredJson> for Wagered {
     fn from(value: &WageredJson) -> Self {
         Self {
             currency: value.currency.clone(),
-            amount: value.amount.parse::<f32>().unwrap_or(0.),
+            amount: parse_or_default(&value.amount, "amount"),
         }
     }
 }
@@ -246,6 +267,17 @@ pub struct Balance {
     pub affiliate: Option<String>,
 }
 
+impl Balance {
+    /// Returns the amount held in `source`, if the site reported one.
+    pub fn amount(&self, source: BalanceSource) -> Option<String> {
+        match source {
+            BalanceSource::Main => self.main.clone(),
+            BalanceSource::Faucet => self.faucet.clone(),
+            BalanceSource::Affiliate => self.affiliate.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WageringBonusJson {
     pub name: String,
@@ -275,7 +307,7 @@ impl From<&WageringBonusJson> for WageringBonus {
             hash: value.hash.clone(),
             status: value.status.clone(),
             symbol: value.symbol.clone(),
-            margin: value.margin.parse::<f32>().unwrap_or(0.),
+            margin: parse_or_default(&value.margin, "margin"),
         }
     }
 }
@@ -344,12 +376,39 @@ impl From<UserInfoJson> for UserInfo {
     }
 }
 
+/// Longest `WarmupMode::Feed` waits for the feed to fill the history.
+const FEED_WARMUP_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Host every API endpoint is under.
+pub const API_URL: &str = "https://duckdice.io";
+
+/// Fetches the seeds bet `hash` was rolled with from the API at `api_url`, within the
+/// `limiter`'s budget. Any bet can be looked up, not only the account's own.
+pub async fn fetch_bet_seed(
+    limiter: &RequestLimiter,
+    client: &reqwest::Client,
+    api_url: &str,
+    hash: &str,
+) -> Result<BetSeed, BetError> {
+    let res: serde_json::Value = limiter
+        .send(client.get(format!("{api_url}/api/bet/{hash}")))
+        .await?
+        .json()
+        .await?;
+    let res: BetDetailsJson = serde_json::from_value(res)?;
+
+    Ok(res.seed)
+}
+
 pub struct DuckDiceIo {
     pub client: reqwest::Client,
     pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
     pub strategy: Box<dyn Strategy>,
     history: Vec<BetResult>,
     history_size: usize,
+    max_history: usize,
     current_bet: f32,
     chance: f32,
     balance: f32,
@@ -361,16 +420,50 @@ pub struct DuckDiceIo {
     client_seed: String,
     default_headers: HeaderMap,
     currency: Currency,
-    faucet: bool,
+    balance_source: BalanceSource,
     initialized_hash: bool,
     wins: u32,
     losses: u32,
     seed_profit: f32,
-    api_key: String,
+    /// Client seed rotation and the nonces each seed rolled.
+    seeds: SeedManager,
+    warmup: WarmupMode,
+    /// Feed of everyone's bets read by `WarmupMode::Feed`.
+    feed_url: String,
+    /// Host the API requests go to; a local server in tests.
+    api_url: String,
+    /// API keys of every account bets may be placed from; one is active at a time.
+    api_keys: Vec<String>,
+    active_key: usize,
+    /// Last known balance of each account, in the order of `api_keys`. All are fetched at
+    /// login; after that only the active account's follows its bets and is fetched again
+    /// when rotated to, so the others show what they held when last used.
+    key_balances: Vec<f32>,
     use_site_balance: bool,
     balance_modifier: f32,
     use_fake_betting: bool,
     tle_hash: Option<String>,
+    reset_on_win_target: bool,
+    bank_profit: bool,
+    banked: f32,
+    /// Account banked profit is tipped to, if any.
+    tip_to: Option<String>,
+    /// Share of each banked win target tipped to `tip_to`.
+    tip_share: f32,
+    /// Banked profit still to be tipped; a failed tip is retried before the next bet.
+    pending_tip: f32,
+    min_chance: f32,
+    limiter: RequestLimiter,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    signer: Option<RequestSigner>,
+    balance_fetched_at: Option<Instant>,
+    max_balance_staleness: Option<Duration>,
+    symbol: Option<String>,
+    /// Configured floor for bets, used when above the currency's site minimum.
+    min_bet_override: Option<f32>,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
 }
 
 impl Default for DuckDiceIo {
@@ -380,6 +473,7 @@ impl Default for DuckDiceIo {
         Self {
             client: reqwest::Client::new(),
             rolls: 0,
+            wagered: 0.,
             strategy: Box::new(
                 // crate::strategies::blaks_runner::BlaksRunner5_0::default()
                 // crate::strategies::my_strategy::MyStrat::default()
@@ -389,7 +483,8 @@ impl Default for DuckDiceIo {
                     .with_initial_bet(currency.get_min_bet(Sites::DuckDiceIo)),
             ),
             history: Vec::new(),
-            history_size: 10,
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
             current_bet: currency.get_min_bet(Sites::DuckDiceIo),
             chance: 2.,
             balance: 3.,
@@ -401,20 +496,483 @@ impl Default for DuckDiceIo {
             client_seed: String::new(),
             default_headers: HeaderMap::new(),
             currency,
-            faucet: true,
+            balance_source: BalanceSource::Faucet,
             initialized_hash: false,
             wins: 0,
             losses: 0,
             seed_profit: 0.,
-            api_key: String::new(),
+            seeds: SeedManager::default(),
+            warmup: WarmupMode::default(),
+            feed_url: feed::FEED_URL.to_string(),
+            api_url: API_URL.to_string(),
+            api_keys: vec![String::new()],
+            active_key: 0,
+            key_balances: vec![0.],
             use_site_balance: true,
             balance_modifier: 1.,
             use_fake_betting: false,
             tle_hash: None,
+            reset_on_win_target: false,
+            bank_profit: false,
+            banked: 0.,
+            tip_to: None,
+            tip_share: 1.,
+            pending_tip: 0.,
+            min_chance: 2.,
+            limiter: RequestLimiter::default(),
+            proxy: None,
+            signer: None,
+            balance_fetched_at: None,
+            max_balance_staleness: None,
+            symbol: None,
+            min_bet_override: None,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
         }
     }
 }
 
+/// Finds the DuckDice balance symbol matching `symbol`, ignoring case.
+///
+/// DuckDice names some currencies differently from our `Currency` names, so a symbol
+/// without a balance entry is reported together with the symbols the account has.
+pub fn resolve_symbol(balances: &[Balance], symbol: &str) -> Result<String, BetError> {
+    balances
+        .iter()
+        .find(|balance| balance.currency.eq_ignore_ascii_case(symbol))
+        .map(|balance| balance.currency.clone())
+        .ok_or_else(|| {
+            let available = balances
+                .iter()
+                .map(|balance| balance.currency.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            BetError::ConfigError(format!(
+                "DuckDice has no balance for symbol {symbol}; available symbols: {available} \
+                 (map the currency to one of them under [duck_dice.symbols])"
+            ))
+        })
+}
+
+impl DuckDiceIo {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::DuckDiceIo),
+            self.min_bet_override,
+        )
+    }
+
+    /// Symbol sent to DuckDice for the configured currency.
+    fn symbol(&self) -> String {
+        self.symbol
+            .clone()
+            .unwrap_or_else(|| self.currency.to_string())
+    }
+
+    /// API key of the account bets are currently placed from.
+    fn api_key(&self) -> &str {
+        &self.api_keys[self.active_key]
+    }
+
+    /// Reads the bet feed at `url` for `WarmupMode::Feed`.
+    pub fn with_feed_url(mut self, url: String) -> Self {
+        self.feed_url = url;
+
+        self
+    }
+
+    /// Tips `share` of every banked win target to the account `to`. Only profit banked
+    /// through `with_profit_target(true)` is tipped.
+    pub fn with_tips(mut self, to: String, share: f32) -> Self {
+        self.tip_to = Some(to);
+        self.tip_share = share;
+
+        self
+    }
+
+    /// Tips `amount` of the betting currency from the active account to `username`.
+    pub async fn tip(&mut self, username: &str, amount: f32) -> Result<(), BetError> {
+        let tip_url = Url::parse_with_params(
+            &format!("{}/api/bot/tip", self.api_url),
+            &[("api_key", self.api_key())],
+        )
+        .expect("Failed to parse tip URL");
+        self.limiter
+            .send(json_body(
+                self.signer.as_ref(),
+                self.client.post(tip_url),
+                &json!({
+                    "symbol": self.symbol(),
+                    "amount": format!("{amount:.8}"),
+                    "username": username,
+                }),
+            )?)
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Sends the tip owed for banked win targets, keeping it owed when the tip fails.
+    async fn send_pending_tip(&mut self) {
+        let Some(to) = self.tip_to.clone() else {
+            return;
+        };
+        if self.pending_tip <= 0. {
+            return;
+        }
+
+        let amount = self.pending_tip;
+        match self.tip(&to, amount).await {
+            Ok(()) => {
+                self.pending_tip = 0.;
+                self.banked -= amount;
+                println!("[TIP] Sent {:0>.8} {} to {}", amount, self.currency, to);
+            }
+            Err(e) => log::warn!("Failed to tip {amount:.8} {} to {to}: {e}", self.currency),
+        }
+    }
+
+    /// Fetches the info of the active account, recording when its balance was last
+    /// known to be current.
+    async fn fetch_user_info(&mut self) -> Result<UserInfo, BetError> {
+        let res = self.fetch_user_info_for(self.active_key).await?;
+        self.balance_fetched_at = Some(Instant::now());
+
+        Ok(res)
+    }
+
+    async fn fetch_user_info_for(&mut self, key_index: usize) -> Result<UserInfo, BetError> {
+        let user_info_url = Url::parse_with_params(
+            &format!("{}/api/bot/user-info", self.api_url),
+            &[("api_key", &self.api_keys[key_index])],
+        )
+        .expect("Failed to parse user_info URL");
+        let res: serde_json::Value = self
+            .limiter
+            .send(self.client.get(user_info_url))
+            .await?
+            .json()
+            .await?;
+        let res: UserInfoJson = serde_json::from_value(res)?;
+
+        Ok(res.into())
+    }
+
+    /// Amount held by an account in the betting currency and balance source.
+    fn account_balance(&self, res: &UserInfo) -> Option<f32> {
+        res.balances
+            .iter()
+            .find(|balance| balance.currency == self.symbol())
+            .and_then(|balance| balance.amount(self.balance_source))
+            .map(|amount| parse_balance_or_default(&amount, "balance"))
+    }
+
+    /// Fetches the seeds bet `hash` was rolled with.
+    async fn fetch_bet_seed(&mut self, hash: &str) -> Result<BetSeed, BetError> {
+        fetch_bet_seed(&self.limiter, &self.client, &self.api_url, hash).await
+    }
+
+    /// Fills the history with the account's last `history_size` bets in the betting
+    /// currency, so the first prediction needs no warmup bets.
+    ///
+    /// The bet list leaves out seeds, so each bet's seeds are fetched like those of a
+    /// live bet and the bets, each with the client seed it was rolled with, are encoded
+    /// exactly as live ones.
+    async fn prime_history(&mut self) -> Result<(), BetError> {
+        let bets_url = Url::parse_with_params(
+            &format!("{}/api/bot/bets", self.api_url),
+            &[
+                ("api_key", self.api_key().to_string()),
+                ("symbol", self.symbol()),
+                ("limit", self.history_size.to_string()),
+            ],
+        )
+        .expect("Failed to parse bets URL");
+        let res: serde_json::Value = self
+            .limiter
+            .send(self.client.get(bets_url))
+            .await?
+            .json()
+            .await?;
+        let page: BetsPageJson = serde_json::from_value(res)?;
+
+        let mut bets = Vec::with_capacity(page.bets.len());
+        let mut previous_hash = None;
+        for bet in page.bets.into_iter().rev() {
+            let mut bet: Bet = bet.into();
+            let seed = self.fetch_bet_seed(&bet.hash).await?;
+            bet.previous_hash = seed.server_seed_hash.clone();
+            bet.hash = seed.server_seed_hash;
+            previous_hash = Some(bet.hash.clone());
+            let mut bet_result: BetResult = bet.into();
+            // The history may span seed rotations, so each bet keeps its own seed.
+            bet_result.client_seed = seed.client_seed;
+            bets.push(bet_result);
+        }
+
+        if let Some(previous_hash) = previous_hash {
+            self.previous_hash = previous_hash;
+        }
+        for bet_result in bets {
+            push_history(&mut self.history, bet_result, self.max_history);
+        }
+        println!(
+            "[HISTORY] Primed {}/{} bets from the account history",
+            self.history.len().min(self.history_size),
+            self.history_size
+        );
+
+        Ok(())
+    }
+
+    /// Fills the history with rolls from the feed of everyone's bets, so the first
+    /// prediction needs no warmup bets.
+    ///
+    /// Only bets that directly follow another bet of their seed chain are taken, so
+    /// each roll carries the seeds it was rolled with. Gives up after
+    /// `FEED_WARMUP_TIMEOUT`, keeping the rolls collected so far.
+    async fn prime_from_feed(&mut self) -> Result<(), BetError> {
+        let mut bet_feed = feed::BetFeed::connect(&self.feed_url).await?;
+        let mut chains = feed::SeedChains::default();
+        let mut primed = 0;
+
+        let res = tokio::time::timeout(FEED_WARMUP_TIMEOUT, async {
+            while primed < self.history_size {
+                let Some(bets) = bet_feed.next_bets().await? else {
+                    return Err(BetError::EmptyReply);
+                };
+                for bet in bets {
+                    let seed = self.fetch_bet_seed(&bet.hash).await?;
+                    if let Some((_, next)) = chains.push(bet, seed) {
+                        push_history(&mut self.history, next, self.max_history);
+                        primed += 1;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+        .await;
+        println!(
+            "[HISTORY] Primed {}/{} bets from the bet feed",
+            primed.min(self.history_size),
+            self.history_size
+        );
+
+        res.unwrap_or_else(|_| {
+            log::warn!("The bet feed gave too few rolls within {FEED_WARMUP_TIMEOUT:?}");
+
+            Ok(())
+        })
+    }
+
+    /// Sets a fresh random client seed on the site. The server seed is rotated with it,
+    /// so the hash chain is restarted from the next bet, which records the new seed.
+    async fn randomize_seed(&mut self) -> Result<(), BetError> {
+        let randomize_url = Url::parse_with_params(
+            &format!("{}/api/randomize", self.api_url),
+            &[("api_key", self.api_key())],
+        )
+        .expect("Failed to parse randomize URL");
+
+        self.client_seed = self.seeds.rotate();
+        let res_randomize = self
+            .limiter
+            .send(json_body(
+                self.signer.as_ref(),
+                self.client.post(randomize_url),
+                &json!({
+                    "clientSeed": self.client_seed.clone(),
+                }),
+            )?)
+            .await?;
+
+        if let Some(delay) = retry_after(res_randomize.headers()) {
+            tokio::time::sleep(delay).await;
+        }
+        self.initialized_hash = false;
+
+        Ok(())
+    }
+
+    /// Client sending the current default headers, through the proxy if one is set.
+    fn build_client(&self) -> Result<reqwest::Client, BetError> {
+        Ok(client_builder(self.proxy.as_ref())
+            .default_headers(self.default_headers.clone())
+            .user_agent("DuckDiceBot/1.0.0")
+            .redirect(Policy::limited(200))
+            .build()?)
+    }
+
+    /// Withdrawal request from the active account, shaped like the bot API's
+    /// `POST /api/bot/withdraw`: the same `api_key` query and `symbol`/`amount`
+    /// body as `tip`, with the destination `address` in place of the `username`.
+    fn withdraw_request(
+        &self,
+        amount: f32,
+        address: &str,
+    ) -> Result<reqwest::RequestBuilder, serde_json::Error> {
+        let withdraw_url = Url::parse_with_params(
+            &format!("{}/api/bot/withdraw", self.api_url),
+            &[("api_key", self.api_key())],
+        )
+        .expect("Failed to parse withdraw URL");
+
+        json_body(
+            self.signer.as_ref(),
+            self.client.post(withdraw_url),
+            &json!({
+                "symbol": self.symbol(),
+                "amount": format!("{amount:.8}"),
+                "address": address,
+            }),
+        )
+    }
+
+    /// Restarts the strategy on a fresh client seed from the balance the account holds,
+    /// once the betting balance can no longer cover a bet.
+    async fn reset_to_site_balance(&mut self) -> Result<(), BetError> {
+        self.wins = 0;
+        self.losses = 0;
+        self.seed_profit = 0.;
+        self.randomize_seed().await?;
+        self.initial_balance = self.refresh_balance().await?;
+        println!("[FAIL] Resetting {:0>.8}", self.site_balance);
+        self.strategy.reset();
+
+        Ok(())
+    }
+
+    /// Takes the strategy's next bet and applies the chance floor, the warmup and the
+    /// minimum bet to it, returning whether to bet high.
+    fn next_bet(&mut self, prediction: f32, confidence: f32) -> bool {
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        self.last_rule = BetRule::Normal;
+
+        self.chance = floor_chance(chance, self.min_chance);
+        if self.chance != chance {
+            self.last_rule = BetRule::ChanceFloored;
+        }
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            self.chance = 50.;
+            self.last_rule = BetRule::Warmup;
+        }
+
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        high
+    }
+
+    /// Bet request for the current bet, placed from the active account.
+    fn bet_request(&self, high: bool) -> Result<reqwest::RequestBuilder, serde_json::Error> {
+        let bet_url = Url::parse_with_params(
+            &format!("{}/api/play", self.api_url),
+            &[("api_key", self.api_key())],
+        )
+        .expect("Failed to parse do_bet URL");
+
+        json_body(
+            self.signer.as_ref(),
+            self.client.post(bet_url),
+            &BetMake {
+                symbol: self.symbol(),
+                chance: format!("{:.2}", self.chance).parse::<f32>().unwrap_or(0.),
+                is_high: high,
+                amount: format!("{:.8}", self.current_bet)
+                    .parse::<f32>()
+                    .unwrap_or(0.),
+                user_wagering_bonus_hash: None, /*Some("97a8d827da".to_string()),*/
+                // Bets only distinguish faucet funds; main and affiliate bets omit the flag.
+                faucet: if self.balance_source == BalanceSource::Faucet {
+                    Some(true)
+                } else {
+                    None
+                },
+                tle_hash: if self.balance_source == BalanceSource::Faucet {
+                    None
+                } else {
+                    self.tle_hash.clone()
+                },
+            },
+        )
+    }
+
+    /// Switches to the next account after the active one was rate limited.
+    ///
+    /// Every account has its own seed chain, so the hash chain is restarted and the
+    /// bankroll is reloaded from the new account.
+    async fn rotate_key(&mut self) -> Result<(), BetError> {
+        self.active_key = (self.active_key + 1) % self.api_keys.len();
+        self.initialized_hash = false;
+        println!(
+            "[KEYS] Rate limited, switching to account {}/{}",
+            self.active_key + 1,
+            self.api_keys.len()
+        );
+
+        let res = self.fetch_user_info().await?;
+        if let Some(val) = self.account_balance(&res) {
+            self.key_balances[self.active_key] = val;
+            self.site_balance = val;
+            self.balance = val * self.balance_modifier;
+            self.initial_balance = val * self.balance_modifier;
+            self.strategy.set_balance(self.balance);
+        }
+
+        Ok(())
+    }
+
+    /// Refreshes the site balance once it is older than `max_balance_staleness`, and caps
+    /// the betting balance and the strategy at the share of it bets may use.
+    ///
+    /// Returns `false` when the balance is stale and could not be refreshed, in which
+    /// case the caller must not bet more than the minimum.
+    async fn ensure_fresh_balance(&mut self) -> bool {
+        let Some(max_staleness) = self.max_balance_staleness else {
+            return true;
+        };
+        if self
+            .balance_fetched_at
+            .is_some_and(|fetched_at| fetched_at.elapsed() <= max_staleness)
+        {
+            return true;
+        }
+
+        if let Err(e) = self.refresh_site_balance().await {
+            println!("[STALE] Balance refresh failed: {e}");
+            return false;
+        }
+        // The account may hold less than tracked locally, e.g. after a withdrawal.
+        let available = self.site_balance * self.balance_modifier;
+        if self.balance > available {
+            self.balance = available;
+            self.strategy.set_balance(available);
+        }
+
+        true
+    }
+
+    /// Fetches the balance bets are funded from, as configured by `balance_source`.
+    async fn refresh_site_balance(&mut self) -> Result<f32, BetError> {
+        let res = self.fetch_user_info().await?;
+        for balance in &res.balances {
+            if balance.currency == self.symbol() {
+                if let Some(amount) = &balance.amount(self.balance_source) {
+                    self.site_balance =
+                        parse_balance(amount).map_or(self.site_balance, |val| val as f32);
+                }
+            }
+        }
+
+        Ok(self.site_balance)
+    }
+}
+
 #[async_trait]
 impl Site for DuckDiceIo {
     async fn login(&mut self) -> Result<(), BetError> {
@@ -434,30 +992,35 @@ impl Site for DuckDiceIo {
             .insert(SERVER, "cloudflare".parse().unwrap());
         self.default_headers.insert(USER_AGENT, "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36".parse().unwrap());
 
-        self.client = reqwest::Client::builder()
-            .default_headers(self.default_headers.clone())
-            .user_agent("DuckDiceBot/1.0.0")
-            .redirect(Policy::limited(200))
-            .build()?;
+        self.client = self.build_client()?;
 
         if self.use_site_balance {
-            let user_info_url = Url::parse(&format!(
-                "https://duckdice.io/api/bot/user-info?api_key={API_KEY}",
-            ))
-            .expect("Failed to parse user_info URL");
-            let res: serde_json::Value =
-                self.client.get(user_info_url).send().await?.json().await?;
-            let res: UserInfoJson = serde_json::from_value(res).unwrap();
-            let res: UserInfo = res.into();
+            let res = self.fetch_user_info().await?;
+            self.symbol = Some(resolve_symbol(&res.balances, &self.symbol())?);
+
+            let active_balance = self.account_balance(&res).unwrap_or(0.);
+            self.key_balances = vec![0.; self.api_keys.len()];
+            for key_index in 0..self.api_keys.len() {
+                self.key_balances[key_index] = if key_index == self.active_key {
+                    active_balance
+                } else {
+                    let account = self.fetch_user_info_for(key_index).await?;
+                    self.account_balance(&account).unwrap_or(0.)
+                };
+            }
+            if self.api_keys.len() > 1 {
+                println!(
+                    "[KEYS] {} accounts, {:0>.8} {} in total",
+                    self.api_keys.len(),
+                    self.key_balances.iter().sum::<f32>(),
+                    self.currency
+                );
+            }
 
             for balance in &res.balances {
-                if balance.currency == self.currency.to_string().as_str() {
-                    if let Some(main) = &if self.faucet {
-                        balance.faucet.clone()
-                    } else {
-                        balance.main.clone()
-                    } {
-                        let val = main.parse::<f32>().unwrap_or(0.);
+                if balance.currency == self.symbol() {
+                    if let Some(main) = &balance.amount(self.balance_source) {
+                        let val: f32 = parse_balance_or_default(main, "balance");
                         self.strategy.set_balance(val * self.balance_modifier);
                         self.site_balance = val;
                         self.balance = val * self.balance_modifier;
@@ -473,10 +1036,26 @@ impl Site for DuckDiceIo {
             self.initial_balance = self.offline_balance * self.balance_modifier;
         }
 
+        if !self.use_fake_betting {
+            let primed = match self.warmup {
+                WarmupMode::History => self.prime_history().await,
+                WarmupMode::Feed => self.prime_from_feed().await,
+                WarmupMode::MinBet => Ok(()),
+            };
+            if let Err(e) = primed {
+                log::warn!("Could not prime the history, warming up with minimum bets: {e}");
+            }
+        }
+
+        if self.seeds.policy().per_session && !self.use_fake_betting {
+            log::info!("Randomizing the client seed for the new session");
+            self.randomize_seed().await?;
+        }
+
         Ok(())
     }
 
-    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetResult, BetError> {
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
         if self.balance >= self.initial_balance * 10. {
             if self.use_site_balance {
                 println!("[WIN] Resetting {:0>.8}", self.site_balance);
@@ -490,23 +1069,12 @@ impl Site for DuckDiceIo {
             self.strategy.reset();
 
             if self.profit > 0. && self.use_site_balance {
-                let user_info_url = Url::parse(&format!(
-                    "https://duckdice.io/api/bot/user-info?api_key={API_KEY}",
-                ))
-                .expect("Failed to parse user_info URL");
-                let res: serde_json::Value =
-                    self.client.get(user_info_url).send().await?.json().await?;
-                let res: UserInfoJson = serde_json::from_value(res).unwrap();
-                let res: UserInfo = res.into();
+                let res = self.fetch_user_info().await?;
 
                 for balance in &res.balances {
-                    if balance.currency == self.currency.to_string().as_str() {
-                        if let Some(main) = &if self.faucet {
-                            balance.faucet.clone()
-                        } else {
-                            balance.main.clone()
-                        } {
-                            let val = main.parse::<f32>().unwrap_or(0.);
+                    if balance.currency == self.symbol() {
+                        if let Some(main) = &balance.amount(self.balance_source) {
+                            let val: f32 = parse_balance_or_default(main, "balance");
                             self.strategy.set_balance(val * self.balance_modifier);
                             self.site_balance = val;
                             self.balance = val * self.balance_modifier;
@@ -527,65 +1095,7 @@ impl Site for DuckDiceIo {
         }
 
         if self.balance - self.current_bet <= 0. && self.use_site_balance {
-            println!("[FAIL] Resetting {:0>.8}", self.site_balance);
-            self.balance = self.site_balance * self.balance_modifier;
-            self.initial_balance = self.site_balance * self.balance_modifier;
-            self.wins = 0;
-            self.losses = 0;
-            self.seed_profit = 0.;
-            self.strategy.reset();
-            let randomize_url = Url::parse_with_params(
-                "https://duckdice.io/api/randomize",
-                &[("api_key", API_KEY)],
-            )
-            .expect("Failed to parse randomize URL");
-
-            self.client_seed = rand::rng()
-                .sample_iter(rand::distr::Alphabetic)
-                .take(30)
-                .map(char::from)
-                .collect();
-            let res_randomize = self
-                .client
-                .post(randomize_url)
-                .json(&json!({
-                    "clientSeed": self.client_seed.clone(),
-                }))
-                .send()
-                .await?;
-
-            if let Some(retry_after) = res_randomize.headers().get("retry-after") {
-                let _ = tokio::time::sleep(Duration::from_secs(
-                    retry_after.to_str().unwrap().parse::<u64>().unwrap(),
-                ))
-                .await;
-            }
-            self.initialized_hash = false;
-            let user_info_url = Url::parse(&format!(
-                "https://duckdice.io/api/bot/user-info?api_key={API_KEY}",
-            ))
-            .expect("Failed to parse user_info URL");
-            let res: serde_json::Value =
-                self.client.get(user_info_url).send().await?.json().await?;
-            let res: UserInfoJson = serde_json::from_value(res).unwrap();
-            let res: UserInfo = res.into();
-
-            for balance in &res.balances {
-                if balance.currency == self.currency.to_string().as_str() {
-                    if let Some(main) = &if self.faucet {
-                        balance.faucet.clone()
-                    } else {
-                        balance.main.clone()
-                    } {
-                        let _val = main.parse::<f32>().unwrap_or(0.);
-                        self.strategy
-                            .set_balance(self.site_balance * self.balance_modifier);
-                        self.balance = self.site_balance * self.balance_modifier;
-                        self.initial_balance = self.site_balance * self.balance_modifier;
-                        self.strategy.reset();
-                    }
-                }
-            }
+            self.reset_to_site_balance().await?;
         } else if self.balance - self.current_bet <= 0. && !self.use_site_balance {
             println!("[FAIL] Resetting {:0>.8}", self.offline_balance);
             reset_server_seed();
@@ -598,137 +1108,74 @@ impl Site for DuckDiceIo {
         }
 
         self.rolls += 1;
-        let next_bet_data = self.strategy.get_next_bet(prediction, confidence);
-        self.current_bet = next_bet_data.0;
-        self.chance = next_bet_data.2;
-        let high = next_bet_data.3;
-
-        self.chance = self.chance.max(2.);
-
-        if self.history.len() < self.history_size {
-            self.current_bet = self.currency.get_min_bet(Sites::DuckDiceIo);
-            self.chance = 50.;
-        }
-
-        self.current_bet = self
-            .current_bet
-            .max(self.currency.get_min_bet(Sites::DuckDiceIo));
-
-        let bet_url =
-            Url::parse_with_params("https://duckdice.io/api/play", &[("api_key", API_KEY)])
-                .expect("Failed to parse do_bet URL");
+        let mut high = self.next_bet(prediction, confidence);
 
         if self.use_fake_betting {
+            if self.current_bet > self.site_balance {
+                self.current_bet = self.min_bet();
+            }
+            check_funds(self.current_bet, self.site_balance)?;
+
             let bet_result = duckdice_fake_bet(
                 high,
                 "BeO2jZRd4nidPz4U40e2G7hT22s9GA",
                 self.current_bet,
-                next_bet_data.1,
+                1. / (self.chance / 100.),
             );
 
-            self.history.push(bet_result.clone().into());
-            if self.history.len() > self.history_size {
-                self.history = self.history[1..].to_vec();
-            }
+            push_history(
+                &mut self.history,
+                bet_result.clone().into(),
+                self.max_history,
+            );
 
-            if self.current_bet > self.site_balance {
-                self.current_bet = self.currency.get_min_bet(Sites::DuckDiceIo);
-            }
+            return Ok(BetOutcome::Resolved(bet_result.into()));
+        }
 
-            if self.current_bet > self.site_balance {
-                panic!("Insufficient Balance");
-            }
+        self.send_pending_tip().await;
 
-            return Ok(bet_result.into());
+        if self.current_bet > self.balance && self.use_site_balance {
+            self.reset_to_site_balance().await?;
+            high = self.next_bet(prediction, confidence);
         }
 
-        if self.current_bet > self.balance && self.use_site_balance {
-            println!("[FAIL] Resetting {:0>.8}", self.site_balance);
-            self.balance = self.site_balance * self.balance_modifier;
-            self.initial_balance = self.site_balance * self.balance_modifier;
-            self.wins = 0;
-            self.losses = 0;
-            self.seed_profit = 0.;
-            self.strategy.reset();
-            let randomize_url = Url::parse_with_params(
-                "https://duckdice.io/api/randomize",
-                &[("api_key", API_KEY)],
-            )
-            .expect("Failed to parse randomize URL");
-
-            self.client_seed = rand::rng()
-                .sample_iter(rand::distr::Alphabetic)
-                .take(30)
-                .map(char::from)
-                .collect();
-            let res_randomize = self
-                .client
-                .post(randomize_url)
-                .json(&json!({
-                    "clientSeed": self.client_seed.clone(),
-                }))
-                .send()
-                .await?;
-
-            if let Some(retry_after) = res_randomize.headers().get("retry-after") {
-                let _ = tokio::time::sleep(Duration::from_secs(
-                    retry_after.to_str().unwrap().parse::<u64>().unwrap(),
-                ))
-                .await;
-            }
-            self.initialized_hash = false;
-            let user_info_url = Url::parse(&format!(
-                "https://duckdice.io/api/bot/user-info?api_key={API_KEY}",
-            ))
-            .expect("Failed to parse user_info URL");
-            let res: serde_json::Value =
-                self.client.get(user_info_url).send().await?.json().await?;
-            let res: UserInfoJson = serde_json::from_value(res).unwrap();
-            let res: UserInfo = res.into();
+        if self.use_site_balance && !self.ensure_fresh_balance().await {
+            let min_bet = self.min_bet();
+            println!(
+                "[STALE] Balance is older than {:?}, betting the minimum {:0>.8}",
+                self.max_balance_staleness.unwrap_or_default(),
+                min_bet
+            );
+            self.current_bet = min_bet;
+            self.last_rule = BetRule::StaleBalance;
+        }
 
-            for balance in &res.balances {
-                if balance.currency == self.currency.to_string().as_str() {
-                    if let Some(main) = &if self.faucet {
-                        balance.faucet.clone()
-                    } else {
-                        balance.main.clone()
-                    } {
-                        let _val = main.parse::<f32>().unwrap_or(0.);
-                        self.strategy
-                            .set_balance(self.site_balance * self.balance_modifier);
-                        self.balance = self.site_balance * self.balance_modifier;
-                        self.initial_balance = self.site_balance * self.balance_modifier;
-                        self.strategy.reset();
+        if self.seeds.rotation_due() {
+            log::info!(
+                "Randomizing the client seed after {} bets",
+                self.seeds.bets_on_seed()
+            );
+            self.randomize_seed().await?;
+        }
+
+        // A refused account hands the bet to the next one until every account was tried.
+        let mut refused = 0;
+        let res = loop {
+            match self.limiter.send(self.bet_request(high)?).await {
+                Err(BetError::RateLimited(retry_after)) => {
+                    refused += 1;
+                    if refused == self.api_keys.len() {
+                        self.rolls -= 1;
+                        return Err(BetError::RateLimited(retry_after));
+                    }
+                    self.rotate_key().await?;
+                    if self.current_bet > self.balance {
+                        self.current_bet = self.min_bet();
                     }
                 }
+                res => break res,
             }
-
-            let next_bet_data = self.strategy.get_next_bet(prediction, confidence);
-            self.current_bet = next_bet_data.0;
-            self.chance = next_bet_data.2;
-            let _high = next_bet_data.3;
-        }
-
-        let res = self
-            .client
-            .post(bet_url)
-            .json(&BetMake {
-                symbol: self.currency.to_string(),
-                chance: format!("{:.2}", self.chance).parse::<f32>().unwrap_or(0.),
-                is_high: high,
-                amount: format!("{:.8}", self.current_bet)
-                    .parse::<f32>()
-                    .unwrap_or(0.),
-                user_wagering_bonus_hash: None, /*Some("97a8d827da".to_string()),*/
-                faucet: if self.faucet { Some(true) } else { None },
-                tle_hash: if self.faucet {
-                    None
-                } else {
-                    self.tle_hash.clone()
-                },
-            })
-            .send()
-            .await;
+        };
 
         match res {
             Ok(res) => {
@@ -738,35 +1185,21 @@ impl Site for DuckDiceIo {
                     self.default_headers
                         .insert("cf-ray", res.headers()["cf-ray"].clone());
 
-                    self.client = reqwest::Client::builder()
-                        .default_headers(self.default_headers.clone())
-                        .user_agent("DuckDiceBot/1.0.0")
-                        .redirect(Policy::limited(200))
-                        .build()?;
+                    self.client = self.build_client()?;
                     return Err(BetError::EmptyReply);
                 }
                 let res: serde_json::Value = res.json().await?;
 
-                let res: BetMakeResponseJson = serde_json::from_value(res).unwrap();
+                let res: BetMakeResponseJson = serde_json::from_value(res)?;
                 let mut res: BetMakeResponse = res.into();
 
                 if !self.initialized_hash {
-                    let res_bet_data = self
-                        .client
-                        .get(format!("https://duckdice.io/api/bet/{}", res.bet.hash))
-                        .send()
-                        .await?;
-                    let res_bet_data: serde_json::Value = res_bet_data.json().await?;
+                    let seed = self.fetch_bet_seed(&res.bet.hash).await?;
 
                     res.bet.previous_hash = self.previous_hash.clone();
-                    self.previous_hash = res_bet_data["seed"]["serverSeedHash"]
-                        .as_str()
-                        .unwrap()
-                        .to_string();
-                    self.client_seed = res_bet_data["seed"]["clientSeed"]
-                        .as_str()
-                        .unwrap()
-                        .to_string();
+                    self.previous_hash = seed.server_seed_hash;
+                    self.client_seed = seed.client_seed;
+                    self.seeds.use_seed(self.client_seed.clone());
                     res.bet.hash = self.previous_hash.clone();
                     self.initialized_hash = true;
                 } else {
@@ -775,44 +1208,96 @@ impl Site for DuckDiceIo {
                 }
 
                 self.default_headers.remove(AUTHORIZATION);
-                self.client = reqwest::Client::builder()
-                    .default_headers(self.default_headers.clone())
-                    .user_agent("DuckDiceBot/1.0.0")
-                    .redirect(Policy::limited(200))
-                    .build()?;
-
-                self.history.push(res.clone().into());
-                if self.history.len() > self.history_size {
-                    self.history = self.history[1..].to_vec();
-                }
+                self.client = self.build_client()?;
 
+                self.seeds.on_bet(res.bet.nonce);
                 let mut bet_result: BetResult = res.into();
                 bet_result.client_seed = self.client_seed.clone();
+                push_history(&mut self.history, bet_result.clone(), self.max_history);
 
-                Ok(bet_result)
+                Ok(BetOutcome::Resolved(bet_result))
             }
             Err(_) => Err(BetError::EmptyReply),
         }
     }
 
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // DuckDice bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, amount: f32, address: &str) -> Result<(), BetError> {
+        if self.use_fake_betting {
+            return Err(BetError::Unsupported(
+                "withdrawals while fake betting".to_string(),
+            ));
+        }
+
+        self.limiter
+            .send(self.withdraw_request(amount, address)?)
+            .await?
+            .error_for_status()?;
+
+        if let Err(e) = self.refresh_balance().await {
+            log::warn!("Failed to refresh the balance after withdrawing: {e}");
+        }
+
+        Ok(())
+    }
+
     fn on_win(&mut self, bet_result: &BetResult) {
         self.offline_balance += bet_result.win_amount;
+        self.key_balances[self.active_key] += bet_result.win_amount;
         self.balance += bet_result.win_amount;
         self.profit += bet_result.win_amount;
         self.seed_profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+        if self.use_fake_betting {
+            // The simulated account pays out from the balance it started with.
+            self.site_balance += bet_result.win_amount;
+        }
         self.strategy.on_win(bet_result);
+        log::debug!("Strategy state: {}", self.strategy.describe_state());
         self.wins += 1;
+
+        if !self.reset_on_win_target {
+            return;
+        }
+        if let Some(cycle_profit) = take_win_target(self.strategy.as_mut()) {
+            if self.bank_profit {
+                self.banked += cycle_profit;
+                self.balance -= cycle_profit;
+                self.offline_balance -= cycle_profit;
+                if self.tip_to.is_some() {
+                    self.pending_tip += cycle_profit * self.tip_share;
+                }
+                println!(
+                    "[TARGET] Banked {:0>.8} {}, total banked {:0>.8}",
+                    cycle_profit, self.currency, self.banked
+                );
+            } else {
+                println!(
+                    "[TARGET] Reached {:0>.8} {} profit, resetting strategy",
+                    cycle_profit, self.currency
+                );
+            }
+        }
     }
 
     fn on_lose(&mut self, bet_result: &BetResult) {
-        self.offline_balance += bet_result.win_amount;
-        self.balance += bet_result.win_amount;
-        self.profit += bet_result.win_amount;
-        self.seed_profit += bet_result.win_amount;
+        self.offline_balance -= bet_result.win_amount;
+        self.key_balances[self.active_key] -= bet_result.win_amount;
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.seed_profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+        if self.use_fake_betting {
+            // The simulated account pays out from the balance it started with.
+            self.site_balance -= bet_result.win_amount;
+        }
         self.losses += 1;
-        let mut bet_result = bet_result.clone();
-        bet_result.win_amount = -bet_result.win_amount;
-        self.strategy.on_lose(&bet_result);
+        self.strategy.on_lose(bet_result);
+        log::debug!("Strategy state: {}", self.strategy.describe_state());
     }
 
     fn get_history(&self) -> Vec<BetResult> {
@@ -823,10 +1308,34 @@ impl Site for DuckDiceIo {
         self.rolls
     }
 
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = self.build_client()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            seed_rotation: true,
+            faucet: true,
+            withdrawal: true,
+            websocket: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn get_current_bet(&self) -> f32 {
         self.current_bet
     }
 
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
     fn get_current_multiplier(&self) -> f32 {
         1. / (self.chance / 100.)
     }
@@ -839,17 +1348,49 @@ impl Site for DuckDiceIo {
         self.profit
     }
 
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
     fn get_balance(&self) -> f32 {
         self.balance
     }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        if !self.use_site_balance {
+            return Ok(self.balance);
+        }
+
+        let site_balance = self.refresh_site_balance().await?;
+        self.balance = site_balance * self.balance_modifier;
+        self.strategy.set_balance(self.balance);
+
+        Ok(self.balance)
+    }
+
+    fn get_account_balances(&self) -> Vec<f32> {
+        self.key_balances.clone()
+    }
 }
 
 impl SiteConfig for DuckDiceIo {
-    fn with_api_key(mut self, api_key: String) -> Self
+    fn with_api_key(self, api_key: String) -> Self
+    where
+        Self: Sized,
+    {
+        self.with_api_keys(vec![api_key])
+    }
+
+    fn with_api_keys(mut self, api_keys: Vec<String>) -> Self
     where
         Self: Sized,
     {
-        self.api_key = api_key;
+        if api_keys.is_empty() {
+            return self;
+        }
+        self.key_balances = vec![0.; api_keys.len()];
+        self.api_keys = api_keys;
+        self.active_key = 0;
 
         self
     }
@@ -867,17 +1408,570 @@ impl SiteConfig for DuckDiceIo {
     where
         Self: Sized,
     {
-        self.strategy = match strategy {
-            TomlStrategies::AiFight => Box::new(crate::strategies::ai_fight::AIFight::default()),
-            TomlStrategies::MyStrategy => {
-                Box::new(crate::strategies::my_strategy::MyStrat::default())
+        self.strategy = self
+            .sizing
+            .strategy(strategy, self.offline_balance, self.min_bet());
+
+        self
+    }
+
+    fn with_simulation(mut self, start_balance: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self.use_fake_betting = true;
+        self.use_site_balance = false;
+        self.balance_source = BalanceSource::Main;
+        self.offline_balance = start_balance;
+        self.site_balance = start_balance;
+        self.strategy
+            .set_balance(start_balance * self.balance_modifier);
+
+        self
+    }
+
+    fn with_balance_source(mut self, balance_source: BalanceSource) -> Self
+    where
+        Self: Sized,
+    {
+        self.balance_source = balance_source;
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self.min_chance = min_chance;
+
+        self
+    }
+
+    fn with_request_limiter(mut self, limiter: RequestLimiter) -> Self
+    where
+        Self: Sized,
+    {
+        self.limiter = limiter;
+
+        self
+    }
+
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self
+    where
+        Self: Sized,
+    {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self
+    where
+        Self: Sized,
+    {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self
+    where
+        Self: Sized,
+    {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self
+    where
+        Self: Sized,
+    {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+
+    fn with_max_balance_staleness(mut self, max_staleness: Duration) -> Self
+    where
+        Self: Sized,
+    {
+        self.max_balance_staleness = Some(max_staleness);
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self
+    where
+        Self: Sized,
+    {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_symbol(mut self, symbol: String) -> Self
+    where
+        Self: Sized,
+    {
+        self.symbol = Some(symbol);
+
+        self
+    }
+
+    fn with_request_signer(mut self, signer: RequestSigner) -> Self
+    where
+        Self: Sized,
+    {
+        self.signer = Some(signer);
+
+        self
+    }
+
+    fn with_profit_target(mut self, bank_profit: bool) -> Self
+    where
+        Self: Sized,
+    {
+        self.reset_on_win_target = true;
+        self.bank_profit = bank_profit;
+
+        self
+    }
+
+    fn with_warmup(mut self, warmup: WarmupMode) -> Self
+    where
+        Self: Sized,
+    {
+        self.warmup = warmup;
+
+        self
+    }
+
+    fn with_seed_rotation(mut self, policy: RotationPolicy) -> Self
+    where
+        Self: Sized,
+    {
+        self.seeds = std::mem::take(&mut self.seeds).with_policy(policy);
+
+        self
+    }
+
+    fn with_seed_file(mut self, path: String) -> Self
+    where
+        Self: Sized,
+    {
+        self.seeds = std::mem::take(&mut self.seeds).with_file(path);
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn balance(currency: &str) -> Balance {
+        Balance {
+            currency: currency.to_string(),
+            main: None,
+            faucet: None,
+            affiliate: None,
+        }
+    }
+
+    /// User info reply of an account holding `main` ETH.
+    fn user_info(main: &str) -> String {
+        format!(
+            r#"{{"hash": "h", "username": "u", "createdAt": 0, "level": 1,
+                "campaign": null, "affiliate": null, "lastDeposit": null, "wagered": [],
+                "balances": [{{"currency": "ETH", "main": "{main}"}}],
+                "wageringBonuses": [], "tle": []}}"#
+        )
+    }
+
+    /// API answering each request with the next of `replies`, a status and a JSON body,
+    /// and returning the request line and body of every request it received.
+    async fn api_server(
+        replies: Vec<(u16, String)>,
+    ) -> (String, tokio::task::JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for (status, reply) in replies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut received = Vec::new();
+                let mut buf = [0u8; 4096];
+                let head_end = loop {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    received.extend_from_slice(&buf[..read]);
+                    if let Some(end) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&received[..head_end]).to_string();
+                let length = head
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                while received.len() < head_end + length {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    received.extend_from_slice(&buf[..read]);
+                }
+                requests.push((
+                    head.lines().next().unwrap_or_default().to_string(),
+                    String::from_utf8_lossy(&received[head_end..]).to_string(),
+                ));
+
+                let response = format!(
+                    "HTTP/1.1 {status} Reply\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+                    reply.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
             }
-            TomlStrategies::BlaksRunner => {
-                Box::new(crate::strategies::blaks_runner::BlaksRunner5_0::default())
+            requests
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_bet_history_page_maps_to_bet_results() {
+        let page: BetsPageJson = serde_json::from_str(
+            r#"{"bets": [{
+                "hash": "a1b2c3", "symbol": "BTC", "choice": ">", "result": true,
+                "number": 7312, "chance": 49.5, "payout": 2.0, "betAmount": "0.00000100",
+                "winAmount": "0.00000200", "profit": "0.00000100", "mined": null,
+                "nonce": 41, "created": 1700000000, "gameMode": "main"
+            }, {
+                "hash": "d4e5f6", "symbol": "BTC", "choice": "<", "result": false,
+                "number": 7312, "chance": 49.5, "payout": 2.0, "betAmount": "0.00000100",
+                "winAmount": "0", "profit": "-0.00000100", "mined": null,
+                "nonce": 42, "created": 1700000001, "gameMode": "main"
+            }]}"#,
+        )
+        .unwrap();
+
+        let bets: Vec<BetResult> = page
+            .bets
+            .into_iter()
+            .map(|bet| Bet::from(bet).into())
+            .collect();
+
+        assert_eq!(bets.len(), 2);
+        assert_eq!(bets[0].nonce, 41);
+        assert_eq!(bets[0].number.value(), 7312);
+        assert!(bets[0].result && bets[0].is_high);
+        assert!((bets[0].win_amount - 1e-6).abs() < 1e-12);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!(!bets[1].result);
+        assert!((bets[1].win_amount - 1e-6).abs() < 1e-12);
+        assert!((bets[1].profit() + 1e-6).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_drained_simulation_stops_with_insufficient_funds() {
+        let min_bet = Currency::ETH.get_min_bet(Sites::DuckDiceIo);
+        let mut site = DuckDiceIo::default().with_simulation(3. * min_bet);
+        site.login().await.unwrap();
+
+        let mut error = None;
+        for _ in 0..1_000_000 {
+            match site.do_bet(5000., 0.).await {
+                Ok(BetOutcome::Resolved(bet_result)) if bet_result.result => {
+                    site.on_win(&bet_result)
+                }
+                Ok(BetOutcome::Resolved(bet_result)) => site.on_lose(&bet_result),
+                Ok(BetOutcome::Pending(_)) => unreachable!("simulated bets settle at once"),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
             }
-            TomlStrategies::None => Box::new(crate::strategies::none::NoStrat::default()),
+        }
+
+        assert!(
+            matches!(error, Some(BetError::InsufficientFunds { .. })),
+            "{error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_feed_warmup_falls_back_to_min_bets_without_a_feed() {
+        // Nothing listens on port 1, so the feed cannot be reached.
+        let mut site = DuckDiceIo {
+            use_site_balance: false,
+            ..DuckDiceIo::default()
+        }
+        .with_warmup(WarmupMode::Feed)
+        .with_feed_url("ws://127.0.0.1:1".to_string());
+
+        site.login().await.unwrap();
+
+        assert!(site.get_history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refreshed_balance_caps_the_betting_balance() {
+        let (api_url, requests) = api_server(vec![(200, user_info("0.5"))]).await;
+        let mut site = DuckDiceIo {
+            api_url,
+            balance_source: BalanceSource::Main,
+            ..DuckDiceIo::default()
+        }
+        .with_max_balance_staleness(Duration::from_secs(60));
+
+        assert!(site.ensure_fresh_balance().await);
+        assert_eq!(site.site_balance, 0.5);
+        assert_eq!(site.balance, 0.5);
+        assert_eq!(site.strategy.get_balance(), 0.5);
+
+        // The balance is fresh now, so it is not fetched again.
+        assert!(site.ensure_fresh_balance().await);
+        assert_eq!(requests.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_primed_history_keeps_each_bets_client_seed() {
+        let bet = |hash: &str, nonce: u64| {
+            format!(
+                r#"{{"hash": "{hash}", "symbol": "ETH", "choice": ">", "result": true,
+                    "number": 7312, "chance": 49.5, "payout": 2.0, "betAmount": "0.001",
+                    "winAmount": "0.002", "profit": "0.001", "mined": null,
+                    "nonce": {nonce}, "created": 1700000000, "gameMode": "main"}}"#
+            )
+        };
+        let seed = |client_seed: &str| {
+            format!(r#"{{"seed": {{"serverSeedHash": "s", "clientSeed": "{client_seed}"}}}}"#)
+        };
+        // The bet list is newest first and spans a seed rotation.
+        let (api_url, _requests) = api_server(vec![
+            (
+                200,
+                format!(r#"{{"bets": [{}, {}]}}"#, bet("new", 0), bet("old", 7)),
+            ),
+            (200, seed("before")),
+            (200, seed("after")),
+        ])
+        .await;
+        let mut site = DuckDiceIo {
+            api_url,
+            history_size: 2,
+            ..DuckDiceIo::default()
         };
 
-        self
+        site.prime_history().await.unwrap();
+
+        assert_eq!(
+            site.history
+                .iter()
+                .map(|bet| (bet.client_seed.as_str(), bet.nonce))
+                .collect::<Vec<_>>(),
+            [("before", 7), ("after", 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refresh_balance_replaces_the_betting_balance() {
+        let (api_url, _requests) =
+            api_server(vec![(200, user_info("0.5")), (200, user_info("2"))]).await;
+        let mut site = DuckDiceIo {
+            api_url,
+            balance_source: BalanceSource::Main,
+            balance: 1.,
+            ..DuckDiceIo::default()
+        };
+
+        // The account may hold more than tracked locally as well as less.
+        for expected in [0.5, 2.] {
+            assert_eq!(site.refresh_balance().await.unwrap(), expected);
+            assert_eq!(site.balance, expected);
+            assert_eq!(site.strategy.get_balance(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_balance_bets_the_minimum() {
+        // Nothing listens on port 1, so neither the refresh nor the bet goes through.
+        let mut site = DuckDiceIo {
+            api_url: "http://127.0.0.1:1".to_string(),
+            strategy: Box::new(crate::strategies::none::NoStrat::default().with_initial_bet(0.01)),
+            history: vec![
+                crate::strategies::test_bet_result(true, 5000, 0.);
+                crate::util::HISTORY_SIZE
+            ],
+            balance: 1.,
+            initial_balance: 1.,
+            site_balance: 1.,
+            ..DuckDiceIo::default()
+        }
+        .with_max_balance_staleness(Duration::from_secs(60));
+
+        assert!(site.do_bet(9000., 90.).await.is_err());
+        assert_eq!(site.get_current_bet(), site.min_bet());
+        assert_eq!(site.last_bet_rule(), BetRule::StaleBalance);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_lookup_reports_rate_limited() {
+        let (api_url, _requests) = api_server(vec![(429, "{}".to_string())]).await;
+        let mut site = DuckDiceIo {
+            api_url,
+            ..DuckDiceIo::default()
+        };
+
+        let err = site.fetch_user_info().await;
+
+        assert!(matches!(err, Err(BetError::RateLimited(None))), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_bet_queried_again_after_a_reset_keeps_the_chance_floor() {
+        let (api_url, requests) = api_server(vec![
+            (200, "{}".to_string()),
+            (200, user_info("1")),
+            (429, "{}".to_string()),
+        ])
+        .await;
+        let mut site = DuckDiceIo {
+            api_url,
+            strategy: Box::new(crate::strategies::none::NoStrat::default().with_initial_bet(0.01)),
+            history: vec![
+                crate::strategies::test_bet_result(true, 5000, 0.);
+                crate::util::HISTORY_SIZE
+            ],
+            balance: 0.005,
+            initial_balance: 0.005,
+            site_balance: 1.,
+            ..DuckDiceIo::default()
+        };
+
+        // A prediction at the edge of the range asks for a chance far below the floor.
+        assert!(site.do_bet(9990., 90.).await.is_err());
+
+        assert_eq!(requests.await.unwrap().len(), 3);
+        assert_eq!(site.chance, site.min_chance);
+        assert_eq!(site.last_bet_rule(), BetRule::ChanceFloored);
+    }
+
+    #[tokio::test]
+    async fn test_reset_bets_from_the_refreshed_site_balance() {
+        let (api_url, _requests) = api_server(vec![
+            (200, "{}".to_string()),
+            (200, user_info("0.5")),
+            (429, "{}".to_string()),
+        ])
+        .await;
+        let mut site = DuckDiceIo {
+            api_url,
+            strategy: Box::new(crate::strategies::none::NoStrat::default().with_initial_bet(0.01)),
+            history: vec![
+                crate::strategies::test_bet_result(true, 5000, 0.);
+                crate::util::HISTORY_SIZE
+            ],
+            balance_source: BalanceSource::Main,
+            balance: 0.005,
+            initial_balance: 0.005,
+            site_balance: 1.,
+            ..DuckDiceIo::default()
+        };
+
+        assert!(site.do_bet(5000., 90.).await.is_err());
+
+        // The account holds less than last seen, and the session restarts from that.
+        assert_eq!(site.balance, 0.5);
+        assert_eq!(site.initial_balance, 0.5);
+        assert_eq!(site.strategy.get_balance(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_tries_every_account_before_waiting() {
+        let (api_url, requests) = api_server(vec![
+            (429, "{}".to_string()),
+            (200, user_info("1")),
+            (429, "{}".to_string()),
+        ])
+        .await;
+        let mut site = DuckDiceIo {
+            api_url,
+            history: vec![
+                crate::strategies::test_bet_result(true, 5000, 0.);
+                crate::util::HISTORY_SIZE
+            ],
+            balance: 1.,
+            initial_balance: 1.,
+            site_balance: 1.,
+            ..DuckDiceIo::default()
+        }
+        .with_api_keys(vec!["first".to_string(), "second".to_string()]);
+
+        let err = site.do_bet(9000., 90.).await;
+
+        assert!(matches!(err, Err(BetError::RateLimited(None))), "{err:?}");
+        assert_eq!(site.get_rolls(), 0);
+        let requests = requests.await.unwrap();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|(line, _)| line.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "POST /api/play?api_key=first HTTP/1.1",
+                "GET /api/bot/user-info?api_key=second HTTP/1.1",
+                "POST /api/play?api_key=second HTTP/1.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_withdraw_request_posts_the_address_and_amount() {
+        let site = DuckDiceIo {
+            symbol: Some("LTC".to_string()),
+            ..DuckDiceIo::default()
+        }
+        .with_api_key("key".to_string());
+
+        let request = site
+            .withdraw_request(0.5, "ltc1address")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::POST);
+        assert_eq!(
+            request.url().as_str(),
+            format!("{API_URL}/api/bot/withdraw?api_key=key")
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(request.body().and_then(|body| body.as_bytes()).unwrap())
+                .unwrap();
+        assert_eq!(
+            body,
+            json!({ "symbol": "LTC", "amount": "0.50000000", "address": "ltc1address" })
+        );
+    }
+
+    #[test]
+    fn test_resolve_symbol_matches_site_symbols() {
+        let balances = [balance("BTC"), balance("USDT_TRC20")];
+
+        assert_eq!(resolve_symbol(&balances, "btc").unwrap(), "BTC");
+        assert_eq!(
+            resolve_symbol(&balances, "usdt_trc20").unwrap(),
+            "USDT_TRC20"
+        );
+
+        let err = resolve_symbol(&balances, "USDT").unwrap_err().to_string();
+        assert!(err.contains("USDT"));
+        assert!(err.contains("BTC, USDT_TRC20"));
     }
 }
@@ -0,0 +1,479 @@
+//! Builds any configured site, so adding a site means adding its builder here.
+//!
+//! Each builder reads the site's section of `config.toml`, together with the shared
+//! sections such as `[network]` and `[bet_limits]`, and returns the site fully
+//! configured and ready to log in.
+
+use crate::config::{BetSizingConfig, SiteConfig, TomlConfig, TomlStrategies};
+use crate::currency::Currency;
+use crate::sites::limiter::RequestLimiter;
+use crate::sites::signing::RequestSigner;
+use crate::sites::totp::Totp;
+use crate::sites::{
+    bc_game::BcGame, bitsler::Bitsler, crypto_games::CryptoGames, dice999::Dice999,
+    duck_dice::DuckDiceIo, free_bitco_in::FreeBitcoIn, primedice::PrimeDice, windice::Windice,
+    wolf_bet::WolfBet, BetError, Site, Sites,
+};
+
+/// Strategy configured for `site`.
+pub fn site_strategy(game_config: &TomlConfig, site: Sites) -> TomlStrategies {
+    match site {
+        Sites::DuckDiceIo => game_config.duck_dice.strategy,
+        Sites::CryptoGames => game_config.crypto_games.strategy,
+        Sites::FreeBitcoIn => game_config.freebitcoin.strategy,
+        Sites::Windice => game_config.windice.strategy,
+        Sites::PrimeDice => game_config.primedice.strategy,
+        Sites::Bitsler => game_config.bitsler.strategy,
+        Sites::BcGame => game_config.bc_game.strategy,
+        Sites::WolfBet => game_config.wolf_bet.strategy,
+        Sites::Dice999 => game_config.dice999.strategy,
+    }
+}
+
+/// Fails when simulation is enabled, which only DuckDice supports.
+fn reject_simulation(game_config: &TomlConfig) -> Result<(), BetError> {
+    if game_config.simulation.enabled {
+        return Err(BetError::ConfigError(
+            "Simulation is only supported on DuckDice".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Settings shared by the sections of the sites that log in with an API key.
+struct ApiKeySection<'a> {
+    api_key: &'a str,
+    currency: &'a Currency,
+    min_bet_override: Option<f32>,
+    min_chance: Option<f32>,
+    sizing: BetSizingConfig,
+}
+
+/// Configures `site`, named `name` in the log, from `section` and the shared sections,
+/// betting with `strategy`. Site-specific settings are applied to `site` beforehand.
+fn build_api_key_site<S: SiteConfig + Site + 'static>(
+    game_config: &TomlConfig,
+    name: &str,
+    site: S,
+    section: ApiKeySection,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    reject_simulation(game_config)?;
+
+    log::info!("Using {name} site");
+    let mut site = site
+        .with_api_key(section.api_key.to_string())
+        .with_currency(section.currency.clone())
+        .with_bet_sizing(section.sizing);
+    if let Some(min_bet) = section.min_bet_override {
+        site = site.with_min_bet(min_bet);
+    }
+    if let Some(history_size) = section.sizing.history_size {
+        site = site.with_history_size(history_size);
+    }
+    if let Some(min_chance) = section.min_chance {
+        site = site.with_min_chance(min_chance);
+    }
+    // None of these sites report a maximum bet to take a share of.
+    if game_config
+        .bet_limits
+        .max_bet_fraction_of_site_max
+        .is_some()
+    {
+        log::warn!(
+            "{name} reports no maximum bet, so max_bet_fraction_of_site_max has no effect there"
+        );
+    }
+    if let Some(proxy) = game_config.network.proxy() {
+        site = site.with_proxy(proxy);
+    }
+
+    // The strategy is seeded with the minimum bet and sizing set above, so it goes last.
+    Ok(Box::new(site.with_strategy(strategy)))
+}
+
+/// Builds the Windice site, betting with `strategy`.
+fn build_windice(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.windice;
+    build_api_key_site(
+        game_config,
+        "Windice",
+        Windice::default(),
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the PrimeDice site, betting with `strategy`.
+fn build_primedice(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.primedice;
+    build_api_key_site(
+        game_config,
+        "PrimeDice",
+        PrimeDice::default(),
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the Bitsler site, betting with `strategy`.
+fn build_bitsler(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.bitsler;
+    build_api_key_site(
+        game_config,
+        "Bitsler",
+        Bitsler::default(),
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the BC.Game site, betting with `strategy`.
+fn build_bc_game(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.bc_game;
+    let site = BcGame::default().with_request_signer(
+        RequestSigner::new(config.api_secret.clone())
+            .with_header(crate::sites::bc_game::SIGNATURE_HEADER.to_string()),
+    );
+
+    build_api_key_site(
+        game_config,
+        "BC.Game",
+        site,
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the Wolf.bet site, betting with `strategy`.
+fn build_wolf_bet(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.wolf_bet;
+    build_api_key_site(
+        game_config,
+        "Wolf.bet",
+        WolfBet::default(),
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the 999dice site, betting with `strategy`.
+fn build_dice999(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    let config = &game_config.dice999;
+    build_api_key_site(
+        game_config,
+        "999dice",
+        Dice999::default(),
+        ApiKeySection {
+            api_key: &config.api_key,
+            currency: &config.currency,
+            min_bet_override: config.min_bet_override,
+            min_chance: config.min_chance,
+            sizing: config.sizing,
+        },
+        strategy,
+    )
+}
+
+/// Builds the FreeBitco.in site, betting with `strategy`.
+fn build_free_bitco_in(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    reject_simulation(game_config)?;
+
+    log::info!("Using FreeBitco.in site");
+    let config = &game_config.freebitcoin;
+    let mut site = FreeBitcoIn::default()
+        .with_username(config.btc_address.clone())
+        .with_password(config.password.clone())
+        .with_bet_sizing(config.sizing);
+    if let Some(tfa_code) = config.tfa_code() {
+        site = site.with_tfa_code(tfa_code);
+    }
+    if let Some(secret) = &config.totp_secret {
+        site = site.with_totp(Totp::from_base32(secret)?);
+    }
+    site = site.with_reward_bonuses(config.reward_bonuses.clone());
+    if let Some(check_every) = config.reward_check_every {
+        site = site.with_reward_check_every(check_every);
+    }
+    if config.claim_faucet {
+        site = site.with_faucet_claims();
+    }
+    if config.halt_on_unverified_roll {
+        site = site.with_halt_on_unverified();
+    }
+    site = site.with_seed_rotation(config.seeds.policy());
+    if let Some(path) = &config.seeds.seed_file {
+        site = site.with_seed_file(path.clone());
+    }
+    if let Some(session_file) = &config.session_file {
+        site = site.with_session_file(session_file.clone());
+    }
+    // The strategy is seeded with the minimum bet, so the override goes first.
+    if let Some(min_bet) = config.min_bet_override {
+        site = site.with_min_bet(min_bet);
+    }
+    if let Some(history_size) = config.sizing.history_size {
+        site = site.with_history_size(history_size);
+    }
+    if let Some(min_chance) = config.min_chance {
+        site = site.with_min_chance(min_chance);
+    }
+    if let Some(fraction) = game_config.bet_limits.max_bet_fraction_of_site_max {
+        site = site.with_max_bet_fraction(fraction);
+    }
+    if let Some(proxy) = game_config.network.proxy() {
+        site = site.with_proxy(proxy);
+    }
+
+    Ok(Box::new(site.with_strategy(strategy)))
+}
+
+/// Builds the CryptoGames site, betting with `strategy`.
+fn build_crypto_games(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    reject_simulation(game_config)?;
+
+    log::info!("Using CryptoGames site");
+    let mut site = CryptoGames::default()
+        .with_api_key(game_config.crypto_games.api_key.clone())
+        .with_currency(game_config.crypto_games.currency.clone())
+        .with_bet_sizing(game_config.crypto_games.sizing);
+    if let Some(min_bet) = game_config.crypto_games.min_bet_override {
+        site = site.with_min_bet(min_bet);
+    }
+    if let Some(history_size) = game_config.crypto_games.sizing.history_size {
+        site = site.with_history_size(history_size);
+    }
+    if let Some(fraction) = game_config.bet_limits.max_bet_fraction_of_site_max {
+        site = site.with_max_bet_fraction(fraction);
+    }
+    if let Some(proxy) = game_config.network.proxy() {
+        site = site.with_proxy(proxy);
+    }
+
+    // The strategy is seeded with the minimum bet and sizing set above, so it goes last.
+    Ok(Box::new(site.with_strategy(strategy)))
+}
+
+/// Builds `site`, betting with `strategy`.
+pub fn build_site(
+    game_config: &TomlConfig,
+    site: Sites,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    match site {
+        Sites::DuckDiceIo => build_duck_dice(game_config, strategy),
+        Sites::CryptoGames => build_crypto_games(game_config, strategy),
+        Sites::FreeBitcoIn => build_free_bitco_in(game_config, strategy),
+        Sites::Windice => build_windice(game_config, strategy),
+        Sites::PrimeDice => build_primedice(game_config, strategy),
+        Sites::Bitsler => build_bitsler(game_config, strategy),
+        Sites::BcGame => build_bc_game(game_config, strategy),
+        Sites::WolfBet => build_wolf_bet(game_config, strategy),
+        Sites::Dice999 => build_dice999(game_config, strategy),
+    }
+}
+
+/// Builds the DuckDice site, betting with `strategy`.
+fn build_duck_dice(
+    game_config: &TomlConfig,
+    strategy: TomlStrategies,
+) -> Result<Box<dyn Site>, BetError> {
+    log::info!("Using DuckDice site");
+    let mut site = DuckDiceIo::default()
+        .with_api_keys(game_config.duck_dice.api_key.keys().to_vec())
+        .with_currency(game_config.duck_dice.currency.clone())
+        .with_bet_sizing(game_config.duck_dice.sizing);
+    // The strategy is seeded with the minimum bet, so the override goes first.
+    if let Some(min_bet) = game_config.duck_dice.min_bet_override {
+        site = site.with_min_bet(min_bet);
+    }
+    if let Some(history_size) = game_config.duck_dice.sizing.history_size {
+        site = site.with_history_size(history_size);
+    }
+    site = site
+        .with_balance_source(game_config.duck_dice.balance_source)
+        .with_request_limiter(RequestLimiter::new(
+            game_config.network.max_concurrent_requests,
+        ))
+        .with_strategy(strategy);
+
+    if let Some(min_chance) = game_config.duck_dice.min_chance {
+        site = site.with_min_chance(min_chance);
+    }
+    if let Some(max_history) = game_config.duck_dice.max_history {
+        site = site.with_max_history(max_history);
+    }
+    if let Some(symbol) = game_config
+        .duck_dice
+        .symbols
+        .get(&game_config.duck_dice.currency)
+    {
+        site = site.with_symbol(symbol.clone());
+    }
+    if let Some(secs) = game_config.duck_dice.max_balance_staleness_secs {
+        site = site.with_max_balance_staleness(std::time::Duration::from_secs(secs));
+    }
+    if let Some(proxy) = game_config.network.proxy() {
+        site = site.with_proxy(proxy);
+    }
+    if let Some(signing) = &game_config.duck_dice.signing {
+        site = site.with_request_signer(signing.signer());
+    }
+    site = site
+        .with_warmup(game_config.duck_dice.warmup)
+        .with_feed_url(game_config.collect.feed_url.clone());
+    site = site.with_seed_rotation(game_config.duck_dice.seeds.policy());
+    if let Some(path) = &game_config.duck_dice.seeds.seed_file {
+        site = site.with_seed_file(path.clone());
+    }
+    if let Some(fraction) = game_config.bet_limits.max_bet_fraction_of_site_max {
+        site = site.with_max_bet_fraction(fraction);
+    }
+
+    if game_config.profit_target.enabled {
+        log::info!(
+            "Resetting the strategy at its win target (bank profit: {})",
+            game_config.profit_target.bank_profit
+        );
+        site = site.with_profit_target(game_config.profit_target.bank_profit);
+    }
+    if let Some(tip) = &game_config.duck_dice.tip {
+        log::info!("Tipping banked profit to {}", tip.to);
+        site = site.with_tips(tip.to.clone(), tip.share.unwrap_or(1.));
+    }
+
+    if game_config.simulation.enabled {
+        let start_balance = game_config
+            .simulation
+            .start_balance_for(&game_config.duck_dice.currency);
+        log::info!(
+            "Simulation enabled, starting balance: {:.8} {}",
+            start_balance,
+            game_config.duck_dice.currency
+        );
+        Ok(Box::new(site.with_simulation(start_balance)))
+    } else {
+        Ok(Box::new(site))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(simulation: bool) -> TomlConfig {
+        toml::from_str(&format!(
+            r#"
+[crypto_games]
+enabled = false
+api_key = "key"
+currency = "BTC"
+strategy = "None"
+
+[freebitcoin]
+enabled = false
+btc_address = "address"
+password = "password"
+strategy = "None"
+
+[duck_dice]
+enabled = true
+api_key = "key"
+currency = "BTC"
+strategy = "Flat"
+
+[windice]
+enabled = true
+api_key = "key"
+strategy = "Kelly"
+
+[simulation]
+enabled = {simulation}
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_builds_each_enabled_site_with_its_strategy() {
+        let config = config(false);
+
+        for site in config.enabled_sites() {
+            assert!(build_site(&config, site, site_strategy(&config, site)).is_ok());
+        }
+        assert_eq!(
+            site_strategy(&config, Sites::DuckDiceIo),
+            TomlStrategies::Flat
+        );
+        assert_eq!(
+            site_strategy(&config, Sites::Windice),
+            TomlStrategies::Kelly
+        );
+    }
+
+    #[test]
+    fn test_simulation_only_builds_duck_dice() {
+        let config = config(true);
+
+        assert!(build_site(&config, Sites::DuckDiceIo, TomlStrategies::Flat).is_ok());
+        assert!(matches!(
+            build_site(&config, Sites::Windice, TomlStrategies::Flat),
+            Err(BetError::ConfigError(_))
+        ));
+    }
+}
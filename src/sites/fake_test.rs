@@ -1,10 +1,10 @@
 use lazy_static::lazy_static;
-use rand::Rng;
-use sha2::{Digest, Sha256, Sha512};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::sync::Mutex;
 
 use crate::sites::duck_dice::{AbsoluteLevel, Bet, BetMakeResponse, User};
 use crate::sites::free_bitco_in::BetSiteResult;
+use crate::sites::roll_scheme::{DuckDiceScheme, FreeBitcoScheme, RollScheme};
 
 lazy_static! {
     pub static ref SERVER_STORAGE: Mutex<FakeServerStorage> =
@@ -26,42 +26,48 @@ pub struct FakeServerStorage {
     pub initialized: bool,
     pub client_seed: String,
     pub server_seed: String,
+    /// When set, server seeds are derived from this value so sessions are reproducible.
+    pub seed: Option<u64>,
 }
 
-/// Returns: (rolled_number, server_seed, nonce)
+impl FakeServerStorage {
+    /// A fresh, deterministic session whose server seeds derive from `seed`.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..Default::default()
+        }
+    }
+}
+
+/// Rolls with the provably-fair `scheme` of the simulated site.
+///
+/// Returns: (rolled_number, server_seed_hash, nonce)
 pub fn gen_fake_bet(
     server_storage: &mut FakeServerStorage,
+    scheme: &dyn RollScheme,
     client_seed: &str,
 ) -> (u32, String, u64) {
     if server_storage.current_nonce == 0 {
-        server_storage.server_seed = rand::rng()
-            .sample_iter(rand::distr::Alphanumeric)
-            .take(64)
-            .map(char::from)
-            .collect();
-    }
-    let mut hasher = Sha256::new();
-    hasher.update(&server_storage.server_seed);
-    let result = hasher.finalize();
-    let server_seed_hash = hex::encode(result);
-
-    // DuckdiceIO Dice Generation.
-    let mut combined_seed = Vec::new();
-    combined_seed.extend_from_slice(server_storage.server_seed.as_bytes());
-    combined_seed.extend_from_slice(client_seed.as_bytes());
-    combined_seed.extend_from_slice(server_storage.current_nonce.to_string().as_bytes());
-
-    let mut hasher = Sha512::new();
-    hasher.update(combined_seed);
-    let result = hasher.finalize();
-    let hash = hex::encode(result);
-    let mut lucky = 100000000;
-    let mut index = 0;
-    while lucky > 1000000 {
-        lucky = u32::from_str_radix(&hash[index..=index + 4], 16).unwrap();
-        index += 5;
+        server_storage.server_seed = match server_storage.seed {
+            Some(seed) => StdRng::seed_from_u64(seed)
+                .sample_iter(rand::distr::Alphanumeric)
+                .take(64)
+                .map(char::from)
+                .collect(),
+            None => rand::rng()
+                .sample_iter(rand::distr::Alphanumeric)
+                .take(64)
+                .map(char::from)
+                .collect(),
+        };
     }
-    let number = lucky % 10000;
+    let server_seed_hash = scheme.server_seed_hash(&server_storage.server_seed);
+    let number = scheme.roll(
+        &server_storage.server_seed,
+        client_seed,
+        server_storage.current_nonce,
+    );
 
     (number, server_seed_hash, server_storage.current_nonce)
 }
@@ -74,7 +80,8 @@ pub fn free_bitcoin_fake_bet(
 ) -> BetSiteResult {
     let server_storage: &mut FakeServerStorage = &mut SERVER_STORAGE.lock().unwrap();
 
-    let (rolled_number, server_seed, _nonce) = gen_fake_bet(server_storage, client_seed);
+    let (rolled_number, server_seed, _nonce) =
+        gen_fake_bet(server_storage, &FreeBitcoScheme, client_seed);
     server_storage.server_seed_hash_previous_roll = server_storage.current_seed_hash.clone();
     server_storage.current_seed_hash = server_storage.server_seed_hash_next_roll.clone();
     server_storage.server_seed_hash_next_roll = server_seed.clone();
@@ -123,9 +130,25 @@ pub fn duckdice_fake_bet(
     stake: f32,
     multiplier: f32,
 ) -> BetMakeResponse {
-    let server_storage: &mut FakeServerStorage = &mut SERVER_STORAGE.lock().unwrap();
+    duckdice_fake_bet_on(
+        &mut SERVER_STORAGE.lock().unwrap(),
+        high,
+        client_seed,
+        stake,
+        multiplier,
+    )
+}
 
-    let (rolled_number, server_seed, _nonce) = gen_fake_bet(server_storage, client_seed);
+/// Like `duckdice_fake_bet`, but rolls on `server_storage` instead of the shared server.
+pub fn duckdice_fake_bet_on(
+    server_storage: &mut FakeServerStorage,
+    high: bool,
+    client_seed: &str,
+    stake: f32,
+    multiplier: f32,
+) -> BetMakeResponse {
+    let (rolled_number, server_seed, _nonce) =
+        gen_fake_bet(server_storage, &DuckDiceScheme, client_seed);
     server_storage.server_seed_hash_previous_roll = server_storage.current_seed_hash.clone();
     server_storage.current_seed_hash = server_storage.server_seed_hash_next_roll.clone();
     server_storage.server_seed_hash_next_roll = server_seed.clone();
@@ -145,7 +168,7 @@ pub fn duckdice_fake_bet(
             previous_hash: server_storage.server_seed_hash_previous_roll.clone(),
             hash: server_storage.current_seed_hash.clone(),
             symbol: "UNKNOWN".to_string(),
-            choice: if server_storage.current_roll > 5000 {
+            choice: if high {
                 "> 5000".to_string()
             } else {
                 "< 5000".to_string()
@@ -203,3 +226,10 @@ pub fn reset_server_seed() {
 
     server_storage.current_nonce = 0;
 }
+
+/// Resets the fake server to a fresh, deterministic session derived from `seed`.
+pub fn reset_server_seed_with(seed: u64) {
+    let server_storage: &mut FakeServerStorage = &mut SERVER_STORAGE.lock().unwrap();
+
+    *server_storage = FakeServerStorage::seeded(seed);
+}
@@ -3,9 +3,18 @@ use rand::Rng;
 use reqwest::{cookie::Jar, Url};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
 use crate::{
-    sites::{fake_test::free_bitcoin_fake_bet, BetError, BetResult, Site},
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    sites::{
+        cap_bet, cap_multiplier, check_funds, client_builder, effective_min_bet,
+        fake_test::free_bitcoin_fake_bet, floor_chance, parse_balance, parse_or_default,
+        push_history, roll_scheme::FreeBitcoScheme, seeds::RotationPolicy, seeds::SeedManager,
+        send_request, totp::Totp, verify, BetError, BetId, BetOutcome, BetResult, BetRule,
+        Capabilities, Site,
+    },
     strategies::Strategy,
 };
 
@@ -18,6 +27,52 @@ pub struct LoginRequest {
     pub tfa_code: String,
 }
 
+/// Login cookies kept between runs, so a restart reuses the session instead of
+/// logging in again.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Session {
+    pub csrf_token: String,
+    pub btc_address: String,
+    pub fbtc_session: String,
+    pub fbtc_userid: String,
+    pub password: String,
+}
+
+impl Session {
+    /// The session saved at `path`, if there is a readable one.
+    pub fn load(path: &str) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents)
+            .inspect_err(|e| log::warn!("Ignoring unreadable session file {path}: {e}"))
+            .ok()
+    }
+
+    /// Saves the session to `path`, readable only by the owner where supported.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let contents = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+    }
+
+    /// Sets the session cookies for `url` in `jar`.
+    fn apply(&self, jar: &Jar, url: &Url) {
+        for (name, value) in [
+            ("csrf_token", &self.csrf_token),
+            ("btc_address", &self.btc_address),
+            ("fbtc_session", &self.fbtc_session),
+            ("fbtc_userid", &self.fbtc_userid),
+            ("password", &self.password),
+        ] {
+            jar.add_cookie_str(&format!("{name}={value}; Path=/; Secure"), url);
+        }
+        jar.add_cookie_str("have_account=1; Path=/; Secure", url);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BetSiteResult {
     pub success_code: String,
@@ -42,39 +97,103 @@ pub struct BetSiteResult {
     pub bonus_account_balance_before_bet: f32,
 }
 
-impl From<&str> for BetSiteResult {
-    fn from(value: &str) -> Self {
-        let bet_split = value
-            .split(':')
-            .map(|val| val.to_string())
-            .collect::<Vec<String>>();
+impl TryFrom<&str> for BetSiteResult {
+    type Error = BetError;
 
-        if bet_split.len() < 22 {
-            panic!("{value:?}");
+    /// Parses a bet reply, a `:`-separated list of 23 fields.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let bet_split = value.split(':').collect::<Vec<&str>>();
+        if bet_split.len() < 23 {
+            return Err(BetError::UnexpectedReply(value.to_string()));
         }
 
-        Self {
-            success_code: bet_split[0].clone(),
-            result: bet_split[1].as_str() == "w",
-            rolled_number: bet_split[2].parse::<u32>().unwrap(),
-            user_balance: bet_split[3].parse::<f32>().unwrap(),
-            amount_won: bet_split[4].parse::<f32>().unwrap(),
-            server_seed_hash_next_roll: bet_split[6].clone(),
-            client_seed_previous_roll: bet_split[7].clone(),
-            nonce_next_roll: bet_split[8].clone(),
-            server_seed_previous_roll: bet_split[9].clone(),
-            server_seed_hash_previous_roll: bet_split[10].clone(),
-            previous_nonce: bet_split[12].clone(),
-            jackpot_result: bet_split[13].parse::<u8>().unwrap(),
-            jackpot_amount_won: bet_split[15].parse::<f32>().unwrap(),
-            bonus_account_balance_after_bet: bet_split[16].parse::<f32>().unwrap(),
-            bonus_acount_wager_remaining: bet_split[17].parse::<f32>().unwrap(),
-            max_amount_bonus_eligable: bet_split[18].parse::<f32>().unwrap(),
-            max_bet: bet_split[19].parse::<f32>().unwrap(),
-            account_balance_before_bet: bet_split[20].parse::<f32>().unwrap(),
-            account_balance_after_bet: bet_split[21].parse::<f32>().unwrap(),
-            bonus_account_balance_before_bet: bet_split[22].parse::<f32>().unwrap(),
+        let malformed =
+            |index: usize| BetError::UnexpectedReply(format!("field {index} of {value:?}"));
+        let number = |index: usize| {
+            bet_split[index]
+                .parse::<f32>()
+                .map_err(|_| malformed(index))
+        };
+        let balance = |index: usize| {
+            parse_balance(bet_split[index])
+                .map(|balance| balance as f32)
+                .ok_or_else(|| malformed(index))
+        };
+
+        Ok(Self {
+            success_code: bet_split[0].to_string(),
+            result: bet_split[1] == "w",
+            rolled_number: bet_split[2].parse::<u32>().map_err(|_| malformed(2))?,
+            user_balance: balance(3)?,
+            amount_won: number(4)?,
+            server_seed_hash_next_roll: bet_split[6].to_string(),
+            client_seed_previous_roll: bet_split[7].to_string(),
+            nonce_next_roll: bet_split[8].to_string(),
+            server_seed_previous_roll: bet_split[9].to_string(),
+            server_seed_hash_previous_roll: bet_split[10].to_string(),
+            previous_nonce: bet_split[12].to_string(),
+            jackpot_result: bet_split[13].parse::<u8>().map_err(|_| malformed(13))?,
+            jackpot_amount_won: number(15)?,
+            bonus_account_balance_after_bet: balance(16)?,
+            bonus_acount_wager_remaining: number(17)?,
+            max_amount_bonus_eligable: number(18)?,
+            max_bet: number(19)?,
+            account_balance_before_bet: balance(20)?,
+            account_balance_after_bet: balance(21)?,
+            bonus_account_balance_before_bet: balance(22)?,
+        })
+    }
+}
+
+/// Reason FreeBitco.in gave for refusing a reward redemption, or `Ok` when it
+/// accepted; replies are `s:...` on success and `e:<reason>` otherwise.
+fn parse_redeem_reply(reply: &str) -> Result<(), String> {
+    if reply.starts_with('s') {
+        return Ok(());
+    }
+
+    Err(reply
+        .split_once(':')
+        .map_or(reply, |(_, reason)| reason)
+        .trim()
+        .to_string())
+}
+
+/// Amount won by a free roll, from replies of the form
+/// `s:<roll>:<balance>:<amount won>:...`, or the reason the site refused it.
+fn parse_free_play_reply(reply: &str) -> Result<f32, String> {
+    let fields = reply.split(':').collect::<Vec<&str>>();
+    if fields[0] != "s" {
+        return Err(fields.get(1).unwrap_or(&reply).trim().to_string());
+    }
+
+    fields
+        .get(3)
+        .and_then(|amount| amount.trim().parse::<f32>().ok())
+        .ok_or_else(|| format!("unexpected reply {reply:?}"))
+}
+
+impl BetSiteResult {
+    /// Jackpot winnings plus any change in the bonus account caused by this bet.
+    pub fn bonus_profit(&self) -> f32 {
+        self.jackpot_amount_won
+            + (self.bonus_account_balance_after_bet - self.bonus_account_balance_before_bet)
+    }
+
+    /// Seeds of the roll this reply settled, revealed along with it; `None` when the
+    /// reply leaves them out.
+    pub fn revealed_roll(&self) -> Option<verify::RevealedRoll> {
+        if self.server_seed_previous_roll.is_empty() {
+            return None;
         }
+
+        Some(verify::RevealedRoll {
+            server_seed: self.server_seed_previous_roll.clone(),
+            server_seed_hash: self.server_seed_hash_previous_roll.clone(),
+            client_seed: self.client_seed_previous_roll.clone(),
+            nonce: self.previous_nonce.parse().ok()?,
+            number: self.rolled_number,
+        })
     }
 }
 
@@ -129,51 +248,420 @@ impl Default for UserStats {
 
 pub struct FreeBitcoIn {
     pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
     pub client_seed: String,
     pub current_bet: f32,
     pub multiplier: f32,
     pub user_stats: UserStats,
-    pub profit: f32,
+    /// Profit from dice rolls alone.
+    pub dice_profit: f32,
+    /// Jackpot winnings and bonus-account credits, kept out of `dice_profit`.
+    pub bonus_profit: f32,
     pub prediction: u32,
     pub strategy: Box<dyn Strategy>,
     client: reqwest::Client,
+    /// Proxy the client is rebuilt with on login, when one is configured.
+    proxy: Option<reqwest::Proxy>,
     cookie_jar: Arc<Jar>,
     csrf_token: String,
+    /// File the login session is saved to and restored from.
+    session_file: Option<String>,
+    btc_address: String,
+    password: String,
+    /// Two-factor code sent with the login, for accounts with 2FA enabled.
+    tfa_code: Option<String>,
+    /// Generates the two-factor code at login, taking precedence over `tfa_code`.
+    totp: Option<Totp>,
+    /// Reward-point bonuses activated whenever the account can afford them.
+    reward_bonuses: Vec<String>,
+    /// Bets between attempts to activate `reward_bonuses`.
+    reward_check_every: u64,
+    /// Wagering left before the bonus account is released, as of the last bet.
+    pub bonus_wager_remaining: f32,
+    /// Claim the hourly free roll in the background while logged in.
+    claim_faucet: bool,
+    /// Amounts won by free rolls the claim task has not handed over yet.
+    faucet_claims: Option<mpsc::UnboundedReceiver<f32>>,
+    sizing: BetSizingConfig,
     history: Vec<BetResult>,
     history_size: usize,
+    max_history: usize,
     use_site_balance: bool,
     use_fake_betting: bool,
-    wins: u64,
-    loses: u64,
+    min_chance: f32,
+    max_multiplier: f32,
+    /// Largest stake the site accepts, as reported with the last bet.
+    max_bet: Option<f32>,
+    /// Share of `max_bet` bets are capped at.
+    max_bet_fraction: f32,
+    /// Configured floor for bets, used when above the site's one satoshi minimum.
+    min_bet_override: Option<f32>,
+    /// Stop betting when a roll fails verification instead of only warning.
+    halt_on_unverified: bool,
+    /// Client seed rotation and the nonces each seed rolled.
+    seeds: SeedManager,
+    last_rule: BetRule,
+}
+
+/// Smallest stake FreeBitco.in accepts, one satoshi.
+const SITE_MIN_BET: f32 = 1e-8;
+
+/// Bankroll the strategy starts from until the site reports the balance.
+const DEFAULT_BALANCE: f32 = 0.02;
+
+/// Smallest bet of the strategy unless configured otherwise.
+const DEFAULT_MIN_BET: f32 = 0.000008;
+
+/// Bets between attempts to activate reward bonuses unless configured otherwise.
+const DEFAULT_REWARD_CHECK_EVERY: u64 = 100;
+
+/// Time between free rolls.
+const FAUCET_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Wait before trying a refused free roll again.
+const FAUCET_RETRY: Duration = Duration::from_secs(5 * 60);
+
+impl FreeBitcoIn {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(SITE_MIN_BET, self.min_bet_override)
+    }
+
+    /// Client keeping the session cookies, through the proxy if one is set.
+    fn build_client(&self) -> Result<reqwest::Client, BetError> {
+        Ok(client_builder(self.proxy.as_ref())
+            .cookie_store(true)
+            .cookie_provider(Arc::clone(&self.cookie_jar))
+            .build()?)
+    }
+
+    /// Caps the multiplier bets are placed at.
+    pub fn with_max_multiplier(mut self, max_multiplier: f32) -> Self {
+        self.max_multiplier = max_multiplier;
+
+        self
+    }
+
+    /// Sends `tfa_code` with the login, for accounts with two-factor authentication.
+    pub fn with_tfa_code(mut self, tfa_code: String) -> Self {
+        self.tfa_code = Some(tfa_code);
+
+        self
+    }
+
+    /// Generates the two-factor code with `totp` at each login, so the account can log
+    /// in unattended.
+    pub fn with_totp(mut self, totp: Totp) -> Self {
+        self.totp = Some(totp);
+
+        self
+    }
+
+    /// Activates each of `bonuses`, reward-point bonus ids such as `free_points_100` or
+    /// `fp_bonus_1000`, at login and then every few bets while the account has the
+    /// points for them.
+    pub fn with_reward_bonuses(mut self, bonuses: Vec<String>) -> Self {
+        self.reward_bonuses = bonuses;
+
+        self
+    }
+
+    /// Tries the reward bonuses again every `check_every` bets.
+    pub fn with_reward_check_every(mut self, check_every: u64) -> Self {
+        self.reward_check_every = check_every;
+
+        self
+    }
+
+    /// Tries to activate every configured reward bonus; bonuses the site refuses, e.g.
+    /// for lack of points or as already active, are tried again at the next check.
+    ///
+    /// Takes the client rather than `&self`, whose strategy is not `Sync`.
+    async fn redeem_rewards(client: &reqwest::Client, csrf_token: &str, bonuses: &[String]) {
+        for bonus in bonuses {
+            let url = Url::parse_with_params(
+                "https://freebitco.in/",
+                &[
+                    ("op", "redeem_rewards"),
+                    ("id", bonus),
+                    ("points", ""),
+                    ("csrf_token", csrf_token),
+                ],
+            )
+            .expect("Failed to create freebitco.in reward URL");
+
+            let reply = match send_request(client.get(url)).await {
+                Ok(res) => res.text().await,
+                Err(e) => {
+                    log::warn!("Failed to activate reward bonus {bonus}: {e}");
+                    continue;
+                }
+            };
+            match reply.map(|reply| parse_redeem_reply(&reply)) {
+                Ok(Ok(())) => log::info!("Activated reward bonus {bonus}"),
+                Ok(Err(reason)) => log::debug!("Reward bonus {bonus} not activated: {reason}"),
+                Err(e) => log::warn!("Failed to activate reward bonus {bonus}: {e}"),
+            }
+        }
+    }
+
+    /// Claims the free roll every hour while logged in, paying for it with reward points
+    /// instead of a captcha, and adds the winnings to the balance.
+    pub fn with_faucet_claims(mut self) -> Self {
+        self.claim_faucet = true;
+
+        self
+    }
+
+    /// Starts claiming the free roll in the background, unless already claiming.
+    fn start_faucet_claims(&mut self) {
+        if !self.claim_faucet || self.faucet_claims.is_some() {
+            return;
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.faucet_claims = Some(rx);
+        let client = self.client.clone();
+        let csrf_token = self.csrf_token.clone();
+        let client_seed = self.client_seed.clone();
+        tokio::spawn(async move {
+            loop {
+                let delay = match Self::claim_free_roll(&client, &csrf_token, &client_seed).await {
+                    Ok(amount) => {
+                        log::info!("Claimed {amount:.8} BTC from the free roll");
+                        if tx.send(amount).is_err() {
+                            // The site is gone, so nobody bets the winnings.
+                            break;
+                        }
+                        FAUCET_INTERVAL
+                    }
+                    Err(e) => {
+                        log::debug!("Free roll not claimed: {e}");
+                        FAUCET_RETRY
+                    }
+                };
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+
+    /// Plays the free roll once, returning the amount won.
+    async fn claim_free_roll(
+        client: &reqwest::Client,
+        csrf_token: &str,
+        client_seed: &str,
+    ) -> Result<f32, BetError> {
+        let reply = send_request(client.post("https://freebitco.in/").form(&[
+            ("csrf_token", csrf_token),
+            ("op", "free_play"),
+            ("fingerprint", ""),
+            ("client_seed", client_seed),
+            ("fingerprint2", ""),
+            // Pay with reward points rather than solving a captcha.
+            ("pwc", "1"),
+            ("g_recaptcha_response", ""),
+        ]))
+        .await?
+        .text()
+        .await?;
+
+        parse_free_play_reply(&reply).map_err(BetError::Unsupported)
+    }
+
+    /// Adds the free roll winnings claimed since the last call to the balance.
+    fn collect_faucet_claims(&mut self) {
+        let Some(claims) = &mut self.faucet_claims else {
+            return;
+        };
+        while let Ok(amount) = claims.try_recv() {
+            self.user_stats.balance += amount;
+            self.bonus_profit += amount;
+            if self.use_site_balance {
+                self.strategy
+                    .set_balance(self.strategy.get_balance() + amount);
+            }
+        }
+    }
+
+    /// Stops betting when a settled roll does not match its revealed seeds, instead of
+    /// only warning about it.
+    pub fn with_halt_on_unverified(mut self) -> Self {
+        self.halt_on_unverified = true;
+
+        self
+    }
+
+    /// Checks the roll `bet_result` settled against its revealed seeds.
+    fn verify_roll(&self, bet_result: &BetSiteResult) -> Result<(), BetError> {
+        let Some(roll) = bet_result.revealed_roll() else {
+            return Ok(());
+        };
+        if let Err(mismatch) = verify::verify(&FreeBitcoScheme, &roll) {
+            log::warn!(
+                "Roll with nonce {} failed verification: {}",
+                roll.nonce,
+                mismatch
+            );
+            if self.halt_on_unverified {
+                return Err(mismatch.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Saves the login session to `path`, and reuses the one saved there while the site
+    /// still accepts it.
+    pub fn with_session_file(mut self, path: String) -> Self {
+        self.session_file = Some(path);
+
+        self
+    }
+
+    /// Account stats of the logged in user; fails when the session is not logged in.
+    ///
+    /// Takes the client rather than `&self`, whose strategy is not `Sync`.
+    async fn fetch_user_stats(client: &reqwest::Client) -> Result<UserStats, BetError> {
+        let user_stats_res: serde_json::Value =
+            send_request(client.get("https://freebitco.in/cgi-bin/api.pl?op=get_user_stats"))
+                .await?
+                .json()
+                .await?;
+        if !user_stats_res["balance"].is_number() {
+            return Err(BetError::LoginFailed);
+        }
+
+        Ok(UserStats::from(user_stats_res))
+    }
+
+    fn set_user_stats(&mut self, user_stats: UserStats) {
+        self.user_stats = user_stats;
+        if self.use_site_balance {
+            self.strategy.set_balance(self.user_stats.balance);
+        }
+    }
+}
+
+impl SiteConfig for FreeBitcoIn {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_max_bet_fraction(mut self, fraction: f32) -> Self {
+        self.max_bet_fraction = fraction;
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance;
+
+        self
+    }
+
+    fn with_username(mut self, btc_address: String) -> Self {
+        self.btc_address = btc_address;
+
+        self
+    }
+
+    fn with_password(mut self, password: String) -> Self {
+        self.password = password;
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        let min_bet = effective_min_bet(
+            SITE_MIN_BET,
+            Some(self.min_bet_override.unwrap_or(DEFAULT_MIN_BET)),
+        );
+        self.strategy = self.sizing.strategy(strategy, DEFAULT_BALANCE, min_bet);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+        self.strategy = sizing.strategy(TomlStrategies::None, DEFAULT_BALANCE, DEFAULT_MIN_BET);
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_seed_rotation(mut self, policy: RotationPolicy) -> Self {
+        self.seeds = std::mem::take(&mut self.seeds).with_policy(policy);
+
+        self
+    }
+
+    fn with_seed_file(mut self, path: String) -> Self {
+        self.seeds = std::mem::take(&mut self.seeds).with_file(path);
+
+        self
+    }
 }
 
 impl Default for FreeBitcoIn {
     fn default() -> Self {
         Self {
             rolls: 0,
+            wagered: 0.,
             client_seed: "BeO2jZRd4nidPz4U40e2G7hT22s9GA".to_string(),
             current_bet: 2e-8,
             multiplier: 2.,
             user_stats: UserStats::default(),
-            profit: 0.,
+            dice_profit: 0.,
+            bonus_profit: 0.,
             prediction: 0,
-            strategy: Box::new(
-                // crate::strategies::blaks_runner::BlaksRunner5_0::default()
-                crate::strategies::none::NoStrat::default()
-                    // crate::strategies::my_strategy::MyStrat::default()
-                    .with_balance(0.02)
-                    .with_min_bet(0.000008)
-                    .with_initial_bet(0.000008),
+            strategy: BetSizingConfig::default().strategy(
+                TomlStrategies::None,
+                DEFAULT_BALANCE,
+                DEFAULT_MIN_BET,
             ),
             client: reqwest::Client::new(),
+            proxy: None,
             cookie_jar: Arc::new(Jar::default()),
             csrf_token: String::new(),
+            session_file: None,
+            btc_address: String::new(),
+            password: String::new(),
+            tfa_code: None,
+            totp: None,
+            reward_bonuses: Vec::new(),
+            reward_check_every: DEFAULT_REWARD_CHECK_EVERY,
+            bonus_wager_remaining: 0.,
+            claim_faucet: false,
+            faucet_claims: None,
+            sizing: BetSizingConfig::default(),
             history: Vec::new(),
-            history_size: 10,
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
             use_site_balance: true,
             use_fake_betting: false,
-            wins: 0,
-            loses: 0,
+            min_chance: 1.,
+            max_multiplier: 4750.,
+            max_bet: None,
+            max_bet_fraction: 1.,
+            min_bet_override: None,
+            halt_on_unverified: false,
+            seeds: SeedManager::default(),
+            last_rule: BetRule::Normal,
         }
     }
 }
@@ -181,10 +669,14 @@ impl Default for FreeBitcoIn {
 #[async_trait]
 impl Site for FreeBitcoIn {
     async fn login(&mut self) -> Result<(), BetError> {
-        self.client = reqwest::Client::builder()
-            .cookie_store(true)
-            .cookie_provider(Arc::clone(&self.cookie_jar))
-            .build()?;
+        self.client = self.build_client()?;
+
+        // The seed is sent with every bet, so a new one takes effect without telling the site.
+        if self.seeds.policy().per_session {
+            self.client_seed = self.seeds.rotate();
+        } else {
+            self.seeds.use_seed(self.client_seed.clone());
+        }
 
         if !self.use_site_balance {
             self.user_stats.balance = self.strategy.get_balance();
@@ -197,6 +689,23 @@ impl Site for FreeBitcoIn {
         let url = "https://freebitco.in/"
             .parse::<reqwest::Url>()
             .expect("Failed to parse freebitco.in address");
+
+        if let Some(session) = self.session_file.as_deref().and_then(Session::load) {
+            session.apply(&self.cookie_jar, &url);
+            match Self::fetch_user_stats(&self.client).await {
+                Ok(user_stats) => {
+                    log::info!("Reusing the saved FreeBitco.in session");
+                    self.csrf_token = session.csrf_token;
+                    self.set_user_stats(user_stats);
+                    Self::redeem_rewards(&self.client, &self.csrf_token, &self.reward_bonuses)
+                        .await;
+                    self.start_faucet_claims();
+                    return Ok(());
+                }
+                Err(e) => log::info!("Saved FreeBitco.in session expired ({e}), logging in"),
+            }
+        }
+
         let charset = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
             .chars()
             .collect::<Vec<char>>();
@@ -215,109 +724,119 @@ impl Site for FreeBitcoIn {
 
         let _ = self.client.get(url.clone()).send().await?;
         let login_post = LoginRequest {
-            csrf_token: "".to_string(),
+            csrf_token: csrf_token.clone(),
             op: "login_new".to_string(),
-            btc_address: "".to_string(),
-            password: "".to_string(),
-            tfa_code: "".to_string(),
+            btc_address: self.btc_address.clone(),
+            password: self.password.clone(),
+            tfa_code: match &self.totp {
+                Some(totp) => totp.now(),
+                None => self.tfa_code.clone().unwrap_or_default(),
+            },
         };
 
-        let login_response = self
-            .client
-            .post(url.clone())
-            .form(&[
-                ("csrf_token", login_post.csrf_token),
-                ("op", login_post.op),
-                ("btc_address", login_post.btc_address),
-                ("password", login_post.password),
-                ("tfa_code", login_post.tfa_code),
-            ])
-            .send()
-            .await?
-            .text()
-            .await?;
+        let login_response = send_request(self.client.post(url.clone()).form(&[
+            ("csrf_token", login_post.csrf_token),
+            ("op", login_post.op),
+            ("btc_address", login_post.btc_address),
+            ("password", login_post.password),
+            ("tfa_code", login_post.tfa_code),
+        ]))
+        .await?
+        .text()
+        .await?;
 
         let login_res_split: Vec<&str> = login_response.split(':').collect();
 
-        if login_res_split.len() < 4 {
+        if login_res_split.len() < 5 {
             eprintln!("{login_response}");
             return Err(BetError::LoginFailed);
         }
 
-        self.cookie_jar.add_cookie_str(
-            &format!("btc_address={}; Path=/; Secure", login_res_split[1]),
-            &url,
-        );
-        self.cookie_jar.add_cookie_str(
-            &format!("fbtc_session={}; Path=/; Secure", login_res_split[4]),
-            &url,
-        );
-        self.cookie_jar.add_cookie_str(
-            &format!("fbtc_userid={}; Path=/; Secure", login_res_split[3]),
-            &url,
-        );
-        self.cookie_jar
-            .add_cookie_str("have_account=1; Path=/; Secure", &url);
-        self.cookie_jar.add_cookie_str(
-            &format!("password={}; Path=/; Secure", login_res_split[2]),
-            &url,
-        );
-
-        let user_stats_res: serde_json::Value = self
-            .client
-            .get("https://freebitco.in/cgi-bin/api.pl?op=get_user_stats")
-            .send()
-            .await?
-            .json()
-            .await?;
-        self.user_stats = UserStats::from(user_stats_res);
-        if self.use_site_balance {
-            self.strategy.set_balance(self.user_stats.balance);
+        let session = Session {
+            csrf_token,
+            btc_address: login_res_split[1].to_string(),
+            fbtc_session: login_res_split[4].to_string(),
+            fbtc_userid: login_res_split[3].to_string(),
+            password: login_res_split[2].to_string(),
+        };
+        session.apply(&self.cookie_jar, &url);
+        if let Some(path) = &self.session_file {
+            if let Err(e) = session.save(path) {
+                log::warn!("Failed to save the session to {path}: {e}");
+            }
         }
 
+        let user_stats = Self::fetch_user_stats(&self.client).await?;
+        self.set_user_stats(user_stats);
+        Self::redeem_rewards(&self.client, &self.csrf_token, &self.reward_bonuses).await;
+        self.start_faucet_claims();
+
         Ok(())
     }
 
-    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetResult, BetError> {
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
         self.rolls += 1;
         let next_bet_data = self.strategy.get_next_bet(prediction, confidence);
         self.current_bet = next_bet_data.0;
         self.multiplier = next_bet_data.1;
         let high = next_bet_data.3;
-        let mut chance = (55.) * (1. - ((prediction - 5000.).abs() / 5000.));
-        chance = chance.clamp(0.01, 50.);
+        self.last_rule = BetRule::Normal;
+        let chance = (55.) * (1. - ((prediction - 5000.).abs() / 5000.));
+        let floored = floor_chance(chance, self.min_chance);
+        if floored != chance {
+            self.last_rule = BetRule::ChanceFloored;
+        }
+        let chance = floored.min(50.);
+
+        let multiplier = 1. / (chance / 100.);
+        self.multiplier = cap_multiplier(multiplier, 1.01, self.max_multiplier);
+        if self.multiplier != multiplier {
+            self.last_rule = BetRule::Capped;
+        }
 
-        let mut multiplier = 1. / (chance / 100.);
-        multiplier = multiplier.clamp(1.01, 4750.);
-        self.multiplier = multiplier;
+        if let Some(max_bet) = self.max_bet {
+            let bet = cap_bet(self.current_bet, max_bet, self.max_bet_fraction);
+            if bet != self.current_bet {
+                self.current_bet = bet;
+                self.last_rule = BetRule::Capped;
+            }
+        }
 
         if self.history.len() < self.history_size {
-            self.current_bet = 1e-8;
+            self.current_bet = self.min_bet();
             self.multiplier = 2.;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        self.collect_faucet_claims();
+        check_funds(self.current_bet, self.user_stats.balance)?;
+
+        if self.seeds.rotation_due() {
+            log::info!(
+                "Randomizing the client seed after {} bets",
+                self.seeds.bets_on_seed()
+            );
+            self.client_seed = self.seeds.rotate();
         }
 
         if self.use_fake_betting {
             let bet_result =
                 free_bitcoin_fake_bet(high, &self.client_seed, self.current_bet, self.multiplier);
+            self.seeds
+                .on_bet(parse_or_default(&bet_result.previous_nonce, "nonce"));
+            let mut bet_result: BetResult = bet_result.into();
+            bet_result.is_high = high;
+            bet_result.bet_amount = self.current_bet;
 
-            self.history.push(bet_result.clone().into());
-            if self.history.len() > self.history_size {
-                self.history = self.history[1..].to_vec();
-            }
-
-            if self.current_bet > self.user_stats.balance {
-                self.loses += 1;
-                self.strategy.set_balance(0.0001);
-                self.strategy.reset();
-                let next_bet_data = self.strategy.get_next_bet(prediction, confidence);
-                self.current_bet = next_bet_data.0;
-                self.multiplier = next_bet_data.1;
+            push_history(&mut self.history, bet_result.clone(), self.max_history);
 
-                panic!("W: {} || L: {}", self.wins, self.loses);
+            Ok(BetOutcome::Resolved(bet_result))
+        } else {
+            if self.rolls.is_multiple_of(self.reward_check_every) {
+                Self::redeem_rewards(&self.client, &self.csrf_token, &self.reward_bonuses).await;
             }
 
-            Ok(bet_result.into())
-        } else {
             let bet_url = Url::parse_with_params(
                 "https://freebitco.in/cgi-bin/bet.pl",
                 &[
@@ -336,34 +855,67 @@ impl Site for FreeBitcoIn {
             )
             .expect("Failed to create freebitco.in bet URL");
 
-            let bet_response = self.client.get(bet_url).send().await?.text().await?;
-            let bet_result = BetSiteResult::from(bet_response.as_str());
-
-            self.history.push(bet_result.clone().into());
-            if self.history.len() > self.history_size {
-                self.history = self.history[1..].to_vec();
+            let bet_response = send_request(self.client.get(bet_url)).await?.text().await?;
+            let bet_result = BetSiteResult::try_from(bet_response.as_str())?;
+            self.verify_roll(&bet_result)?;
+            self.seeds
+                .on_bet(parse_or_default(&bet_result.previous_nonce, "nonce"));
+            self.bonus_profit += bet_result.bonus_profit();
+            if self.bonus_wager_remaining > 0. && bet_result.bonus_acount_wager_remaining <= 0. {
+                log::info!("Bonus wagering complete, the bonus balance is released");
             }
-
-            if self.current_bet > self.user_stats.balance {
-                panic!("Not enough money!");
+            self.bonus_wager_remaining = bet_result.bonus_acount_wager_remaining;
+            if bet_result.max_bet > 0. {
+                self.max_bet = Some(bet_result.max_bet);
             }
+            let mut bet_result: BetResult = bet_result.into();
+            bet_result.is_high = high;
+            bet_result.bet_amount = self.current_bet;
+
+            push_history(&mut self.history, bet_result.clone(), self.max_history);
 
-            Ok(bet_result.into())
+            Ok(BetOutcome::Resolved(bet_result))
         }
     }
 
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // FreeBitco.in bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        // The FreeBitco.in client has no withdrawal endpoint wired up yet.
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        // Without the site balance, bets are tracked against the strategy's own.
+        if self.use_fake_betting || !self.use_site_balance {
+            return Ok(self.user_stats.balance);
+        }
+
+        let user_stats = Self::fetch_user_stats(&self.client).await?;
+        self.set_user_stats(user_stats);
+
+        Ok(self.user_stats.balance)
+    }
+
     fn on_win(&mut self, bet_result: &BetResult) {
         self.user_stats.balance += bet_result.win_amount;
-        self.profit += bet_result.win_amount;
+        self.dice_profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
         self.strategy.on_win(bet_result);
+        log::debug!("Strategy state: {}", self.strategy.describe_state());
     }
 
     fn on_lose(&mut self, bet_result: &BetResult) {
         self.user_stats.balance -= bet_result.win_amount;
-        self.profit -= bet_result.win_amount;
+        self.dice_profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
         // let mut bet_result = bet_result.clone();
         // bet_result.win_amount = -bet_result.win_amount;
         self.strategy.on_lose(bet_result);
+        log::debug!("Strategy state: {}", self.strategy.describe_state());
     }
 
     fn get_history(&self) -> Vec<BetResult> {
@@ -374,10 +926,32 @@ impl Site for FreeBitcoIn {
         self.rolls
     }
 
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = self.build_client()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            seed_rotation: true,
+            jackpot: true,
+            ..Capabilities::default()
+        }
+    }
+
     fn get_current_bet(&self) -> f32 {
         self.current_bet
     }
 
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
     fn get_current_multiplier(&self) -> f32 {
         self.multiplier
     }
@@ -387,10 +961,142 @@ impl Site for FreeBitcoIn {
     }
 
     fn get_profit(&self) -> f32 {
-        self.profit
+        self.dice_profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_bonus_profit(&self) -> f32 {
+        self.bonus_profit
     }
 
     fn get_balance(&self) -> f32 {
         self.user_stats.balance
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bonus_profit_is_separate_from_dice_winnings() {
+        let reply = "s1:w:6789:0.00100000:0.00000050:x:hashnext:seed:42:seedprev:hashprev:x:41:1:x:0.00000200:0.00000350:0:0:0:0.00099950:0.00100000:0.00000100";
+        let bet = BetSiteResult::try_from(reply).unwrap();
+
+        assert_eq!(bet.amount_won, 0.0000005);
+        assert!((bet.bonus_profit() - 0.0000045).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_malformed_bet_replies_are_errors() {
+        let reply = "s1:w:6789:0.00100000:0.00000050:x:hashnext:seed:42:seedprev:hashprev:x:41:1:x:0.00000200:0.00000350:0:0:0:0.00099950:0.00100000";
+
+        // One field short of a full reply.
+        assert!(matches!(
+            BetSiteResult::try_from(reply),
+            Err(BetError::UnexpectedReply(_))
+        ));
+        assert!(matches!(
+            BetSiteResult::try_from(format!("{reply}:not a balance").as_str()),
+            Err(BetError::UnexpectedReply(_))
+        ));
+        assert!(BetSiteResult::try_from(format!("{reply}:0.00000100").as_str()).is_ok());
+    }
+
+    #[test]
+    fn test_unverified_rolls_halt_only_when_asked() {
+        use crate::sites::roll_scheme::RollScheme;
+
+        let roll = FreeBitcoScheme.roll("seedprev", "seed", 41);
+        let hash = FreeBitcoScheme.server_seed_hash("seedprev");
+        let reply = |number: u32| {
+            BetSiteResult::try_from(format!("s1:w:{number}:0.001:0.0000005:x:hashnext:seed:42:seedprev:{hash}:x:41:0:x:0:0:0:0:0:0.001:0.001:0").as_str()).unwrap()
+        };
+        let wrong = reply((roll + 1) % 10_000);
+
+        assert!(FreeBitcoIn::default().verify_roll(&reply(roll)).is_ok());
+        assert!(FreeBitcoIn::default().verify_roll(&wrong).is_ok());
+        assert!(matches!(
+            FreeBitcoIn::default()
+                .with_halt_on_unverified()
+                .verify_roll(&wrong),
+            Err(BetError::VerificationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_redeem_reply_reports_refusal_reason() {
+        assert_eq!(parse_redeem_reply("s:1200:0.00000100"), Ok(()));
+        assert_eq!(
+            parse_redeem_reply("e:You do not have enough reward points"),
+            Err("You do not have enough reward points".to_string())
+        );
+    }
+
+    #[test]
+    fn test_free_play_reply_yields_amount_won() {
+        assert_eq!(
+            parse_free_play_reply("s:4821:0.00051234:0.00000034:1700000000"),
+            Ok(0.00000034)
+        );
+        assert_eq!(
+            parse_free_play_reply("e:You need to wait before claiming again"),
+            Err("You need to wait before claiming again".to_string())
+        );
+        assert!(parse_free_play_reply("s:4821").is_err());
+    }
+
+    #[test]
+    fn test_faucet_claims_add_to_balance() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut site = FreeBitcoIn {
+            faucet_claims: Some(rx),
+            ..FreeBitcoIn::default()
+        };
+        let balance = site.get_balance();
+
+        tx.send(0.0000005).unwrap();
+        site.collect_faucet_claims();
+
+        assert!((site.get_balance() - balance - 0.0000005).abs() < 1e-9);
+        assert_eq!(site.get_bonus_profit(), 0.0000005);
+    }
+
+    #[test]
+    fn test_session_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("fbtc-session-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let session = Session {
+            csrf_token: "csrf".to_string(),
+            btc_address: "1Address".to_string(),
+            fbtc_session: "session".to_string(),
+            fbtc_userid: "42".to_string(),
+            password: "hash".to_string(),
+        };
+
+        session.save(path).unwrap();
+        let loaded = Session::load(path);
+        std::fs::write(path, "not json").unwrap();
+        let corrupt = Session::load(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded, Some(session));
+        assert_eq!(corrupt, None);
+        assert_eq!(Session::load(path), None);
+    }
+
+    #[test]
+    fn test_wagered_counts_won_and_lost_stakes() {
+        use crate::strategies::{assert_close, test_bet_result};
+
+        let mut site = FreeBitcoIn::default();
+        site.on_win(&test_bet_result(true, 6000, 1e-6));
+        site.on_lose(&test_bet_result(false, 2000, 3e-6));
+
+        assert_close(site.get_wagered(), 4e-6);
+        assert_close(site.get_profit(), -2e-6);
+    }
+}
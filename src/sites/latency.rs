@@ -0,0 +1,168 @@
+//! Round-trip timing of bet requests.
+//!
+//! `settle_bet` records how long each `do_bet` call took. The rolling average drives
+//! the delay between bets, so the bot slows down while the site is under load, and
+//! the session totals are logged when betting stops. `DelayJitter` then spreads that
+//! delay randomly, so requests do not arrive at a machine-regular cadence.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+/// Samples the rolling average is taken over.
+pub const LATENCY_WINDOW: usize = 20;
+
+/// Latency of recent and all bet requests in a session.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyStats {
+    recent: VecDeque<Duration>,
+    count: u32,
+    total: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        if self.recent.len() == LATENCY_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency);
+
+        self.count += 1;
+        self.total += latency;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = Some(self.max.map_or(latency, |max| max.max(latency)));
+    }
+
+    /// Number of requests recorded this session.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Average over the last `LATENCY_WINDOW` requests.
+    pub fn rolling_average(&self) -> Option<Duration> {
+        let len = u32::try_from(self.recent.len())
+            .ok()
+            .filter(|len| *len > 0)?;
+
+        Some(self.recent.iter().sum::<Duration>() / len)
+    }
+
+    /// Average over the whole session.
+    pub fn average(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// Delay before the next bet: at least `base`, stretched to the rolling average
+    /// latency when the site is answering slower than that.
+    pub fn bet_delay(&self, base: Duration) -> Duration {
+        self.rolling_average()
+            .map_or(base, |average| base.max(average))
+    }
+}
+
+/// Random offset of up to `jitter` either side of the delay between bets.
+#[derive(Debug)]
+pub struct DelayJitter {
+    jitter: Duration,
+    rng: StdRng,
+}
+
+impl DelayJitter {
+    /// Without a `seed` the offsets are drawn from entropy; with one they repeat per run.
+    pub fn new(jitter: Duration, seed: Option<u64>) -> Self {
+        Self {
+            jitter,
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            },
+        }
+    }
+
+    /// `base ± jitter`, never below zero.
+    pub fn apply(&mut self, base: Duration) -> Duration {
+        if self.jitter.is_zero() {
+            return base;
+        }
+
+        let jitter = self.jitter.as_nanos() as i128;
+        let offset = self.rng.random_range(-jitter..=jitter);
+        let delay = (base.as_nanos() as i128 + offset).max(0);
+        Duration::from_nanos(delay as u64)
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.average(), self.max) {
+            (Some(min), Some(average), Some(max)) => write!(
+                f,
+                "{} bets, latency min {:?} / avg {:?} / max {:?}",
+                self.count, min, average, max
+            ),
+            _ => write!(f, "no bets timed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_average_tracks_recent_window() {
+        let mut stats = LatencyStats::default();
+        let base = Duration::from_millis(100);
+        assert_eq!(stats.bet_delay(base), base);
+
+        stats.record(Duration::from_millis(1000));
+        for _ in 0..LATENCY_WINDOW {
+            stats.record(Duration::from_millis(50));
+        }
+
+        assert_eq!(stats.rolling_average(), Some(Duration::from_millis(50)));
+        assert_eq!(stats.min(), Some(Duration::from_millis(50)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(1000)));
+        assert_eq!(stats.count(), LATENCY_WINDOW as u32 + 1);
+        assert_eq!(stats.bet_delay(base), base);
+
+        for _ in 0..LATENCY_WINDOW {
+            stats.record(Duration::from_millis(400));
+        }
+        assert_eq!(stats.bet_delay(base), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_delay_jitter_is_bounded_and_seeded() {
+        let base = Duration::from_millis(100);
+        let jitter = Duration::from_millis(30);
+        let delays = |seed| {
+            let mut jitter = DelayJitter::new(jitter, Some(seed));
+            (0..50).map(|_| jitter.apply(base)).collect::<Vec<_>>()
+        };
+
+        let first = delays(7);
+        assert_eq!(first, delays(7));
+        assert_ne!(first, delays(8));
+        assert!(first
+            .iter()
+            .all(|delay| *delay >= base - jitter && *delay <= base + jitter));
+        assert!(first.iter().any(|delay| *delay != base));
+
+        assert_eq!(DelayJitter::new(Duration::ZERO, Some(7)).apply(base), base);
+        let mut wide = DelayJitter::new(Duration::from_secs(10), Some(7));
+        assert!((0..50).all(|_| wide.apply(base) <= base + Duration::from_secs(10)));
+    }
+}
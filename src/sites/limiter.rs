@@ -0,0 +1,74 @@
+//! Shared budget for outbound site requests.
+//!
+//! Bets, balance lookups and any background refresh of a site all go through one
+//! `RequestLimiter`, so the total number of requests in flight stays within the
+//! configured bound no matter how many tasks are talking to the site.
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::sites::{send_request, BetError};
+
+/// Bounds the number of concurrent requests across every clone of the limiter.
+#[derive(Clone, Debug)]
+pub struct RequestLimiter {
+    permits: Arc<Semaphore>,
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl RequestLimiter {
+    /// Creates a limiter allowing `max_concurrent` requests at once (at least one).
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Waits for a free slot; the slot is released when the permit is dropped.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Request limiter semaphore is never closed")
+    }
+
+    /// Sends `request` once a slot is free, with `send_request`'s handling of 429 replies.
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, BetError> {
+        let _permit = self.acquire().await;
+        send_request(request).await
+    }
+
+    /// Number of requests that could start right now.
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clones_share_one_budget() {
+        let limiter = RequestLimiter::new(2);
+        let other = limiter.clone();
+
+        let first = limiter.acquire().await;
+        let _second = other.acquire().await;
+        assert_eq!(limiter.available(), 0);
+
+        drop(first);
+        assert_eq!(other.available(), 1);
+        assert_eq!(RequestLimiter::new(0).available(), 1);
+    }
+}
@@ -3,42 +3,77 @@
 //! This module contains implementations for different gambling sites,
 //! providing a unified interface through the `Site` trait.
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 
+use crate::sites::latency::LatencyStats;
+use crate::util::Roll;
+
+pub mod bc_game;
+pub mod bitsler;
 pub mod crypto_games;
+pub mod dice999;
 pub mod duck_dice;
+pub mod factory;
 pub mod fake_test;
 pub mod free_bitco_in;
+pub mod latency;
+pub mod limiter;
+pub mod nonce;
+pub mod primedice;
+pub mod retry;
+pub mod roll_scheme;
+pub mod seeds;
+pub mod signing;
+pub mod tor;
+pub mod totp;
+pub mod verify;
 pub mod windice;
+pub mod wolf_bet;
 
-#[derive(Debug)]
+pub use factory::{build_site, site_strategy};
+
+#[derive(Debug, thiserror::Error)]
 pub enum BetError {
+    #[error("Received empty reply from server")]
     EmptyReply,
+    #[error("Operation failed")]
     Failed,
+    #[error("Login failed")]
     LoginFailed,
+    #[error("Configuration error: {0}")]
     ConfigError(String),
+    #[error("Model error: {0}")]
     ModelError(String),
-    ReqwestError(reqwest::Error),
-}
-
-impl std::fmt::Display for BetError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BetError::EmptyReply => write!(f, "Received empty reply from server"),
-            BetError::Failed => write!(f, "Operation failed"),
-            BetError::LoginFailed => write!(f, "Login failed"),
-            BetError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
-            BetError::ModelError(msg) => write!(f, "Model error: {}", msg),
-            BetError::ReqwestError(e) => write!(f, "Network error: {}", e),
-        }
-    }
+    #[error("Network error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("Failed to parse site response: {0}")]
+    ParseError(#[from] serde_json::Error),
+    /// A reply that is not in the format the site's API documents.
+    #[error("Unexpected site response: {0}")]
+    UnexpectedReply(String),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Not supported by this site: {0}")]
+    Unsupported(String),
+    #[error("Insufficient funds: the bet needs {needed:.8} but the balance is {available:.8}")]
+    InsufficientFunds { needed: f32, available: f32 },
+    /// The site refused the bet for sending too fast; carries its `retry-after`, if any.
+    #[error("Rate limited by the site")]
+    RateLimited(Option<Duration>),
+    /// A settled bet skipped nonces after the previous one on its seed chain.
+    #[error("Nonce mismatch: expected {expected} but the site reported {reported}")]
+    NonceMismatch { expected: u32, reported: u32 },
+    #[error("Provably-fair verification failed: {0}")]
+    VerificationFailed(#[from] verify::Mismatch),
+    #[error("WebSocket error: {0}")]
+    WebSocketError(Box<tokio_tungstenite::tungstenite::Error>),
 }
 
-impl std::error::Error for BetError {}
-
-impl From<reqwest::Error> for BetError {
-    fn from(value: reqwest::Error) -> Self {
-        Self::ReqwestError(value)
+impl From<tokio_tungstenite::tungstenite::Error> for BetError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocketError(Box::new(err))
     }
 }
 
@@ -50,8 +85,11 @@ pub struct BetResult {
     pub nonce: u32,
     pub symbol: String,
     pub result: bool,
+    /// Side the bet was placed on, as sent in the bet request.
     pub is_high: bool,
-    pub number: u32,
+    /// Whether the roll landed in the upper half of the range, whichever side was bet.
+    pub rolled_high: bool,
+    pub number: Roll,
     pub threshold: u32,
     pub chance: f32,
     pub payout: f32,
@@ -59,17 +97,37 @@ pub struct BetResult {
     pub win_amount: f32,
 }
 
+impl BetResult {
+    /// Whether this bet directly follows `previous` on its seed chain: the same client
+    /// seed, one nonce later.
+    pub fn follows(&self, previous: &BetResult) -> bool {
+        self.client_seed == previous.client_seed
+            && previous.nonce.checked_add(1) == Some(self.nonce)
+    }
+
+    /// Net result of the bet: `win_amount` on a win, minus it on a loss.
+    pub fn profit(&self) -> f32 {
+        if self.result {
+            self.win_amount
+        } else {
+            -self.win_amount
+        }
+    }
+}
+
 impl From<free_bitco_in::BetSiteResult> for BetResult {
     fn from(value: free_bitco_in::BetSiteResult) -> Self {
         Self {
             hash_previous_roll: value.server_seed_hash_previous_roll,
             hash_next_roll: value.server_seed_hash_next_roll,
             client_seed: value.client_seed_previous_roll.clone(),
-            nonce: value.nonce_next_roll.clone().parse::<u32>().unwrap_or(0),
+            nonce: parse_or_default(&value.nonce_next_roll, "nonce"),
             symbol: "BTC".to_string(),
             result: value.result,
-            is_high: value.rolled_number > 5000 && value.result,
-            number: value.rolled_number,
+            // The reply does not echo the side bet on; `do_bet` fills it in from the request.
+            is_high: false,
+            rolled_high: Roll::clamped(value.rolled_number).is_high(),
+            number: Roll::clamped(value.rolled_number),
             // We can't get this number from freebitco.in without external data so we won't include
             // that data.
             threshold: 0,
@@ -77,7 +135,7 @@ impl From<free_bitco_in::BetSiteResult> for BetResult {
             chance: 0.,
             // And for this as well.
             payout: 0.,
-            // You guessed it.
+            // Filled in by `do_bet` from the request, like `is_high`.
             bet_amount: 0.,
             win_amount: value.amount_won,
         }
@@ -86,20 +144,29 @@ impl From<free_bitco_in::BetSiteResult> for BetResult {
 
 impl From<duck_dice::BetMakeResponse> for BetResult {
     fn from(value: duck_dice::BetMakeResponse) -> Self {
+        value.bet.into()
+    }
+}
+
+impl From<duck_dice::Bet> for BetResult {
+    fn from(value: duck_dice::Bet) -> Self {
         Self {
-            hash_previous_roll: value.bet.previous_hash.clone(),
-            hash_next_roll: value.bet.hash.clone(),
+            hash_previous_roll: value.previous_hash.clone(),
+            hash_next_roll: value.hash.clone(),
             client_seed: String::new(),
-            nonce: value.bet.nonce as u32,
-            symbol: value.bet.symbol,
-            result: value.bet.result,
-            is_high: value.bet.choice.chars().next().unwrap_or(' ') == '>',
-            number: value.bet.number,
+            nonce: value.nonce as u32,
+            symbol: value.symbol,
+            result: value.result,
+            is_high: value.choice.chars().next().unwrap_or(' ') == '>',
+            rolled_high: Roll::clamped(value.number).is_high(),
+            number: Roll::clamped(value.number),
             threshold: 0,
-            chance: value.bet.chance,
-            payout: value.bet.payout,
-            bet_amount: value.bet.bet_amount,
-            win_amount: value.bet.profit,
+            chance: value.chance,
+            payout: value.payout,
+            bet_amount: value.bet_amount,
+            // DuckDice reports a loss as negative profit; positive for both outcomes, as
+            // sites pass it to `on_lose`.
+            win_amount: value.profit.abs(),
         }
     }
 }
@@ -113,21 +180,287 @@ impl From<crypto_games::BetSiteResult> for BetResult {
             nonce: 0,
             symbol: "SOL".to_string(),
             result: value.profit > 0.,
-            is_high: value.roll as u32 > 5000 && value.profit > 0.,
-            number: value.roll as u32,
+            // The reply does not echo the side bet on; `do_bet` fills it in from the request.
+            is_high: false,
+            rolled_high: Roll::clamped(value.roll as u32).is_high(),
+            number: Roll::clamped(value.roll as u32),
             threshold: 0,
             chance: 0.,
             payout: value.payout as f32,
+            // Filled in by `do_bet` from the request, like `is_high`.
             bet_amount: 0.,
-            win_amount: value.profit as f32,
+            // The site reports a loss as negative profit; positive for both outcomes, as
+            // sites pass it to `on_lose`.
+            win_amount: value.profit.abs() as f32,
+        }
+    }
+}
+
+impl From<windice::BetSiteResult> for BetResult {
+    fn from(value: windice::BetSiteResult) -> Self {
+        let roll = value.roll;
+        let result = roll.win > 0.;
+        let chance = (roll.high + 1 - roll.low) as f32 / 100.;
+
+        Self {
+            hash_previous_roll: roll.hash,
+            hash_next_roll: value.next_hash,
+            client_seed: value.client_seed,
+            nonce: roll.nonce as u32,
+            symbol: roll.curr.to_uppercase(),
+            result,
+            // Bets on the high side are placed as a range ending at the top of the roll range.
+            is_high: roll.low > 0,
+            rolled_high: Roll::clamped(roll.result).is_high(),
+            number: Roll::clamped(roll.result),
+            threshold: 0,
+            chance,
+            // A loss pays 0, so only a win shows the multiplier the site paid at; a loss
+            // records the one its chance pays.
+            payout: if result && roll.bet > 0. {
+                (roll.win / roll.bet) as f32
+            } else {
+                payout_at(chance, windice::HOUSE_EDGE)
+            },
+            bet_amount: roll.bet as f32,
+            win_amount: if result {
+                (roll.win - roll.bet) as f32
+            } else {
+                roll.bet as f32
+            },
         }
     }
 }
 
+impl From<primedice::BetSiteResult> for BetResult {
+    fn from(value: primedice::BetSiteResult) -> Self {
+        let roll = value.roll;
+        let result = roll.payout > 0.;
+        let number = Roll::clamped(roll.state.scaled_result());
+
+        Self {
+            hash_previous_roll: value.server_seed_hash.clone(),
+            hash_next_roll: value.server_seed_hash,
+            client_seed: value.client_seed,
+            nonce: roll.nonce as u32,
+            symbol: roll.currency.to_uppercase(),
+            result,
+            is_high: roll.state.condition == primedice::Condition::Above,
+            rolled_high: number.is_high(),
+            number,
+            threshold: (roll.state.target * 100.).round() as u32,
+            chance: roll.state.chance(),
+            payout: roll.payout_multiplier as f32,
+            bet_amount: roll.amount as f32,
+            win_amount: if result {
+                (roll.payout - roll.amount) as f32
+            } else {
+                roll.amount as f32
+            },
+        }
+    }
+}
+
+impl From<bitsler::Roll> for BetResult {
+    fn from(value: bitsler::Roll) -> Self {
+        let result = value.profit > 0.;
+        let number = Roll::clamped(value.scaled_result());
+        let chance = value.chance();
+
+        Self {
+            hash_previous_roll: value.server_seed_hash.clone(),
+            // The server seed stays until the seeds are changed.
+            hash_next_roll: value.server_seed_hash,
+            client_seed: value.client_seed,
+            nonce: value.nonce as u32,
+            symbol: value.devise.to_uppercase(),
+            result,
+            is_high: value.condition == bitsler::Condition::Over,
+            rolled_high: number.is_high(),
+            number,
+            threshold: (value.game * 100.).round() as u32,
+            chance,
+            // The reply leaves out the multiplier, which follows from the chance.
+            payout: payout_at(chance, bitsler::HOUSE_EDGE),
+            bet_amount: value.amount as f32,
+            // Positive for both outcomes, as sites pass it to `on_lose`.
+            win_amount: value.profit.abs() as f32,
+        }
+    }
+}
+
+impl From<bc_game::Roll> for BetResult {
+    fn from(value: bc_game::Roll) -> Self {
+        let result = value.payout > 0.;
+        let number = Roll::clamped(value.scaled_result());
+        let chance = value.chance();
+
+        Self {
+            hash_previous_roll: value.server_seed_hash.clone(),
+            // The server seed stays until the seeds are changed.
+            hash_next_roll: value.server_seed_hash,
+            client_seed: value.client_seed,
+            nonce: value.nonce as u32,
+            symbol: value.currency.to_uppercase(),
+            result,
+            is_high: value.over,
+            rolled_high: number.is_high(),
+            number,
+            threshold: (value.target * 100.).round() as u32,
+            chance,
+            // The reply only has the amount paid, 0 on a loss, so the multiplier follows
+            // from the chance.
+            payout: payout_at(chance, bc_game::HOUSE_EDGE),
+            bet_amount: value.amount as f32,
+            win_amount: if result {
+                (value.payout - value.amount) as f32
+            } else {
+                value.amount as f32
+            },
+        }
+    }
+}
+
+impl From<wolf_bet::Bet> for BetResult {
+    fn from(value: wolf_bet::Bet) -> Self {
+        let result = value.state == "win";
+        let number = Roll::clamped(value.scaled_result());
+        let chance = value.chance();
+
+        Self {
+            hash_previous_roll: value.server_seed_hashed.clone(),
+            // The server seed stays until the seeds are refreshed.
+            hash_next_roll: value.server_seed_hashed,
+            client_seed: value.user_seed,
+            nonce: value.nonce as u32,
+            symbol: value.currency.to_uppercase(),
+            result,
+            is_high: value.rule == wolf_bet::Rule::Over,
+            rolled_high: number.is_high(),
+            number,
+            threshold: (value.bet_value * 100.).round() as u32,
+            chance,
+            payout: value.multiplier as f32,
+            bet_amount: value.amount as f32,
+            // Positive for both outcomes, as sites pass it to `on_lose`.
+            win_amount: value.profit.abs() as f32,
+        }
+    }
+}
+
+impl From<dice999::BetSiteResult> for BetResult {
+    fn from(value: dice999::BetSiteResult) -> Self {
+        let reply = value.reply;
+        let result = (value.low..=value.high).contains(&reply.secret);
+        let number = Roll::clamped(dice999::scale_roll(reply.secret));
+        let pay_in = value.pay_in as f32 / 1e8;
+        let chance = (value.high + 1 - value.low) as f32 / 10_000.;
+
+        Self {
+            hash_previous_roll: value.server_seed_hash,
+            hash_next_roll: reply.next,
+            client_seed: value.client_seed.to_string(),
+            // Every bet is rolled with a fresh server seed, so there is no nonce.
+            nonce: 0,
+            symbol: value.symbol,
+            result,
+            is_high: value.high == dice999::ROLL_MAX && value.low > 0,
+            rolled_high: number.is_high(),
+            number,
+            threshold: 0,
+            chance,
+            // The reply only has the amount paid, 0 on a loss, so the multiplier follows
+            // from the chance.
+            payout: payout_at(chance, dice999::HOUSE_EDGE),
+            bet_amount: pay_in,
+            win_amount: if result {
+                (reply.pay_out - value.pay_in) as f32 / 1e8
+            } else {
+                pay_in
+            },
+        }
+    }
+}
+
+/// Site-assigned identifier of a placed bet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BetId(pub String);
+
+/// State of a placed bet.
+#[derive(Clone, Debug)]
+pub enum BetOutcome {
+    /// The bet settled and its result is known.
+    Resolved(BetResult),
+    /// The bet was accepted but has not settled yet; poll it with `Site::poll_result`.
+    Pending(BetId),
+}
+
+/// Which rule decided the size or odds of the last bet, when it was not the strategy alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BetRule {
+    /// The strategy's bet was placed as requested.
+    #[default]
+    Normal,
+    /// The history was shorter than the model window, so a minimum 50% bet was placed.
+    Warmup,
+    /// The chance was raised to the site's minimum chance.
+    ChanceFloored,
+    /// The multiplier or stake was capped to the site's limits.
+    Capped,
+    /// The balance could not be refreshed, so only the minimum was bet.
+    StaleBalance,
+}
+
+impl std::fmt::Display for BetRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rule = match self {
+            Self::Normal => "normal",
+            Self::Warmup => "warmup",
+            Self::ChanceFloored => "chance-floored",
+            Self::Capped => "capped",
+            Self::StaleBalance => "stale-balance",
+        };
+        write!(f, "{rule}")
+    }
+}
+
+/// Optional features a site client implements, so callers can skip or warn about
+/// what a site cannot do instead of relying on no-op or `Unsupported` methods.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Client seeds are changed during a session.
+    pub seed_rotation: bool,
+    /// Bets can be placed from faucet funds.
+    pub faucet: bool,
+    /// Jackpot winnings are reported with bets.
+    pub jackpot: bool,
+    /// `Site::withdraw` can move funds off the site.
+    pub withdrawal: bool,
+    /// Several bets can be placed in one request.
+    pub batch_bets: bool,
+    /// Rolls are streamed over a websocket, so history can be read without betting.
+    pub websocket: bool,
+}
+
+/// How many times `settle_bet` polls a pending bet before giving up.
+const MAX_POLLS: u32 = 50;
+/// Delay between polls of a pending bet.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
 #[async_trait]
 pub trait Site {
     async fn login(&mut self) -> Result<(), BetError>;
-    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetResult, BetError>;
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError>;
+    /// Checks on a bet that `do_bet` returned as pending.
+    async fn poll_result(&mut self, id: &BetId) -> Result<BetOutcome, BetError>;
+    /// Moves `amount` of the betting currency off the site to `address`.
+    async fn withdraw(&mut self, amount: f32, address: &str) -> Result<(), BetError>;
+    /// Fetches the account balance from the site and returns it as the new betting
+    /// balance: it replaces the locally tracked one, which drifts from the account through
+    /// bonuses, tips and bets placed elsewhere, and is passed to the strategy's
+    /// `set_balance`. Sites not betting from the account balance, such as simulations,
+    /// keep the tracked one.
+    async fn refresh_balance(&mut self) -> Result<f32, BetError>;
     fn on_win(&mut self, bet_result: &BetResult);
     fn on_lose(&mut self, bet_result: &BetResult);
     fn get_history(&self) -> Vec<BetResult>;
@@ -135,16 +468,573 @@ pub trait Site {
     fn get_rolls(&self) -> u64;
     fn get_current_bet(&self) -> f32;
     fn get_current_multiplier(&self) -> f32;
+    /// Profit from dice rolls only.
     fn get_profit(&self) -> f32;
+    /// Jackpot, bonus and faucet credits, which `get_profit` leaves out.
+    fn get_bonus_profit(&self) -> f32 {
+        0.
+    }
+    /// Total staked on settled bets this session.
+    fn get_wagered(&self) -> f32;
+    /// Win target of the active strategy; 0 when it has none.
+    fn get_win_target(&self) -> f32 {
+        0.
+    }
     fn get_balance(&self) -> f32;
+    /// Balance of each account bets are placed from, for sites that rotate accounts.
+    fn get_account_balances(&self) -> Vec<f32> {
+        vec![self.get_balance()]
+    }
+    /// Rule that shaped the last bet placed by `do_bet`.
+    fn last_bet_rule(&self) -> BetRule {
+        BetRule::Normal
+    }
+    /// Drops the open connections to the site, so the next requests connect afresh,
+    /// e.g. over a new Tor circuit.
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        Ok(())
+    }
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// Parses a numeric `field` of a site response, falling back to the default.
+///
+/// Fallbacks are logged: nonces and amounts feed the model and the accounting, where a
+/// silently zeroed value would corrupt results without any visible error.
+pub fn parse_or_default<T>(value: &str, field: &str) -> T
+where
+    T: std::str::FromStr + Default + std::fmt::Debug,
+{
+    value.parse().unwrap_or_else(|_| {
+        let fallback = T::default();
+        log::warn!("Unparseable {field} {value:?} in site response, using {fallback:?}");
+        fallback
+    })
+}
+
+/// Parses a balance as sites format it: plain decimals, scientific notation (`"1e-5"`),
+/// thousands separators (`"1,234.5"`) and a trailing currency code (`"0.5 BTC"`).
+///
+/// A comma is read as a decimal point when it is the only separator and is not
+/// followed by a group of exactly three digits, as in `"0,00012345"`.
+pub fn parse_balance(value: &str) -> Option<f64> {
+    let number = value
+        .split_whitespace()
+        .next()?
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '_');
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let groups = integer.split(',').collect::<Vec<_>>();
+    let number = if groups.len() == 1 {
+        number.to_string()
+    } else if groups[1..].iter().all(|group| group.len() == 3) {
+        format!("{}.{}", groups.concat(), fraction)
+    } else if groups.len() == 2 && !number.contains('.') {
+        groups.join(".")
+    } else {
+        return None;
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|balance| balance.is_finite())
+}
+
+/// Parses a balance `field` of a site response with `parse_balance`, falling back to 0.
+pub fn parse_balance_or_default(value: &str, field: &str) -> f32 {
+    parse_balance(value).map_or_else(
+        || {
+            log::warn!("Unparseable {field} {value:?} in site response, using 0.0");
+            0.
+        },
+        |balance| balance as f32,
+    )
+}
+
+/// Deserializes an amount sites send either as a JSON number or as a string, reading
+/// strings like `parse_balance`.
+pub(crate) fn amount<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Number(f64),
+        Text(String),
+    }
+
+    match <Amount as serde::Deserialize>::deserialize(deserializer)? {
+        Amount::Number(amount) => Ok(amount),
+        Amount::Text(text) => parse_balance(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid amount {text:?}"))),
+    }
+}
+
+/// Appends `bet_result` to `history`, dropping the oldest entries beyond `max_history`.
+pub fn push_history(history: &mut Vec<BetResult>, bet_result: BetResult, max_history: usize) {
+    history.push(bet_result);
+    if history.len() > max_history {
+        history.drain(..history.len() - max_history);
+    }
+}
+
+/// Raises `chance` to `min_chance`, warning when it had to.
+///
+/// Chance formulas built on the prediction collapse towards 0 at the edges of the roll
+/// range, which would otherwise place near-impossible bets at the maximum multiplier.
+/// The bet is clamped rather than skipped: the next prediction comes from the next
+/// roll, so a skipped bet would be predicted (and skipped) again forever.
+pub fn floor_chance(chance: f32, min_chance: f32) -> f32 {
+    if chance.is_nan() || chance < min_chance {
+        log::warn!(
+            "Chance {:.4}% is below the {:.2}% floor, betting at the floor instead",
+            chance,
+            min_chance
+        );
+        return min_chance;
+    }
+
+    chance
+}
+
+/// Lowers `chance` to `max_chance`, the highest a site accepts, warning when it had to.
+pub fn cap_chance(chance: f32, max_chance: f32) -> f32 {
+    if chance > max_chance {
+        log::warn!(
+            "Chance {:.2}% exceeds the site maximum of {:.2}%, capping it",
+            chance,
+            max_chance
+        );
+        return max_chance;
+    }
+
+    chance
+}
+
+/// Bounds `chance` to `[min_chance, max_chance]`, together with the rule that moved it,
+/// or `BetRule::Normal` when it was already inside.
+pub fn bound_chance(chance: f32, min_chance: f32, max_chance: f32) -> (f32, BetRule) {
+    let floored = floor_chance(chance, min_chance);
+    if floored != chance {
+        return (floored, BetRule::ChanceFloored);
+    }
+    let capped = cap_chance(chance, max_chance);
+    if capped != chance {
+        return (capped, BetRule::Capped);
+    }
+
+    (chance, BetRule::Normal)
+}
+
+/// Multiplier paid on a win at `chance` percent by a site keeping `house_edge` percent.
+pub fn payout_at(chance: f32, house_edge: f32) -> f32 {
+    (100. - house_edge) / chance
+}
+
+/// Clamps `multiplier` into the site's `[min, max]` payout range, warning when a
+/// strategy asked for more than the site pays.
+pub fn cap_multiplier(multiplier: f32, min: f32, max: f32) -> f32 {
+    if multiplier > max {
+        log::warn!(
+            "Multiplier {:.2}x exceeds the site maximum of {:.2}x, capping it",
+            multiplier,
+            max
+        );
+    }
+
+    multiplier.clamp(min, max)
+}
+
+/// Caps `bet` at `fraction` of the largest stake the site accepts, warning when it does.
+pub fn cap_bet(bet: f32, site_max: f32, fraction: f32) -> f32 {
+    let max = site_max * fraction;
+    if bet > max {
+        log::warn!(
+            "Bet {:.8} exceeds {:.0}% of the site maximum of {:.8}, capping it",
+            bet,
+            fraction * 100.,
+            site_max
+        );
+        return max;
+    }
+
+    bet
+}
+
+/// Smallest bet to place: `min_bet_override` when set, but never below `site_min`.
+pub fn effective_min_bet(site_min: f32, min_bet_override: Option<f32>) -> f32 {
+    min_bet_override.map_or(site_min, |min_bet| min_bet.max(site_min))
+}
+
+/// Places a bet and polls it until it settles, recording the `do_bet` round-trip in `latency`.
+pub async fn settle_bet(
+    site: &mut dyn Site,
+    prediction: f32,
+    confidence: f32,
+    latency: &mut LatencyStats,
+) -> Result<BetResult, BetError> {
+    let started = std::time::Instant::now();
+    let mut outcome = site.do_bet(prediction, confidence).await?;
+    latency.record(started.elapsed());
+
+    for _ in 0..MAX_POLLS {
+        match outcome {
+            BetOutcome::Resolved(bet_result) => return Ok(bet_result),
+            BetOutcome::Pending(id) => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                outcome = site.poll_result(&id).await?;
+            }
+        }
+    }
+
+    match outcome {
+        BetOutcome::Resolved(bet_result) => Ok(bet_result),
+        BetOutcome::Pending(id) => {
+            log::warn!("Bet {:?} still unresolved after {} polls", id, MAX_POLLS);
+            Err(BetError::EmptyReply)
+        }
+    }
+}
+
+/// Consecutive empty replies after which the session is assumed dropped.
+pub const MAX_EMPTY_REPLIES: u32 = 10;
+
+/// Counts an empty reply, logging into `site` again once `MAX_EMPTY_REPLIES` arrived in
+/// a row so a dropped session is not retried forever.
+pub async fn handle_empty_reply(
+    site: &mut dyn Site,
+    empty_replies: &mut u32,
+) -> Result<(), BetError> {
+    *empty_replies += 1;
+    if *empty_replies < MAX_EMPTY_REPLIES {
+        return Ok(());
+    }
+
+    log::warn!("{} empty replies in a row, logging in again", empty_replies);
+    *empty_replies = 0;
+    site.login().await
+}
+
+/// Fails with `BetError::InsufficientFunds` when a bet of `needed` exceeds `available`.
+pub fn check_funds(needed: f32, available: f32) -> Result<(), BetError> {
+    if needed > available {
+        return Err(BetError::InsufficientFunds { needed, available });
+    }
+
+    Ok(())
+}
+
+/// Attempts at a rate-limited bet before the session gives up.
+pub const MAX_RATE_LIMIT_ATTEMPTS: u32 = 3;
+
+/// Wait before retrying a bet refused on `attempt` (1-based): the site's `retry-after`
+/// when it sent one, otherwise 1s, 2s, 4s, ...
+pub fn rate_limit_backoff(retry_after: Option<Duration>, attempt: u32) -> Duration {
+    retry_after.unwrap_or_else(|| Duration::from_secs(1 << attempt.saturating_sub(1).min(6)))
+}
+
+/// Starts an HTTP client, routed through `proxy` when one is set.
+pub fn client_builder(proxy: Option<&reqwest::Proxy>) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder();
+    match proxy {
+        Some(proxy) => builder.proxy(proxy.clone()),
+        None => builder,
+    }
+}
+
+/// HTTP client sending every request through `proxy`.
+///
+/// # Panics
+///
+/// Like `reqwest::Client::new`, when the TLS backend cannot be initialized.
+pub fn proxied_client(proxy: &reqwest::Proxy) -> reqwest::Client {
+    client_builder(Some(proxy))
+        .build()
+        .expect("Failed to build the HTTP client")
+}
+
+/// The site's `retry-after`, when it sent one in seconds.
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok()?.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Sends `request`, turning a 429 reply into `BetError::RateLimited` with the site's
+/// `retry-after`, so the caller backs off instead of failing to parse the reply.
+pub async fn send_request(request: reqwest::RequestBuilder) -> Result<reqwest::Response, BetError> {
+    let res = request.send().await?;
+    if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(BetError::RateLimited(retry_after(res.headers())));
+    }
+    Ok(res)
+}
+
+/// Counts a rate-limited bet and sleeps before it is retried, giving up with the error
+/// once `MAX_RATE_LIMIT_ATTEMPTS` were refused in a row.
+pub async fn handle_rate_limit(
+    retry_after: Option<Duration>,
+    attempts: &mut u32,
+) -> Result<(), BetError> {
+    *attempts += 1;
+    if *attempts >= MAX_RATE_LIMIT_ATTEMPTS {
+        return Err(BetError::RateLimited(retry_after));
+    }
+
+    let delay = rate_limit_backoff(retry_after, *attempts);
+    log::warn!("Rate limited, retrying in {:?}", delay);
+    tokio::time::sleep(delay).await;
+    Ok(())
 }
 
 pub trait SiteCurrency {
     fn get_min_bet(&self) -> f32;
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Sites {
     DuckDiceIo,
     CryptoGames,
     FreeBitcoIn,
+    Windice,
+    PrimeDice,
+    Bitsler,
+    BcGame,
+    WolfBet,
+    Dice999,
+}
+
+impl Sites {
+    /// Name of the site's section in `config.toml`.
+    pub fn config_key(&self) -> &'static str {
+        match self {
+            Self::DuckDiceIo => "duck_dice",
+            Self::CryptoGames => "crypto_games",
+            Self::FreeBitcoIn => "freebitcoin",
+            Self::Windice => "windice",
+            Self::PrimeDice => "primedice",
+            Self::Bitsler => "bitsler",
+            Self::BcGame => "bc_game",
+            Self::WolfBet => "wolf_bet",
+            Self::Dice999 => "dice999",
+        }
+    }
+}
+
+impl std::fmt::Display for Sites {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::DuckDiceIo => "DuckDice",
+            Self::CryptoGames => "CryptoGames",
+            Self::FreeBitcoIn => "FreeBitco.in",
+            Self::Windice => "Windice",
+            Self::PrimeDice => "PrimeDice",
+            Self::Bitsler => "Bitsler",
+            Self::BcGame => "BC.Game",
+            Self::WolfBet => "Wolf.bet",
+            Self::Dice999 => "999dice",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_capabilities_match_implemented_features() {
+        let duck_dice = duck_dice::DuckDiceIo::default().capabilities();
+        assert!(duck_dice.seed_rotation && duck_dice.faucet && duck_dice.withdrawal);
+        assert!(duck_dice.websocket);
+
+        let free_bitcoin = free_bitco_in::FreeBitcoIn::default().capabilities();
+        assert!(free_bitcoin.jackpot && !free_bitcoin.withdrawal);
+
+        assert_eq!(
+            crypto_games::CryptoGames::default().capabilities(),
+            Capabilities {
+                withdrawal: true,
+                ..Capabilities::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_bet_direction_is_recorded_apart_from_roll() {
+        for high in [true, false] {
+            let bet_result: BetResult = fake_test::duckdice_fake_bet(high, "seed", 1e-8, 2.).into();
+
+            assert_eq!(bet_result.is_high, high);
+            assert_eq!(bet_result.rolled_high, bet_result.number.value() >= 5000);
+        }
+    }
+
+    #[test]
+    fn test_parse_balance_handles_site_formats() {
+        assert_eq!(parse_balance("1.23456789"), Some(1.23456789));
+        assert_eq!(parse_balance("1e-5"), Some(1e-5));
+        assert_eq!(parse_balance("1,234.5"), Some(1234.5));
+        assert_eq!(parse_balance("1,234,567"), Some(1234567.));
+        assert_eq!(parse_balance(" 0.5 BTC"), Some(0.5));
+        assert_eq!(parse_balance("0.5USDT_TRC"), Some(0.5));
+        assert_eq!(parse_balance("0,00012345"), Some(0.00012345));
+
+        assert_eq!(parse_balance(""), None);
+        assert_eq!(parse_balance("1,23,4.5"), None);
+        assert_eq!(parse_balance("NaN"), None);
+        assert_eq!(parse_balance_or_default("n/a", "balance"), 0.);
+    }
+
+    #[test]
+    fn test_amount_accepts_numbers_and_strings() {
+        let parse = |json: &str| amount(&serde_json::from_str::<serde_json::Value>(json).unwrap());
+
+        assert_eq!(parse("0.000001").unwrap(), 1e-6);
+        assert_eq!(parse(r#""0.00000100""#).unwrap(), 1e-6);
+        assert_eq!(parse(r#""1,234.5""#).unwrap(), 1234.5);
+        assert!(parse(r#""n/a""#).is_err());
+        assert!(parse("null").is_err());
+    }
+
+    #[test]
+    fn test_push_history_keeps_latest() {
+        let mut history = Vec::new();
+        for number in 0..5 {
+            let mut bet_result = crate::strategies::test_bet_result(true, number, 0.);
+            bet_result.nonce = number;
+            push_history(&mut history, bet_result, 3);
+        }
+
+        assert_eq!(
+            history.iter().map(|bet| bet.nonce).collect::<Vec<u32>>(),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_floor_chance() {
+        assert_eq!(floor_chance(49.5, 1.), 49.5);
+        assert_eq!(floor_chance(0., 1.), 1.);
+        assert_eq!(floor_chance(f32::NAN, 2.), 2.);
+    }
+
+    #[test]
+    fn test_bound_chance() {
+        assert_eq!(bound_chance(49.5, 0.01, 98.), (49.5, BetRule::Normal));
+        assert_eq!(
+            bound_chance(0.001, 0.01, 98.),
+            (0.01, BetRule::ChanceFloored)
+        );
+        assert_eq!(
+            bound_chance(f32::NAN, 2., 98.),
+            (2., BetRule::ChanceFloored)
+        );
+        assert_eq!(bound_chance(99.5, 0.01, 98.), (98., BetRule::Capped));
+        assert!((payout_at(49.5, 1.) - 2.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_or_default() {
+        assert_eq!(parse_or_default::<u32>("42", "nonce"), 42);
+        assert_eq!(parse_or_default::<u32>("4.2", "nonce"), 0);
+        assert_eq!(parse_or_default::<f32>("0.5", "amount"), 0.5);
+        assert_eq!(parse_or_default::<f32>("", "amount"), 0.);
+    }
+
+    #[test]
+    fn test_cap_multiplier() {
+        assert_eq!(cap_multiplier(2., 1.01, 4750.), 2.);
+        assert_eq!(cap_multiplier(10_000., 1.01, 4750.), 4750.);
+        assert_eq!(cap_multiplier(1., 1.02, 9900.), 1.02);
+    }
+
+    #[test]
+    fn test_cap_bet() {
+        assert_eq!(cap_bet(0.5, 2., 0.5), 0.5);
+        assert_eq!(cap_bet(1.5, 2., 0.5), 1.);
+        assert_eq!(cap_bet(3., 2., 1.), 2.);
+    }
+
+    #[test]
+    fn test_effective_min_bet_never_lowers_site_minimum() {
+        assert_eq!(effective_min_bet(0.01, None), 0.01);
+        assert_eq!(effective_min_bet(0.01, Some(0.5)), 0.5);
+        assert_eq!(effective_min_bet(0.01, Some(0.001)), 0.01);
+    }
+
+    #[tokio::test]
+    async fn test_handle_empty_reply_logs_in_again_at_threshold() {
+        use crate::config::SiteConfig;
+
+        let mut site = duck_dice::DuckDiceIo::default().with_simulation(1.);
+        let mut empty_replies = 0;
+        for _ in 1..MAX_EMPTY_REPLIES {
+            handle_empty_reply(&mut site, &mut empty_replies)
+                .await
+                .unwrap();
+        }
+        assert_eq!(empty_replies, MAX_EMPTY_REPLIES - 1);
+
+        handle_empty_reply(&mut site, &mut empty_replies)
+            .await
+            .unwrap();
+        assert_eq!(empty_replies, 0);
+        assert_eq!(site.get_balance(), 1.);
+    }
+
+    #[test]
+    fn test_check_funds_reports_shortfall() {
+        assert!(check_funds(1., 1.).is_ok());
+        assert!(matches!(
+            check_funds(2., 1.),
+            Err(BetError::InsufficientFunds {
+                needed: 2.,
+                available: 1.
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_backs_off_then_gives_up() {
+        assert_eq!(rate_limit_backoff(None, 1), Duration::from_secs(1));
+        assert_eq!(rate_limit_backoff(None, 3), Duration::from_secs(4));
+        let retry_after = Some(Duration::from_secs(30));
+        assert_eq!(rate_limit_backoff(retry_after, 3), Duration::from_secs(30));
+
+        // The last refusal gives up without sleeping.
+        let mut attempts = MAX_RATE_LIMIT_ATTEMPTS - 1;
+        assert!(matches!(
+            handle_rate_limit(retry_after, &mut attempts).await,
+            Err(BetError::RateLimited(Some(_)))
+        ));
+    }
+
+    #[test]
+    fn test_retry_after_reads_seconds() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(reqwest::header::RETRY_AFTER, "30".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(30)));
+
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2026 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_bet_error_preserves_source() {
+        let err: BetError = serde_json::from_str::<u32>("not json").unwrap_err().into();
+
+        assert!(err.to_string().starts_with("Failed to parse site response"));
+        assert!(err.source().is_some());
+        assert!(BetError::Failed.source().is_none());
+    }
 }
@@ -0,0 +1,76 @@
+//! Nonce continuity of settled bets.
+//!
+//! Each bet on a seed chain rolls the nonce after the previous one. A reported nonce
+//! that skips values means bets were rolled that this session never saw, missed or
+//! placed from elsewhere on the account, so the history window no longer holds
+//! consecutive rolls.
+
+use crate::sites::{BetError, BetResult};
+
+/// Nonce the next bet of a site is expected to report.
+#[derive(Debug, Default)]
+pub struct NonceTracker {
+    /// Client seed of the chain being followed.
+    client_seed: String,
+    expected: Option<u32>,
+}
+
+impl NonceTracker {
+    /// Checks that `bet` follows the last bet seen, then expects the nonce after it.
+    ///
+    /// A changed client seed or a nonce of 0 starts a new chain, so neither is a gap;
+    /// sites that report no nonces always report 0 and are never flagged.
+    pub fn check(&mut self, bet: &BetResult) -> Result<(), BetError> {
+        let expected = self
+            .expected
+            .filter(|_| self.client_seed == bet.client_seed && bet.nonce != 0);
+        if self.client_seed != bet.client_seed {
+            self.client_seed = bet.client_seed.clone();
+        }
+        self.expected = Some(bet.nonce.wrapping_add(1));
+
+        match expected {
+            Some(expected) if expected != bet.nonce => Err(BetError::NonceMismatch {
+                expected,
+                reported: bet.nonce,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::test_bet_result;
+
+    fn bet(client_seed: &str, nonce: u32) -> BetResult {
+        BetResult {
+            client_seed: client_seed.to_string(),
+            nonce,
+            ..test_bet_result(true, 5000, 1e-6)
+        }
+    }
+
+    #[test]
+    fn test_flags_skipped_nonces_only() {
+        let mut nonces = NonceTracker::default();
+
+        assert!(nonces.check(&bet("a", 7)).is_ok());
+        assert!(nonces.check(&bet("a", 8)).is_ok());
+        assert!(matches!(
+            nonces.check(&bet("a", 11)),
+            Err(BetError::NonceMismatch {
+                expected: 9,
+                reported: 11
+            })
+        ));
+        // The chain continues from the reported nonce.
+        assert!(nonces.check(&bet("a", 12)).is_ok());
+
+        // A new seed or a reset nonce starts over.
+        assert!(nonces.check(&bet("b", 40)).is_ok());
+        assert!(nonces.check(&bet("b", 0)).is_ok());
+        assert!(nonces.check(&bet("b", 0)).is_ok());
+    }
+}
@@ -0,0 +1,582 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        bound_chance, check_funds, client_builder, effective_min_bet, payout_at, proxied_client,
+        push_history, send_request, BetError, BetId, BetOutcome, BetResult, BetRule, Capabilities,
+        Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+/// PrimeDice shares Stake's GraphQL API.
+const API_URL: &str = "https://api.primedice.com/graphql";
+const ACCESS_TOKEN_HEADER: &str = "x-access-token";
+/// Chances PrimeDice accepts, in percent; targets have two decimals.
+const MIN_CHANCE: f32 = 0.01;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet PrimeDice keeps, in percent.
+const HOUSE_EDGE: f32 = 1.;
+
+const USER_QUERY: &str = "query User {
+    user {
+        name
+        balances { available { amount currency } }
+        activeClientSeed { seed }
+        activeServerSeed { seedHash nonce }
+    }
+}";
+
+const ROLL_MUTATION: &str = "mutation PrimediceRoll(
+    $amount: Float!, $target: Float!, $condition: CasinoGamePrimediceConditionEnum!,
+    $currency: CurrencyEnum!
+) {
+    primediceRoll(amount: $amount, target: $target, condition: $condition, currency: $currency) {
+        id
+        nonce
+        amount
+        payout
+        payoutMultiplier
+        currency
+        state { ... on CasinoGamePrimedice { result target condition } }
+    }
+}";
+
+/// Every GraphQL reply carries either `data` or a list of `errors`.
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub data: Option<T>,
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        match self.data {
+            Some(data) if self.errors.is_empty() => Ok(data),
+            _ => {
+                let messages = self
+                    .errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>();
+                log::error!("PrimeDice request failed: {}", messages.join("; "));
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserData {
+    pub user: User,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub name: String,
+    pub balances: Vec<Balance>,
+    pub active_client_seed: ClientSeed,
+    pub active_server_seed: ServerSeed,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Balance {
+    pub available: Amount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Amount {
+    pub amount: f64,
+    /// Lowercase currency code.
+    pub currency: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClientSeed {
+    pub seed: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerSeed {
+    /// SHA-256 of the active server seed.
+    pub seed_hash: String,
+    /// Nonce of the next roll.
+    pub nonce: u64,
+}
+
+/// Side of `target` a roll must land on to win.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Condition {
+    Above,
+    Below,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollData {
+    pub primedice_roll: Roll,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Roll {
+    pub id: String,
+    pub nonce: u64,
+    pub amount: f64,
+    /// Amount paid back, stake included; 0 on a loss.
+    pub payout: f64,
+    /// Multiplier the roll paid or would have paid on a win.
+    pub payout_multiplier: f64,
+    pub currency: String,
+    pub state: RollState,
+}
+
+/// Rolls and targets are on a 0.00-100.00 scale.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RollState {
+    pub result: f64,
+    pub target: f64,
+    pub condition: Condition,
+}
+
+/// A roll together with the seeds it was made with, which the roll reply leaves out.
+#[derive(Clone, Debug)]
+pub struct BetSiteResult {
+    pub roll: Roll,
+    pub client_seed: String,
+    /// Hash of the server seed the roll was made with, which stays active for the next.
+    pub server_seed_hash: String,
+}
+
+impl RollState {
+    /// Roll on the model's 0-9999 scale.
+    pub fn scaled_result(&self) -> u32 {
+        (self.result * 100.).round() as u32
+    }
+
+    /// Win chance in percent of a roll against `target`.
+    pub fn chance(&self) -> f32 {
+        match self.condition {
+            Condition::Above => (100. - self.target) as f32,
+            Condition::Below => self.target as f32,
+        }
+    }
+}
+
+pub struct PrimeDice {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    token: String,
+    currency: Currency,
+    client_seed: ClientSeed,
+    server_seed: ServerSeed,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl PrimeDice {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::PrimeDice),
+            self.min_bet_override,
+        )
+    }
+
+    /// PrimeDice names currencies in lowercase.
+    fn currency_code(&self) -> String {
+        self.currency.to_string().to_lowercase()
+    }
+
+    /// Takes over the balance `user` holds in the betting currency.
+    fn set_balance(&mut self, user: &User) -> Result<(), BetError> {
+        let code = self.currency_code();
+        let balance = user
+            .balances
+            .iter()
+            .find(|balance| balance.available.currency == code)
+            .ok_or_else(|| {
+                BetError::ConfigError(format!("PrimeDice has no {} balance", self.currency))
+            })?;
+        self.balance = balance.available.amount as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(())
+    }
+
+    /// Takes the client and token rather than `&self`, whose strategy is not `Sync`.
+    async fn query<T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        token: &str,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, BetError> {
+        let response: Response<T> = send_request(
+            client
+                .post(API_URL)
+                .header(ACCESS_TOKEN_HEADER, token)
+                .json(&json!({ "query": query, "variables": variables })),
+        )
+        .await?
+        .json()
+        .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for PrimeDice {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.token = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+}
+
+impl Default for PrimeDice {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::PrimeDice);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            token: String::new(),
+            currency,
+            client_seed: ClientSeed::default(),
+            server_seed: ServerSeed::default(),
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for PrimeDice {
+    async fn login(&mut self) -> Result<(), BetError> {
+        let UserData { user } = Self::query(&self.client, &self.token, USER_QUERY, json!({}))
+            .await
+            .map_err(|e| {
+                log::error!("PrimeDice login failed: {e}");
+                BetError::LoginFailed
+            })?;
+
+        self.set_balance(&user)?;
+
+        self.client_seed = user.active_client_seed;
+        self.server_seed = user.active_server_seed;
+        log::info!(
+            "Logged in to PrimeDice as {} with {:.8} {}, nonce {}",
+            user.name,
+            self.balance,
+            self.currency,
+            self.server_seed.nonce
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // PrimeDice pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        // PrimeDice takes the target to roll over or under, with two decimals.
+        let chance = chance as f64;
+        let (condition, target) = if high {
+            (Condition::Above, 100. - chance)
+        } else {
+            (Condition::Below, chance)
+        };
+        let target = (target * 100.).round() / 100.;
+
+        let RollData { primedice_roll } = Self::query(
+            &self.client,
+            &self.token,
+            ROLL_MUTATION,
+            json!({
+                "amount": self.current_bet as f64,
+                "target": target,
+                "condition": condition,
+                "currency": self.currency_code(),
+            }),
+        )
+        .await?;
+
+        // The server seed stays until the client seed is changed, so the next roll is
+        // committed to the same hash with the nonce moved on.
+        self.server_seed.nonce = primedice_roll.nonce + 1;
+        let bet_result: BetResult = BetSiteResult {
+            roll: primedice_roll,
+            client_seed: self.client_seed.seed.clone(),
+            server_seed_hash: self.server_seed.seed_hash.clone(),
+        }
+        .into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // PrimeDice rolls settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let UserData { user } =
+            Self::query(&self.client, &self.token, USER_QUERY, json!({})).await?;
+        self.set_balance(&user)?;
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roll(reply: &str) -> BetResult {
+        let RollData { primedice_roll } = serde_json::from_str::<Response<RollData>>(reply)
+            .unwrap()
+            .into_data()
+            .unwrap();
+
+        BetSiteResult {
+            roll: primedice_roll,
+            client_seed: "client".to_string(),
+            server_seed_hash: "5e8ff9bf55ba3508199d22e984129be6".to_string(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_roll_reply_maps_seeds_and_amounts() {
+        let bet = roll(
+            r#"{"data": {"primediceRoll": {
+                "id": "0b3f6c1e", "nonce": 41, "amount": 0.000001, "payout": 0.00000198,
+                "payoutMultiplier": 1.98, "currency": "btc",
+                "state": {"result": 73.12, "target": 50.5, "condition": "above"}
+            }}}"#,
+        );
+
+        assert_eq!(bet.hash_previous_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.hash_next_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.client_seed, "client");
+        assert_eq!(bet.nonce, 41);
+        assert_eq!(bet.symbol, "BTC");
+        assert_eq!(bet.number.value(), 7312);
+        assert_eq!(bet.threshold, 5050);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        assert!((bet.payout - 1.98).abs() < 1e-6);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost = roll(
+            r#"{"data": {"primediceRoll": {
+                "id": "0b3f6c1f", "nonce": 42, "amount": 0.000001, "payout": 0,
+                "payoutMultiplier": 2, "currency": "btc",
+                "state": {"result": 99.99, "target": 49.5, "condition": "below"}
+            }}}"#,
+        );
+
+        assert!(!lost.result && !lost.is_high && lost.rolled_high);
+        assert_eq!(lost.number.value(), 9999);
+        // The multiplier the roll would have paid, not the 0 it did.
+        assert!((lost.payout - 2.).abs() < 1e-6);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((lost.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_graphql_errors_are_an_error() {
+        let reply = r#"{"data": null, "errors": [{"message": "insufficientBalance"}]}"#;
+
+        assert!(serde_json::from_str::<Response<RollData>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+    }
+}
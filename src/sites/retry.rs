@@ -0,0 +1,173 @@
+//! Retries of site requests that failed for transient network reasons.
+//!
+//! A dropped connection or a timed out request says nothing about the bet or the
+//! account, so it is retried after an exponentially growing pause instead of ending
+//! the session. Only network failures are retried: errors the site replied with are
+//! returned as they are.
+
+use std::time::Duration;
+
+use crate::sites::BetError;
+
+/// How often, and how patiently, a failed request is retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included.
+    pub max_attempts: u32,
+    /// Pause before the first retry; it doubles with every further retry.
+    pub initial_backoff: Duration,
+    /// Longest pause between two attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Pause before retry `retry` (1-based): `initial_backoff`, twice that, four times
+    /// that, ..., never more than `max_backoff`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether `err` is a network failure worth retrying: a timeout, a failed connection
+/// or a server error status.
+pub fn is_transient(err: &BetError) -> bool {
+    match err {
+        BetError::ReqwestError(err) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.status().is_some_and(|status| status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// Runs `op` until it succeeds, retrying transient failures with backoff until
+/// `policy.max_attempts` attempts were made.
+///
+/// Only use it for requests that are safe to repeat, such as logins and balance
+/// lookups; a timed out bet may have been placed anyway.
+pub async fn retry<T>(
+    policy: &RetryPolicy,
+    mut op: impl AsyncFnMut() -> Result<T, BetError>,
+) -> Result<T, BetError> {
+    let mut failures = 0;
+    loop {
+        match op().await {
+            Err(err) if is_transient(&err) && failures + 1 < policy.max_attempts => {
+                failures += 1;
+                let delay = policy.backoff(failures);
+                log::warn!("Network error ({err}), retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Counts a transient failure of a bet and sleeps before the next one, giving up with
+/// the error once `policy.max_attempts` bets failed in a row.
+///
+/// The bet itself is not repeated: the next one is predicted afresh, as a timed out
+/// bet may still have been placed.
+pub async fn handle_transient_error(
+    err: BetError,
+    policy: &RetryPolicy,
+    failures: &mut u32,
+) -> Result<(), BetError> {
+    *failures += 1;
+    if *failures >= policy.max_attempts {
+        return Err(err);
+    }
+
+    let delay = policy.backoff(*failures);
+    log::warn!("Network error ({err}), betting again in {delay:?}");
+    tokio::time::sleep(delay).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(3),
+        }
+    }
+
+    async fn connect_error() -> BetError {
+        // Nothing listens on port 1, so the connection is refused straight away.
+        reqwest::get("http://127.0.0.1:1").await.unwrap_err().into()
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts() {
+        let mut attempts = 0;
+        let res: Result<(), BetError> = retry(&policy(), async || {
+            attempts += 1;
+            Err(connect_error().await)
+        })
+        .await;
+
+        assert!(matches!(res, Err(BetError::ReqwestError(_))));
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_returns_site_errors_at_once() {
+        let mut attempts = 0;
+        let res = retry(&policy(), async || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(connect_error().await)
+            } else {
+                Err::<(), _>(BetError::LoginFailed)
+            }
+        })
+        .await;
+
+        assert!(matches!(res, Err(BetError::LoginFailed)));
+        assert_eq!(attempts, 2);
+        assert!(!is_transient(&BetError::EmptyReply));
+    }
+
+    #[tokio::test]
+    async fn test_transient_bet_errors_give_up_after_max_attempts() {
+        let mut failures = 0;
+        for _ in 1..policy().max_attempts {
+            handle_transient_error(connect_error().await, &policy(), &mut failures)
+                .await
+                .unwrap();
+        }
+
+        assert!(
+            handle_transient_error(connect_error().await, &policy(), &mut failures)
+                .await
+                .is_err()
+        );
+    }
+}
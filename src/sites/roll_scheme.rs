@@ -0,0 +1,114 @@
+//! Provably-fair roll algorithms of the supported sites.
+//!
+//! The fake server and the synthetic training data derive their rolls through a
+//! `RollScheme`, so simulated rolls follow the same algorithm as the site they stand
+//! in for. Training on rolls from another site's algorithm would teach the model a
+//! pattern the real site does not have.
+
+use ring::hmac;
+use sha2::{Digest, Sha256, Sha512};
+
+/// How a site turns its seeds and nonce into a roll.
+pub trait RollScheme: Send + Sync {
+    /// Roll for the bet, 0-9999 (0-10000 on FreeBitco.in).
+    fn roll(&self, server_seed: &str, client_seed: &str, nonce: u64) -> u32;
+
+    /// Hash of the server seed published before the roll.
+    fn server_seed_hash(&self, server_seed: &str) -> String {
+        hex::encode(Sha256::digest(server_seed.as_bytes()))
+    }
+}
+
+/// DuckDice: SHA-512 of `server_seed + client_seed + nonce`, read five hex digits at a
+/// time until a value below 1,000,000 turns up.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DuckDiceScheme;
+
+impl RollScheme for DuckDiceScheme {
+    fn roll(&self, server_seed: &str, client_seed: &str, nonce: u64) -> u32 {
+        let hash = hex::encode(Sha512::digest(format!("{server_seed}{client_seed}{nonce}")));
+
+        hash.as_bytes()
+            .chunks_exact(5)
+            .filter_map(|chunk| u32::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok())
+            .find(|lucky| *lucky < 1_000_000)
+            // The remaining three digits are always below the bound.
+            .unwrap_or_else(|| u32::from_str_radix(&hash[125..], 16).unwrap_or_default())
+            % 10_000
+    }
+}
+
+/// FreeBitco.in: HMAC-SHA512 of `nonce:server_seed:nonce` keyed with
+/// `nonce:client_seed:nonce`, the first eight hex digits scaled to 0-10000.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FreeBitcoScheme;
+
+impl RollScheme for FreeBitcoScheme {
+    fn roll(&self, server_seed: &str, client_seed: &str, nonce: u64) -> u32 {
+        let key = hmac::Key::new(
+            hmac::HMAC_SHA512,
+            format!("{nonce}:{client_seed}:{nonce}").as_bytes(),
+        );
+        let tag = hmac::sign(&key, format!("{nonce}:{server_seed}:{nonce}").as_bytes());
+        let head = u32::from_be_bytes(tag.as_ref()[..4].try_into().unwrap_or_default());
+
+        (f64::from(head) / 429_496.729_5).round() as u32
+    }
+}
+
+/// HMAC-SHA256 keyed with the server seed over `server_seed + client_seed + nonce`, the
+/// first four bytes little-endian modulo 10,000. The scheme the synthetic training
+/// data was first generated with; no supported site uses it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HmacSha256Scheme;
+
+impl RollScheme for HmacSha256Scheme {
+    fn roll(&self, server_seed: &str, client_seed: &str, nonce: u64) -> u32 {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, server_seed.as_bytes());
+        let mut message = Vec::new();
+        message.extend_from_slice(server_seed.as_bytes());
+        message.extend_from_slice(client_seed.as_bytes());
+        message.extend_from_slice(&nonce.to_be_bytes());
+        let tag = hmac::sign(&key, &message);
+
+        u32::from_le_bytes(tag.as_ref()[..4].try_into().unwrap_or_default()) % 10_000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemes_are_deterministic_and_in_range() {
+        let schemes: [(&dyn RollScheme, u32); 3] = [
+            (&DuckDiceScheme, 9999),
+            (&FreeBitcoScheme, 10_000),
+            (&HmacSha256Scheme, 9999),
+        ];
+
+        for (scheme, max) in schemes {
+            for nonce in 0..200 {
+                let roll = scheme.roll("server", "client", nonce);
+                assert!(roll <= max);
+                assert_eq!(roll, scheme.roll("server", "client", nonce));
+            }
+        }
+
+        let rolls = |scheme: &dyn RollScheme| {
+            (0..20)
+                .map(|nonce| scheme.roll("server", "client", nonce))
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(rolls(&DuckDiceScheme), rolls(&FreeBitcoScheme));
+        assert_ne!(rolls(&DuckDiceScheme), rolls(&HmacSha256Scheme));
+    }
+
+    #[test]
+    fn test_server_seed_hash_is_sha256() {
+        assert_eq!(
+            DuckDiceScheme.server_seed_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Client seed management shared by the sites.
+//!
+//! A `SeedManager` generates client seeds, decides when the next one is due according
+//! to a `RotationPolicy`, and remembers which seed rolled which nonces. The history can
+//! be kept in a file, so rolls recorded in earlier sessions can still be matched to
+//! the seed they were rolled with.
+
+use serde::{Deserialize, Serialize};
+
+/// Length of generated client seeds; short enough for every supported site.
+pub const SEED_LEN: usize = 30;
+
+/// Bets between saves of the seed file while a seed stays in use.
+const SAVE_EVERY: u64 = 100;
+
+/// A fresh client seed of `SEED_LEN` letters and digits, about 178 bits of entropy
+/// from the OS-seeded thread generator.
+pub fn generate() -> String {
+    rand::Rng::sample_iter(rand::rng(), rand::distr::Alphanumeric)
+        .take(SEED_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// When a new client seed is started.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RotationPolicy {
+    /// Bets after which the seed is replaced; `None` keeps it.
+    pub every_bets: Option<u64>,
+    /// Start every session with a new seed.
+    pub per_session: bool,
+}
+
+impl RotationPolicy {
+    /// Whether the seed is due to be replaced after `bets_on_seed` bets.
+    pub fn due(&self, bets_on_seed: u64) -> bool {
+        self.every_bets
+            .is_some_and(|interval| bets_on_seed >= interval)
+    }
+}
+
+/// A client seed and the nonces rolled with it, once it rolled any.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SeedPeriod {
+    pub client_seed: String,
+    pub first_nonce: Option<u64>,
+    pub last_nonce: Option<u64>,
+}
+
+impl SeedPeriod {
+    fn contains(&self, nonce: u64) -> bool {
+        self.first_nonce.is_some_and(|first| first <= nonce)
+            && self.last_nonce.is_some_and(|last| nonce <= last)
+    }
+}
+
+/// Client seed in use, the seeds used before it and when to replace it.
+#[derive(Debug, Default)]
+pub struct SeedManager {
+    policy: RotationPolicy,
+    /// Seeds in the order they were used; the last one is active.
+    periods: Vec<SeedPeriod>,
+    /// File the periods are saved to, if any.
+    path: Option<String>,
+    bets_on_seed: u64,
+}
+
+impl SeedManager {
+    /// Replaces the seed as `policy` asks.
+    pub fn with_policy(mut self, policy: RotationPolicy) -> Self {
+        self.policy = policy;
+
+        self
+    }
+
+    /// Keeps the seed history in `path`, continuing the one saved there.
+    pub fn with_file(mut self, path: String) -> Self {
+        self.periods = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| {
+                serde_json::from_str(&contents)
+                    .inspect_err(|e| log::warn!("Ignoring unreadable seed file {path}: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        self.path = Some(path);
+
+        self
+    }
+
+    pub fn policy(&self) -> RotationPolicy {
+        self.policy
+    }
+
+    /// The active client seed, if one was started.
+    pub fn current(&self) -> Option<&str> {
+        self.periods
+            .last()
+            .map(|period| period.client_seed.as_str())
+    }
+
+    pub fn bets_on_seed(&self) -> u64 {
+        self.bets_on_seed
+    }
+
+    /// Whether the policy asks for a new seed before the next bet.
+    pub fn rotation_due(&self) -> bool {
+        self.policy.due(self.bets_on_seed)
+    }
+
+    /// Starts a freshly generated seed and returns it.
+    pub fn rotate(&mut self) -> String {
+        let seed = generate();
+        self.use_seed(seed.clone());
+
+        seed
+    }
+
+    /// Makes `seed` the active seed, for seeds chosen elsewhere such as by the site.
+    /// Does nothing when it already is.
+    pub fn use_seed(&mut self, seed: String) {
+        if self.current() == Some(seed.as_str()) {
+            return;
+        }
+
+        self.periods.push(SeedPeriod {
+            client_seed: seed,
+            first_nonce: None,
+            last_nonce: None,
+        });
+        self.bets_on_seed = 0;
+        self.save();
+    }
+
+    /// Counts a bet rolled with the active seed at `nonce`.
+    pub fn on_bet(&mut self, nonce: u64) {
+        self.bets_on_seed += 1;
+        if let Some(period) = self.periods.last_mut() {
+            period.first_nonce.get_or_insert(nonce);
+            period.last_nonce = Some(nonce);
+        }
+        if self.bets_on_seed.is_multiple_of(SAVE_EVERY) {
+            self.save();
+        }
+    }
+
+    /// Seed that rolled `nonce`, the latest one when several did.
+    pub fn seed_for(&self, nonce: u64) -> Option<&str> {
+        self.periods
+            .iter()
+            .rev()
+            .find(|period| period.contains(nonce))
+            .map(|period| period.client_seed.as_str())
+    }
+
+    /// Writes the history to the seed file, if there is one.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let res = serde_json::to_string_pretty(&self.periods)
+            .map_err(std::io::Error::other)
+            .and_then(|contents| std::fs::write(path, contents));
+        if let Err(e) = res {
+            log::warn!("Failed to save seed file {path}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_seeds_are_long_and_distinct() {
+        let seed = generate();
+
+        assert_eq!(seed.len(), SEED_LEN);
+        assert!(seed.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(seed, generate());
+    }
+
+    #[test]
+    fn test_rotation_due_every_interval() {
+        let every = |every_bets| RotationPolicy {
+            every_bets,
+            per_session: false,
+        };
+
+        assert!(!every(None).due(100));
+        assert!(!every(Some(3)).due(0));
+        assert!(!every(Some(3)).due(2));
+        assert!(every(Some(3)).due(3));
+    }
+
+    #[test]
+    fn test_tracks_the_nonces_of_each_seed_across_sessions() {
+        let path = std::env::temp_dir().join(format!("seeds-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let policy = RotationPolicy {
+            every_bets: Some(2),
+            per_session: false,
+        };
+
+        let mut seeds = SeedManager::default()
+            .with_policy(policy)
+            .with_file(path.clone());
+        seeds.use_seed("first".to_string());
+        seeds.on_bet(10);
+        assert!(!seeds.rotation_due());
+        seeds.on_bet(11);
+        assert!(seeds.rotation_due());
+        let second = seeds.rotate();
+        assert!(!seeds.rotation_due());
+        seeds.on_bet(0);
+        seeds.save();
+
+        let restored = SeedManager::default()
+            .with_policy(policy)
+            .with_file(path.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.current(), Some(second.as_str()));
+        assert_eq!(restored.seed_for(11), Some("first"));
+        assert_eq!(restored.seed_for(0), Some(second.as_str()));
+        assert_eq!(restored.seed_for(12), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Betting over Tor, with a fresh circuit every few bets.
+//!
+//! Requests reach Tor through its SOCKS port like any other proxy. New circuits are
+//! requested on Tor's control port with `SIGNAL NEWNYM`; the site then drops its open
+//! connections, which would otherwise keep using the old circuit.
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::sites::{BetError, Site};
+
+/// Client for the control port of a local Tor daemon.
+#[derive(Clone, Debug)]
+pub struct TorController {
+    control_addr: String,
+    password: Option<String>,
+}
+
+impl TorController {
+    /// Controller for the port at `control_addr`, authenticating with `password` when
+    /// the daemon has a `HashedControlPassword`.
+    pub fn new(control_addr: String, password: Option<String>) -> Self {
+        Self {
+            control_addr,
+            password,
+        }
+    }
+
+    /// Asks Tor to build new circuits for the connections opened from now on.
+    pub async fn new_circuit(&self) -> Result<(), BetError> {
+        let stream = TcpStream::connect(&self.control_addr).await?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let authenticate = match &self.password {
+            Some(password) => format!("AUTHENTICATE \"{}\"", escape(password)),
+            None => "AUTHENTICATE".to_string(),
+        };
+        for command in [authenticate.as_str(), "SIGNAL NEWNYM"] {
+            writer
+                .write_all(format!("{command}\r\n").as_bytes())
+                .await?;
+
+            let mut reply = String::new();
+            reader.read_line(&mut reply).await?;
+            if !reply.starts_with("250") {
+                let verb = command.split(' ').next().unwrap_or(command);
+                return Err(BetError::ConfigError(format!(
+                    "Tor refused {verb}: {}",
+                    reply.trim_end()
+                )));
+            }
+        }
+
+        writer.write_all(b"QUIT\r\n").await?;
+        Ok(())
+    }
+}
+
+/// Quotes `value` for a control port command.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Switches to a new Tor circuit after every `every` settled bets.
+#[derive(Debug)]
+pub struct CircuitRenewal {
+    controller: TorController,
+    every: u64,
+    bets: u64,
+}
+
+impl CircuitRenewal {
+    pub fn new(controller: TorController, every: u64) -> Self {
+        Self {
+            controller,
+            every,
+            bets: 0,
+        }
+    }
+
+    /// Counts a settled bet, and once `every` were placed on the current circuit moves
+    /// `site` to a new one.
+    pub async fn on_bet(&mut self, site: &mut dyn Site) -> Result<(), BetError> {
+        self.bets += 1;
+        if self.bets < self.every {
+            return Ok(());
+        }
+
+        // Counted afresh even when Tor fails, so it is not asked again on every bet.
+        self.bets = 0;
+        self.controller.new_circuit().await?;
+        site.reconnect()?;
+        log::info!("Switched to a new Tor circuit");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Control port answering each command with the next of `replies`, returning the
+    /// commands it received.
+    async fn control_port(
+        replies: &'static [&'static str],
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut commands = Vec::new();
+            for reply in replies {
+                commands.push(lines.next_line().await.unwrap().unwrap());
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+            commands
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_new_circuit_authenticates_and_signals_newnym() {
+        let (addr, commands) = control_port(&["250 OK\r\n", "250 OK\r\n"]).await;
+
+        TorController::new(addr, Some("pa\"ss".to_string()))
+            .new_circuit()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            commands.await.unwrap(),
+            ["AUTHENTICATE \"pa\\\"ss\"", "SIGNAL NEWNYM"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_circuit_reports_refused_authentication() {
+        let (addr, _) = control_port(&["515 Authentication failed\r\n"]).await;
+
+        let err = TorController::new(addr, None).new_circuit().await;
+
+        assert!(
+            matches!(err, Err(BetError::ConfigError(ref msg)) if msg == "Tor refused AUTHENTICATE: 515 Authentication failed"),
+            "{err:?}"
+        );
+    }
+}
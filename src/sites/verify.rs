@@ -0,0 +1,88 @@
+//! Provably-fair verification of settled bets.
+//!
+//! Once a site reveals the server seed a roll was made with, the roll can be derived
+//! again from the seeds and nonce. A seed that does not hash to the hash published
+//! before the bet, or a roll that does not follow from the seeds, means the site did
+//! not roll the way it claims to.
+
+use crate::sites::roll_scheme::RollScheme;
+
+/// Seeds of a settled bet once the site revealed its server seed, with the roll the
+/// site reported for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RevealedRoll {
+    pub server_seed: String,
+    /// Hash of the server seed published before the bet.
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub number: u32,
+}
+
+/// How a revealed roll failed verification.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum Mismatch {
+    #[error("server seed hashes to {actual}, but {published} was published before the bet")]
+    ServerSeedHash { published: String, actual: String },
+    #[error("the seeds roll {expected}, but the site reported {reported}")]
+    Roll { expected: u32, reported: u32 },
+}
+
+/// Checks `roll` against the site's roll `scheme`.
+pub fn verify(scheme: &dyn RollScheme, roll: &RevealedRoll) -> Result<(), Mismatch> {
+    let actual = scheme.server_seed_hash(&roll.server_seed);
+    if !actual.eq_ignore_ascii_case(&roll.server_seed_hash) {
+        return Err(Mismatch::ServerSeedHash {
+            published: roll.server_seed_hash.clone(),
+            actual,
+        });
+    }
+
+    let expected = scheme.roll(&roll.server_seed, &roll.client_seed, roll.nonce);
+    if expected != roll.number {
+        return Err(Mismatch::Roll {
+            expected,
+            reported: roll.number,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sites::roll_scheme::FreeBitcoScheme;
+
+    fn revealed(number: u32) -> RevealedRoll {
+        RevealedRoll {
+            server_seed: "server".to_string(),
+            server_seed_hash: FreeBitcoScheme.server_seed_hash("server"),
+            client_seed: "client".to_string(),
+            nonce: 42,
+            number,
+        }
+    }
+
+    #[test]
+    fn test_verifies_honest_rolls_and_flags_mismatches() {
+        let number = FreeBitcoScheme.roll("server", "client", 42);
+        assert_eq!(verify(&FreeBitcoScheme, &revealed(number)), Ok(()));
+
+        let reported = (number + 1) % 10_000;
+        assert_eq!(
+            verify(&FreeBitcoScheme, &revealed(reported)),
+            Err(Mismatch::Roll {
+                expected: number,
+                reported
+            })
+        );
+
+        let mut swapped = revealed(number);
+        swapped.server_seed = "other".to_string();
+        assert!(matches!(
+            verify(&FreeBitcoScheme, &swapped),
+            Err(Mismatch::ServerSeedHash { .. })
+        ));
+    }
+}
@@ -1 +1,504 @@
+use async_trait::async_trait;
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        amount, bound_chance, check_funds, client_builder, effective_min_bet, payout_at,
+        proxied_client, push_history, send_request, BetError, BetId, BetOutcome, BetResult,
+        BetRule, Capabilities, Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+const API_URL: &str = "https://windice.io/api/v1/api";
+/// Chances Windice accepts, in percent; ranges cover whole hundredths of the roll range.
+const MIN_CHANCE: f32 = 0.01;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet Windice keeps, in percent.
+pub const HOUSE_EDGE: f32 = 1.;
+
+/// Every Windice reply wraps its payload in `{"status": ..., "data": ...}`.
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub status: String,
+    pub data: Option<T>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        match self.data {
+            Some(data) if self.status == "success" => Ok(data),
+            _ => {
+                log::error!(
+                    "Windice request failed: {}",
+                    self.message.unwrap_or(self.status)
+                );
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub username: String,
+    /// Balance per lowercase currency code.
+    pub balance: std::collections::HashMap<String, serde_json::Value>,
+}
+
+/// Seeds the next roll is made with.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Seed {
+    pub client: String,
+    /// SHA-256 of the active server seed.
+    pub hash: String,
+    pub nonce: u64,
+}
+
+/// A roll wins when it lands in `[low, high]` for `game = "in"`.
+#[derive(Debug, Serialize)]
+pub struct Bet {
+    pub curr: String,
+    pub bet: f64,
+    pub game: String,
+    pub low: u32,
+    pub high: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Roll {
+    /// Hash of the server seed the roll was made with.
+    pub hash: String,
+    pub nonce: u64,
+    #[serde(deserialize_with = "amount")]
+    pub bet: f64,
+    /// Amount paid back, stake included; 0 on a loss.
+    #[serde(deserialize_with = "amount")]
+    pub win: f64,
+    pub result: u32,
+    pub low: u32,
+    pub high: u32,
+    pub curr: String,
+}
+
+/// A roll together with the seeds it was made with, which the roll reply leaves out.
+#[derive(Clone, Debug)]
+pub struct BetSiteResult {
+    pub roll: Roll,
+    pub client_seed: String,
+    /// Server seed hash committed to for the next roll.
+    pub next_hash: String,
+}
+
+pub struct Windice {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    key: String,
+    currency: Currency,
+    seed: Seed,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl Windice {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::Windice),
+            self.min_bet_override,
+        )
+    }
+
+    /// Windice names currencies in lowercase.
+    fn currency_code(&self) -> String {
+        self.currency.to_string().to_lowercase()
+    }
+
+    /// Takes over the balance `user` holds in the betting currency.
+    fn set_balance(&mut self, user: &User) -> Result<(), BetError> {
+        let balance = user
+            .balance
+            .get(&self.currency_code())
+            .map(|balance| amount(balance).ok())
+            .ok_or_else(|| {
+                BetError::ConfigError(format!("Windice has no {} balance", self.currency))
+            })?
+            .ok_or(BetError::Failed)?;
+        self.balance = balance as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(())
+    }
+
+    /// Takes the client and key rather than `&self`, whose strategy is not `Sync`.
+    async fn get<T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        key: &str,
+        path: &str,
+    ) -> Result<T, BetError> {
+        let response: Response<T> = send_request(
+            client
+                .get(format!("{API_URL}/{path}"))
+                .header(AUTHORIZATION, key),
+        )
+        .await?
+        .json()
+        .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for Windice {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.key = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+}
+
+impl Default for Windice {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::Windice);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            key: String::new(),
+            currency,
+            seed: Seed::default(),
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for Windice {
+    async fn login(&mut self) -> Result<(), BetError> {
+        let user: User = Self::get(&self.client, &self.key, "user")
+            .await
+            .map_err(|e| {
+                log::error!("Windice login failed: {e}");
+                BetError::LoginFailed
+            })?;
+
+        self.set_balance(&user)?;
+
+        self.seed = Self::get(&self.client, &self.key, "seed").await?;
+        log::info!(
+            "Logged in to Windice as {} with {:.8} {}, nonce {}",
+            user.username,
+            self.balance,
+            self.currency,
+            self.seed.nonce
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // Windice pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        // Windice settles a roll as a win when it lands inside `[low, high]`.
+        let width = ((chance * 100.).round() as u32).max(1);
+        let (low, high_bound) = if high {
+            (10_000 - width, 9999)
+        } else {
+            (0, width - 1)
+        };
+
+        let response: Response<Roll> = send_request(
+            self.client
+                .post(format!("{API_URL}/roll"))
+                .header(AUTHORIZATION, &self.key)
+                .json(&Bet {
+                    curr: self.currency_code(),
+                    bet: self.current_bet as f64,
+                    game: "in".to_string(),
+                    low,
+                    high: high_bound,
+                }),
+        )
+        .await?
+        .json()
+        .await?;
+        let roll = response.into_data()?;
+
+        // The server seed stays until the client seed is changed, so the next roll is
+        // committed to the same hash with the nonce moved on.
+        self.seed.nonce = roll.nonce + 1;
+        self.seed.hash = roll.hash.clone();
+        let bet_result: BetResult = BetSiteResult {
+            roll,
+            client_seed: self.seed.client.clone(),
+            next_hash: self.seed.hash.clone(),
+        }
+        .into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // Windice rolls settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let user: User = Self::get(&self.client, &self.key, "user").await?;
+        self.set_balance(&user)?;
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_reply_maps_seeds_and_amounts() {
+        let reply = r#"{
+            "status": "success",
+            "data": {
+                "hash": "5e8ff9bf55ba3508199d22e984129be6",
+                "nonce": 41,
+                "bet": "0.00000100",
+                "win": 0.00000198,
+                "result": 7312,
+                "low": 5050,
+                "high": 9999,
+                "curr": "btc"
+            }
+        }"#;
+        let roll = serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .unwrap();
+
+        let bet: BetResult = BetSiteResult {
+            roll,
+            client_seed: "client".to_string(),
+            next_hash: "5e8ff9bf55ba3508199d22e984129be6".to_string(),
+        }
+        .into();
+
+        assert_eq!(bet.hash_previous_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.hash_next_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.client_seed, "client");
+        assert_eq!(bet.nonce, 41);
+        assert_eq!(bet.number.value(), 7312);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        // The win paid 1.98x, not the 2x that 49.5% pays at the house edge.
+        assert!((bet.payout - 1.98).abs() < 1e-4);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost = r#"{"status": "success", "data": {"hash": "h", "nonce": 42, "bet": 0.000001,
+            "win": 0, "result": 100, "low": 0, "high": 4949, "curr": "btc"}}"#;
+        let roll = serde_json::from_str::<Response<Roll>>(lost)
+            .unwrap()
+            .into_data()
+            .unwrap();
+        let bet: BetResult = BetSiteResult {
+            roll,
+            client_seed: "client".to_string(),
+            next_hash: "h".to_string(),
+        }
+        .into();
+
+        assert!(!bet.result && !bet.is_high);
+        assert!((bet.payout - 2.).abs() < 1e-4);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((bet.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_failed_reply_is_an_error() {
+        let reply = r#"{"status": "error", "message": "Insufficient balance"}"#;
+
+        assert!(serde_json::from_str::<Response<Roll>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+    }
+}
@@ -0,0 +1,512 @@
+use async_trait::async_trait;
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{BetSizingConfig, SiteConfig, TomlStrategies},
+    currency::Currency,
+    sites::{
+        amount, bound_chance, check_funds, client_builder, effective_min_bet, payout_at,
+        proxied_client, push_history, send_request, BetError, BetId, BetOutcome, BetResult,
+        BetRule, Capabilities, Site, Sites,
+    },
+    strategies::Strategy,
+};
+
+const API_URL: &str = "https://wolf.bet/api/v1";
+/// Chances Wolf.bet accepts, in percent; targets have two decimals.
+const MIN_CHANCE: f32 = 0.01;
+const MAX_CHANCE: f32 = 98.;
+/// Share of each bet Wolf.bet keeps, in percent.
+const HOUSE_EDGE: f32 = 1.;
+
+/// Failed requests reply with an `error` object instead of the payload.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Response<T> {
+    Ok(T),
+    Err { error: ApiError },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiError {
+    pub message: String,
+}
+
+impl<T> Response<T> {
+    fn into_data(self) -> Result<T, BetError> {
+        match self {
+            Self::Ok(data) => Ok(data),
+            Self::Err { error } => {
+                log::error!("Wolf.bet request failed: {}", error.message);
+                Err(BetError::Failed)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub user: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Balances {
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Balance {
+    #[serde(deserialize_with = "amount")]
+    pub amount: f64,
+    /// Lowercase currency code.
+    pub currency: String,
+}
+
+/// Side of `bet_value` a roll must land on to win.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rule {
+    Over,
+    Under,
+}
+
+/// A manual dice bet, with every number sent as a string.
+#[derive(Debug, Serialize)]
+pub struct Play {
+    pub currency: String,
+    pub game: String,
+    pub amount: String,
+    pub rule: Rule,
+    pub multiplier: String,
+    pub bet_value: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayReply {
+    pub bet: Bet,
+}
+
+/// A settled bet; rolls and targets are on a 0.00-99.99 scale. Wolf.bet sends every
+/// amount, multiplier and roll as a decimal string.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Bet {
+    /// Site identifier of the bet.
+    pub hash: String,
+    pub nonce: u64,
+    pub user_seed: String,
+    /// SHA-256 of the server seed the roll was made with.
+    pub server_seed_hashed: String,
+    pub currency: String,
+    #[serde(deserialize_with = "amount")]
+    pub amount: f64,
+    /// Net result of the bet, negative on a loss.
+    #[serde(deserialize_with = "amount")]
+    pub profit: f64,
+    #[serde(deserialize_with = "amount")]
+    pub multiplier: f64,
+    #[serde(deserialize_with = "amount")]
+    pub bet_value: f64,
+    #[serde(deserialize_with = "amount")]
+    pub result_value: f64,
+    pub rule: Rule,
+    /// `"win"` or `"loss"`.
+    pub state: String,
+}
+
+impl Bet {
+    /// Roll on the model's 0-9999 scale.
+    pub fn scaled_result(&self) -> u32 {
+        (self.result_value * 100.).round() as u32
+    }
+
+    /// Win chance in percent of a roll against `bet_value`.
+    pub fn chance(&self) -> f32 {
+        match self.rule {
+            Rule::Over => (99.99 - self.bet_value) as f32,
+            Rule::Under => self.bet_value as f32,
+        }
+    }
+}
+
+pub struct WolfBet {
+    pub rolls: u64,
+    /// Total staked on settled bets this session.
+    pub wagered: f32,
+    pub current_bet: f32,
+    pub multiplier: f32,
+    pub balance: f32,
+    pub profit: f32,
+    pub strategy: Box<dyn Strategy>,
+    client: reqwest::Client,
+    /// Proxy the client is rebuilt with, when one is configured.
+    proxy: Option<reqwest::Proxy>,
+    token: String,
+    currency: Currency,
+    history: Vec<BetResult>,
+    history_size: usize,
+    max_history: usize,
+    /// Configured floor for bets, used when above the currency's minimum.
+    min_bet_override: Option<f32>,
+    /// Lowest chance (in percent) a bet is placed at.
+    min_chance: f32,
+    sizing: BetSizingConfig,
+    last_rule: BetRule,
+}
+
+impl WolfBet {
+    /// Smallest bet placed: the configured override, but never below the site minimum.
+    fn min_bet(&self) -> f32 {
+        effective_min_bet(
+            self.currency.get_min_bet(Sites::WolfBet),
+            self.min_bet_override,
+        )
+    }
+
+    /// Wolf.bet names currencies in lowercase.
+    fn currency_code(&self) -> String {
+        self.currency.to_string().to_lowercase()
+    }
+
+    /// Takes the client and token rather than `&self`, whose strategy is not `Sync`.
+    async fn get<T: serde::de::DeserializeOwned>(
+        client: &reqwest::Client,
+        token: &str,
+        path: &str,
+    ) -> Result<T, BetError> {
+        let response: Response<T> = send_request(
+            client
+                .get(format!("{API_URL}/{path}"))
+                .header(AUTHORIZATION, format!("Bearer {token}")),
+        )
+        .await?
+        .json()
+        .await?;
+
+        response.into_data()
+    }
+}
+
+impl SiteConfig for WolfBet {
+    fn with_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.client = proxied_client(&proxy);
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    fn with_api_key(mut self, api_key: String) -> Self {
+        self.token = api_key;
+
+        self
+    }
+
+    fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = currency;
+        self.current_bet = self.min_bet();
+
+        self
+    }
+
+    fn with_strategy(mut self, strategy: TomlStrategies) -> Self {
+        self.strategy = self.sizing.strategy(strategy, self.balance, self.min_bet());
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet_override = Some(min_bet);
+
+        self
+    }
+
+    fn with_min_chance(mut self, min_chance: f32) -> Self {
+        self.min_chance = min_chance.max(MIN_CHANCE);
+
+        self
+    }
+
+    fn with_bet_sizing(mut self, sizing: BetSizingConfig) -> Self {
+        self.sizing = sizing;
+
+        self
+    }
+
+    fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self.max_history = self.max_history.max(history_size);
+
+        self
+    }
+
+    fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(self.history_size);
+
+        self
+    }
+}
+
+impl Default for WolfBet {
+    fn default() -> Self {
+        let currency = Currency::default();
+        let min_bet = currency.get_min_bet(Sites::WolfBet);
+
+        Self {
+            rolls: 0,
+            wagered: 0.,
+            current_bet: min_bet,
+            multiplier: 2.,
+            balance: 0.,
+            profit: 0.,
+            strategy: crate::strategies::from_config(TomlStrategies::None, 0., min_bet, min_bet),
+            client: reqwest::Client::new(),
+            proxy: None,
+            token: String::new(),
+            currency,
+            history: Vec::new(),
+            history_size: crate::util::HISTORY_SIZE,
+            max_history: crate::util::HISTORY_SIZE,
+            min_bet_override: None,
+            min_chance: MIN_CHANCE,
+            sizing: BetSizingConfig::default(),
+            last_rule: BetRule::Normal,
+        }
+    }
+}
+
+#[async_trait]
+impl Site for WolfBet {
+    async fn login(&mut self) -> Result<(), BetError> {
+        let Profile { user } = Self::get(&self.client, &self.token, "user/profile")
+            .await
+            .map_err(|e| {
+                log::error!("Wolf.bet login failed: {e}");
+                BetError::LoginFailed
+            })?;
+
+        self.refresh_balance().await?;
+
+        log::info!(
+            "Logged in to Wolf.bet as {} with {:.8} {}",
+            user.login,
+            self.balance,
+            self.currency
+        );
+
+        Ok(())
+    }
+
+    async fn do_bet(&mut self, prediction: f32, confidence: f32) -> Result<BetOutcome, BetError> {
+        self.rolls += 1;
+        let (bet, _, chance, high) = self.strategy.get_next_bet(prediction, confidence);
+        self.current_bet = bet;
+        // Wolf.bet pays by chance, so bounding the chance also bounds the multiplier.
+        let (mut chance, rule) = bound_chance(chance, self.min_chance, MAX_CHANCE);
+        self.last_rule = rule;
+
+        if self.history.len() < self.history_size {
+            self.current_bet = self.min_bet();
+            chance = 49.5;
+            self.last_rule = BetRule::Warmup;
+        }
+        self.multiplier = payout_at(chance, HOUSE_EDGE);
+        self.current_bet = self.current_bet.max(self.min_bet());
+
+        check_funds(self.current_bet, self.strategy.get_balance())?;
+
+        // Wolf.bet takes both the target and the multiplier it pays, which must agree
+        // with its 1% house edge.
+        let chance = chance as f64;
+        let (rule, bet_value) = if high {
+            (Rule::Over, 99.99 - chance)
+        } else {
+            (Rule::Under, chance)
+        };
+
+        let response: Response<PlayReply> = send_request(
+            self.client
+                .post(format!("{API_URL}/dice/manual/play"))
+                .header(AUTHORIZATION, format!("Bearer {}", self.token))
+                .json(&Play {
+                    currency: self.currency_code(),
+                    game: "dice".to_string(),
+                    amount: format!("{:.8}", self.current_bet),
+                    rule,
+                    multiplier: format!("{:.4}", self.multiplier),
+                    bet_value: format!("{bet_value:.2}"),
+                }),
+        )
+        .await?
+        .json()
+        .await?;
+        let bet_result: BetResult = response.into_data()?.bet.into();
+
+        push_history(&mut self.history, bet_result.clone(), self.max_history);
+
+        Ok(BetOutcome::Resolved(bet_result))
+    }
+
+    async fn poll_result(&mut self, _id: &BetId) -> Result<BetOutcome, BetError> {
+        // Wolf.bet bets settle in the same request, so there is never anything to poll.
+        Err(BetError::Failed)
+    }
+
+    async fn withdraw(&mut self, _amount: f32, _address: &str) -> Result<(), BetError> {
+        Err(BetError::Unsupported("withdrawals".to_string()))
+    }
+
+    async fn refresh_balance(&mut self) -> Result<f32, BetError> {
+        let Balances { balances } = Self::get(&self.client, &self.token, "user/balances").await?;
+        let code = self.currency_code();
+        let balance = balances
+            .iter()
+            .find(|balance| balance.currency == code)
+            .ok_or_else(|| {
+                BetError::ConfigError(format!("Wolf.bet has no {} balance", self.currency))
+            })?;
+        self.balance = balance.amount as f32;
+        self.strategy.set_balance(self.balance);
+
+        Ok(self.balance)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.balance += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_win(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.balance -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.wagered += bet_result.bet_amount;
+
+        if self.history.len() >= self.history_size {
+            self.strategy.on_lose(bet_result);
+            log::debug!("Strategy state: {}", self.strategy.describe_state());
+        }
+    }
+
+    fn get_history(&self) -> Vec<BetResult> {
+        self.history.clone()
+    }
+
+    fn get_history_size(&self) -> usize {
+        self.history_size
+    }
+
+    fn get_rolls(&self) -> u64 {
+        self.rolls
+    }
+
+    fn get_current_bet(&self) -> f32 {
+        self.current_bet
+    }
+
+    fn get_current_multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn get_win_target(&self) -> f32 {
+        self.strategy.get_win_target()
+    }
+
+    fn get_wagered(&self) -> f32 {
+        self.wagered
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.balance
+    }
+
+    fn last_bet_rule(&self) -> BetRule {
+        self.last_rule
+    }
+
+    fn reconnect(&mut self) -> Result<(), BetError> {
+        self.client = client_builder(self.proxy.as_ref()).build()?;
+
+        Ok(())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(reply: &str) -> BetResult {
+        serde_json::from_str::<Response<PlayReply>>(reply)
+            .unwrap()
+            .into_data()
+            .unwrap()
+            .bet
+            .into()
+    }
+
+    #[test]
+    fn test_play_reply_maps_every_field() {
+        let bet = play(
+            r#"{"bet": {
+                "hash": "c1d2e3f4", "nonce": 41, "user_seed": "client",
+                "server_seed_hashed": "5e8ff9bf55ba3508199d22e984129be6",
+                "currency": "btc", "amount": "0.00000100", "profit": "0.00000098",
+                "multiplier": "1.9800", "bet_value": "50.49", "result_value": "73.12",
+                "rule": "over", "state": "win"
+            }, "userBalance": {"amount": "0.00100098", "currency": "btc"}}"#,
+        );
+
+        assert_eq!(bet.hash_previous_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.hash_next_roll, "5e8ff9bf55ba3508199d22e984129be6");
+        assert_eq!(bet.client_seed, "client");
+        assert_eq!(bet.nonce, 41);
+        assert_eq!(bet.symbol, "BTC");
+        assert_eq!(bet.number.value(), 7312);
+        assert!(bet.result && bet.is_high && bet.rolled_high);
+        assert!((bet.chance - 49.5).abs() < 1e-4);
+        assert!((bet.payout - 1.98).abs() < 1e-6);
+        assert!((bet.bet_amount - 1e-6).abs() < 1e-12);
+        assert!((bet.win_amount - 0.98e-6).abs() < 1e-12);
+
+        let lost = play(
+            r#"{"bet": {
+                "hash": "c1d2e3f5", "nonce": 42, "user_seed": "client",
+                "server_seed_hashed": "5e8ff9bf55ba3508199d22e984129be6",
+                "currency": "btc", "amount": "0.00000100", "profit": "-0.00000100",
+                "multiplier": "2", "bet_value": "49.50", "result_value": "62.50",
+                "rule": "under", "state": "loss"
+            }}"#,
+        );
+
+        assert!(!lost.result && !lost.is_high && lost.rolled_high);
+        // Positive on a loss too, as `on_lose` subtracts it.
+        assert!((lost.win_amount - 1e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_error_reply_is_an_error() {
+        let reply = r#"{"error": {"message": "Insufficient funds"}}"#;
+
+        assert!(serde_json::from_str::<Response<PlayReply>>(reply)
+            .unwrap()
+            .into_data()
+            .is_err());
+    }
+}
@@ -111,4 +111,44 @@ impl Strategy for AIFight {
     fn get_profit(&self) -> f32 {
         self.profit
     }
+
+    fn describe_state(&self) -> String {
+        format!(
+            "win streak {}, loss streak {}, bet {:.8}, chance {:.2}%, profit {:.8}",
+            self.win_streak, self.loss_streak, self.current_bet, self.chance, self.profit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    #[test]
+    fn test_ai_fight_scales_with_streaks() {
+        let mut strategy = AIFight::default()
+            .with_balance(1.)
+            .with_min_bet(1e-5)
+            .with_initial_bet(1e-5);
+
+        // The very first call without a prediction is a fixed probe bet.
+        assert_eq!(strategy.get_next_bet(0., 0.), (1e-8, 2., 50., false));
+
+        let (bet, multiplier, chance, _) = strategy.get_next_bet(5000., 0.);
+        assert_close(bet, 1e-4);
+        assert_close(multiplier, 2.);
+        assert_close(chance, 50.);
+
+        // A loss streak raises the bet by 5% per step.
+        strategy.on_lose(&test_bet_result(false, 9000, 1e-4));
+        assert_close(strategy.get_balance(), 0.9999);
+        assert_close(strategy.get_next_bet(5000., 0.).0, 0.9999e-4 * 1.05);
+
+        // A win streak adds half a minimum bet per win on top.
+        strategy.on_win(&test_bet_result(true, 1000, 1e-4));
+        strategy.on_win(&test_bet_result(true, 1000, 1e-4));
+        assert_close(strategy.get_next_bet(5000., 0.).0, 1e-5 + 1.0001e-4 * 1.1);
+        assert_close(strategy.get_profit(), 1e-4);
+    }
 }
@@ -266,7 +266,7 @@ impl Strategy for BlaksRunner5_0 {
         }
         self.temp_win_mult = self.win_mult;
         self.next_bet = self.base_bet;
-        self.calc_chance(true, bet_result.number);
+        self.calc_chance(true, bet_result.number.value());
         self.auto_tune();
     }
 
@@ -274,8 +274,8 @@ impl Strategy for BlaksRunner5_0 {
         self.loss_count += 1;
         self.high_low_loss_count += 1;
         self.spent += bet_result.win_amount;
-        self.bankroll += bet_result.win_amount;
-        self.profit += bet_result.win_amount;
+        self.bankroll -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
         self.profit = self.profit.max(0.);
 
         let win_temp = (100. - (100. * (self.house_percent / 100.))) / self.chance;
@@ -291,7 +291,7 @@ impl Strategy for BlaksRunner5_0 {
             self.high_low_loss_count = 0;
         }
 
-        self.calc_chance(false, bet_result.number);
+        self.calc_chance(false, bet_result.number.value());
         self.auto_tune();
     }
 
@@ -306,4 +306,61 @@ impl Strategy for BlaksRunner5_0 {
     fn get_win_target(&self) -> f32 {
         self.win_target
     }
+
+    fn describe_state(&self) -> String {
+        format!(
+            "step {}/{}, losses {}, base bet {:.8}, next bet {:.8}, chance {:.2}%, profit {:.8} of target {:.8}",
+            self.current_step,
+            self.step_count,
+            self.loss_count,
+            self.base_bet,
+            self.next_bet,
+            self.chance,
+            self.profit,
+            self.win_target
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    #[test]
+    fn test_blaks_runner_progression() {
+        let mut strategy = BlaksRunner5_0::default()
+            .with_balance(0.001)
+            .with_min_bet(1e-8)
+            .with_initial_bet(1e-8);
+
+        let (first, multiplier, chance, high) = strategy.get_next_bet(5000., 0.);
+        assert_close(first, 1.0107369e-8);
+        assert_close(multiplier, 100.);
+        assert_close(chance, 1.);
+        assert!(!high);
+
+        // Losses are added to the amount to recover, so the bet creeps up.
+        strategy.on_lose(&test_bet_result(false, 9000, first));
+        let second = strategy.get_next_bet(5000., 0.).0;
+        assert_close(second, 1.0216069e-8);
+        strategy.on_lose(&test_bet_result(false, 9000, second));
+        let third = strategy.get_next_bet(5000., 0.).0;
+        assert_close(third, 1.0324774e-8);
+        assert_close(strategy.get_balance(), 0.001 - first - second);
+        assert!(strategy
+            .describe_state()
+            .starts_with(&format!("step {}/", strategy.current_step)));
+        assert!(strategy.describe_state().contains("losses 2"));
+
+        // A win resets the bet and moves the chance to the average of recent winning rolls.
+        let won = third * 21.;
+        strategy.on_win(&test_bet_result(true, 300, won));
+        let (bet, multiplier, chance, _) = strategy.get_next_bet(5000., 0.);
+        assert_close(bet, 1.0039928e-8);
+        assert_close(chance, 0.375);
+        assert_close(multiplier, 1. / 0.00375);
+        assert_close(strategy.get_balance(), 0.001 - first - second + won);
+        assert_close(strategy.get_profit(), won);
+    }
 }
@@ -0,0 +1,72 @@
+use crate::sites::BetResult;
+use crate::strategies::Strategy;
+
+#[derive(Debug)]
+pub struct FlatBet {
+    high: bool,
+    bet: f32,
+    chance: f32,
+    bank: f32,
+    profit: f32,
+}
+
+impl Default for FlatBet {
+    fn default() -> Self {
+        Self {
+            high: false,
+            bet: 1e-8,
+            chance: 50.,
+            bank: 1e-8,
+            profit: 0.,
+        }
+    }
+}
+
+impl Strategy for FlatBet {
+    fn with_initial_bet(mut self, initial_bet: f32) -> Self {
+        self.bet = initial_bet;
+
+        self
+    }
+
+    fn with_balance(mut self, balance: f32) -> Self {
+        self.bank = balance;
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.bet = self.bet.max(min_bet);
+
+        self
+    }
+
+    fn set_balance(&mut self, balance: f32) {
+        self.bank = balance;
+        self.profit = 0.;
+    }
+
+    fn get_next_bet(&mut self, prediction: f32, _confidence: f32) -> (f32, f32, f32, bool) {
+        self.high = prediction > 5000.;
+
+        (self.bet, 1. / (self.chance / 100.), self.chance, self.high)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.bank += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.bank -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.bank
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+}
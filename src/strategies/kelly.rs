@@ -0,0 +1,141 @@
+use crate::sites::BetResult;
+use crate::strategies::Strategy;
+
+/// Sizes bets by the Kelly criterion, taking the model's confidence as the chance of
+/// winning. Only `fraction` of the Kelly stake is bet, as the confidence is an estimate
+/// and a full Kelly stake on an overestimated edge quickly ruins the bankroll.
+#[derive(Debug)]
+pub struct KellyStrat {
+    chance: f32,
+    /// Share of the Kelly stake that is bet, 0-1.
+    fraction: f32,
+    min_bet: f32,
+    bank: f32,
+    profit: f32,
+}
+
+impl Default for KellyStrat {
+    fn default() -> Self {
+        Self {
+            chance: 50.,
+            fraction: 0.25,
+            min_bet: 1e-8,
+            bank: 1e-8,
+            profit: 0.,
+        }
+    }
+}
+
+impl KellyStrat {
+    /// Bets `fraction` (0-1) of the Kelly stake.
+    pub fn with_fraction(mut self, fraction: f32) -> Self {
+        self.fraction = fraction.clamp(0., 1.);
+
+        self
+    }
+
+    /// Share of the bankroll the Kelly criterion stakes at `multiplier` when a bet wins
+    /// with probability `win_probability`; 0 without an edge.
+    pub fn kelly_fraction(win_probability: f32, multiplier: f32) -> f32 {
+        let net_odds = multiplier - 1.;
+        if net_odds <= 0. {
+            return 0.;
+        }
+
+        let win_probability = win_probability.clamp(0., 1.);
+        (win_probability - (1. - win_probability) / net_odds).clamp(0., 1.)
+    }
+}
+
+impl Strategy for KellyStrat {
+    fn with_initial_bet(self, _initial_bet: f32) -> Self {
+        // Every bet is sized from the bankroll, so there is no fixed first bet.
+        self
+    }
+
+    fn with_balance(mut self, balance: f32) -> Self {
+        self.bank = balance;
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.min_bet = min_bet;
+
+        self
+    }
+
+    fn set_balance(&mut self, balance: f32) {
+        self.bank = balance;
+        self.profit = 0.;
+    }
+
+    fn get_next_bet(&mut self, prediction: f32, confidence: f32) -> (f32, f32, f32, bool) {
+        let multiplier = 1. / (self.chance / 100.);
+        let kelly = Self::kelly_fraction(confidence / 100., multiplier);
+        let bet = (self.bank * kelly * self.fraction).max(self.min_bet);
+
+        (bet, multiplier, self.chance, prediction > 5000.)
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.bank += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.bank -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.bank
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    fn strategy() -> KellyStrat {
+        KellyStrat::default()
+            .with_balance(1.)
+            .with_min_bet(0.001)
+            .with_fraction(0.5)
+    }
+
+    #[test]
+    fn test_kelly_bets_a_fraction_of_the_edge() {
+        // At 2x, winning 60% of the time stakes 20% of the bankroll; half Kelly is 10%.
+        let (bet, multiplier, chance, high) = strategy().get_next_bet(7500., 60.);
+        assert_close(bet, 0.1);
+        assert_close(multiplier, 2.);
+        assert_close(chance, 50.);
+        assert!(high);
+
+        let mut strategy = strategy();
+        strategy.on_lose(&test_bet_result(false, 1000, 0.1));
+        assert_close(strategy.get_balance(), 0.9);
+        assert_close(strategy.get_next_bet(0., 60.).0, 0.09);
+        strategy.on_win(&test_bet_result(true, 1000, 0.09));
+        assert_close(strategy.get_profit(), -0.01);
+    }
+
+    #[test]
+    fn test_kelly_bets_the_minimum_without_an_edge() {
+        // Break-even: 50% at 2x.
+        assert_eq!(KellyStrat::kelly_fraction(0.5, 2.), 0.);
+        assert_close(strategy().get_next_bet(0., 50.).0, 0.001);
+
+        // A negative edge never turns into a negative bet.
+        assert_eq!(KellyStrat::kelly_fraction(0.2, 2.), 0.);
+        assert_eq!(KellyStrat::kelly_fraction(-1., 2.), 0.);
+        assert_close(strategy().get_next_bet(0., 20.).0, 0.001);
+        assert_close(strategy().get_next_bet(0., 0.).0, 0.001);
+    }
+}
@@ -0,0 +1,141 @@
+//! Baseline strategies that pick high/low from the previous roll and ignore the model.
+//!
+//! They bet a flat amount at 50% chance, so comparing their results with a model-driven
+//! run shows whether the model beats a one-line heuristic.
+
+use crate::sites::BetResult;
+use crate::strategies::Strategy;
+use crate::util::Roll;
+
+/// How the previous roll picks the side of the next bet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LastRollMode {
+    /// Bet on the same side as the last roll (momentum).
+    Follow,
+    /// Bet on the opposite side of the last roll (mean reversion).
+    Opposite,
+}
+
+#[derive(Debug)]
+pub struct LastRoll {
+    mode: LastRollMode,
+    last_roll: Option<Roll>,
+    bet: f32,
+    chance: f32,
+    bank: f32,
+    profit: f32,
+}
+
+impl LastRoll {
+    pub fn new(mode: LastRollMode) -> Self {
+        Self {
+            mode,
+            last_roll: None,
+            bet: 1e-8,
+            chance: 50.,
+            bank: 1e-8,
+            profit: 0.,
+        }
+    }
+
+    fn bet_high(&self) -> bool {
+        let Some(last_roll) = self.last_roll else {
+            return false;
+        };
+        let last_high = last_roll.value() > 5000;
+
+        match self.mode {
+            LastRollMode::Follow => last_high,
+            LastRollMode::Opposite => !last_high,
+        }
+    }
+}
+
+impl Strategy for LastRoll {
+    fn with_initial_bet(mut self, initial_bet: f32) -> Self {
+        self.bet = initial_bet;
+
+        self
+    }
+
+    fn with_balance(mut self, balance: f32) -> Self {
+        self.bank = balance;
+
+        self
+    }
+
+    fn with_min_bet(mut self, min_bet: f32) -> Self {
+        self.bet = self.bet.max(min_bet);
+
+        self
+    }
+
+    fn set_balance(&mut self, balance: f32) {
+        self.bank = balance;
+        self.profit = 0.;
+    }
+
+    fn get_next_bet(&mut self, _prediction: f32, _confidence: f32) -> (f32, f32, f32, bool) {
+        (
+            self.bet,
+            1. / (self.chance / 100.),
+            self.chance,
+            self.bet_high(),
+        )
+    }
+
+    fn on_win(&mut self, bet_result: &BetResult) {
+        self.bank += bet_result.win_amount;
+        self.profit += bet_result.win_amount;
+        self.last_roll = Some(bet_result.number);
+    }
+
+    fn on_lose(&mut self, bet_result: &BetResult) {
+        self.bank -= bet_result.win_amount;
+        self.profit -= bet_result.win_amount;
+        self.last_roll = Some(bet_result.number);
+    }
+
+    fn get_balance(&self) -> f32 {
+        self.bank
+    }
+
+    fn get_profit(&self) -> f32 {
+        self.profit
+    }
+
+    fn reset(&mut self) {
+        self.profit = 0.;
+        self.last_roll = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    #[test]
+    fn test_last_roll_picks_side_from_previous_roll() {
+        let mut follow = LastRoll::new(LastRollMode::Follow).with_balance(1.);
+        let mut opposite = LastRoll::new(LastRollMode::Opposite).with_balance(1.);
+
+        // The prediction is ignored.
+        assert!(!follow.get_next_bet(9999., 1.).3);
+
+        for strategy in [&mut follow, &mut opposite] {
+            strategy.on_lose(&test_bet_result(false, 8000, 0.1));
+        }
+        assert!(follow.get_next_bet(0., 0.).3);
+        assert!(!opposite.get_next_bet(0., 0.).3);
+
+        for strategy in [&mut follow, &mut opposite] {
+            strategy.on_win(&test_bet_result(true, 1200, 0.1));
+        }
+        assert!(!follow.get_next_bet(0., 0.).3);
+        assert!(opposite.get_next_bet(0., 0.).3);
+
+        assert_close(follow.get_balance(), 1.);
+        assert_close(follow.get_next_bet(0., 0.).1, 2.);
+    }
+}
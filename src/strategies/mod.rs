@@ -5,9 +5,13 @@
 
 pub mod ai_fight;
 pub mod blaks_runner;
+pub mod flat;
+pub mod kelly;
+pub mod last_roll;
 pub mod my_strategy;
 pub mod none;
 
+use crate::config::TomlStrategies;
 use crate::sites::BetResult;
 
 pub trait Strategy: std::fmt::Debug + Send {
@@ -42,4 +46,188 @@ pub trait Strategy: std::fmt::Debug + Send {
         0.
     }
     fn reset(&mut self) {}
+
+    /// Human-readable snapshot of the progression state, logged at debug after each bet.
+    fn describe_state(&self) -> String {
+        format!(
+            "balance {:.8}, profit {:.8}, win target {:.8}",
+            self.get_balance(),
+            self.get_profit(),
+            self.get_win_target()
+        )
+    }
+}
+
+/// Resets `strategy` once its profit reaches a positive win target.
+///
+/// Returns the profit made in the finished cycle, or `None` when the target is unset or
+/// not reached yet.
+pub fn take_win_target(strategy: &mut dyn Strategy) -> Option<f32> {
+    let target = strategy.get_win_target();
+    let profit = strategy.get_profit();
+    if target <= 0. || profit < target {
+        return None;
+    }
+
+    strategy.reset();
+    Some(profit)
+}
+
+/// Why a session stopped betting at a profit or loss target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    TakeProfit,
+    StopLoss,
+    WinTarget,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::TakeProfit => "take-profit target",
+            Self::StopLoss => "stop-loss limit",
+            Self::WinTarget => "strategy win target",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// Checks the session `profit` against its targets, returning why it should stop.
+///
+/// `stop_loss` is the loss allowed, as a positive amount. A `win_target` of 0 or less
+/// is unset, like every `None` target, so a session without targets never stops here.
+pub fn stop_reason(
+    profit: f32,
+    take_profit: Option<f32>,
+    stop_loss: Option<f32>,
+    win_target: f32,
+) -> Option<StopReason> {
+    if take_profit.is_some_and(|take_profit| profit >= take_profit) {
+        Some(StopReason::TakeProfit)
+    } else if stop_loss.is_some_and(|stop_loss| profit <= -stop_loss) {
+        Some(StopReason::StopLoss)
+    } else if win_target > 0. && profit >= win_target {
+        Some(StopReason::WinTarget)
+    } else {
+        None
+    }
+}
+
+/// Builds the strategy selected in the config, seeded with a bankroll, minimum bet and
+/// first bet.
+pub fn from_config(
+    strategy: TomlStrategies,
+    balance: f32,
+    min_bet: f32,
+    initial_bet: f32,
+) -> Box<dyn Strategy> {
+    match strategy {
+        TomlStrategies::AiFight => Box::new(
+            ai_fight::AIFight::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::BlaksRunner => Box::new(
+            blaks_runner::BlaksRunner5_0::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::MyStrategy => Box::new(
+            my_strategy::MyStrat::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::Flat => Box::new(
+            flat::FlatBet::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::Kelly => Box::new(
+            kelly::KellyStrat::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::FollowLast => Box::new(
+            last_roll::LastRoll::new(last_roll::LastRollMode::Follow)
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::OppositeLast => Box::new(
+            last_roll::LastRoll::new(last_roll::LastRollMode::Opposite)
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+        TomlStrategies::None => Box::new(
+            none::NoStrat::default()
+                .with_balance(balance)
+                .with_min_bet(min_bet)
+                .with_initial_bet(initial_bet),
+        ),
+    }
+}
+
+/// Builds a settled bet for driving strategies in tests.
+///
+/// `win_amount` is positive for both outcomes, matching what sites pass to `on_lose`.
+#[cfg(test)]
+pub(crate) fn test_bet_result(result: bool, number: u32, win_amount: f32) -> BetResult {
+    BetResult {
+        hash_previous_roll: String::new(),
+        hash_next_roll: String::new(),
+        client_seed: String::new(),
+        nonce: 0,
+        symbol: "BTC".to_string(),
+        result,
+        is_high: number > 5000,
+        rolled_high: number >= 5000,
+        number: crate::util::Roll::clamped(number),
+        threshold: 0,
+        chance: 50.,
+        payout: 2.,
+        bet_amount: win_amount,
+        win_amount,
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() <= expected.abs() * 1e-4 + 1e-12,
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_reason_checks_each_target() {
+        assert_eq!(stop_reason(5., None, None, 0.), None);
+        assert_eq!(stop_reason(-5., None, None, 0.), None);
+
+        assert_eq!(stop_reason(0.9, Some(1.), Some(1.), 0.), None);
+        assert_eq!(
+            stop_reason(1., Some(1.), Some(1.), 0.),
+            Some(StopReason::TakeProfit)
+        );
+        assert_eq!(stop_reason(-0.9, Some(1.), Some(1.), 0.), None);
+        assert_eq!(
+            stop_reason(-1., Some(1.), Some(1.), 0.),
+            Some(StopReason::StopLoss)
+        );
+
+        assert_eq!(stop_reason(0.4, None, None, 0.5), None);
+        assert_eq!(
+            stop_reason(0.5, None, None, 0.5),
+            Some(StopReason::WinTarget)
+        );
+    }
 }
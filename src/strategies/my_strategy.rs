@@ -141,6 +141,19 @@ impl Strategy for MyStrat {
         self.profit
     }
 
+    fn describe_state(&self) -> String {
+        format!(
+            "win streak {}, loss streak {}, bet {:.8} (initial {:.8}), chance {:.2}%, loss {:.8}, profit {:.8}",
+            self.win_streak,
+            self.loss_streak,
+            self.current_bet,
+            self.initial_bet,
+            self.chance,
+            self.loss,
+            self.profit
+        )
+    }
+
     fn reset(&mut self) {
         self.profit = 0.;
         self.gain = 0.;
@@ -149,3 +162,38 @@ impl Strategy for MyStrat {
         self.win_streak = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    #[test]
+    fn test_my_strat_recovers_losses() {
+        let mut strategy = MyStrat::default()
+            .with_balance(1.)
+            .with_min_bet(0.01)
+            .with_initial_bet(0.01);
+        let multiplier = 1. / 0.51;
+
+        let (bet, next_multiplier, chance, high) = strategy.get_next_bet(5000., 0.);
+        assert_close(bet, 0.01);
+        assert_close(next_multiplier, multiplier);
+        assert_close(chance, 51.);
+        assert!(!high);
+
+        // Each loss raises the bet so that a win recovers everything lost so far.
+        strategy.on_lose(&test_bet_result(false, 9000, bet));
+        let bet = strategy.get_next_bet(5000., 0.).0;
+        assert_close(bet, 0.01 / (multiplier - 1.));
+        strategy.on_lose(&test_bet_result(false, 9000, bet));
+        let second = strategy.get_next_bet(5000., 0.).0;
+        assert_close(second, (0.01 + bet) / (multiplier - 1.));
+        assert_close(strategy.get_balance(), 1. - 0.01 - bet);
+
+        // A win clears the losses and drops back to the minimum bet.
+        strategy.on_win(&test_bet_result(true, 1000, 0.02));
+        assert_close(strategy.get_next_bet(5000., 0.).0, 0.01);
+        assert_close(strategy.get_profit(), 0.02 - 0.01 - bet);
+    }
+}
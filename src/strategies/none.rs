@@ -113,6 +113,7 @@ impl Strategy for NoStrat {
         Self: Sized,
     {
         self.bank = balance;
+        self.initial_bank = balance;
         self.profit = 0.;
         self.win_target = balance;
 
@@ -169,3 +170,53 @@ impl Strategy for NoStrat {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{assert_close, test_bet_result};
+
+    #[test]
+    fn test_no_strat_progression() {
+        let mut strategy = NoStrat::default()
+            .with_balance(1.)
+            .with_min_bet(0.01)
+            .with_initial_bet(0.01);
+
+        let (bet, multiplier, chance, high) = strategy.get_next_bet(5000., 0.);
+        assert_close(bet, 0.01);
+        assert_close(multiplier, 2.);
+        assert_close(chance, 50.);
+        assert!(!high);
+
+        // A win adds a quarter of the winnings, but never drops below the base bet of bank / 50.
+        strategy.on_win(&test_bet_result(true, 1000, 0.01));
+        assert_close(strategy.get_balance(), 1.01);
+        assert_close(strategy.get_next_bet(5000., 0.).0, 0.02);
+        strategy.on_win(&test_bet_result(true, 1000, 0.02));
+        assert_close(strategy.get_next_bet(5000., 0.).0, 0.025);
+
+        // A loss falls back to the base bet.
+        strategy.on_lose(&test_bet_result(false, 9000, 0.025));
+        assert_close(strategy.get_next_bet(5000., 0.).0, 0.02);
+        assert_close(strategy.get_balance(), 1.005);
+        assert_close(strategy.get_profit(), 0.005);
+    }
+
+    #[test]
+    fn test_no_strat_resets_at_win_target() {
+        let mut strategy = NoStrat::default()
+            .with_balance(0.01)
+            .with_min_bet(0.001)
+            .with_initial_bet(0.001);
+
+        strategy.on_win(&test_bet_result(true, 1000, 0.005));
+        assert_eq!(crate::strategies::take_win_target(&mut strategy), None);
+
+        strategy.on_win(&test_bet_result(true, 1000, 0.005));
+        let banked = crate::strategies::take_win_target(&mut strategy).unwrap();
+        assert_close(banked, 0.01);
+        assert_close(strategy.get_profit(), 0.);
+        assert_close(strategy.get_balance(), 0.01);
+    }
+}
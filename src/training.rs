@@ -1,24 +1,42 @@
 use crate::{
-    data::{BetBatch, BetBatcher},
+    data::{continuous_features, BetBatch, BetBatcher},
     dataset::BetResultsDataset,
-    model::{Model, ModelConfig},
+    model::{Model, ModelConfig, OutputHead},
+    normalizer::Normalizer,
+};
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
 };
 
 use burn::{
-    data::dataloader::DataLoaderBuilder,
-    lr_scheduler::noam::NoamLrSchedulerConfig,
-    nn::loss::CrossEntropyLossConfig,
-    optim::AdamConfig,
+    data::{
+        dataloader::{DataLoader, DataLoaderBuilder},
+        dataset::Dataset,
+    },
+    grad_clipping::GradientClippingConfig,
+    lr_scheduler::{noam::NoamLrSchedulerConfig, step::StepLrSchedulerConfig, LrScheduler},
+    nn::loss::{CrossEntropyLossConfig, MseLoss, Reduction},
+    optim::{decay::WeightDecayConfig, AdamConfig, AdamWConfig, Optimizer, SgdConfig},
     prelude::*,
     record::CompactRecorder,
     tensor::backend::AutodiffBackend,
     train::{
         metric::{CudaMetric, HammingScore, LearningRateMetric, LossMetric},
         renderer::{MetricState, MetricsRenderer, TrainingProgress},
-        LearnerBuilder, MultiLabelClassificationOutput, TrainOutput, TrainStep, ValidStep,
+        LearnerBuilder, MultiLabelClassificationOutput, RegressionOutput, TrainOutput, TrainStep,
+        ValidStep,
     },
+    LearningRate,
 };
 
+/// Number of batches whose gradients are accumulated before each optimizer step.
+const GRADS_ACCUMULATION: usize = 6;
+
+/// Number of training batches processed so far, used to report where a loss went non-finite.
+static TRAIN_BATCH_INDEX: AtomicUsize = AtomicUsize::new(0);
+
 impl<B: Backend> Model<B> {
     pub fn forward_classification(&self, item: BetBatch<B>) -> MultiLabelClassificationOutput<B> {
         let class_indices = item.targets.clone().argmax(1).flatten::<1>(0, 1);
@@ -27,13 +45,21 @@ impl<B: Backend> Model<B> {
             .init(&output.device())
             .forward(output.clone(), class_indices.clone());
 
-        MultiLabelClassificationOutput::new(loss, output, item.targets)
+        MultiLabelClassificationOutput::new(loss, output, item.targets.int())
+    }
+
+    pub fn forward_regression(&self, item: BetBatch<B>) -> RegressionOutput<B> {
+        let output = self.forward(item.clone());
+        let loss = MseLoss::new().forward(output.clone(), item.targets.clone(), Reduction::Mean);
+
+        RegressionOutput::new(loss, output, item.targets)
     }
 }
 
 impl<B: AutodiffBackend> TrainStep<BetBatch<B>, MultiLabelClassificationOutput<B>> for Model<B> {
     fn step(&self, batch: BetBatch<B>) -> TrainOutput<MultiLabelClassificationOutput<B>> {
         let item = self.forward_classification(batch);
+        abort_on_non_finite_loss(&item.loss);
 
         TrainOutput::new(self, item.loss.backward(), item)
     }
@@ -45,19 +71,178 @@ impl<B: Backend> ValidStep<BetBatch<B>, MultiLabelClassificationOutput<B>> for M
     }
 }
 
+impl<B: AutodiffBackend> TrainStep<BetBatch<B>, RegressionOutput<B>> for Model<B> {
+    fn step(&self, batch: BetBatch<B>) -> TrainOutput<RegressionOutput<B>> {
+        let item = self.forward_regression(batch);
+        abort_on_non_finite_loss(&item.loss);
+
+        TrainOutput::new(self, item.loss.backward(), item)
+    }
+}
+
+impl<B: Backend> ValidStep<BetBatch<B>, RegressionOutput<B>> for Model<B> {
+    fn step(&self, batch: BetBatch<B>) -> RegressionOutput<B> {
+        self.forward_regression(batch)
+    }
+}
+
+/// Counts the training batch and panics when its loss is not finite.
+fn abort_on_non_finite_loss<B: Backend>(loss: &Tensor<B, 1>) {
+    let batch_index = TRAIN_BATCH_INDEX.fetch_add(1, Ordering::Relaxed);
+    let loss = loss.clone().into_scalar().elem::<f64>();
+    if let Err(err) = check_loss(loss, batch_index) {
+        log::error!("{err}");
+        panic!("{err}");
+    }
+}
+
+/// Fails when `loss` is NaN or infinite, so a diverged run stops before writing a checkpoint.
+fn check_loss(loss: f64, batch_index: usize) -> Result<(), String> {
+    if loss.is_finite() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Training loss became {loss} at batch {batch_index}; aborting before the model is corrupted. \
+             Try lowering the learning rate or setting grad_clip_norm."
+        ))
+    }
+}
+
+/// Optimizer used to train the model.
+#[derive(Config, Debug, PartialEq)]
+pub enum OptimizerKind {
+    Adam,
+    AdamW,
+    Sgd,
+}
+
+/// Learning-rate schedule applied on top of `TrainingConfig::learning_rate`.
+#[derive(Config, Debug, PartialEq)]
+pub enum LrSchedule {
+    /// Keep the learning rate fixed.
+    Constant,
+    /// Noam schedule: linear warmup followed by inverse square root decay.
+    Noam { warmup_steps: usize },
+    /// Linear warmup followed by cosine decay to `min_lr` over `decay_steps`.
+    WarmupCosine {
+        warmup_steps: usize,
+        decay_steps: usize,
+        min_lr: f64,
+    },
+    /// Multiply the learning rate by `gamma` every `step_size` steps.
+    Step { step_size: usize, gamma: f64 },
+}
+
+/// Linear warmup to `max_lr`, then cosine decay to `min_lr`, then constant at `min_lr`.
+#[derive(Clone, Debug)]
+pub struct WarmupCosineLrScheduler {
+    max_lr: LearningRate,
+    min_lr: LearningRate,
+    warmup_steps: usize,
+    decay_steps: usize,
+    current_step: usize,
+}
+
+impl WarmupCosineLrScheduler {
+    pub fn new(
+        max_lr: LearningRate,
+        min_lr: LearningRate,
+        warmup_steps: usize,
+        decay_steps: usize,
+    ) -> Self {
+        Self {
+            max_lr,
+            min_lr,
+            warmup_steps,
+            decay_steps,
+            current_step: 0,
+        }
+    }
+}
+
+impl LrScheduler for WarmupCosineLrScheduler {
+    type Record<B: Backend> = usize;
+
+    fn step(&mut self) -> LearningRate {
+        self.current_step += 1;
+
+        if self.current_step <= self.warmup_steps {
+            return self.max_lr * self.current_step as f64 / self.warmup_steps as f64;
+        }
+
+        let progress = ((self.current_step - self.warmup_steps) as f64
+            / self.decay_steps.max(1) as f64)
+            .min(1.);
+        self.min_lr
+            + 0.5 * (self.max_lr - self.min_lr) * (1. + (std::f64::consts::PI * progress).cos())
+    }
+
+    fn to_record<B: Backend>(&self) -> Self::Record<B> {
+        self.current_step
+    }
+
+    fn load_record<B: Backend>(mut self, record: Self::Record<B>) -> Self {
+        self.current_step = record;
+        self
+    }
+}
+
 #[derive(Config)]
 pub struct TrainingConfig {
-    pub optimizer: AdamConfig,
+    /// Architecture the model is built with; saved in `config.json` so a checkpoint
+    /// is loaded back into the same layout.
+    #[config(default = "ModelConfig::new()")]
+    pub model: ModelConfig,
+    #[config(default = "OptimizerKind::Adam")]
+    pub optimizer: OptimizerKind,
+    /// Peak learning rate, split across the accumulated gradient steps.
+    #[config(default = 0.01)]
+    pub learning_rate: f64,
+    /// L2 weight decay; `0` disables it (AdamW always applies its own decoupled decay).
+    #[config(default = 0.)]
+    pub weight_decay: f64,
+    #[config(default = "LrSchedule::Noam { warmup_steps: 6000 }")]
+    pub lr_schedule: LrSchedule,
+    /// Clip gradients to this L2 norm before each optimizer step; unset disables clipping.
+    pub grad_clip_norm: Option<f32>,
     #[config(default = 512)]
     pub max_seq_len: usize,
     #[config(default = 10000000)]
     pub num_epochs: usize,
+    /// Rolls per batch; must be a whole number of `model.history_size` windows.
     #[config(default = 100)]
     pub batch_size: usize,
+    /// Data-loader worker threads generating and batching samples.
     #[config(default = 1)]
     pub num_workers: usize,
     #[config(default = 42)]
     pub seed: u64,
+    /// Fit a `Normalizer` on the continuous features, feed them to the model and save it
+    /// next to `config.json`, where inference loads it from.
+    #[config(default = false)]
+    pub normalize_features: bool,
+}
+
+/// Number of training records sampled to fit the feature normalizer.
+const NORMALIZER_SAMPLES: usize = 10_000;
+
+impl TrainingConfig {
+    /// Validates the data-loading settings
+    pub fn validate(&self) -> Result<(), String> {
+        let history_size = self.model.history_size;
+        if self.batch_size == 0 || !self.batch_size.is_multiple_of(history_size) {
+            return Err(format!(
+                "batch_size must be a positive multiple of the history size ({history_size}), got {}",
+                self.batch_size
+            ));
+        }
+        if self.num_workers == 0 {
+            return Err("num_workers must be at least 1".to_string());
+        }
+        self.model.validate()?;
+
+        Ok(())
+    }
 }
 
 #[allow(dead_code)]
@@ -80,35 +265,197 @@ fn create_artifact_dir(artifact_dir: &str) {
 }
 
 pub fn train<B: AutodiffBackend>(artifact_dir: &str, config: TrainingConfig, device: B::Device) {
+    if let Err(err) = config.validate() {
+        panic!("Invalid training config: {err}");
+    }
     create_artifact_dir(artifact_dir);
     config
         .save(format!("{artifact_dir}/config.json"))
         .expect("Config should be saved successfully");
     B::seed(config.seed);
 
-    let model = ModelConfig::new().init::<B>(&device);
+    let dataset_train = BetResultsDataset::train().unwrap();
+    let normalizer = config.normalize_features.then(|| {
+        let samples = (0..dataset_train.len().min(NORMALIZER_SAMPLES))
+            .filter_map(|index| dataset_train.get(index))
+            .map(|record| continuous_features(&record))
+            .collect::<Vec<Vec<f32>>>();
+        let normalizer = Normalizer::fit(&samples);
+        normalizer
+            .save_to(artifact_dir)
+            .expect("Normalizer should be saved successfully");
+        normalizer
+    });
+
+    let model = config.model.init::<B>(&device);
 
-    let batcher_train = BetBatcher::<B>::new(device.clone());
-    let batcher_valid = BetBatcher::<B::InnerBackend>::new(device.clone());
+    let mut batcher_train = BetBatcher::<B>::new(device.clone())
+        .with_head(config.model.head.clone())
+        .with_history_size(config.model.history_size);
+    let mut batcher_valid = BetBatcher::<B::InnerBackend>::new(device.clone())
+        .with_head(config.model.head.clone())
+        .with_history_size(config.model.history_size);
+    // The same statistics scale both sets, and are reloaded at inference.
+    if let Some(normalizer) = normalizer {
+        batcher_train = batcher_train.with_normalizer(normalizer.clone());
+        batcher_valid = batcher_valid.with_normalizer(normalizer);
+    }
 
     let dataloader_train = DataLoaderBuilder::new(batcher_train)
         .batch_size(config.batch_size)
         .num_workers(config.num_workers)
-        .build(BetResultsDataset::train().unwrap());
+        .build(dataset_train);
 
     let dataloader_test = DataLoaderBuilder::new(batcher_valid)
         .batch_size(config.batch_size)
         .num_workers(config.num_workers)
         .build(BetResultsDataset::test().unwrap());
 
-    let accum = 6;
-    let optim = config.optimizer.init();
-    let lr_scheduler = NoamLrSchedulerConfig::new(0.01 / accum as f64)
-        .with_warmup_steps(6000)
-        .init()
-        .expect("Failed to create learning rate scheduler");
+    let model_trained = match config.optimizer {
+        OptimizerKind::Adam => {
+            let optim = AdamConfig::new()
+                .with_weight_decay(weight_decay(&config))
+                .with_grad_clipping(grad_clipping(&config));
+            fit_with_schedule(
+                artifact_dir,
+                &config,
+                model,
+                optim.init(),
+                dataloader_train,
+                dataloader_test,
+            )
+        }
+        OptimizerKind::AdamW => {
+            let mut optim = AdamWConfig::new().with_grad_clipping(grad_clipping(&config));
+            if config.weight_decay > 0. {
+                optim = optim.with_weight_decay(config.weight_decay as f32);
+            }
+            fit_with_schedule(
+                artifact_dir,
+                &config,
+                model,
+                optim.init(),
+                dataloader_train,
+                dataloader_test,
+            )
+        }
+        OptimizerKind::Sgd => {
+            let optim = SgdConfig::new()
+                .with_weight_decay(weight_decay(&config))
+                .with_gradient_clipping(grad_clipping(&config));
+            fit_with_schedule(
+                artifact_dir,
+                &config,
+                model,
+                optim.init(),
+                dataloader_train,
+                dataloader_test,
+            )
+        }
+    };
+
+    model_trained
+        .save_file(format!("{artifact_dir}/model"), &CompactRecorder::new())
+        .expect("Trained model should be saved successfully");
+}
+
+fn weight_decay(config: &TrainingConfig) -> Option<WeightDecayConfig> {
+    (config.weight_decay > 0.).then(|| WeightDecayConfig::new(config.weight_decay as f32))
+}
+
+fn grad_clipping(config: &TrainingConfig) -> Option<GradientClippingConfig> {
+    config.grad_clip_norm.map(GradientClippingConfig::Norm)
+}
+
+fn fit_with_schedule<B, O>(
+    artifact_dir: &str,
+    config: &TrainingConfig,
+    model: Model<B>,
+    optim: O,
+    dataloader_train: Arc<dyn DataLoader<B, BetBatch<B>>>,
+    dataloader_test: Arc<dyn DataLoader<B::InnerBackend, BetBatch<B::InnerBackend>>>,
+) -> Model<B>
+where
+    B: AutodiffBackend,
+    O: Optimizer<Model<B>, B> + 'static,
+{
+    let learning_rate = config.learning_rate / GRADS_ACCUMULATION as f64;
 
-    let learner = LearnerBuilder::new(artifact_dir)
+    match config.lr_schedule {
+        LrSchedule::Constant => fit(
+            artifact_dir,
+            config,
+            model,
+            optim,
+            learning_rate,
+            dataloader_train,
+            dataloader_test,
+        ),
+        LrSchedule::Noam { warmup_steps } => fit(
+            artifact_dir,
+            config,
+            model,
+            optim,
+            NoamLrSchedulerConfig::new(learning_rate)
+                .with_warmup_steps(warmup_steps)
+                .init()
+                .expect("Failed to create learning rate scheduler"),
+            dataloader_train,
+            dataloader_test,
+        ),
+        LrSchedule::WarmupCosine {
+            warmup_steps,
+            decay_steps,
+            min_lr,
+        } => fit(
+            artifact_dir,
+            config,
+            model,
+            optim,
+            WarmupCosineLrScheduler::new(learning_rate, min_lr, warmup_steps, decay_steps),
+            dataloader_train,
+            dataloader_test,
+        ),
+        LrSchedule::Step { step_size, gamma } => fit(
+            artifact_dir,
+            config,
+            model,
+            optim,
+            StepLrSchedulerConfig::new(learning_rate, step_size)
+                .with_gamma(gamma)
+                .init()
+                .expect("Failed to create learning rate scheduler"),
+            dataloader_train,
+            dataloader_test,
+        ),
+    }
+}
+
+fn fit<B, O, S>(
+    artifact_dir: &str,
+    config: &TrainingConfig,
+    model: Model<B>,
+    optim: O,
+    lr_scheduler: S,
+    dataloader_train: Arc<dyn DataLoader<B, BetBatch<B>>>,
+    dataloader_test: Arc<dyn DataLoader<B::InnerBackend, BetBatch<B::InnerBackend>>>,
+) -> Model<B>
+where
+    B: AutodiffBackend,
+    O: Optimizer<Model<B>, B> + 'static,
+    S: LrScheduler + 'static,
+{
+    // The output type picks which `TrainStep` the learner runs, so each head gets its
+    // own builder; only classification outputs have a Hamming score.
+    match config.model.head {
+        OutputHead::Classification { .. } => LearnerBuilder::<
+            B,
+            MultiLabelClassificationOutput<B>,
+            MultiLabelClassificationOutput<B::InnerBackend>,
+            _,
+            _,
+            _,
+        >::new(artifact_dir)
         .metric_train(CudaMetric::new())
         .metric_valid(CudaMetric::new())
         .metric_train(LossMetric::new())
@@ -116,15 +463,112 @@ pub fn train<B: AutodiffBackend>(artifact_dir: &str, config: TrainingConfig, dev
         .metric_train_numeric(LearningRateMetric::new())
         .metric_train_numeric(HammingScore::new())
         .with_file_checkpointer(CompactRecorder::new())
-        .grads_accumulation(accum)
+        .grads_accumulation(GRADS_ACCUMULATION)
         .num_epochs(config.num_epochs)
         // .renderer(NoRenderer {})
         .summary()
-        .build(model, optim, lr_scheduler);
+        .build(model, optim, lr_scheduler)
+        .fit(dataloader_train, dataloader_test),
+        OutputHead::Regression => LearnerBuilder::<
+            B,
+            RegressionOutput<B>,
+            RegressionOutput<B::InnerBackend>,
+            _,
+            _,
+            _,
+        >::new(artifact_dir)
+        .metric_train(CudaMetric::new())
+        .metric_valid(CudaMetric::new())
+        .metric_train(LossMetric::new())
+        .metric_valid(LossMetric::new())
+        .metric_train_numeric(LearningRateMetric::new())
+        .with_file_checkpointer(CompactRecorder::new())
+        .grads_accumulation(GRADS_ACCUMULATION)
+        .num_epochs(config.num_epochs)
+        .summary()
+        .build(model, optim, lr_scheduler)
+        .fit(dataloader_train, dataloader_test),
+    }
+}
 
-    let model_trained = learner.fit(dataloader_train, dataloader_test);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    model_trained
-        .save_file(format!("{artifact_dir}/model"), &CompactRecorder::new())
-        .expect("Trained model should be saved successfully");
+    type TestBackend = burn::backend::NdArray;
+
+    #[test]
+    fn test_warmup_cosine_schedule() {
+        let mut scheduler = WarmupCosineLrScheduler::new(1., 0.1, 2, 2);
+        let lrs = (0..6)
+            .map(|_| LrScheduler::step(&mut scheduler))
+            .collect::<Vec<f64>>();
+
+        assert!((lrs[0] - 0.5).abs() < 1e-9);
+        assert!((lrs[1] - 1.).abs() < 1e-9);
+        assert!((lrs[2] - 0.55).abs() < 1e-9);
+        assert!((lrs[3] - 0.1).abs() < 1e-9);
+        assert!((lrs[5] - 0.1).abs() < 1e-9);
+
+        let record = scheduler.to_record::<TestBackend>();
+        let restored =
+            WarmupCosineLrScheduler::new(1., 0.1, 2, 2).load_record::<TestBackend>(record);
+        assert_eq!(restored.current_step, 6);
+    }
+
+    #[test]
+    fn test_check_loss_rejects_non_finite_values() {
+        assert!(check_loss(0.7, 0).is_ok());
+        assert!(check_loss(f64::NAN, 12).unwrap_err().contains("batch 12"));
+        assert!(check_loss(f64::INFINITY, 3).is_err());
+    }
+
+    #[test]
+    fn test_training_config_validates_batching() {
+        assert!(TrainingConfig::new().validate().is_ok());
+        assert!(TrainingConfig::new()
+            .with_batch_size(250)
+            .with_num_workers(4)
+            .validate()
+            .is_ok());
+        assert!(TrainingConfig::new()
+            .with_batch_size(105)
+            .validate()
+            .is_err());
+        assert!(TrainingConfig::new().with_batch_size(0).validate().is_err());
+        assert!(TrainingConfig::new()
+            .with_num_workers(0)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_training_config_round_trips_optimizer_choice() {
+        let config = TrainingConfig::new()
+            .with_optimizer(OptimizerKind::AdamW)
+            .with_weight_decay(0.01)
+            .with_grad_clip_norm(Some(1.))
+            .with_batch_size(500)
+            .with_num_workers(8)
+            .with_lr_schedule(LrSchedule::Step {
+                step_size: 100,
+                gamma: 0.5,
+            });
+
+        let restored: TrainingConfig =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(restored.optimizer, OptimizerKind::AdamW);
+        assert_eq!(restored.weight_decay, 0.01);
+        assert_eq!(restored.grad_clip_norm, Some(1.));
+        assert_eq!(restored.batch_size, 500);
+        assert_eq!(restored.num_workers, 8);
+        assert_eq!(
+            restored.lr_schedule,
+            LrSchedule::Step {
+                step_size: 100,
+                gamma: 0.5
+            }
+        );
+    }
 }
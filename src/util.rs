@@ -1,14 +1,140 @@
 use burn::prelude::*;
+use log::warn;
+use serde::Deserialize;
+
+/// Number of consecutive rolls the model sees as one input window
+pub const HISTORY_SIZE: usize = 10;
 
 // Feature dimension constants for tensor processing
+/// Values per feature block; each field of a roll is padded or cut to one block
+pub const FEATURE_STRIDE: usize = 256;
+/// Feature blocks per roll: hash_next_roll, hash_previous_roll, client_seed and nonce
+pub const FEATURE_BLOCKS: usize = 4;
 /// Size of the hash_next_roll feature vector
-pub const HASH_NEXT_ROLL_SIZE: usize = 256;
+pub const HASH_NEXT_ROLL_SIZE: usize = FEATURE_STRIDE;
 /// Size after appending hash_previous_roll
-pub const HASH_PREVIOUS_ROLL_SIZE: usize = 512;
+pub const HASH_PREVIOUS_ROLL_SIZE: usize = 2 * FEATURE_STRIDE;
 /// Size after appending client_seed
-pub const CLIENT_SEED_SIZE: usize = 768;
+pub const CLIENT_SEED_SIZE: usize = 3 * FEATURE_STRIDE;
 /// Final size after appending nonce
-pub const FINAL_FEATURE_SIZE: usize = 1024;
+pub const FINAL_FEATURE_SIZE: usize = FEATURE_BLOCKS * FEATURE_STRIDE;
+/// Bits of the nonce encoded after the client seed; all 64 fit in the remaining slots
+pub const NONCE_BITS: usize = 64;
+/// Slot of the first standardized continuous feature, after the nonce bits
+pub const CONTINUOUS_OFFSET: usize = CLIENT_SEED_SIZE + NONCE_BITS;
+/// Continuous features per roll: the rolled number and the amount won
+pub const CONTINUOUS_FEATURES: usize = 2;
+const _: () = assert!(CONTINUOUS_OFFSET + CONTINUOUS_FEATURES <= FINAL_FEATURE_SIZE);
+
+/// Number of buckets the 0-9999 roll range is split into for classification
+pub const BUCKET_COUNT: usize = 100;
+/// Number of consecutive rolls covered by one bucket
+pub const BUCKET_WIDTH: u32 = 100;
+
+/// A dice roll on the 0-9999 scale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Roll(u32);
+
+impl Roll {
+    /// Highest possible roll
+    pub const MAX: u32 = 9999;
+    /// Lowest roll of the upper half of the range
+    pub const MIDPOINT: u32 = 5000;
+
+    /// Returns `None` when `value` is outside 0-9999.
+    pub fn new(value: u32) -> Option<Self> {
+        (value <= Self::MAX).then_some(Self(value))
+    }
+
+    /// Clamps `value` into 0-9999.
+    pub fn clamped(value: u32) -> Self {
+        Self(value.min(Self::MAX))
+    }
+
+    pub fn value(self) -> u32 {
+        self.0
+    }
+
+    /// Whether the roll is in the upper half of the range, 5000-9999.
+    pub fn is_high(self) -> bool {
+        self.0 >= Self::MIDPOINT
+    }
+
+    /// The bucket this roll falls into.
+    pub fn bucket(self) -> Bucket {
+        Bucket(self.0 as usize / BUCKET_WIDTH as usize)
+    }
+}
+
+impl From<Roll> for u32 {
+    fn from(roll: Roll) -> Self {
+        roll.0
+    }
+}
+
+impl std::fmt::Display for Roll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Index of a `BUCKET_WIDTH`-wide range of rolls, as used for the model's output classes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bucket(usize);
+
+impl Bucket {
+    /// Returns `None` when `index` is not below `BUCKET_COUNT`.
+    pub fn new(index: usize) -> Option<Self> {
+        (index < BUCKET_COUNT).then_some(Self(index))
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    /// The lowest roll in this bucket.
+    pub fn first_roll(self) -> Roll {
+        Roll(self.0 as u32 * BUCKET_WIDTH)
+    }
+
+    /// The roll within this bucket that `anchor` picks.
+    pub fn roll_at(self, anchor: BucketAnchor) -> Roll {
+        Roll(self.first_roll().value() + anchor.offset(BUCKET_WIDTH))
+    }
+}
+
+/// Which roll of a predicted bucket is taken as the prediction.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketAnchor {
+    /// The lowest roll of the bucket, biasing predictions down by half a bucket.
+    #[default]
+    Low,
+    Center,
+    /// The highest roll of the bucket.
+    High,
+}
+
+impl BucketAnchor {
+    /// Position of the anchored roll within a range of `width` rolls.
+    pub fn offset(self, width: u32) -> u32 {
+        match self {
+            Self::Low => 0,
+            Self::Center => width / 2,
+            Self::High => width.saturating_sub(1),
+        }
+    }
+}
+
+/// Encodes `nonce` as `NONCE_BITS` values, least significant bit first.
+///
+/// Nonces below 2^32 encode the same as they did when only 32 bits were used, since the
+/// upper slots were zero padding before.
+pub fn nonce_to_binary_vec<B: Backend>(nonce: u64) -> Vec<B::FloatElem> {
+    (0..NONCE_BITS)
+        .map(|i| ((nonce >> i) & 1).elem::<B::FloatElem>())
+        .collect()
+}
 
 /// Converts a hexadecimal string to a vector of binary values.
 ///
@@ -33,3 +159,155 @@ pub fn hex_string_to_binary_vec<B: Backend>(hex_str: &str) -> Vec<B::FloatElem>
         })
         .collect()
 }
+
+/// A character that is not a hexadecimal digit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("invalid hex character {character:?} at index {index}")]
+pub struct HexError {
+    pub index: usize,
+    pub character: char,
+}
+
+/// Fails on the first character of `hex_str` that is not a hexadecimal digit.
+pub fn check_hex(hex_str: &str) -> Result<(), HexError> {
+    match hex_str
+        .chars()
+        .enumerate()
+        .find(|(_, chr)| !chr.is_ascii_hexdigit())
+    {
+        Some((index, character)) => Err(HexError { index, character }),
+        None => Ok(()),
+    }
+}
+
+/// Like `hex_string_to_binary_vec`, but fails on the first character that is not a
+/// hexadecimal digit instead of encoding it as zero.
+pub fn hex_string_to_binary_vec_checked<B: Backend>(
+    hex_str: &str,
+) -> Result<Vec<B::FloatElem>, HexError> {
+    check_hex(hex_str)?;
+
+    Ok(hex_string_to_binary_vec::<B>(hex_str))
+}
+
+/// Trims or pads `items` so they split evenly into windows of `history_size`.
+///
+/// A trailing partial window is dropped. When there is not even one full window, the
+/// last item is repeated to fill it. Both cases are logged, since they mean the dataset
+/// or batch size is not a multiple of `history_size`.
+pub fn fit_to_windows<T: Clone>(mut items: Vec<T>, history_size: usize) -> Vec<T> {
+    let remainder = items.len() % history_size;
+    if remainder == 0 {
+        return items;
+    }
+
+    if items.len() < history_size {
+        warn!(
+            "Only {} items for a window of {}, padding with the last item",
+            items.len(),
+            history_size
+        );
+        if let Some(last) = items.last().cloned() {
+            items.resize(history_size, last);
+        }
+    } else {
+        warn!(
+            "{} items is not a multiple of the window size {}, dropping the last {}",
+            items.len(),
+            history_size,
+            remainder
+        );
+        items.truncate(items.len() - remainder);
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_hex_reports_the_bad_character() {
+        type TestBackend = burn::backend::NdArray;
+
+        assert_eq!(
+            hex_string_to_binary_vec_checked::<TestBackend>("a1").unwrap(),
+            hex_string_to_binary_vec::<TestBackend>("a1")
+        );
+        assert_eq!(
+            hex_string_to_binary_vec_checked::<TestBackend>("00g0"),
+            Err(HexError {
+                index: 2,
+                character: 'g'
+            })
+        );
+        assert!(hex_string_to_binary_vec_checked::<TestBackend>("").is_ok());
+    }
+
+    #[test]
+    fn test_fit_to_windows_keeps_whole_windows() {
+        assert_eq!(fit_to_windows((0..20).collect(), 10).len(), 20);
+    }
+
+    #[test]
+    fn test_fit_to_windows_trims_remainder() {
+        let items = fit_to_windows((0..25).collect::<Vec<u32>>(), 10);
+        assert_eq!(items, (0..20).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_fit_to_windows_pads_short_input() {
+        let items = fit_to_windows(vec![1, 2, 3], 5);
+        assert_eq!(items, vec![1, 2, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_roll_bucket_round_trip() {
+        assert_eq!(Roll::new(10_000), None);
+        assert_eq!(Roll::clamped(10_000).value(), 9999);
+        assert!(!Roll::clamped(4999).is_high());
+        assert!(Roll::clamped(5000).is_high());
+        assert_eq!(Roll::new(0).unwrap().bucket().index(), 0);
+        assert_eq!(Roll::new(4999).unwrap().bucket().index(), 49);
+        assert_eq!(Roll::new(9999).unwrap().bucket().index(), BUCKET_COUNT - 1);
+        assert_eq!(Bucket::new(BUCKET_COUNT), None);
+        assert_eq!(Bucket::new(42).unwrap().first_roll().value(), 4200);
+        assert_eq!(
+            Bucket::new(42)
+                .unwrap()
+                .roll_at(BucketAnchor::Center)
+                .value(),
+            4250
+        );
+        assert_eq!(
+            Bucket::new(BUCKET_COUNT - 1)
+                .unwrap()
+                .roll_at(BucketAnchor::High),
+            Roll::new(Roll::MAX).unwrap()
+        );
+        for bucket in 0..BUCKET_COUNT {
+            let bucket = Bucket::new(bucket).unwrap();
+            assert_eq!(bucket.first_roll().bucket(), bucket);
+            for anchor in [BucketAnchor::Low, BucketAnchor::Center, BucketAnchor::High] {
+                assert_eq!(bucket.roll_at(anchor).bucket(), bucket);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nonce_encoding_keeps_high_bits() {
+        type TestBackend = burn::backend::NdArray;
+
+        let bits = nonce_to_binary_vec::<TestBackend>((1 << 32) + 5);
+        assert_eq!(bits.len(), NONCE_BITS);
+        assert_eq!(&bits[..3], &[1., 0., 1.]);
+        assert_eq!(bits[32], 1.);
+        assert_eq!(bits.iter().sum::<f32>(), 3.);
+    }
+
+    #[test]
+    fn test_fit_to_windows_empty() {
+        assert!(fit_to_windows(Vec::<u32>::new(), 10).is_empty());
+    }
+}
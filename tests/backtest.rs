@@ -0,0 +1,51 @@
+//! Golden test of the backtest accounting on a hand-checked roll sequence.
+//!
+//! `tests/data/backtest_golden.csv` holds eight rolls. A flat 1.0 bet at 50% and 2x
+//! payout predicts from the previous roll (5000 before the first), so it bets low,
+//! low, high, high, low, low, high, low:
+//!
+//! | roll | side | outcome | balance |
+//! |------|------|---------|---------|
+//! | 1200 | low  | win     | 11      |
+//! | 7000 | low  | loss    | 10      |
+//! | 8000 | high | win     | 11      |
+//! | 3000 | high | loss    | 10      |
+//! | 2000 | low  | win     | 11      |
+//! | 6000 | low  | loss    | 10      |
+//! | 4000 | high | loss    | 9       |
+//! | 9000 | low  | loss    | 8       |
+//!
+//! That is 3 wins, a profit of -2 and a drawdown of 3 from the peak of 11.
+
+use freebitco_in::backtest::{replay, BacktestReport};
+use freebitco_in::dataset::CsvFormat;
+use freebitco_in::strategies::flat::FlatBet;
+use freebitco_in::strategies::Strategy;
+
+#[test]
+fn test_flat_bet_backtest_matches_golden_numbers() {
+    let records = CsvFormat::default()
+        .read_records(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/data/backtest_golden.csv"
+        ))
+        .unwrap();
+    assert_eq!(records.len(), 8);
+
+    let mut strategy = FlatBet::default().with_initial_bet(1.);
+    let report = replay(&mut strategy, &records, 10.);
+
+    assert_eq!(
+        report,
+        BacktestReport {
+            bets: 8,
+            wins: 3,
+            profit: -2.,
+            final_balance: 8.,
+            max_drawdown: 3.,
+            busted: false,
+        }
+    );
+    assert_eq!(strategy.get_profit(), -2.);
+    assert_eq!(strategy.get_balance(), 8.);
+}